@@ -0,0 +1,259 @@
+use bitcoin::{
+    absolute,
+    opcodes::all::{
+        OP_CHECKSIG, OP_CSV, OP_DROP, OP_DUP, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_HASH160, OP_IF,
+    },
+    psbt::Psbt,
+    script::{Builder, Instruction},
+    transaction::{OutPoint, Transaction, TxIn, TxOut},
+    Amount, PublicKey, Script, ScriptBuf, Sequence, Witness,
+};
+use bitcoincore_rpc::json;
+use tracing::info;
+
+use crate::error::{SearcherError, ValidationError};
+
+// vbytes a P2WPKH anchor's witness adds to the child over spending P2A
+pub const KEYED_ANCHOR_EXTRA_VSIZE: u64 = 27;
+
+// Which anchors a searcher spends: the anyone-can-spend P2A, a zero-value P2WPKH
+// anchor to a key the searcher handed out so nobody else can bump the parent, or either
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnchorPolicy {
+    #[default]
+    P2a,
+    Keyed,
+    Either,
+}
+
+impl AnchorPolicy {
+    pub fn accepts_p2a(self) -> bool {
+        self != AnchorPolicy::Keyed
+    }
+
+    pub fn accepts_keyed(self) -> bool {
+        self != AnchorPolicy::P2a
+    }
+}
+
+// A deposit keyed-anchor parents have to carry: an output the searcher can spend at once
+// with the anchor key, or the submitter after `delay` blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepositTerms {
+    pub amount_sat: u64,
+    pub delay: u16,
+}
+
+// The anchor a parent carries in its first output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    P2a,
+    Keyed(ScriptBuf),
+}
+
+// P2A script: OP_1 <0x4e73>. rust-bitcoin only gains ScriptBuf::new_p2a in 0.33, so
+// the builder and every check share these bytes instead of assembling their own.
+pub const P2A_SCRIPT: [u8; 4] = [0x51, 0x02, 0x4e, 0x73];
+
+pub fn create_p2a_script() -> ScriptBuf {
+    ScriptBuf::from_bytes(P2A_SCRIPT.to_vec())
+}
+
+pub fn is_p2a(script: &Script) -> bool {
+    script.as_bytes() == P2A_SCRIPT
+}
+
+pub fn validate_anchor(tx: &Transaction) -> Result<(), ValidationError> {
+    find_anchor(tx, AnchorPolicy::P2a).map(|_| ())
+}
+
+// Check the first output is a zero-value anchor the policy allows. Keyed anchors are
+// only checked for shape; whether the key is ours is up to the caller.
+pub fn find_anchor(tx: &Transaction, policy: AnchorPolicy) -> Result<Anchor, ValidationError> {
+    let output = tx.output.first().ok_or(ValidationError::NoOutputs)?;
+
+    let anchor = if is_p2a(&output.script_pubkey) && policy.accepts_p2a() {
+        Anchor::P2a
+    } else if output.script_pubkey.is_p2wpkh() && policy.accepts_keyed() {
+        Anchor::Keyed(output.script_pubkey.clone())
+    } else if policy == AnchorPolicy::Keyed {
+        return Err(ValidationError::MissingKeyedAnchor);
+    } else {
+        return Err(ValidationError::MissingAnchor);
+    };
+
+    if output.value != Amount::ZERO {
+        return Err(ValidationError::NonZeroAnchor);
+    }
+
+    Ok(anchor)
+}
+
+// Witness script of a deposit: the anchor key spends it with a signature and its pubkey,
+// the refund key with a signature and an empty push once the delay has passed
+pub fn deposit_script(anchor_script: &ScriptBuf, refund_key: &PublicKey, delay: u16) -> Option<ScriptBuf> {
+    let Some(Ok(Instruction::PushBytes(hash))) = anchor_script.instructions().nth(1) else {
+        return None;
+    };
+    if !anchor_script.is_p2wpkh() {
+        return None;
+    }
+    Some(Builder::new()
+        .push_opcode(OP_IF)
+        .push_opcode(OP_DUP)
+        .push_opcode(OP_HASH160)
+        .push_slice(hash)
+        .push_opcode(OP_EQUALVERIFY)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_ELSE)
+        .push_sequence(Sequence::from_height(delay))
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_DROP)
+        .push_key(refund_key)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_ENDIF)
+        .into_script())
+}
+
+// The refund key and delay of a deposit script for this anchor, if it is one
+pub fn parse_deposit_script(script: &ScriptBuf, anchor_script: &ScriptBuf) -> Option<(PublicKey, u16)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let delay = u16::try_from(instructions.get(7)?.script_num()?).ok()?;
+    let Instruction::PushBytes(key) = instructions.get(10)? else {
+        return None;
+    };
+    let refund_key = PublicKey::from_slice(key.as_bytes()).ok()?;
+    (deposit_script(anchor_script, &refund_key, delay)? == *script).then_some((refund_key, delay))
+}
+
+// Find the parent's deposit among outputs whose PSBT entry carries the witness script,
+// returning its index
+pub fn find_deposit(psbt: &Psbt, terms: &DepositTerms) -> Result<usize, ValidationError> {
+    let anchor_script = &psbt.unsigned_tx.output.first().ok_or(ValidationError::NoOutputs)?.script_pubkey;
+    let mut problem = "no output carries a deposit witness script".to_string();
+    for (vout, (output, psbt_output)) in psbt.unsigned_tx.output.iter().zip(&psbt.outputs).enumerate() {
+        let Some(witness_script) = &psbt_output.witness_script else {
+            continue;
+        };
+        if output.script_pubkey != ScriptBuf::new_p2wsh(&witness_script.wscript_hash()) {
+            problem = format!("output {} does not pay its witness script", vout);
+        } else if let Some((_, delay)) = parse_deposit_script(witness_script, anchor_script) {
+            if delay < terms.delay {
+                problem = format!("output {} is refundable after {} blocks, {} required", vout, delay, terms.delay);
+            } else if output.value.to_sat() < terms.amount_sat {
+                problem = format!("output {} deposits {} sats, {} required", vout, output.value.to_sat(), terms.amount_sat);
+            } else {
+                return Ok(vout);
+            }
+        } else {
+            problem = format!("output {} is not a deposit to the anchor key", vout);
+        }
+    }
+    Err(ValidationError::MissingDeposit(problem))
+}
+
+pub fn create_cpfp_transaction(
+    parent_tx: &Transaction,
+    searcher_utxo: &json::ListUnspentResultEntry,
+    change_script: ScriptBuf,
+    rune_script: Option<ScriptBuf>,
+    revenue: Option<TxOut>,
+    fee_rate: f64,
+) -> Result<Transaction, SearcherError> {
+    let mut inputs = Vec::new();
+
+    // Input 1: anchor output from parent transaction (first output)
+    let parent_txid = parent_tx.compute_txid();
+    inputs.push(TxIn {
+        previous_output: OutPoint {
+            txid: parent_txid,
+            vout: 0, // The anchor is always first output
+        },
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(),
+    });
+
+    // Input 2: Searcher's UTXO
+    inputs.push(TxIn {
+        previous_output: OutPoint {
+            txid: searcher_utxo.txid,
+            vout: searcher_utxo.vout,
+        },
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(),
+    });
+
+    // Without a runestone the anchor's runes go to the first output, so a rune output
+    // goes ahead of the change, and the operator's revenue after it
+    let mut outputs: Vec<TxOut> = rune_script.into_iter()
+        .map(|script| TxOut { value: script.minimal_non_dust(), script_pubkey: script })
+        .chain(revenue)
+        .collect();
+    let paid_out: u64 = outputs.iter().map(|output| output.value.to_sat()).sum();
+
+    // Build a dummy child transaction to get accurate size
+    let dummy_output = TxOut {
+        value: Amount::from_sat(searcher_utxo.amount.to_sat()),
+        script_pubkey: change_script.clone(),
+    };
+
+    let dummy_tx = Transaction {
+        version: bitcoin::transaction::Version(3),
+        lock_time: absolute::LockTime::ZERO,
+        input: inputs.clone(),
+        output: outputs.iter().cloned().chain([dummy_output]).collect(),
+    };
+
+    // Calculate virtual sizes (weight / 4)
+    let parent_weight = parent_tx.weight().to_wu();
+    let child_weight = dummy_tx.weight().to_wu();
+    let parent_vsize = parent_weight.div_ceil(4); // Round up
+    let mut child_vsize = child_weight.div_ceil(4); // Round up
+
+    // Unlike P2A, a keyed anchor needs a signature the dummy doesn't have yet
+    if parent_tx.output[0].script_pubkey.is_p2wpkh() {
+        child_vsize += KEYED_ANCHOR_EXTRA_VSIZE;
+    }
+
+    info!("Parent transaction vsize: {} vbytes", parent_vsize);
+    info!("Child transaction vsize: {} vbytes", child_vsize);
+
+    // Calculate total fee needed for both transactions
+    let total_vsize = parent_vsize + child_vsize;
+    let total_fee = (total_vsize as f64 * fee_rate).ceil() as u64;
+
+    info!("Total vsize: {} vbytes, Fee rate: {} sat/vB, Total fee: {} sats",
+          total_vsize, fee_rate, total_fee);
+
+    // Output: Return searcher's funds minus total fees
+    let utxo_value = searcher_utxo.amount.to_sat();
+    let output_value = utxo_value
+        .checked_sub(total_fee)
+        .and_then(|left| left.checked_sub(paid_out))
+        .ok_or_else(|| SearcherError::Cpfp(format!(
+            "UTXO of {} sats can't pay {} sats in fees and {} sats to the child's outputs",
+            utxo_value, total_fee, paid_out
+        )))?;
+    // Dust change wouldn't relay
+    let dust = change_script.minimal_non_dust().to_sat();
+    if output_value < dust {
+        return Err(SearcherError::Cpfp(format!(
+            "Change of {} sats would be under the {} sat dust limit",
+            output_value, dust
+        )));
+    }
+
+    outputs.push(TxOut {
+        value: Amount::from_sat(output_value),
+        script_pubkey: change_script,
+    });
+
+    Ok(Transaction {
+        version: bitcoin::transaction::Version(3),
+        lock_time: absolute::LockTime::ZERO,
+        input: inputs,
+        output: outputs,
+    })
+}
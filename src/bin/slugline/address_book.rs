@@ -0,0 +1,113 @@
+use bitcoin::address::{Address, NetworkUnchecked};
+use clap::Subcommand;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::CliError;
+
+#[derive(Subcommand, Debug)]
+pub enum AddressCommand {
+    /// Save an address under a label, to pass as @label
+    Add {
+        label: String,
+        address: String,
+    },
+    /// List saved addresses
+    List,
+    /// Forget a label
+    Remove {
+        label: String,
+    },
+}
+
+// Labelled addresses, kept as a JSON object of label to address
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    entries: BTreeMap<String, String>,
+}
+
+// ~/.slugline/addresses.json, unless --address-book says otherwise
+pub fn default_path() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join(".slugline").join("addresses.json"))
+}
+
+impl AddressBook {
+    // A missing file is an empty book
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(AddressBook::default()),
+            Err(e) => return Err(format!("Failed to read address book {}: {}", path.display(), e).into()),
+        };
+        let entries = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid address book {}: {}", path.display(), e))?;
+        Ok(AddressBook { entries })
+    }
+
+    fn save(&self, path: &Path) -> Result<(), CliError> {
+        let failed = |e: std::io::Error| format!("Failed to write address book {}: {}", path.display(), e);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(failed)?;
+        }
+        let json = serde_json::to_string_pretty(&self.entries).map_err(std::io::Error::other).map_err(failed)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json + "\n").map_err(failed)?;
+        std::fs::rename(&tmp, path).map_err(failed)?;
+        Ok(())
+    }
+
+    // "@label" to its address; anything else is already an address
+    pub fn resolve(&self, address: &str) -> Result<String, CliError> {
+        match address.strip_prefix('@') {
+            Some(label) => self.entries.get(label)
+                .cloned()
+                .ok_or_else(|| format!("No address labelled {} in the address book", label).into()),
+            None => Ok(address.to_string()),
+        }
+    }
+
+    pub fn label(&self, address: &str) -> Option<&str> {
+        self.entries.iter().find(|(_, saved)| *saved == address).map(|(label, _)| label.as_str())
+    }
+
+    // The address followed by its label, when it has one
+    pub fn show(&self, address: &str) -> String {
+        match self.label(address) {
+            Some(label) => format!("{} (@{})", address, label),
+            None => address.to_string(),
+        }
+    }
+}
+
+pub fn run(path: &Path, command: AddressCommand) -> Result<(), CliError> {
+    let mut book = AddressBook::load(path)?;
+    match command {
+        AddressCommand::Add { label, address } => {
+            if label.is_empty() || label.starts_with('@') || label.chars().any(char::is_whitespace) {
+                return Err(format!("Invalid label {}: use a name without spaces, leaving off the @", label).into());
+            }
+            // Checked against the network where it's used, so one book can serve several
+            address.parse::<Address<NetworkUnchecked>>()
+                .map_err(|e| format!("Invalid address {}: {}", address, e))?;
+            if let Some(saved) = book.entries.get(&label) {
+                return Err(format!("@{} is already {}, remove it first", label, saved).into());
+            }
+            book.entries.insert(label.clone(), address.clone());
+            book.save(path)?;
+            println!("Saved @{}: {}", label, address);
+        }
+        AddressCommand::List => {
+            for (label, address) in &book.entries {
+                println!("@{:<20} {}", label, address);
+            }
+        }
+        AddressCommand::Remove { label } => {
+            let label = label.trim_start_matches('@').to_string();
+            let address = book.entries.remove(&label)
+                .ok_or_else(|| format!("No address labelled {} in the address book", label))?;
+            book.save(path)?;
+            println!("Removed @{}: {}", label, address);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use bitcoin::consensus::encode::{deserialize, deserialize_hex};
+use bitcoin::psbt::Psbt;
+use bitcoin::Transaction;
+use slugline::chain::ChainSource;
+use slugline::error::BuildError;
+use slugline::rpc::parse_network;
+use std::fs;
+use std::str::FromStr;
+
+use crate::error::CliError;
+use crate::qr;
+
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    chain: Option<&ChainSource>,
+    tx_path: &str,
+) -> Result<(), CliError> {
+    let contents = fs::read_to_string(tx_path)
+        .map_err(|e| format!("Error reading transaction file {}: {}", tx_path, e))?;
+    let contents = contents.trim();
+
+    // Either a fully signed PSBT (like a signed payjoin) or a raw transaction in hex, as
+    // text or scanned from a signer's QR codes
+    let tx: Transaction = match qr::decode(contents)? {
+        Some(bytes) => match Psbt::deserialize(&bytes) {
+            Ok(psbt) => psbt.extract_tx().map_err(|e| format!("PSBT is not fully signed: {}", e))?,
+            Err(_) => deserialize(&bytes).map_err(|e| format!("Scanned data is not a PSBT or transaction: {}", e))?,
+        },
+        None => match Psbt::from_str(contents) {
+            Ok(psbt) => psbt.extract_tx().map_err(|e| format!("PSBT is not fully signed: {}", e))?,
+            Err(_) => deserialize_hex(contents).map_err(|e| format!("Not a PSBT or raw transaction: {}", e))?,
+        },
+    };
+
+    let chain = chain.cloned().unwrap_or(ChainSource::Core);
+    println!("Broadcasting {} through {}", tx.compute_txid(), chain);
+
+    let backend = chain
+        .connect(bitcoind_host, bitcoind_user, bitcoind_password, parse_network(network))
+        .map_err(BuildError::from)?;
+    let txid = backend.broadcast(&tx).map_err(BuildError::from)?;
+
+    println!("Broadcast transaction {}", txid);
+    Ok(())
+}
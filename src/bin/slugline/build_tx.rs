@@ -0,0 +1,473 @@
+use bitcoin::bip32::{DerivationPath, Fingerprint};
+use bitcoin::psbt::Psbt;
+use bitcoin::{absolute, Address, Amount, Network, PublicKey, ScriptBuf, Sequence, Transaction, TxOut};
+use bitcoincore_rpc::Client;
+use slugline::anchor::{create_p2a_script, deposit_script};
+use slugline::build_tx::{bip69_key, build_transaction, fetch_rune_utxos, select_utxos, valuable_contents};
+use slugline::chain::ChainSource;
+use slugline::error::BuildError;
+use slugline::hwi::{add_key_origin, Hwi};
+use slugline::miniscript::{self, estimate_signed_vsize};
+use slugline::ord::{fetch_utxos, Utxo};
+use slugline::rpc::{self, parse_network};
+use std::str::FromStr;
+use slugline::runes::{mark_payment_input, RuneAmount, RUNE_NAME};
+use slugline::watch::{derive_address, ensure_watch_wallet, fetch_descriptor_utxos, fill_psbt, import_descriptor};
+
+use crate::address_book::AddressBook;
+use crate::config::Locktime;
+use crate::error::CliError;
+use crate::qr::{self, QrFormat};
+
+// Where the bitcoin for the payment comes from
+#[derive(Debug, Clone)]
+pub enum BtcSource {
+    Address(String),
+    Descriptor(DescriptorSource),
+}
+
+// A descriptor tracked watch-only in a bitcoind wallet
+#[derive(Debug, Clone)]
+pub struct DescriptorSource {
+    pub descriptor: String,
+    pub wallet: String,
+    pub range: u32,
+    pub rescan_since: u64,
+}
+
+// Where to find the keys when signing with a hardware wallet
+#[derive(Debug, Clone)]
+pub struct HwwOptions {
+    pub hwi_binary: String,
+    pub fingerprint: Option<String>,
+    pub path: String,
+    pub runes_path: Option<String>,
+}
+
+// A deposit the searcher asked for with a keyed anchor, refundable to our key
+#[derive(Debug, Clone)]
+pub struct DepositOptions {
+    pub amount: u64,
+    pub delay: u16,
+    pub refund_key: PublicKey,
+}
+
+// Where change goes, how the parent signals replaceability and locks, and the most runes
+// a quote may ask for, from the flags or a build profile
+#[derive(Debug, Clone)]
+pub struct TxPolicy {
+    pub change_address: Option<String>,
+    pub rbf: bool,
+    pub locktime: Locktime,
+    pub max_rune_amount: Option<String>,
+    pub timelocked_path: bool,
+    pub deterministic: bool,
+}
+
+// Add the deposit after the destination, paid out of change. Returns its index and witness script.
+fn add_deposit(tx: &mut Transaction, options: &DepositOptions) -> Result<(usize, ScriptBuf), CliError> {
+    let anchor_script = &tx.output[0].script_pubkey;
+    let witness_script = deposit_script(anchor_script, &options.refund_key, options.delay)
+        .ok_or("Deposits need a keyed anchor, pass --anchor-script from the quote")?;
+    let change = tx.output.get_mut(2)
+        .filter(|change| change.value.to_sat() >= options.amount)
+        .ok_or_else(|| format!("Not enough change to deposit {} sats", options.amount))?;
+    change.value -= Amount::from_sat(options.amount);
+    if change.value == Amount::ZERO {
+        tx.output.remove(2);
+    }
+    tx.output.insert(2, TxOut {
+        value: Amount::from_sat(options.amount),
+        script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+    });
+    Ok((2, witness_script))
+}
+
+fn parse_path(path: &str) -> Result<DerivationPath, CliError> {
+    path.parse().map_err(|e| format!("Invalid derivation path {}: {}", path, e).into())
+}
+
+// Signers need the spent outputs to show the fee and sign segwit inputs, and taproot
+// sighashes, MuSig2 ones included, commit to all of them
+fn add_spent_outputs(psbt: &mut Psbt, inputs: &[&Utxo]) -> Result<(), CliError> {
+    for (input, utxo) in psbt.inputs.iter_mut().zip(inputs) {
+        let script_pubkey = ScriptBuf::from_hex(&utxo.script_pubkey)
+            .map_err(|e| format!("Invalid script_pubkey for {}: {}", utxo.outpoint, e))?;
+        input.witness_utxo = Some(TxOut { value: Amount::from_sat(utxo.value), script_pubkey });
+    }
+    Ok(())
+}
+
+fn sign_with_hww(mut psbt: Psbt, network: Network, options: &HwwOptions) -> Result<Psbt, CliError> {
+    let hwi = Hwi::new(&options.hwi_binary, network);
+    let device = hwi.device(options.fingerprint.as_deref())?;
+    println!("\nUsing {} {} at {}", device.device_type, device.model, device.path);
+    let fingerprint: Fingerprint = device.fingerprint.as_deref()
+        .ok_or("Device did not report its fingerprint")?
+        .parse()
+        .map_err(|e| format!("Invalid device fingerprint: {}", e))?;
+
+    let mut paths = vec![parse_path(&options.path)?];
+    if let Some(runes_path) = &options.runes_path {
+        paths.push(parse_path(runes_path)?);
+    }
+
+    let mut matched = 0;
+    for path in &paths {
+        let xpub = hwi.xpub(&device, path)?;
+        matched += add_key_origin(&mut psbt, fingerprint, path, &xpub);
+    }
+    if matched < psbt.inputs.len() {
+        return Err(format!(
+            "Only {} of {} inputs belong to the given derivation paths",
+            matched,
+            psbt.inputs.len(),
+        ).into());
+    }
+
+    println!("Confirm the transaction on your device...");
+    Ok(hwi.sign(&device, &psbt)?)
+}
+
+// Take the cheapest timelocked branch of each of the descriptor's scripts: its input gets
+// the relative lock that branch's older() needs, and the parent its after()
+fn use_timelocked_path(psbt: &mut Psbt) -> Result<(), CliError> {
+    let mut timelocked = false;
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        let Some(spend) = input.witness_script.as_deref().and_then(miniscript::analyze) else {
+            continue;
+        };
+        let Some(path) = spend.timelocked() else {
+            continue;
+        };
+        if let Some(older) = path.older {
+            psbt.unsigned_tx.input[index].sequence = older;
+        }
+        if let Some(after) = path.after {
+            let current = psbt.unsigned_tx.lock_time;
+            if current != absolute::LockTime::ZERO && !current.is_same_unit(after) {
+                return Err(format!("Input {} needs locktime {}, which can't be combined with {}", index, after, current).into());
+            }
+            if after.to_consensus_u32() > current.to_consensus_u32() {
+                psbt.unsigned_tx.lock_time = after;
+            }
+        }
+        timelocked = true;
+    }
+    match timelocked {
+        true => Ok(()),
+        false => Err("--timelocked-path: none of the descriptor's inputs has a timelocked branch".into()),
+    }
+}
+
+// Track the descriptor watch-only in bitcoind and return its coins and wallet, with change
+// going back to its first address
+fn fetch_descriptor_source(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: Network,
+    ord_server: &str,
+    http: &reqwest::blocking::Client,
+    source: &DescriptorSource,
+) -> Result<(Vec<Utxo>, String, Client), CliError> {
+    let node = rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, network, None)
+        .map_err(BuildError::from)?;
+    ensure_watch_wallet(&node, &source.wallet)?;
+    let wallet = rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, network, Some(&source.wallet))
+        .map_err(BuildError::from)?;
+
+    let watched = import_descriptor(&wallet, &source.descriptor, source.range, source.rescan_since)?;
+    let change_address = derive_address(&wallet, &watched, 0, network)?;
+
+    Ok((fetch_descriptor_utxos(&wallet, http, ord_server)?, change_address.to_string(), wallet))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    ord_server: &str,
+    http: &reqwest::blocking::Client,
+    chain: Option<&ChainSource>,
+    source: &BtcSource,
+    runes_address: Option<&str>,
+    destination_address: &str,
+    amount: u64,
+    rune_amount: Option<&str>,
+    lightning: bool,
+    allow_valuable_rune_utxo: bool,
+    policy: &TxPolicy,
+    book: &AddressBook,
+    anchor_script: Option<&str>,
+    deposit: Option<&DepositOptions>,
+    hww: Option<&HwwOptions>,
+    qr: Option<QrFormat>,
+) -> Result<(), CliError> {
+    // Searchers with keyed anchors hand out a P2WPKH script with each quote
+    let anchor_script = match anchor_script {
+        Some(hex) => {
+            let script = ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid anchor script {}: {}", hex, e))?;
+            if !script.is_p2wpkh() {
+                return Err(format!("Anchor script {} is not P2WPKH", hex).into());
+            }
+            script
+        }
+        None => create_p2a_script(),
+    };
+
+    // Everything else is fixed by the UTXO set and arguments, but the tip moves
+    if policy.deterministic && policy.locktime == Locktime::Tip {
+        return Err("--locktime tip depends on when the parent is built, pass a block height with --deterministic".into());
+    }
+    if policy.timelocked_path && !matches!(source, BtcSource::Descriptor(_)) {
+        return Err("--timelocked-path spends descriptor coins, pass --btc-descriptor".into());
+    }
+
+    println!("Building transaction...");
+    match source {
+        BtcSource::Address(address) => println!("BTC address: {}", book.show(address)),
+        BtcSource::Descriptor(descriptor) => println!("BTC descriptor: {}", descriptor.descriptor),
+    }
+    println!("Runes address: {}", runes_address.map_or("<none>".to_string(), |address| book.show(address)));
+    println!("Destination address: {}", book.show(destination_address));
+    if let Some(change_address) = &policy.change_address {
+        println!("Change address: {}", book.show(change_address));
+    }
+    println!("Amount: {} sats", amount);
+    println!("Network: {}", network);
+    
+    // Fetch BTC UTXOs
+    let network = parse_network(network);
+    let (mut utxos, btc_address, descriptor_wallet) = match source {
+        BtcSource::Address(address) => match chain {
+            Some(chain) => {
+                println!("\nFetching UTXOs from BTC address through {}...", chain);
+                let backend = chain.connect(bitcoind_host, bitcoind_user, bitcoind_password, network)
+                    .map_err(BuildError::from)?;
+                let parsed = Address::from_str(address)
+                    .map_err(BuildError::from)?
+                    .require_network(network)
+                    .map_err(BuildError::from)?;
+                let utxos = backend.utxos(&parsed).map_err(BuildError::from)?
+                    .into_iter()
+                    .map(|u| u.into_ord_utxo(address))
+                    .collect();
+                (utxos, address.clone(), None)
+            }
+            None => {
+                println!("\nFetching UTXOs from BTC address...");
+                (fetch_utxos(http, ord_server, address).map_err(BuildError::from)?, address.clone(), None)
+            }
+        },
+        BtcSource::Descriptor(descriptor) => {
+            println!("\nFetching UTXOs from watch-only descriptor...");
+            let (utxos, change, wallet) =
+                fetch_descriptor_source(bitcoind_host, bitcoind_user, bitcoind_password, network, ord_server, http, descriptor)?;
+            (utxos, change, Some(wallet))
+        }
+    };
+    println!("Found {} UTXOs", utxos.len());
+    if policy.deterministic {
+        utxos.sort_by(|a, b| bip69_key(a).cmp(&bip69_key(b)));
+    }
+    
+    // Calculate total balance
+    let total_balance: u64 = utxos.iter().map(|u| u.value).sum();
+    println!("Total balance: {} sats", total_balance);
+    
+    // Select UTXOs
+    let mut selected = select_utxos(&utxos, amount)?;
+    if policy.deterministic {
+        selected.sort_by(|a, b| bip69_key(a).cmp(&bip69_key(b)));
+    }
+    println!("\nSelected {} UTXOs for transaction:", selected.len());
+    let mut selected_total = 0u64;
+    for utxo in &selected {
+        println!("  - {} ({} sats)", utxo.outpoint, utxo.value);
+        selected_total += utxo.value;
+    }
+    println!("Selected total: {} sats", selected_total);
+    
+    // Fetch Rune UTXOs, unless the fee is paid over Lightning
+    let mut rune_utxos = Vec::new();
+    match runes_address {
+        Some(runes_address) if !lightning => {
+            println!("\nFetching rune UTXOs from runes address...");
+            rune_utxos = fetch_rune_utxos(http, ord_server, runes_address)?;
+            println!("Found {} UTXOs containing {}", rune_utxos.len(), RUNE_NAME);
+        }
+        _ => println!("\nFee paid over Lightning, skipping rune input"),
+    }
+
+    if policy.deterministic {
+        rune_utxos.sort_by(|a, b| bip69_key(a).cmp(&bip69_key(b)));
+    }
+
+    // Only keep rune UTXOs that cover the quoted rune amount
+    // ord reports the rune's divisibility with each UTXO, which says how to read the amount
+    if let Some(rune_amount) = rune_amount {
+        let divisibility = rune_utxos.iter()
+            .find_map(|u| u.runes.get(RUNE_NAME))
+            .map_or(0, |r| r.divisibility);
+        let rune_amount = RuneAmount::parse(rune_amount, divisibility)
+            .map_err(|e| format!("Invalid --rune-amount: {}", e))?;
+        if let Some(max) = &policy.max_rune_amount {
+            let max = RuneAmount::parse(max, divisibility)
+                .map_err(|e| format!("Invalid --max-rune-amount: {}", e))?;
+            if rune_amount.units > max.units {
+                return Err(format!("The quote asks for {} {}, more than the {} budgeted", rune_amount, RUNE_NAME, max).into());
+            }
+        }
+        rune_utxos.retain(|u| {
+            u.runes.get(RUNE_NAME).is_some_and(|r| r.amount >= rune_amount.units)
+        });
+        println!("{} UTXOs hold at least {} {}", rune_utxos.len(), rune_amount, RUNE_NAME);
+    }
+    
+    for utxo in &rune_utxos {
+        if let Some(rune_info) = utxo.runes.get(RUNE_NAME) {
+            println!("  - {} ({} sats, {} {} runes)", 
+                utxo.outpoint, 
+                utxo.value, 
+                RuneAmount::new(rune_info.amount, rune_info.divisibility),
+                rune_info.symbol
+            );
+        }
+    }
+    
+    // Pass over rune UTXOs carrying inscriptions or rare sats, which the searcher would keep
+    let mut valuable = Vec::new();
+    let mut plain = Vec::new();
+    for utxo in &rune_utxos {
+        match valuable_contents(utxo) {
+            Some(contents) => valuable.push((utxo, contents)),
+            None => plain.push(utxo),
+        }
+    }
+    for (utxo, contents) in &valuable {
+        println!("Warning: rune UTXO {} also carries {}", utxo.outpoint, contents);
+    }
+    let payment_utxo = match (plain.first(), valuable.first()) {
+        (Some(utxo), _) => Some(*utxo),
+        (None, Some((utxo, _))) if allow_valuable_rune_utxo => {
+            println!("Spending {} anyway, as --allow-valuable-rune-utxo asks", utxo.outpoint);
+            Some(*utxo)
+        }
+        (None, Some((utxo, contents))) => {
+            return Err(BuildError::ValuableRuneUtxos {
+                outpoint: utxo.outpoint.clone(),
+                contents: contents.clone(),
+            }.into());
+        }
+        _ => None,
+    };
+
+    // Build the transaction
+    let rune_utxo = match lightning {
+        true => None,
+        false => Some(payment_utxo.ok_or(BuildError::NoRuneUtxos)?),
+    };
+    let keyed_anchor = anchor_script.is_p2wpkh();
+    let change_address = policy.change_address.clone().unwrap_or(btc_address);
+    let mut tx = build_transaction(&selected, rune_utxo, &change_address, destination_address, amount, anchor_script, network)?;
+    // TRUC parents are replaceable either way, so this only drops the BIP 125 signal
+    if !policy.rbf {
+        for input in &mut tx.input {
+            input.sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        }
+    }
+    tx.lock_time = match policy.locktime {
+        Locktime::None => absolute::LockTime::ZERO,
+        Locktime::Height(height) => absolute::LockTime::from_height(height)
+            .map_err(|e| format!("Invalid locktime {}: {}", height, e))?,
+        Locktime::Tip => {
+            let backend = chain.unwrap_or(&ChainSource::Core)
+                .connect(bitcoind_host, bitcoind_user, bitcoind_password, network)
+                .map_err(BuildError::from)?;
+            let (height, _) = backend.tip().map_err(BuildError::from)?;
+            absolute::LockTime::from_height(height as u32)
+                .map_err(|e| format!("Invalid locktime {}: {}", height, e))?
+        }
+    };
+    let deposit = deposit.map(|options| add_deposit(&mut tx, options)).transpose()?;
+
+    // Signers need the descriptor's scripts and key origins, which also say which inputs
+    // have timelocked branches
+    let payment_input = tx.input.len() - 1;
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(BuildError::from)?;
+    let mut inputs = selected.clone();
+    inputs.extend(rune_utxo);
+    add_spent_outputs(&mut psbt, &inputs)?;
+    if let Some(wallet) = &descriptor_wallet {
+        psbt = fill_psbt(wallet, &psbt)?;
+        if policy.timelocked_path {
+            use_timelocked_path(&mut psbt)?;
+        }
+    }
+    let tx = &psbt.unsigned_tx;
+    println!("\nTransaction created successfully!");
+    println!("Transaction ID: {}", tx.compute_txid());
+    if policy.deterministic {
+        println!("Deterministic: anyone building from the same UTXOs and arguments gets this txid");
+    }
+    println!("Version: {}", tx.version);
+    println!("Locktime: {}", tx.lock_time);
+    println!("Inputs: {}", tx.input.len());
+    println!("Outputs: {}", tx.output.len());
+    
+    // Show output details
+    for (i, output) in tx.output.iter().enumerate() {
+        let desc = match i {
+            0 if keyed_anchor => " (keyed anchor)",
+            0 => " (P2A anchor)",
+            1 => " (destination)",
+            2 if deposit.is_some() => " (deposit)",
+            2 | 3 => " (change)",
+            _ => "",
+        };
+        println!("  Output {}: {} sats{}", i, output.value.to_sat(), desc);
+    }
+    
+    // Calculate fee
+    let total_inputs = selected_total + rune_utxo.map_or(0, |u| u.value);
+    let total_outputs: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+    let fee = total_inputs - total_outputs;
+    println!("Total inputs: {} sats", total_inputs);
+    println!("Total outputs: {} sats", total_outputs);
+    println!("Fee: {} sats", fee);
+    println!("Estimated signed vsize: {} vbytes", estimate_signed_vsize(&psbt));
+    
+    println!("\nRaw transaction hex:");
+    println!("{}", bitcoin::consensus::encode::serialize_hex(tx));
+    
+    // Mark the rune input so searchers know which input pays
+    if rune_utxo.is_some() {
+        mark_payment_input(&mut psbt, payment_input);
+        println!("Payment input: {}", payment_input);
+    }
+    // Searchers find the deposit by its witness script, which the refund needs too
+    if let Some((vout, witness_script)) = deposit {
+        println!("Deposit output: {}", vout);
+        println!("Deposit witness script: {}", witness_script.to_hex_string());
+        psbt.outputs[vout].witness_script = Some(witness_script);
+    }
+    
+    // Output PSBT in base64 format
+    println!("\nPSBT (base64):");
+    println!("{}", psbt);
+    if let Some(format) = qr {
+        qr::show(&qr::psbt_parts(&psbt, format))?;
+    }
+
+    if let Some(options) = hww {
+        let signed = sign_with_hww(psbt, network, options)?;
+
+        println!("\nSigned PSBT (base64):");
+        println!("{}", signed);
+    }
+
+    Ok(())
+}
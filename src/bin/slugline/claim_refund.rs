@@ -0,0 +1,88 @@
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::transaction::Version;
+use bitcoin::{absolute, ecdsa, Address, Amount, OutPoint, PrivateKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use slugline::anchor::parse_deposit_script;
+use slugline::chain::ChainSource;
+use slugline::error::BuildError;
+use slugline::rpc::parse_network;
+use std::str::FromStr;
+
+use crate::error::CliError;
+
+// An ECDSA signature is at most 73 bytes with its sighash byte
+const MAX_SIGNATURE_SIZE: usize = 73;
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    chain: Option<&ChainSource>,
+    deposit: &str,
+    amount: u64,
+    witness_script: &str,
+    anchor_script: &str,
+    refund_key: &str,
+    destination_address: &str,
+    fee_rate: f64,
+) -> Result<(), CliError> {
+    let network = parse_network(network);
+    let deposit = OutPoint::from_str(deposit).map_err(|e| format!("Invalid deposit outpoint {}: {}", deposit, e))?;
+    let witness_script = ScriptBuf::from_hex(witness_script)
+        .map_err(|e| format!("Invalid witness script {}: {}", witness_script, e))?;
+    let anchor_script = ScriptBuf::from_hex(anchor_script)
+        .map_err(|e| format!("Invalid anchor script {}: {}", anchor_script, e))?;
+    let (refund_pubkey, delay) = parse_deposit_script(&witness_script, &anchor_script)
+        .ok_or("Witness script is not a deposit to this anchor")?;
+
+    let secp = Secp256k1::new();
+    let refund_key = PrivateKey::from_wif(refund_key).map_err(|e| format!("Invalid refund key: {}", e))?;
+    if refund_key.public_key(&secp) != refund_pubkey {
+        return Err("Refund key does not match the deposit's refund pubkey".into());
+    }
+    let destination = Address::from_str(destination_address)
+        .map_err(BuildError::from)?
+        .require_network(network)
+        .map_err(BuildError::from)?;
+
+    // CSV needs version 2 and the delay in the input's sequence
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: deposit,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::from_height(delay),
+            witness: Witness::from_slice(&[vec![0; MAX_SIGNATURE_SIZE], Vec::new(), witness_script.to_bytes()]),
+        }],
+        output: vec![TxOut { value: Amount::from_sat(amount), script_pubkey: destination.script_pubkey() }],
+    };
+    let fee = (tx.vsize() as f64 * fee_rate).ceil() as u64;
+    let refund = amount.checked_sub(fee)
+        .filter(|refund| *refund >= destination.script_pubkey().minimal_non_dust().to_sat())
+        .ok_or_else(|| format!("Deposit of {} sats doesn't cover the {} sat fee", amount, fee))?;
+    tx.output[0].value = Amount::from_sat(refund);
+
+    let sighash = SighashCache::new(&tx)
+        .p2wsh_signature_hash(0, &witness_script, Amount::from_sat(amount), EcdsaSighashType::All)
+        .map_err(|e| format!("Cannot compute sighash: {}", e))?;
+    let signature = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&Message::from(sighash), &refund_key.inner));
+    // An empty push takes the refund branch
+    tx.input[0].witness = Witness::from_slice(&[signature.to_vec(), Vec::new(), witness_script.to_bytes()]);
+
+    println!("Refunding {} sats from {} to {}, paying {} sats in fees", refund, deposit, destination, fee);
+    println!("Spendable {} blocks after the deposit confirms", delay);
+    println!("\nRaw transaction hex:");
+    println!("{}", serialize_hex(&tx));
+
+    let chain = chain.cloned().unwrap_or(ChainSource::Core);
+    let backend = chain
+        .connect(bitcoind_host, bitcoind_user, bitcoind_password, network)
+        .map_err(BuildError::from)?;
+    let txid = backend.broadcast(&tx).map_err(BuildError::from)?;
+    println!("Broadcast refund {}", txid);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use bitcoin::psbt::Psbt;
+use slugline::multisig::{self, InputStatus};
+use slugline::searcher::client::SearcherClient;
+use slugline::searcher::{SubmitPsbtRequest, PROTOCOL_VERSION};
+use std::fs;
+
+use crate::error::CliError;
+use crate::qr::{self, QrFormat};
+
+// Merge what each cosigner signed, finalize once every input has its signatures, and
+// hand the parent to a searcher if one is given
+pub fn run(
+    client: Option<SearcherClient>,
+    psbt_paths: &[String],
+    payment_hash: Option<&str>,
+    output: &str,
+    qr: Option<QrFormat>,
+) -> Result<(), CliError> {
+    let mut psbts = Vec::new();
+    for path in psbt_paths {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading PSBT file {}: {}", path, e))?;
+        let psbt: Psbt = qr::read_psbt(&contents)?
+            .parse()
+            .map_err(|e| format!("Invalid PSBT in {}: {}", path, e))?;
+        psbts.push(psbt);
+    }
+    let mut psbt = multisig::combine(psbts)?;
+    println!("Combined {} PSBTs for {}", psbt_paths.len(), psbt.unsigned_tx.compute_txid());
+
+    let status = multisig::status(&psbt);
+    for (index, input) in status.iter().enumerate() {
+        match input {
+            InputStatus::Finalized => println!("  Input {}: finalized", index),
+            InputStatus::Signed { required: 0, .. } => println!("  Input {}: needs no signature", index),
+            InputStatus::Signed { signatures, required } => {
+                println!("  Input {}: {} of {} signatures", index, signatures, required)
+            }
+            InputStatus::Unsupported => println!("  Input {}: unknown script, not finalized here", index),
+        }
+    }
+
+    let complete = status.iter().all(InputStatus::is_ready);
+    if complete {
+        multisig::finalize(&mut psbt)?;
+    }
+    fs::write(output, psbt.to_string()).map_err(|e| format!("Error writing PSBT file {}: {}", output, e))?;
+    match complete {
+        true => println!("\nFinalized PSBT written to: {}", output),
+        false => println!("\nStill missing signatures, combined PSBT written to: {}", output),
+    }
+    if let Some(format) = qr {
+        qr::show(&qr::psbt_parts(&psbt, format))?;
+    }
+
+    let Some(client) = client else {
+        return Ok(());
+    };
+    if !complete {
+        return Err("Not submitting until every input is signed".into());
+    }
+    println!("\nSubmitting to: {}", client.base_url());
+    let response = client.submit_psbt(&SubmitPsbtRequest {
+        psbt: psbt.to_string(),
+        payment_hash: payment_hash.map(str::to_string),
+        cancel_token: None,
+        commit_psbt: None,
+        payment_inputs: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+    })?;
+    if !response.success {
+        return Err(CliError::Failed(response.message));
+    }
+    println!("Searcher anchored the parent: {}", response.package_txids.unwrap_or_default().join(" <- "));
+    Ok(())
+}
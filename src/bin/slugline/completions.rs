@@ -0,0 +1,8 @@
+use clap::Command;
+use clap_complete::Shell;
+use std::io;
+
+pub fn run(mut command: Command, shell: Shell) {
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
@@ -0,0 +1,129 @@
+use bitcoin::absolute::LOCK_TIME_THRESHOLD;
+use bitcoin::address::{Address, NetworkUnchecked};
+use serde::Deserialize;
+use slugline::runes::RuneAmount;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::CliError;
+
+// The TOML file passed with --config
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, BuildProfile>,
+}
+
+// Saved build-tx settings under [profiles.<name>], filling in whatever isn't passed as a flag
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildProfile {
+    pub btc_address: Option<String>,
+    pub btc_descriptor: Option<String>,
+    pub runes_address: Option<String>,
+    pub destination_address: Option<String>,
+    pub amount: Option<u64>,
+    // Most runes a quote may ask for, in whole runes
+    pub max_rune_amount: Option<String>,
+    pub change_address: Option<String>,
+    pub rbf: Option<bool>,
+    pub locktime: Option<Locktime>,
+    pub deterministic: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "LocktimeSpec")]
+pub enum Locktime {
+    None,
+    // The current block height, so miners gain nothing reorging earlier blocks to take its fee
+    Tip,
+    Height(u32),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LocktimeSpec {
+    Height(u32),
+    Name(String),
+}
+
+impl TryFrom<LocktimeSpec> for Locktime {
+    type Error = String;
+
+    fn try_from(spec: LocktimeSpec) -> Result<Self, Self::Error> {
+        match spec {
+            LocktimeSpec::Height(height) => height.to_string().parse(),
+            LocktimeSpec::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl std::str::FromStr for Locktime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Locktime::None),
+            "tip" => Ok(Locktime::Tip),
+            height => match height.parse::<u32>() {
+                Ok(height) if height < LOCK_TIME_THRESHOLD => Ok(Locktime::Height(height)),
+                Ok(_) => Err(format!("locktime {} is a timestamp, expected a block height", height)),
+                Err(_) => Err(format!("invalid locktime {}: expected none, tip or a block height", height)),
+            },
+        }
+    }
+}
+
+impl BuildProfile {
+    fn validate(&self) -> Result<(), String> {
+        if self.deterministic == Some(true) && self.locktime == Some(Locktime::Tip) {
+            return Err("locktime = \"tip\" can't be deterministic".to_string());
+        }
+        if self.btc_address.is_some() && self.btc_descriptor.is_some() {
+            return Err("btc_address and btc_descriptor can't both be set".to_string());
+        }
+        let addresses = [
+            ("btc_address", &self.btc_address),
+            ("runes_address", &self.runes_address),
+            ("destination_address", &self.destination_address),
+            ("change_address", &self.change_address),
+        ];
+        for (field, address) in addresses {
+            // @labels are looked up in the address book when the profile is used
+            if let Some(address) = address.as_ref().filter(|address| !address.starts_with('@')) {
+                address.parse::<Address<NetworkUnchecked>>()
+                    .map_err(|e| format!("invalid {} {}: {}", field, address, e))?;
+            }
+        }
+        // The rune's divisibility isn't known until ord reports it, so allow the places given
+        if let Some(amount) = &self.max_rune_amount {
+            let places = amount.split_once('.').map_or(0, |(_, fraction)| fraction.trim_end_matches('0').len());
+            RuneAmount::parse(amount, u8::try_from(places).unwrap_or(u8::MAX))
+                .map_err(|e| format!("invalid max_rune_amount: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    // Every profile is checked here, so a typo surfaces before anything is built with it
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        let config: Config = toml::from_str(&text)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        for (name, profile) in &config.profiles {
+            profile.validate()
+                .map_err(|e| format!("Invalid profile {} in {}: {}", name, path.display(), e))?;
+        }
+        Ok(config)
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&BuildProfile, CliError> {
+        self.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            format!("No profile {} in the config (have: {})", name, known.join(", ")).into()
+        })
+    }
+}
@@ -0,0 +1,58 @@
+use slugline::nostr::{fetch_announcements, Announcement, Event};
+use slugline::rpc::parse_network;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::CliError;
+
+pub fn run(network: &str, relays: &[String], rune: &str, max_age: u64) -> Result<(), CliError> {
+    let network = parse_network(network).to_string();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    // Relays may hold several versions of an announcement, keep the newest per searcher
+    let mut searchers: HashMap<String, (Event, Announcement)> = HashMap::new();
+    let mut reachable = 0;
+    for relay in relays {
+        match fetch_announcements(relay, rune, now.saturating_sub(max_age)) {
+            Ok(announcements) => {
+                reachable += 1;
+                for (event, announcement) in announcements {
+                    if announcement.network != network {
+                        continue;
+                    }
+                    let newer = searchers.get(&event.pubkey)
+                        .is_none_or(|(seen, _)| seen.created_at < event.created_at);
+                    if newer {
+                        searchers.insert(event.pubkey.clone(), (event, announcement));
+                    }
+                }
+            }
+            Err(e) => eprintln!("Warning: {}: {}", relay, e),
+        }
+    }
+
+    if reachable == 0 {
+        return Err("No Nostr relay could be queried".into());
+    }
+
+    let mut searchers: Vec<_> = searchers.into_values().collect();
+    searchers.sort_by_key(|(_, announcement)| announcement.rune_price);
+
+    println!("{} searchers accepting {} on {}\n", searchers.len(), rune, network);
+    println!("{:<40} {:>10} {:>10} {:>8}  PUBKEY", "URL", "PRICE", "FEE RATE", "AGE");
+    for (event, announcement) in &searchers {
+        println!(
+            "{:<40} {:>10} {:>10} {:>7}s  {}",
+            announcement.url,
+            announcement.rune_price,
+            announcement.fee_rate,
+            now.saturating_sub(event.created_at),
+            event.pubkey,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use slugline::runes::{RuneAmount, RUNE_NAME};
+use slugline::searcher::jobs;
+use std::path::Path;
+
+use crate::error::CliError;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+// What the searcher spent and earned, from its job store, since the start of a UTC day
+pub fn run(job_store: &Path, since: Option<&str>, rune_divisibility: u8) -> Result<(), CliError> {
+    let since_secs = match since {
+        Some(date) => jobs::parse_day(date).ok_or_else(|| format!("invalid date {}: expected YYYY-MM-DD", date))? * SECS_PER_DAY,
+        None => 0,
+    };
+    let jobs = jobs::read(job_store)?;
+    let earnings = jobs::earnings(jobs.values(), since_secs);
+
+    match since {
+        Some(date) => println!("Jobs received since {}: {}", date, earnings.jobs),
+        None => println!("Jobs received: {}", earnings.jobs),
+    }
+    println!("Packages submitted: {}", earnings.packages_submitted);
+    println!("Payjoins signed: {}", earnings.payjoins_signed);
+    println!();
+    println!("Fees spent: {} sats", earnings.fees_spent_sat);
+    if earnings.sponsored_sat > 0 {
+        println!("  from the sponsorship budget: {} sats", earnings.sponsored_sat);
+    }
+    println!("Runes earned: {} {}", RuneAmount::new(earnings.runes_earned, rune_divisibility), RUNE_NAME);
+    println!("Lightning earned: {} sats", earnings.lightning_earned_sat);
+    if earnings.inscriptions_earned > 0 {
+        println!("Inscriptions earned: {}", earnings.inscriptions_earned);
+    }
+    if earnings.revenue_sat > 0 {
+        println!("Revenue output: {} sats", earnings.revenue_sat);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use bitcoincore_rpc::jsonrpc;
+use slugline::error::{AuditError, BuildError, ChainError, ClientError, HwiError, MultisigError, MusigError, OrdError, SearcherError, TorError};
+use std::process::ExitCode;
+use thiserror::Error;
+
+// Exit codes: 1 for failures, 2 for usage errors (reported by clap), 3 when a
+// service we depend on (ord, bitcoind or a searcher) could not be reached
+const EXIT_FAILURE: u8 = 1;
+const EXIT_UNREACHABLE: u8 = 3;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error(transparent)]
+    Build(#[from] BuildError),
+
+    #[error(transparent)]
+    Searcher(#[from] SearcherError),
+
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    #[error(transparent)]
+    Hwi(#[from] HwiError),
+
+    #[error(transparent)]
+    Multisig(#[from] MultisigError),
+
+    #[error(transparent)]
+    Musig(#[from] MusigError),
+
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+
+    #[error("Searcher unreachable at {url}: {source}")]
+    Unreachable { url: String, source: ClientError },
+
+    #[error("Searcher is {0}")]
+    Unhealthy(String),
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> ExitCode {
+        let unreachable = match self {
+            CliError::Unreachable { .. } => true,
+            CliError::Build(BuildError::Ord(OrdError::Http(e))) => e.is_connect() || e.is_timeout(),
+            CliError::Client(ClientError::Http(e)) => e.is_connect() || e.is_timeout(),
+            CliError::Searcher(SearcherError::Rpc(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_)))) => true,
+            CliError::Searcher(SearcherError::Tor(TorError::Io(_))) => true,
+            CliError::Build(BuildError::Rpc(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_)))) => true,
+            CliError::Build(BuildError::Chain(ChainError::Http(e))) => e.is_connect() || e.is_timeout(),
+            CliError::Build(BuildError::Chain(ChainError::Io(_))) => true,
+            CliError::Build(BuildError::Chain(ChainError::Rpc(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_))))) => true,
+            _ => false,
+        };
+
+        ExitCode::from(if unreachable { EXIT_UNREACHABLE } else { EXIT_FAILURE })
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Failed(message)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        CliError::Failed(message.to_string())
+    }
+}
+
+// The wallet, monitor and simulate helpers mix RPC, ord and I/O errors freely
+impl From<Box<dyn std::error::Error>> for CliError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        CliError::Failed(e.to_string())
+    }
+}
@@ -0,0 +1,156 @@
+use clap::ValueEnum;
+use slugline::searcher::jobs::{self, LedgerEntry};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::error::CliError;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+// One column of the ledger, with None for an empty cell
+enum Values {
+    Int(Vec<Option<i64>>),
+    Text(Vec<Option<String>>),
+}
+
+// The searcher's fees and income per sponsored job between two UTC days, both included, as
+// a file accounting and tax tools can import
+pub fn run(job_store: &Path, from: Option<&str>, to: Option<&str>, format: Format, output: Option<&Path>) -> Result<(), CliError> {
+    let day = |date: &str| jobs::parse_day(date).ok_or_else(|| format!("invalid date {}: expected YYYY-MM-DD", date));
+    let from_secs = match from {
+        Some(date) => day(date)? * SECS_PER_DAY,
+        None => 0,
+    };
+    let to_secs = match to {
+        Some(date) => (day(date)? + 1) * SECS_PER_DAY,
+        None => u64::MAX,
+    };
+    let jobs = jobs::read(job_store)?;
+    let entries = jobs::ledger(jobs.values(), from_secs, to_secs);
+
+    let out: Box<dyn Write + Send> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("Error creating {}: {}", path.display(), e))?,
+        )),
+        None => Box::new(io::stdout()),
+    };
+    match format {
+        Format::Csv => write_csv(&columns(&entries), out).map_err(|e| format!("Error writing CSV: {}", e))?,
+        Format::Parquet => write_parquet(&columns(&entries), out)?,
+    }
+    if let Some(path) = output {
+        eprintln!("Wrote {} ledger entries to {}", entries.len(), path.display());
+    }
+    Ok(())
+}
+
+fn columns(entries: &[LedgerEntry]) -> Vec<(&'static str, Values)> {
+    let int = |value: fn(&LedgerEntry) -> Option<u64>| {
+        Values::Int(entries.iter().map(|entry| value(entry).map(|value| value as i64)).collect())
+    };
+    let text = |value: fn(&LedgerEntry) -> Option<String>| Values::Text(entries.iter().map(value).collect());
+    vec![
+        ("job", int(|entry| Some(entry.job))),
+        ("kind", text(|entry| Some(format!("{:?}", entry.kind).to_lowercase()))),
+        ("status", text(|entry| Some(format!("{:?}", entry.status).to_lowercase()))),
+        ("date", text(|entry| Some(jobs::format_day(entry.paid_at / SECS_PER_DAY)))),
+        ("paid_at", int(|entry| Some(entry.paid_at))),
+        ("confirmed_at", int(|entry| entry.confirmed_at)),
+        ("parent_txid", text(|entry| entry.parent_txid.clone())),
+        ("child_txid", text(|entry| entry.child_txid.clone())),
+        ("wallet", text(|entry| entry.wallet.clone())),
+        ("fee_sat", int(|entry| Some(entry.fee_sat))),
+        ("sponsored_sat", int(|entry| Some(entry.sponsored_sat))),
+        ("payment_method", text(|entry| entry.payment_method.clone())),
+        ("rune_amount", int(|entry| Some(entry.rune_amount))),
+        ("lightning_sat", int(|entry| Some(entry.lightning_sat))),
+        ("revenue_sat", int(|entry| Some(entry.revenue_sat))),
+    ]
+}
+
+fn write_csv(columns: &[(&str, Values)], mut out: impl Write) -> io::Result<()> {
+    let header: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    writeln!(out, "{}", header.join(","))?;
+    let rows = match columns.first() {
+        Some((_, Values::Int(values))) => values.len(),
+        Some((_, Values::Text(values))) => values.len(),
+        None => 0,
+    };
+    for row in 0..rows {
+        let cells: Vec<String> = columns.iter()
+            .map(|(_, values)| match values {
+                Values::Int(values) => values[row].map(|value| value.to_string()).unwrap_or_default(),
+                Values::Text(values) => values[row].as_deref().map(csv_field).unwrap_or_default(),
+            })
+            .collect();
+        writeln!(out, "{}", cells.join(","))?;
+    }
+    out.flush()
+}
+
+// Quoted only when it has to be, with quotes doubled, as RFC 4180 has it
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_columns: &[(&str, Values)], _out: impl Write + Send) -> Result<(), CliError> {
+    Err("Parquet export needs slugline built with --features parquet".into())
+}
+
+// One row group with a column per ledger field, every one optional
+#[cfg(feature = "parquet")]
+fn write_parquet(columns: &[(&str, Values)], out: impl Write + Send) -> Result<(), CliError> {
+    use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::types::Type;
+    use std::sync::Arc;
+
+    let failed = |e: parquet::errors::ParquetError| format!("Error writing Parquet: {}", e);
+    let fields = columns.iter()
+        .map(|(name, values)| {
+            let field = match values {
+                Values::Int(_) => Type::primitive_type_builder(name, PhysicalType::INT64),
+                Values::Text(_) => Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+                    .with_logical_type(Some(LogicalType::String)),
+            };
+            field.with_repetition(Repetition::OPTIONAL).build().map(Arc::new)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(failed)?;
+    let schema = Type::group_type_builder("ledger").with_fields(fields).build().map_err(failed)?;
+
+    let mut writer = SerializedFileWriter::new(out, Arc::new(schema), Default::default()).map_err(failed)?;
+    let mut row_group = writer.next_row_group().map_err(failed)?;
+    for (name, values) in columns {
+        let mut column = row_group.next_column().map_err(failed)?
+            .ok_or_else(|| format!("Error writing Parquet: no column for {}", name))?;
+        match values {
+            Values::Int(values) => {
+                let levels: Vec<i16> = values.iter().map(|value| i16::from(value.is_some())).collect();
+                let present: Vec<i64> = values.iter().flatten().copied().collect();
+                column.typed::<Int64Type>().write_batch(&present, Some(&levels), None).map_err(failed)?;
+            }
+            Values::Text(values) => {
+                let levels: Vec<i16> = values.iter().map(|value| i16::from(value.is_some())).collect();
+                let present: Vec<ByteArray> = values.iter().flatten().map(|value| ByteArray::from(value.as_str())).collect();
+                column.typed::<ByteArrayType>().write_batch(&present, Some(&levels), None).map_err(failed)?;
+            }
+        }
+        column.close().map_err(failed)?;
+    }
+    row_group.close().map_err(failed)?;
+    writer.close().map_err(failed)?;
+    Ok(())
+}
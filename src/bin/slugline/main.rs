@@ -0,0 +1,1644 @@
+use bitcoin::Address;
+use clap::builder::FalseyValueParser;
+use clap::{ArgAction, Command, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use slugline::anchor::{AnchorPolicy, DepositTerms};
+use slugline::chain::ChainSource;
+use slugline::fees::BLOCK_VBYTES;
+use slugline::http::HttpConfig;
+use slugline::inscriptions::InscriptionPayment;
+use slugline::liquidation::LiquidationConfig;
+use slugline::notify;
+use slugline::payments;
+use slugline::policy::PolicyMode;
+use slugline::pricing::{HistogramPricing, PriceTier, RateProvider};
+use slugline::rpc::parse_network;
+use slugline::rules;
+use slugline::rune_index;
+use slugline::runes::RuneAmount;
+use slugline::signer::SignerConfig;
+use slugline::searcher::abuse::AbuseConfig;
+use slugline::searcher::client::SearcherClient;
+use slugline::searcher::consolidate::ConsolidationConfig;
+use slugline::sponsorship::{ScriptTemplate, SponsorshipConfig};
+use slugline::searcher::{daemon, listen, preflight, relay, server};
+
+use crate::error::CliError;
+
+mod address_book;
+mod broadcast;
+mod build_tx;
+mod claim_refund;
+mod combine;
+mod completions;
+mod config;
+mod discover;
+mod earnings;
+mod error;
+mod export;
+mod man;
+mod monitor;
+mod musig;
+mod payjoin;
+mod qr;
+mod quote;
+mod replay;
+mod rune_payments;
+mod simulate;
+mod status;
+mod tx_status;
+mod verify_audit_log;
+mod wallet;
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Network {
+    Regtest,
+    Testnet4,
+    Signet,
+    Mainnet,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum EphemeralPolicy {
+    Off,
+    Warn,
+    Enforce,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum AnchorKind {
+    P2a,
+    Keyed,
+    Either,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum SoftFailMode {
+    Off,
+    Broadcast,
+    Return,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum LightningBackend {
+    Lnd,
+    Cln,
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Bitcoin daemon host
+    #[arg(long, default_value = "localhost")]
+    bitcoind_host: String,
+
+    /// Bitcoin daemon username
+    #[arg(long)]
+    bitcoind_user: Option<String>,
+
+    /// Bitcoin daemon password
+    #[arg(long, conflicts_with = "bitcoind_password_file")]
+    bitcoind_password: Option<String>,
+
+    /// File holding the Bitcoin daemon password, so it stays out of ps output and the environment
+    #[arg(long)]
+    bitcoind_password_file: Option<PathBuf>,
+
+    /// Read the Bitcoin daemon password from the OS keyring entry for service "slugline" and this user
+    #[cfg(feature = "keyring")]
+    #[arg(long, conflicts_with_all = ["bitcoind_password", "bitcoind_password_file"])]
+    bitcoind_password_keyring: Option<String>,
+
+    /// Bitcoin network
+    #[arg(long, value_enum, default_value = "mainnet")]
+    network: Network,
+
+    /// Ord server URL
+    #[arg(long, default_value = "http://localhost")]
+    ord_server: String,
+
+    /// Chain data source instead of ord and bitcoind: core, esplora:<url> or electrum:<host:port>
+    #[arg(long)]
+    chain_backend: Option<ChainSource>,
+
+    /// Seconds to wait for HTTP connections to ord and other services
+    #[arg(long, global = true, default_value = "10")]
+    http_connect_timeout: u64,
+
+    /// Seconds to wait for an HTTP request to finish before giving up
+    #[arg(long, global = true, default_value = "30")]
+    http_timeout: u64,
+
+    /// Proxy for HTTP requests: http://host:port, or socks5://host:port for Tor (e.g. socks5://127.0.0.1:9050)
+    #[arg(long, global = true, alias = "http-proxy", value_parser = parse_proxy)]
+    proxy: Option<reqwest::Proxy>,
+
+    /// Log more (-v for debug, -vv for trace); RUST_LOG overrides this
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// OTLP/HTTP collector to export tracing spans to (e.g. http://localhost:4318/v1/traces)
+    #[cfg(feature = "otel")]
+    #[arg(long, global = true)]
+    otlp_endpoint: Option<String>,
+
+    /// TOML config file holding build profiles
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Address book file for @label addresses (default: ~/.slugline/addresses.json)
+    #[arg(long)]
+    address_book: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
+enum Commands {
+    /// Build a transaction
+    BuildTx {
+        /// Build profile from the config to fill in the flags not given
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Bitcoin address to use as input
+        #[arg(long, required_unless_present_any = ["btc_descriptor", "profile"])]
+        btc_address: Option<String>,
+
+        /// Descriptor to fund the payment from instead of a single address
+        #[arg(long, conflicts_with = "btc_address")]
+        btc_descriptor: Option<String>,
+
+        /// bitcoind wallet that tracks the descriptor watch-only
+        #[arg(long, default_value = "slugline-watch")]
+        watch_wallet: String,
+
+        /// How many addresses of a ranged descriptor to scan
+        #[arg(long, default_value = "1000")]
+        descriptor_range: u32,
+
+        /// Unix timestamp to rescan from when importing the descriptor
+        #[arg(long, default_value = "0")]
+        rescan_since: u64,
+        
+        /// Runes address
+        #[arg(long, required_unless_present_any = ["lightning", "profile"])]
+        runes_address: Option<String>,
+        
+        /// Destination address, @label from the address book, or a bitcoin: payment URI
+        #[arg(long, required_unless_present = "profile")]
+        destination_address: Option<String>,
+        
+        /// Amount to send (in satoshis), unless the profile or a bitcoin: URI gives it
+        #[arg(long)]
+        amount: Option<u64>,
+
+        /// Minimum rune amount the fee-paying UTXO must hold (from `quote`), in whole runes like 1.5
+        #[arg(long)]
+        rune_amount: Option<String>,
+
+        /// Leave out the rune input because the fee is paid over Lightning
+        #[arg(long)]
+        lightning: bool,
+
+        /// Pay with a rune UTXO that also carries inscriptions or rare sats when no other will do
+        #[arg(long)]
+        allow_valuable_rune_utxo: bool,
+
+        /// Refuse quotes asking for more than this many runes, in whole runes like 1.5
+        #[arg(long)]
+        max_rune_amount: Option<String>,
+
+        /// Send change here instead of back to the BTC address
+        #[arg(long)]
+        change_address: Option<String>,
+
+        /// Don't signal BIP 125 replaceability (TRUC parents stay replaceable regardless)
+        #[arg(long)]
+        no_rbf: bool,
+
+        /// Parent locktime: none, tip for the current block height, or a block height
+        #[arg(long)]
+        locktime: Option<config::Locktime>,
+
+        /// Spend descriptor coins through their timelocked branch, setting the relative locks and locktime their older() and after() need
+        #[arg(long)]
+        timelocked_path: bool,
+
+        /// Build reproducibly: canonical input order, no --locktime tip, so the same UTXOs and arguments give the same parent
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Anchor to the hex P2WPKH script from `quote` instead of P2A
+        #[arg(long)]
+        anchor_script: Option<String>,
+
+        /// Deposit to add for a keyed anchor (in satoshis, from `quote`)
+        #[arg(long, requires_all = ["anchor_script", "refund_pubkey"])]
+        deposit_amount: Option<u64>,
+
+        /// Blocks after which the deposit can be refunded (from `quote`)
+        #[arg(long, default_value = "144")]
+        deposit_delay: u16,
+
+        /// Public key (hex) the deposit is refunded to
+        #[arg(long)]
+        refund_pubkey: Option<String>,
+
+        /// Sign the PSBT with a hardware wallet through HWI
+        #[arg(long, requires = "hww_path")]
+        sign_with_hww: bool,
+
+        /// Path to the hwi binary
+        #[arg(long, default_value = "hwi")]
+        hwi_binary: String,
+
+        /// Fingerprint of the device to use when several are connected
+        #[arg(long)]
+        hww_fingerprint: Option<String>,
+
+        /// Derivation path of the BTC address key (e.g. 84h/1h/0h/0/0)
+        #[arg(long)]
+        hww_path: Option<String>,
+
+        /// Derivation path of the runes address key, if it differs
+        #[arg(long)]
+        hww_runes_path: Option<String>,
+
+        /// Show the PSBT as QR codes for an air-gapped signer: ur (the default) or bbqr
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "ur")]
+        qr: Option<qr::QrFormat>,
+    },
+    /// Run the searcher
+    RunSearcher {
+        /// Bitcoin Core wallet to fund children from (repeat to shard jobs across several)
+        #[arg(long = "wallet", default_value = "searcher")]
+        wallets: Vec<String>,
+        
+        /// Fee rate in sat/vB for CPFP transactions (the fallback with --fee-target)
+        #[arg(long, default_value = "100.0")]
+        fee_rate: f64,
+
+        /// Never pay more than this fee rate in sat/vB, clamping static and estimated rates
+        #[arg(long)]
+        max_fee_rate: Option<f64>,
+
+        /// Refuse jobs whose fee would be more than this many sats
+        #[arg(long)]
+        max_fee: Option<u64>,
+
+        /// Refuse jobs whose fee would be more than this percentage of the wallet UTXO spent
+        #[arg(long)]
+        max_fee_percent: Option<f64>,
+
+        /// Refuse parents paying more than this many sats in fees themselves (0 for zero-fee parents only)
+        #[arg(long)]
+        max_parent_fee: Option<u64>,
+
+        /// Estimate the fee rate for confirmation within this many blocks
+        #[arg(long)]
+        fee_target: Option<u16>,
+
+        /// mempool.space API to estimate fees from when bitcoind can't (e.g. https://mempool.space/api)
+        #[arg(long)]
+        mempool_api: Option<String>,
+
+        /// Address where the searcher receives rune payments
+        #[arg(long)]
+        rune_address: Option<String>,
+
+        /// Pay each parent's runes to a fresh wallet address instead of one static address
+        #[arg(long)]
+        rotate_rune_address: bool,
+
+        /// Sign with this remote signer (HTTP, PSBT in and out) instead of keys in bitcoind
+        #[arg(long)]
+        external_signer: Option<String>,
+
+        /// Bearer token to authenticate to the external signer
+        #[arg(long, requires = "external_signer")]
+        external_signer_token: Option<String>,
+
+        /// Cold address every child pays --revenue-sats to
+        #[arg(long, requires = "revenue_sats")]
+        revenue_address: Option<String>,
+
+        /// Sats each child sends to the revenue address
+        #[arg(long, requires = "revenue_address")]
+        revenue_sats: Option<u64>,
+
+        /// Take quotes only while the wallets hold fewer spendable sats than this
+        #[arg(long)]
+        min_balance: Option<u64>,
+
+        /// Take quotes only while fewer wallet UTXOs than this can each fund a package
+        #[arg(long)]
+        min_free_utxos: Option<u64>,
+
+        /// How often to check the wallets against --min-balance and --min-free-utxos (in seconds)
+        #[arg(long, default_value = "60")]
+        funds_check_interval: u64,
+
+        /// URL to POST a JSON alert to when funds run low and when they recover
+        #[arg(long)]
+        low_funds_webhook: Option<String>,
+
+        /// Hex Nostr pubkey to mention in a note when funds run low and when they recover
+        #[arg(long)]
+        low_funds_nostr: Option<String>,
+
+        /// Merge confirmed change under this many sats back into UTXOs of this size
+        #[arg(long, conflicts_with = "external_signer")]
+        consolidate_to: Option<u64>,
+
+        /// Change UTXOs that have to pile up before consolidating them
+        #[arg(long, default_value = "10", requires = "consolidate_to")]
+        consolidate_min_utxos: usize,
+
+        /// Only consolidate while the fee rate is at or under this (in sat/vB)
+        #[arg(long, default_value = "2", requires = "consolidate_to")]
+        consolidate_max_fee_rate: f64,
+
+        /// How often to look for change to consolidate (in seconds)
+        #[arg(long, default_value = "600", requires = "consolidate_to")]
+        consolidate_interval: u64,
+
+        /// Where to tell the operator about trouble: webhook:<url>, telegram:<bot token>:<chat id>,
+        /// matrix:<access token>@<homeserver url>/<room id>, or email:<address> (repeat for each)
+        #[arg(long = "notify")]
+        notify: Vec<String>,
+
+        /// Also notify when a job paying at least this many sats in fees confirms
+        #[arg(long)]
+        notify_large_job: Option<u64>,
+
+        /// Price in runes per vbyte of sponsored package, like 0.5 for a divisible rune
+        #[arg(long, default_value = "1")]
+        rune_price: String,
+
+        /// Decimal places of the rune, as etched
+        #[arg(long, default_value = "0")]
+        rune_divisibility: u8,
+
+        /// Confirmations a rune payment input's funding transaction needs, so unconfirmed payments can't vanish
+        #[arg(long, default_value = "1")]
+        min_payment_confirmations: u32,
+
+        /// Another rune index that has to agree with ord about every payment input: ord:<url> or
+        /// unisat:[<api key>@]<url> (repeat for each)
+        #[arg(long = "rune-index")]
+        rune_indexes: Vec<String>,
+
+        /// How long quotes stay valid (in seconds)
+        #[arg(long, default_value = "60")]
+        quote_ttl: u64,
+
+        /// Withdraw standing quotes when the estimated fee rate rises this many percent over what they were priced at
+        #[arg(long, requires = "fee_target")]
+        quote_fee_spike: Option<f64>,
+
+        /// Check packages against bitcoind's block template, reporting and watching whether they make the next block
+        #[arg(long)]
+        predict_next_block: bool,
+
+        /// Replace the child of a package that falls out of the next block with one paying its way back in (needs --predict-next-block)
+        #[arg(long)]
+        bump_to_next_block: bool,
+
+        /// URL clients should use to reach this searcher, for announcements
+        #[arg(long)]
+        public_url: Option<String>,
+
+        /// How to reach the operator (e.g. an email address or Nostr npub), published at /info
+        #[arg(long)]
+        contact: Option<String>,
+
+        /// Nostr relay to announce on (repeat for each relay)
+        #[arg(long = "nostr-relay")]
+        nostr_relays: Vec<String>,
+
+        /// Hex secret key to sign Nostr announcements with
+        #[arg(long)]
+        nostr_secret_key: Option<String>,
+
+        /// Lightning node to accept fee payments through
+        #[arg(long, value_enum, requires_all = ["lightning_url", "lightning_auth"])]
+        lightning_backend: Option<LightningBackend>,
+
+        /// Lightning node REST URL
+        #[arg(long)]
+        lightning_url: Option<String>,
+
+        /// LND macaroon (hex) or CLN rune for the Lightning node
+        #[arg(long)]
+        lightning_auth: Option<String>,
+
+        /// Lightning price in sats per vbyte of sponsored package (defaults to the fee rate)
+        #[arg(long)]
+        lightning_price: Option<u64>,
+
+        /// Accept inscriptions sent to this address as payment
+        #[arg(long)]
+        ordinal_address: Option<String>,
+
+        /// Only accept inscriptions that are children of this parent inscription
+        #[arg(long, requires = "ordinal_address")]
+        inscription_collection: Option<String>,
+
+        /// Price in USD per vbyte of sponsored package, converted to runes at quote time
+        #[arg(long, requires = "rate_providers")]
+        usd_price: Option<f64>,
+
+        /// USD per rune unit source, tried in order: static:<rate> or <url>#<json pointer>
+        #[arg(long = "rate-provider")]
+        rate_providers: Vec<RateProvider>,
+
+        /// Oldest exchange rate to quote with when every provider is failing (in seconds)
+        #[arg(long, default_value = "600")]
+        rate_max_age: u64,
+
+        /// Price runes from the mempool fee histogram at this many sats per whole rune, with --rune-price as the fallback
+        #[arg(long, conflicts_with = "usd_price")]
+        sats_per_rune: Option<f64>,
+
+        /// Margin over the mempool fee rate for histogram pricing (in percent)
+        #[arg(long, default_value = "10", requires = "sats_per_rune")]
+        price_margin: f64,
+
+        /// How deep into the mempool histogram pricing reads its fee rate (in vbytes)
+        #[arg(long, default_value = "1000000", requires = "sats_per_rune")]
+        histogram_depth: u64,
+
+        /// Refuse rune payments too small to get the package within this many blocks' worth of the top of the mempool
+        #[arg(long)]
+        max_mempool_depth: Option<f64>,
+
+        /// Price multiplier for parents up to a vsize: <max parent vsize>:<multiplier> (repeat for each tier)
+        #[arg(long = "price-tier")]
+        price_tiers: Vec<PriceTier>,
+
+        /// Refuse packages larger than this many vbytes
+        #[arg(long)]
+        max_package_vsize: Option<u64>,
+
+        /// How to treat parents that break the ephemeral dust relay rules
+        #[arg(long, value_enum, default_value = "warn")]
+        ephemeral_policy: EphemeralPolicy,
+
+        /// Anchors to accept: P2A, P2WPKH to a key handed out by /quote, or either
+        #[arg(long, value_enum, default_value = "p2a")]
+        anchor_policy: AnchorKind,
+
+        /// Require keyed parents to deposit this much to the anchor key (in satoshis)
+        #[arg(long)]
+        anchor_deposit: Option<u64>,
+
+        /// Blocks after which submitters can take back a deposit the searcher hasn't claimed
+        #[arg(long, default_value = "144")]
+        deposit_delay: u16,
+
+        /// Detach from the terminal and run in the background
+        #[arg(long)]
+        daemon: bool,
+
+        /// Write the searcher's PID here, refusing to start if another searcher holds it
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+
+        /// Where a daemonized searcher writes its logs (defaults to discarding them)
+        #[arg(long, requires = "daemon")]
+        log_file: Option<PathBuf>,
+
+        /// Address to serve on, like [::]:3000 for IPv6 and IPv4 (repeat for each)
+        #[arg(long = "listen", default_value = server::LISTEN_ADDR)]
+        listen: Vec<SocketAddr>,
+
+        /// Take the client address from X-Forwarded-For on connections from this IP or CIDR block (repeat for each)
+        #[arg(long = "trusted-proxy")]
+        trusted_proxies: Vec<String>,
+
+        /// Require a PROXY protocol v1 or v2 header on every connection, as sent by HAProxy or a TCP load balancer
+        #[arg(long)]
+        proxy_protocol: bool,
+
+        /// Tor control port to publish the searcher as an onion service through (e.g. 127.0.0.1:9051)
+        #[arg(long)]
+        tor_control: Option<String>,
+
+        /// Tor control port password (defaults to cookie authentication)
+        #[arg(long, requires = "tor_control")]
+        tor_password: Option<String>,
+
+        /// File holding the onion service key, so the .onion address survives restarts
+        #[arg(long, requires = "tor_control")]
+        tor_key_file: Option<PathBuf>,
+
+        /// Shared key to sign responses with (HMAC-SHA256), so clients can detect tampering and replays
+        #[arg(long)]
+        response_key: Option<String>,
+
+        /// File to record jobs in, so /stats covers more than the current run
+        #[arg(long)]
+        job_store: Option<PathBuf>,
+
+        /// Append-only, hash-chained log of every job accepted, rejected, cancelled or confirmed
+        #[arg(long)]
+        audit_log: Option<PathBuf>,
+
+        /// Sponsor parents paying this script for free: an address, script:<hex> or prefix:<hex> (repeat for each)
+        #[arg(long = "sponsor-script", requires = "sponsor_budget")]
+        sponsor_scripts: Vec<String>,
+
+        /// Sats per UTC day to spend sponsoring whitelisted parents
+        #[arg(long, requires = "sponsor_scripts")]
+        sponsor_budget: Option<u64>,
+
+        /// Also anchor paying parents broadcast straight to the mempool, scanning this often (in seconds)
+        #[arg(long)]
+        scan_mempool: Option<u64>,
+
+        /// When the child can't be built or signed: fail, broadcast the parent alone if it relays, or return it with a diagnosis
+        #[arg(long, value_enum, default_value = "off")]
+        soft_fail: SoftFailMode,
+
+        /// Seconds a submission may take before it's abandoned and the client told to retry
+        #[arg(long, default_value = "120")]
+        job_timeout: u64,
+
+        /// Flag clients making more than this many quotes or dry runs per submission
+        #[arg(long)]
+        max_quote_ratio: Option<f64>,
+
+        /// Flag clients after this many submissions refused for their own fault
+        #[arg(long)]
+        max_invalid_submissions: Option<u64>,
+
+        /// Window over which client quotes and submissions are counted (in seconds)
+        #[arg(long, default_value = "3600")]
+        abuse_window: u64,
+
+        /// Ban flagged clients for this long instead of only logging them (in seconds)
+        #[arg(long)]
+        ban_duration: Option<u64>,
+
+        /// Also send accepted packages to this bitcoind, to help them propagate:
+        /// [<user>:<password>@]<host>[:<port>], with the main node's credentials by default (repeat for each)
+        #[arg(long = "broadcast-node")]
+        broadcast_nodes: Vec<server::BroadcastNode>,
+
+        /// P2P peer (host:port) to announce packages to when bitcoind predates submitpackage,
+        /// instead of sending parent and child one at a time
+        #[arg(long)]
+        package_peer: Option<String>,
+
+        /// Also serve another network under /<network>: network=<name>,bitcoind=<url>,ord=<url>,wallet=<name>[,rune-address=<address>] (repeat for each network)
+        #[arg(long = "serve-network")]
+        serve_networks: Vec<server::NetworkConfig>,
+
+        /// Rule every parent must pass: destinations=<address>,..., no-op-return, or command=<path> (repeat for each rule)
+        #[arg(long = "parent-rule")]
+        parent_rules: Vec<String>,
+
+        /// Regtest only: mine a block after each accepted package
+        #[arg(long)]
+        auto_mine: bool,
+
+        /// Regtest only: mine a block this often (in seconds)
+        #[arg(long)]
+        auto_mine_interval: Option<u64>,
+
+        /// Check bitcoind, the wallets and ord, print the report and exit instead of starting
+        #[arg(long)]
+        preflight: bool,
+
+        /// Marketplace endpoint to post signed rune sell offers to
+        #[arg(long, requires_all = ["liquidation_price", "rune_address"])]
+        liquidation_venue: Option<String>,
+
+        /// Rune units to keep; anything above is offered for sale
+        #[arg(long, default_value = "0", requires = "liquidation_venue")]
+        liquidate_above: u64,
+
+        /// Asking price in sats per rune unit
+        #[arg(long, requires = "liquidation_venue")]
+        liquidation_price: Option<f64>,
+
+        /// Only sell runes while the wallet holds fewer than this many sats
+        #[arg(long, requires = "liquidation_venue")]
+        float_target: Option<u64>,
+    },
+    /// Relay submissions to the cheapest of several upstream searchers
+    Relay {
+        /// Upstream searcher base URL (repeat for each searcher)
+        #[arg(long = "upstream", required = true)]
+        upstreams: Vec<String>,
+
+        /// Submit to every upstream at once instead of one at a time
+        #[arg(long)]
+        race: bool,
+
+        /// Key upstream searchers sign responses with; unsigned or tampered responses are ignored
+        #[arg(long)]
+        upstream_key: Option<String>,
+
+        /// Shared key to sign the relay's own responses with
+        #[arg(long)]
+        response_key: Option<String>,
+    },
+    /// Save, list and remove labelled addresses to pass as @label
+    Address {
+        #[command(subcommand)]
+        command: address_book::AddressCommand,
+    },
+    /// Sign for a taproot UTXO shared with cosigners, with MuSig2
+    Musig {
+        #[command(subcommand)]
+        command: musig::MusigCommand,
+    },
+    /// Manage the searcher's Bitcoin Core wallet
+    Wallet {
+        /// Bitcoin Core wallet name to use
+        #[arg(long, default_value = "searcher")]
+        wallet: String,
+
+        #[command(subcommand)]
+        command: wallet::WalletCommand,
+    },
+    /// Run a full narrated demo against regtest
+    Simulate {
+        /// Path to the ord binary used for etching
+        #[arg(long, default_value = "ord")]
+        ord_binary: String,
+    },
+    /// Show a searcher's operational status
+    Status {
+        /// Searcher base URL
+        #[arg(long)]
+        searcher_url: String,
+
+        /// Key the searcher signs responses with; unsigned, tampered or replayed responses are rejected
+        #[arg(long)]
+        searcher_key: Option<String>,
+    },
+    /// Check whether a transaction has confirmed
+    TxStatus {
+        /// Transaction ID to look up
+        #[arg(long)]
+        txid: String,
+
+        /// mempool.space API to ask when bitcoind doesn't know the transaction
+        #[arg(long)]
+        mempool_api: Option<String>,
+    },
+    /// Watch the mempool for slugline packages
+    Monitor {
+        /// Runes to look for in parent payments
+        #[arg(long = "rune", default_value = "TESTSLUGLINERUNE")]
+        runes: Vec<String>,
+
+        /// Seconds between mempool scans
+        #[arg(long, default_value = "10")]
+        interval: u64,
+
+        /// Scan once and exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Find searchers announced on Nostr
+    Discover {
+        /// Nostr relay to search (repeat for each relay)
+        #[arg(long = "relay", required = true)]
+        relays: Vec<String>,
+
+        /// Rune the searcher must accept
+        #[arg(long, default_value = "TESTSLUGLINERUNE")]
+        rune: String,
+
+        /// Ignore announcements older than this (in seconds)
+        #[arg(long, default_value = "3600")]
+        max_age: u64,
+    },
+    /// Take back a keyed-anchor deposit once its delay has passed
+    ClaimRefund {
+        /// Deposit outpoint (txid:vout)
+        #[arg(long)]
+        deposit: String,
+
+        /// Deposit value (in satoshis)
+        #[arg(long)]
+        amount: u64,
+
+        /// Deposit witness script printed by build-tx
+        #[arg(long)]
+        witness_script: String,
+
+        /// Anchor script the parent was built with
+        #[arg(long)]
+        anchor_script: String,
+
+        /// WIF private key of the refund pubkey
+        #[arg(long, env = "SLUGLINE_REFUND_KEY", hide_env_values = true)]
+        refund_key: String,
+
+        /// Address to send the refund to
+        #[arg(long)]
+        destination_address: String,
+
+        /// Fee rate for the refund (in sat/vB)
+        #[arg(long, default_value = "2.0")]
+        fee_rate: f64,
+    },
+    /// Broadcast a signed transaction or PSBT through the chain backend
+    Broadcast {
+        /// File containing a fully signed PSBT or raw transaction hex, or their scanned UR or BBQr parts
+        #[arg(long)]
+        tx: String,
+    },
+    /// Have a searcher fund the parent directly instead of with a CPFP child
+    Payjoin {
+        /// Searcher base URL
+        #[arg(long)]
+        searcher_url: String,
+
+        /// File containing the base64 PSBT built by build-tx, or its scanned UR or BBQr parts
+        #[arg(long)]
+        psbt: String,
+
+        /// Payment hash of a paid Lightning quote
+        #[arg(long)]
+        payment_hash: Option<String>,
+
+        /// File to write the searcher-signed PSBT to
+        #[arg(long, default_value = "payjoin.psbt")]
+        output: String,
+
+        /// Show the searcher-signed PSBT as QR codes for an air-gapped signer: ur (the default) or bbqr
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "ur")]
+        qr: Option<qr::QrFormat>,
+
+        /// Key the searcher signs responses with; unsigned, tampered or replayed responses are rejected
+        #[arg(long)]
+        searcher_key: Option<String>,
+    },
+    /// Merge cosigners' signatures on a parent PSBT, finalize it once complete, and optionally submit it
+    Combine {
+        /// PSBT file from a cosigner, base64 or scanned UR or BBQr parts (repeat for each)
+        #[arg(long = "psbt", required = true)]
+        psbts: Vec<String>,
+
+        /// File to write the combined, or once complete finalized, PSBT to
+        #[arg(long, default_value = "combined.psbt")]
+        output: String,
+
+        /// Submit the finalized parent to this searcher
+        #[arg(long)]
+        searcher_url: Option<String>,
+
+        /// Payment hash of a paid Lightning quote, when submitting
+        #[arg(long, requires = "searcher_url")]
+        payment_hash: Option<String>,
+
+        /// Show the combined PSBT as QR codes for the next air-gapped cosigner: ur (the default) or bbqr
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "ur")]
+        qr: Option<qr::QrFormat>,
+
+        /// Key the searcher signs responses with; unsigned, tampered or replayed responses are rejected
+        #[arg(long, requires = "searcher_url")]
+        searcher_key: Option<String>,
+    },
+    /// Fetch a fee quote from a searcher
+    Quote {
+        /// Searcher base URL
+        #[arg(long)]
+        searcher_url: String,
+
+        /// File containing the base64 PSBT to quote, or its scanned UR or BBQr parts
+        #[arg(long, conflicts_with = "vsize", required_unless_present = "vsize")]
+        psbt: Option<String>,
+
+        /// Parent transaction vsize to quote (in vbytes)
+        #[arg(long)]
+        vsize: Option<u64>,
+
+        /// Ask for a Lightning invoice instead of a rune price
+        #[arg(long)]
+        lightning: bool,
+
+        /// Show the Lightning invoice as a QR code for a phone wallet to scan
+        #[arg(long)]
+        qr: bool,
+
+        /// Key the searcher signs responses with; unsigned, tampered or replayed responses are rejected
+        #[arg(long)]
+        searcher_key: Option<String>,
+    },
+    /// List rune payments by the address they were received at, from a searcher's job store
+    RunePayments {
+        /// The searcher's --job-store file
+        #[arg(long)]
+        job_store: PathBuf,
+    },
+    /// Check a searcher's audit log hasn't been altered since it was written
+    VerifyAuditLog {
+        /// The searcher's --audit-log file
+        #[arg(long)]
+        audit_log: PathBuf,
+
+        /// Head hash from an earlier check, which has to still be in the chain
+        #[arg(long)]
+        checkpoint: Option<String>,
+    },
+    /// Report what a searcher spent in fees and earned in payments, from its job store
+    Earnings {
+        /// The searcher's --job-store file
+        #[arg(long)]
+        job_store: PathBuf,
+
+        /// Only count jobs received from the start of this UTC day (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Decimal places of the rune, to show rune amounts in whole runes
+        #[arg(long, default_value = "0")]
+        rune_divisibility: u8,
+    },
+    /// Export a ledger of the fees a searcher paid and the payments it took, from its job store
+    Export {
+        /// The searcher's --job-store file
+        #[arg(long)]
+        job_store: PathBuf,
+
+        /// First UTC day to include (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Last UTC day to include (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// File format; parquet needs slugline built with --features parquet
+        #[arg(long, value_enum, default_value = "csv")]
+        format: export::Format,
+
+        /// File to write instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Re-run a stored job's validation against the chain as it is now, to see why it failed or
+    /// was evicted. Nothing is broadcast.
+    Replay {
+        /// The searcher's --job-store file
+        #[arg(long)]
+        job_store: PathBuf,
+
+        /// Job id, or the txid of its parent or child
+        #[arg(long)]
+        job: String,
+
+        /// The searcher's --min-payment-confirmations
+        #[arg(long, default_value = "1")]
+        min_payment_confirmations: u32,
+
+        /// The searcher's --rune-index settings, to cross-check payment inputs the same way
+        #[arg(long = "rune-index")]
+        rune_indexes: Vec<String>,
+    },
+    /// Print a shell completion script, e.g. `slugline completions bash > /etc/bash_completion.d/slugline`
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, e.g. `slugline man > /usr/local/share/man/man1/slugline.1`
+    Man {
+        /// Write pages for slugline and every subcommand to this directory instead
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+// Services log at info by default, one-shot commands only warn so their output stays readable
+fn init_logging(cli: &Cli) -> Result<(), CliError> {
+    let service = matches!(cli.command, Commands::RunSearcher { .. } | Commands::Relay { .. });
+    let level = match cli.verbose + if service { 2 } else { 1 } {
+        0 | 1 => "warn",
+        2 => "info",
+        3 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,slugline={}", level)));
+
+    #[cfg(feature = "otel")]
+    let otel = match &cli.otlp_endpoint {
+        Some(endpoint) => Some(
+            slugline::telemetry::otlp_layer(endpoint)
+                .map_err(|e| format!("Cannot export traces to {}: {}", endpoint, e))?,
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "otel"))]
+    let otel: Option<tracing_subscriber::layer::Identity> = None;
+
+    // try_init rather than init, which panics if a subscriber is already installed
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(otel)
+        .try_init();
+    Ok(())
+}
+
+fn setup(cli: &Cli) -> Result<(), CliError> {
+    // Forking has to come before logging starts any exporter threads
+    if let Commands::RunSearcher { daemon: true, log_file, .. } = &cli.command {
+        daemon::daemonize(log_file.as_deref())?;
+    }
+    init_logging(cli)
+}
+
+// Every flag can also be set with SLUGLINE_<FLAG>, like SLUGLINE_BITCOIND_PASSWORD, so
+// secrets stay out of shell history and process listings. The command line wins.
+fn with_env(command: Command) -> Command {
+    command
+        .mut_args(|arg| {
+            if arg.is_positional() || arg.get_id() == "verbose" {
+                return arg;
+            }
+            let flag = arg.get_long().unwrap_or(arg.get_id().as_str());
+            let name = format!("SLUGLINE_{}", flag.replace('-', "_").to_uppercase());
+            // Switches would otherwise only take exactly "true" or "false"
+            let arg = match arg.get_action() {
+                ArgAction::SetTrue => arg.value_parser(FalseyValueParser::new()),
+                _ => arg,
+            };
+            arg.env(name).hide_env_values(true)
+        })
+        .mut_subcommands(with_env)
+}
+
+fn command() -> Command {
+    with_env(Cli::command())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::from_arg_matches(&command().get_matches()).unwrap_or_else(|e| e.exit());
+
+    let result = setup(&cli).and_then(|()| run(cli));
+    #[cfg(feature = "otel")]
+    slugline::telemetry::shutdown();
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+// The address book, or an empty one when there's nowhere to keep it
+fn address_book(path: Option<&Path>) -> Result<address_book::AddressBook, CliError> {
+    match path.map(Path::to_path_buf).or_else(address_book::default_path) {
+        Some(path) => address_book::AddressBook::load(&path),
+        None => Ok(address_book::AddressBook::default()),
+    }
+}
+
+fn searcher_client(url: &str, http: &HttpConfig, response_key: Option<String>) -> SearcherClient {
+    SearcherClient::with_http(url, http.blocking_client()).with_response_key(response_key)
+}
+
+// socks5:// resolves names locally, which can't work for .onion hosts and leaks every
+// lookup around Tor, so have the proxy resolve them instead
+fn parse_proxy(url: &str) -> Result<reqwest::Proxy, reqwest::Error> {
+    match url.strip_prefix("socks5://") {
+        Some(rest) => reqwest::Proxy::all(format!("socks5h://{}", rest)),
+        None => reqwest::Proxy::all(url),
+    }
+}
+
+// Keyring entries are looked up under this service name
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "slugline";
+
+// The password from the flag, a file (without its trailing newline), or the OS keyring
+fn bitcoind_password(cli: &Cli) -> Result<Option<String>, CliError> {
+    if let Some(path) = &cli.bitcoind_password_file {
+        let password = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading password file {}: {}", path.display(), e))?;
+        return Ok(Some(password.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    #[cfg(feature = "keyring")]
+    if let Some(user) = &cli.bitcoind_password_keyring {
+        let password = keyring::Entry::new(KEYRING_SERVICE, user)
+            .and_then(|entry| entry.get_password())
+            .map_err(|e| format!("Error reading keyring entry {} for {}: {}", KEYRING_SERVICE, user, e))?;
+        return Ok(Some(password));
+    }
+    Ok(cli.bitcoind_password.clone())
+}
+
+fn run(mut cli: Cli) -> Result<(), CliError> {
+    cli.bitcoind_password = bitcoind_password(&cli)?;
+    let http = HttpConfig {
+        connect_timeout: Duration::from_secs(cli.http_connect_timeout),
+        timeout: Duration::from_secs(cli.http_timeout),
+        proxy: cli.proxy.clone(),
+    };
+
+    match cli.command {
+        Commands::BuildTx {
+            profile,
+            btc_address,
+            btc_descriptor,
+            watch_wallet,
+            descriptor_range,
+            rescan_since,
+            runes_address,
+            destination_address,
+            amount,
+            rune_amount,
+            lightning,
+            allow_valuable_rune_utxo,
+            max_rune_amount,
+            change_address,
+            no_rbf,
+            locktime,
+            timelocked_path,
+            deterministic,
+            anchor_script,
+            deposit_amount,
+            deposit_delay,
+            refund_pubkey,
+            sign_with_hww,
+            hwi_binary,
+            hww_fingerprint,
+            hww_path,
+            hww_runes_path,
+            qr,
+        } => {
+            // Flags win over the profile
+            let profile = match profile {
+                Some(name) => {
+                    let path = cli.config.as_deref().ok_or("--profile needs --config")?;
+                    config::Config::load(path)?.profile(&name)?.clone()
+                }
+                None => config::BuildProfile::default(),
+            };
+            let (btc_address, btc_descriptor) = match (btc_address, btc_descriptor) {
+                (None, None) => (profile.btc_address, profile.btc_descriptor),
+                flags => flags,
+            };
+            if btc_address.is_none() && btc_descriptor.is_none() {
+                return Err("--btc-address or --btc-descriptor is required, on the command line or in the profile".into());
+            }
+            let runes_address = runes_address.or(profile.runes_address);
+            if runes_address.is_none() && !lightning {
+                return Err("--runes-address is required without --lightning, on the command line or in the profile".into());
+            }
+            let destination_address = destination_address.or(profile.destination_address)
+                .ok_or("--destination-address is required, on the command line or in the profile")?;
+            // Any address can be given as @label
+            let book = address_book(cli.address_book.as_deref())?;
+            let btc_address = btc_address.map(|address| book.resolve(&address)).transpose()?;
+            let runes_address = runes_address.map(|address| book.resolve(&address)).transpose()?;
+            let destination_address = book.resolve(&destination_address)?;
+            // A pasted bitcoin: URI names the amount too, which --amount has to agree with
+            let (destination_address, amount) = match destination_address.get(..8) {
+                Some(scheme) if scheme.eq_ignore_ascii_case("bitcoin:") => {
+                    let request: slugline::build_tx::PaymentUri = destination_address.parse()?;
+                    if let (Some(amount), Some(requested)) = (amount, request.amount_sat)
+                        && amount != requested
+                    {
+                        return Err(format!("--amount {} doesn't match the {} sats the URI asks for", amount, requested).into());
+                    }
+                    if let Some(label) = &request.label {
+                        println!("Payment request from: {}", label);
+                    }
+                    if let Some(message) = &request.message {
+                        println!("Payment request message: {}", message);
+                    }
+                    (request.address, amount.or(request.amount_sat))
+                }
+                _ => (destination_address, amount),
+            };
+            let amount = amount.or(profile.amount)
+                .ok_or("--amount is required, on the command line, in the URI or in the profile")?;
+            let change_address = change_address.or(profile.change_address)
+                .map(|address| book.resolve(&address))
+                .transpose()?;
+            let policy = build_tx::TxPolicy {
+                change_address,
+                rbf: !no_rbf && profile.rbf.unwrap_or(true),
+                locktime: locktime.or(profile.locktime).unwrap_or(config::Locktime::None),
+                max_rune_amount: max_rune_amount.or(profile.max_rune_amount),
+                timelocked_path,
+                deterministic: deterministic || profile.deterministic.unwrap_or(false),
+            };
+            let source = match (btc_descriptor, btc_address) {
+                (Some(descriptor), _) => build_tx::BtcSource::Descriptor(build_tx::DescriptorSource {
+                    descriptor,
+                    wallet: watch_wallet,
+                    range: descriptor_range,
+                    rescan_since,
+                }),
+                (None, address) => build_tx::BtcSource::Address(address.unwrap_or_default()),
+            };
+            let hww = match (sign_with_hww, hww_path) {
+                (true, Some(path)) => Some(build_tx::HwwOptions {
+                    hwi_binary,
+                    fingerprint: hww_fingerprint,
+                    path,
+                    runes_path: hww_runes_path,
+                }),
+                _ => None,
+            };
+            let deposit = match (deposit_amount, refund_pubkey) {
+                (Some(amount), Some(refund_pubkey)) => Some(build_tx::DepositOptions {
+                    amount,
+                    delay: deposit_delay,
+                    refund_key: bitcoin::PublicKey::from_str(&refund_pubkey)
+                        .map_err(|e| format!("Invalid refund pubkey {}: {}", refund_pubkey, e))?,
+                }),
+                _ => None,
+            };
+            build_tx::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                &cli.ord_server,
+                &http.blocking_client(),
+                cli.chain_backend.as_ref(),
+                &source,
+                runes_address.as_deref(),
+                &destination_address,
+                amount,
+                rune_amount.as_deref(),
+                lightning,
+                allow_valuable_rune_utxo,
+                &policy,
+                &book,
+                anchor_script.as_deref(),
+                deposit.as_ref(),
+                hww.as_ref(),
+                qr,
+            )?;
+        }
+        Commands::RunSearcher {
+            wallets,
+            fee_rate,
+            max_fee_rate,
+            max_fee,
+            max_fee_percent,
+            max_parent_fee,
+            rune_address,
+            rotate_rune_address,
+            rune_price,
+            rune_divisibility,
+            min_payment_confirmations,
+            rune_indexes,
+            quote_ttl,
+            quote_fee_spike,
+            predict_next_block,
+            bump_to_next_block,
+            public_url,
+            contact,
+            nostr_relays,
+            nostr_secret_key,
+            lightning_backend,
+            lightning_url,
+            lightning_auth,
+            lightning_price,
+            ordinal_address,
+            inscription_collection,
+            usd_price,
+            rate_providers,
+            rate_max_age,
+            sats_per_rune,
+            price_margin,
+            histogram_depth,
+            max_mempool_depth,
+            price_tiers,
+            max_package_vsize,
+            ephemeral_policy,
+            anchor_policy,
+            anchor_deposit,
+            deposit_delay,
+            fee_target,
+            mempool_api,
+            pid_file,
+            listen,
+            trusted_proxies,
+            proxy_protocol,
+            tor_control,
+            tor_password,
+            tor_key_file,
+            response_key,
+            job_store,
+            audit_log,
+            liquidation_venue,
+            liquidate_above,
+            liquidation_price,
+            float_target,
+            sponsor_scripts,
+            sponsor_budget,
+            scan_mempool,
+            soft_fail,
+            job_timeout,
+            max_quote_ratio,
+            max_invalid_submissions,
+            abuse_window,
+            ban_duration,
+            serve_networks,
+            broadcast_nodes,
+            package_peer,
+            parent_rules,
+            auto_mine,
+            auto_mine_interval,
+            preflight,
+            revenue_address,
+            revenue_sats,
+            min_balance,
+            min_free_utxos,
+            funds_check_interval,
+            consolidate_to,
+            consolidate_min_utxos,
+            consolidate_max_fee_rate,
+            consolidate_interval,
+            low_funds_webhook,
+            low_funds_nostr,
+            notify,
+            notify_large_job,
+            external_signer,
+            external_signer_token,
+            ..
+        } => {
+            let network = parse_network(&format!("{:?}", cli.network).to_lowercase());
+            let revenue = match (revenue_address, revenue_sats) {
+                (Some(address), Some(amount_sat)) => Some(server::Revenue {
+                    address: Address::from_str(&address)
+                        .and_then(|a| a.require_network(network))
+                        .map_err(|e| format!("Invalid revenue address {}: {}", address, e))?,
+                    amount_sat,
+                }),
+                _ => None,
+            };
+            let low_funds = (min_balance.is_some() || min_free_utxos.is_some()).then_some(server::LowFunds {
+                min_balance_sat: min_balance,
+                min_utxos: min_free_utxos,
+                interval: funds_check_interval,
+                webhook: low_funds_webhook,
+                nostr_pubkey: low_funds_nostr,
+            });
+            let inscription_payment = match ordinal_address {
+                Some(address) => {
+                    let script_pubkey = Address::from_str(&address)
+                        .and_then(|a| a.require_network(network))
+                        .map_err(|e| format!("Invalid ordinal address {}: {}", address, e))?
+                        .script_pubkey();
+                    Some(InscriptionPayment {
+                        collection: inscription_collection,
+                        address,
+                        script_pubkey,
+                    })
+                }
+                None => None,
+            };
+            let sponsorship = match sponsor_budget {
+                Some(daily_budget_sat) => Some(SponsorshipConfig {
+                    templates: sponsor_scripts.iter()
+                        .map(|template| ScriptTemplate::parse(template, network))
+                        .collect::<Result<_, _>>()?,
+                    daily_budget_sat,
+                }),
+                None => None,
+            };
+            let lightning = lightning_backend.map(|backend| payments::LightningConfig {
+                backend: match backend {
+                    LightningBackend::Lnd => payments::LightningBackend::Lnd,
+                    LightningBackend::Cln => payments::LightningBackend::Cln,
+                },
+                url: lightning_url.unwrap_or_default(),
+                auth: lightning_auth.unwrap_or_default(),
+                price: lightning_price,
+            });
+            let rune_price = RuneAmount::parse(&rune_price, rune_divisibility)
+                .map_err(|e| format!("Invalid --rune-price: {}", e))?;
+            if max_mempool_depth.is_some_and(|blocks| !(blocks > 0.0 && blocks.is_finite())) {
+                return Err("--max-mempool-depth has to be a positive number of blocks".into());
+            }
+            if quote_fee_spike.is_some_and(|percent| !(percent > 0.0 && percent.is_finite())) {
+                return Err("--quote-fee-spike has to be a positive percentage".into());
+            }
+            if job_timeout == 0 {
+                return Err("--job-timeout has to be at least a second".into());
+            }
+            if sats_per_rune.is_some_and(|sats| !(sats > 0.0 && sats.is_finite())) {
+                return Err("--sats-per-rune has to be a positive number".into());
+            }
+
+            let config = server::Config {
+                bitcoind_host: cli.bitcoind_host,
+                bitcoind_user: cli.bitcoind_user,
+                bitcoind_password: cli.bitcoind_password,
+                broadcast_nodes,
+                package_peer,
+                network,
+                ord_server: cli.ord_server,
+                wallets,
+                fee_rate,
+                max_fee_rate,
+                max_fee,
+                max_fee_percent,
+                max_parent_fee,
+                rune_address,
+                rotate_rune_address,
+                rune_price: rune_price.units,
+                rune_divisibility,
+                min_payment_confirmations,
+                rune_indexes: rune_indexes.iter().map(|index| rune_index::parse_index(index)).collect::<Result<_, _>>()?,
+                quote_ttl,
+                quote_fee_spike,
+                predict_next_block,
+                bump_to_next_block,
+                public_url,
+                contact,
+                nostr_relays,
+                nostr_secret_key,
+                lightning,
+                inscription_payment,
+                usd_price,
+                rate_providers,
+                rate_max_age,
+                histogram_pricing: sats_per_rune.map(|sats_per_rune| HistogramPricing {
+                    sats_per_rune,
+                    margin_percent: price_margin,
+                    depth_vbytes: histogram_depth,
+                }),
+                max_mempool_depth: max_mempool_depth.map(|blocks| (blocks * BLOCK_VBYTES as f64) as u64),
+                price_tiers,
+                max_package_vsize,
+                ephemeral_policy: match ephemeral_policy {
+                    EphemeralPolicy::Off => PolicyMode::Off,
+                    EphemeralPolicy::Warn => PolicyMode::Warn,
+                    EphemeralPolicy::Enforce => PolicyMode::Enforce,
+                },
+                anchor_policy: match anchor_policy {
+                    AnchorKind::P2a => AnchorPolicy::P2a,
+                    AnchorKind::Keyed => AnchorPolicy::Keyed,
+                    AnchorKind::Either => AnchorPolicy::Either,
+                },
+                revenue,
+                low_funds,
+                consolidation: consolidate_to.map(|target_sat| ConsolidationConfig {
+                    target_sat,
+                    min_inputs: consolidate_min_utxos,
+                    max_fee_rate: consolidate_max_fee_rate,
+                    interval: consolidate_interval,
+                }),
+                notify: notify.iter().map(|sink| notify::parse_sink(sink)).collect::<Result<_, _>>()?,
+                notify_large_job_sat: notify_large_job,
+                external_signer: external_signer.map(|url| SignerConfig { url, token: external_signer_token }),
+                anchor_deposit: anchor_deposit.map(|amount_sat| DepositTerms { amount_sat, delay: deposit_delay }),
+                fee_target,
+                mempool_api,
+                pid_file,
+                http,
+                listen,
+                trusted_proxies: trusted_proxies.iter().map(|proxy| listen::parse_proxy(proxy)).collect::<Result<_, _>>()?,
+                proxy_protocol,
+                tor_control,
+                tor_password,
+                tor_key_file,
+                response_key,
+                job_store,
+                audit_log,
+                liquidation: liquidation_venue.map(|venue_url| LiquidationConfig {
+                    venue_url,
+                    threshold: liquidate_above,
+                    price: liquidation_price.unwrap_or_default(),
+                    float_target,
+                    network,
+                }),
+                sponsorship,
+                mempool_scan: scan_mempool,
+                soft_fail: match soft_fail {
+                    SoftFailMode::Off => server::SoftFail::Off,
+                    SoftFailMode::Broadcast => server::SoftFail::Broadcast,
+                    SoftFailMode::Return => server::SoftFail::Return,
+                },
+                job_timeout,
+                abuse: AbuseConfig {
+                    max_quote_ratio,
+                    max_invalid: max_invalid_submissions,
+                    window_secs: abuse_window,
+                    ban_secs: ban_duration,
+                },
+                networks: serve_networks,
+                rules: parent_rules.iter()
+                    .map(|rule| rules::parse_rule(rule, network))
+                    .collect::<Result<_, _>>()?,
+                auto_mine,
+                auto_mine_interval,
+            };
+            if preflight {
+                let report = preflight::preflight(&config);
+                for check in &report.checks {
+                    println!("{}", check);
+                }
+                if !report.failures().is_empty() {
+                    return Err("Preflight failed".into());
+                }
+            } else {
+                server::run(config)?;
+            }
+        }
+        Commands::Relay {
+            upstreams,
+            race,
+            upstream_key,
+            response_key,
+        } => {
+            relay::run(relay::Config {
+                upstreams,
+                race,
+                http,
+                upstream_key,
+                response_key,
+            })?;
+        }
+        Commands::Address { command } => {
+            let path = cli.address_book.clone()
+                .or_else(address_book::default_path)
+                .ok_or("No home directory to keep the address book in, pass --address-book")?;
+            address_book::run(&path, command)?;
+        }
+        Commands::Musig { command } => {
+            musig::run(command, parse_network(&format!("{:?}", cli.network).to_lowercase()))?;
+        }
+        Commands::Wallet { wallet, command } => {
+            wallet::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                &wallet,
+                &command,
+            )?;
+        }
+        Commands::Simulate { ord_binary } => {
+            simulate::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                &cli.ord_server,
+                &ord_binary,
+                http.blocking_client(),
+            )?;
+        }
+        Commands::Status { searcher_url, searcher_key } => {
+            status::run(searcher_client(&searcher_url, &http, searcher_key))?;
+        }
+        Commands::TxStatus { txid, mempool_api } => {
+            tx_status::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                &txid,
+                mempool_api.as_deref(),
+            )?;
+        }
+        Commands::Monitor {
+            runes,
+            interval,
+            once,
+        } => {
+            monitor::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                &cli.ord_server,
+                &http.blocking_client(),
+                &runes,
+                interval,
+                once,
+            )?;
+        }
+        Commands::Discover {
+            relays,
+            rune,
+            max_age,
+        } => {
+            discover::run(
+                &format!("{:?}", cli.network).to_lowercase(),
+                &relays,
+                &rune,
+                max_age,
+            )?;
+        }
+        Commands::ClaimRefund {
+            deposit,
+            amount,
+            witness_script,
+            anchor_script,
+            refund_key,
+            destination_address,
+            fee_rate,
+        } => {
+            claim_refund::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                cli.chain_backend.as_ref(),
+                &deposit,
+                amount,
+                &witness_script,
+                &anchor_script,
+                &refund_key,
+                &destination_address,
+                fee_rate,
+            )?;
+        }
+        Commands::Broadcast { tx } => {
+            broadcast::run(
+                &cli.bitcoind_host,
+                cli.bitcoind_user.as_deref(),
+                cli.bitcoind_password.as_deref(),
+                &format!("{:?}", cli.network).to_lowercase(),
+                cli.chain_backend.as_ref(),
+                &tx,
+            )?;
+        }
+        Commands::Payjoin {
+            searcher_url,
+            psbt,
+            payment_hash,
+            output,
+            qr,
+            searcher_key,
+        } => {
+            payjoin::run(searcher_client(&searcher_url, &http, searcher_key), &psbt, payment_hash.as_deref(), &output, qr)?;
+        }
+        Commands::Combine { psbts, output, searcher_url, payment_hash, qr, searcher_key } => {
+            let client = searcher_url.map(|url| searcher_client(&url, &http, searcher_key));
+            combine::run(client, &psbts, payment_hash.as_deref(), &output, qr)?;
+        }
+        Commands::Quote {
+            searcher_url,
+            psbt,
+            vsize,
+            lightning,
+            qr,
+            searcher_key,
+        } => {
+            quote::run(searcher_client(&searcher_url, &http, searcher_key), psbt.as_deref(), vsize, lightning, qr)?;
+        }
+        Commands::RunePayments { job_store } => {
+            rune_payments::run(&job_store, &address_book(cli.address_book.as_deref())?)?;
+        }
+        Commands::VerifyAuditLog { audit_log, checkpoint } => {
+            verify_audit_log::run(&audit_log, checkpoint.as_deref())?;
+        }
+        Commands::Earnings { job_store, since, rune_divisibility } => {
+            earnings::run(&job_store, since.as_deref(), rune_divisibility)?;
+        }
+        Commands::Export { job_store, from, to, format, output } => {
+            export::run(&job_store, from.as_deref(), to.as_deref(), format, output.as_deref())?;
+        }
+        Commands::Replay { job_store, job, min_payment_confirmations, rune_indexes } => {
+            replay::run(&job_store, &job, min_payment_confirmations, replay::Chain {
+                bitcoind_host: &cli.bitcoind_host,
+                bitcoind_user: cli.bitcoind_user.as_deref(),
+                bitcoind_password: cli.bitcoind_password.as_deref(),
+                network: &format!("{:?}", cli.network).to_lowercase(),
+                ord_server: &cli.ord_server,
+                http: http.client(),
+                rune_indexes: rune_indexes.iter().map(|index| rune_index::parse_index(index)).collect::<Result<_, _>>()?,
+            })?;
+        }
+        Commands::Completions { shell } => {
+            completions::run(command(), shell);
+        }
+        Commands::Man { out_dir } => {
+            man::run(command(), out_dir.as_deref())?;
+        }
+    }
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+use clap::Command;
+use clap_mangen::Man;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::error::CliError;
+
+// One page to stdout, or a page per subcommand (slugline-run-searcher.1, ...) in a directory
+pub fn run(command: Command, out_dir: Option<&Path>) -> Result<(), CliError> {
+    match out_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+            clap_mangen::generate_to(command, dir).map_err(|e| format!("Error writing man pages to {}: {}", dir.display(), e))?;
+            println!("Wrote man pages to {}", dir.display());
+        }
+        None => Man::new(command)
+            .render(&mut io::stdout())
+            .map_err(|e| format!("Error writing man page: {}", e))?,
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use bitcoin::{
+    transaction::{OutPoint, Transaction},
+    Address, Network, ScriptBuf, Txid,
+};
+use bitcoincore_rpc::{Client, RpcApi};
+use slugline::anchor::is_p2a;
+use slugline::ord::fetch_output;
+use slugline::rpc::{self, parse_network};
+use std::collections::HashMap;
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::CliError;
+
+// What we learned about a mempool transaction the first time we saw it
+#[derive(Debug, Clone)]
+struct Parent {
+    anchor_vout: u32,
+    rune_payment: Vec<(String, u64)>,
+}
+
+// Anything else only matters as a potential child, so keep just its inputs and payout
+#[derive(Debug, Clone)]
+struct Other {
+    inputs: Vec<OutPoint>,
+    payout: Option<ScriptBuf>,
+}
+
+#[derive(Debug, Clone)]
+enum Seen {
+    Parent(Parent),
+    Other(Other),
+}
+
+impl From<Transaction> for Seen {
+    fn from(tx: Transaction) -> Self {
+        Seen::Other(Other {
+            inputs: tx.input.iter().map(|input| input.previous_output).collect(),
+            payout: tx.output.into_iter().next().map(|o| o.script_pubkey),
+        })
+    }
+}
+
+fn connect(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: Network,
+) -> Result<Client, Box<dyn Error>> {
+    rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, network, None).map_err(|e| {
+        let rpc_url = rpc::rpc_url(bitcoind_host, network, None);
+        format!("Failed to connect to Bitcoin Core at {} (network: {:?}): {}", rpc_url, network, e).into()
+    })
+}
+
+// Classify a transaction: v3 with a P2A anchor and a known rune in its last input is a slugline parent
+fn inspect(tx: Transaction, http: &reqwest::blocking::Client, ord_server: &str, runes: &[String]) -> Seen {
+    if tx.version != bitcoin::transaction::Version(3) {
+        return tx.into();
+    }
+
+    let Some(anchor_vout) = tx.output.iter().position(|o| is_p2a(&o.script_pubkey)) else {
+        return tx.into();
+    };
+
+    let Some(last_input) = tx.input.last() else {
+        return tx.into();
+    };
+
+    let rune_payment: Vec<(String, u64)> = match fetch_output(http, ord_server, &last_input.previous_output) {
+        Ok(output) => output.runes.into_iter()
+            .filter(|(name, _)| runes.contains(name))
+            .map(|(name, info)| (name, info.amount))
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to look up runes for {}: {}", last_input.previous_output, e);
+            Vec::new()
+        }
+    };
+
+    if rune_payment.is_empty() {
+        return tx.into();
+    }
+
+    Seen::Parent(Parent {
+        anchor_vout: anchor_vout as u32,
+        rune_payment,
+    })
+}
+
+fn scan(
+    client: &Client,
+    network: Network,
+    http: &reqwest::blocking::Client,
+    ord_server: &str,
+    runes: &[String],
+    seen: &mut HashMap<Txid, Seen>,
+) -> Result<(), Box<dyn Error>> {
+    let mempool = client.get_raw_mempool_verbose()?;
+
+    // Forget anything that left the mempool since the last scan
+    seen.retain(|txid, _| mempool.contains_key(txid));
+
+    for txid in mempool.keys() {
+        if seen.contains_key(txid) {
+            continue;
+        }
+        // The tx may have been evicted or mined between the two calls
+        if let Ok(tx) = client.get_raw_transaction(txid, None) {
+            seen.insert(*txid, inspect(tx, http, ord_server, runes));
+        }
+    }
+
+    println!(
+        "{:<64}  {:>7}  {:>9}  {:>9}  {:<24}  CHILDREN",
+        "PARENT", "VSIZE", "FEE RATE", "PKG RATE", "RUNE PAYMENT"
+    );
+
+    let mut parents = 0;
+    for (txid, entry) in &mempool {
+        let Some(Seen::Parent(parent)) = seen.get(txid) else {
+            continue;
+        };
+        parents += 1;
+
+        let fee_rate = entry.fees.base.to_sat() as f64 / entry.vsize as f64;
+        let package_rate = entry.fees.descendant.to_sat() as f64 / entry.descendant_size as f64;
+
+        let rune_payment = parent.rune_payment.iter()
+            .map(|(name, amount)| format!("{} {}", amount, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Children that spend the anchor, labelled with the address they pay out to
+        let children = entry.spent_by.iter()
+            .filter_map(|child_txid| match seen.get(child_txid) {
+                Some(Seen::Other(child)) => Some((child_txid, child)),
+                _ => None,
+            })
+            .filter(|(_, child)| {
+                child.inputs.contains(&OutPoint { txid: *txid, vout: parent.anchor_vout })
+            })
+            .map(|(child_txid, child)| {
+                let searcher = child.payout.as_ref()
+                    .and_then(|script| Address::from_script(script, network).ok())
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|| "<unknown>".to_string());
+                format!("{} -> {}", child_txid, searcher)
+            })
+            .collect::<Vec<_>>();
+
+        println!(
+            "{:<64}  {:>7}  {:>9.2}  {:>9.2}  {:<24}  {}",
+            txid,
+            entry.vsize,
+            fee_rate,
+            package_rate,
+            rune_payment,
+            if children.is_empty() { "-".to_string() } else { children.join(", ") }
+        );
+    }
+
+    println!("{} slugline parents in a mempool of {} transactions\n", parents, mempool.len());
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    ord_server: &str,
+    http: &reqwest::blocking::Client,
+    runes: &[String],
+    interval: u64,
+    once: bool,
+) -> Result<(), CliError> {
+    let network = parse_network(network);
+
+    let client = connect(bitcoind_host, bitcoind_user, bitcoind_password, network)?;
+
+    println!("Watching mempool for slugline packages paying {}", runes.join(", "));
+
+    let mut seen = HashMap::new();
+    loop {
+        let result = scan(&client, network, http, ord_server, runes, &mut seen);
+
+        if once {
+            return Ok(result?);
+        }
+        if let Err(e) = result {
+            eprintln!("Error scanning mempool: {}", e);
+        }
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
@@ -0,0 +1,298 @@
+use bitcoin::hashes::Hash;
+use bitcoin::key::TweakedPublicKey;
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::{taproot, Address, Network, PrivateKey, ScriptBuf};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use slugline::musig::{self, AggregateNonce, KeyAgg, PartialSignature, PublicNonce, SecretNonce};
+use std::fs;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::error::CliError;
+use crate::qr;
+
+#[derive(Subcommand, Debug)]
+pub enum MusigCommand {
+    /// Show the taproot address the cosigners' keys share, to fund the shared UTXO
+    Address {
+        /// Hex public key of a cosigner (repeat for each, including yours)
+        #[arg(long = "key", required = true)]
+        keys: Vec<String>,
+    },
+    /// Start signing an input of a parent: make this cosigner's nonces and a session file
+    Nonce {
+        /// PSBT file from build-tx, base64 or scanned UR or BBQr parts
+        #[arg(long)]
+        psbt: String,
+
+        /// Index of the input spending the shared UTXO
+        #[arg(long)]
+        input: usize,
+
+        /// WIF private key of this cosigner
+        #[arg(long)]
+        secret_key: String,
+
+        /// Hex public key of another cosigner (repeat for each)
+        #[arg(long = "cosigner", required = true)]
+        cosigners: Vec<String>,
+
+        /// File to keep the session in, secret nonce included; don't share it
+        #[arg(long, default_value = "musig.session")]
+        session: String,
+
+        /// File to write the public nonce to, for the other cosigners
+        #[arg(long, default_value = "musig-nonce.json")]
+        share: String,
+    },
+    /// Sign once every cosigner's nonce is in, using up the session's secret nonce
+    Sign {
+        #[arg(long, default_value = "musig.session")]
+        session: String,
+
+        /// WIF private key of this cosigner
+        #[arg(long)]
+        secret_key: String,
+
+        /// Nonce file from another cosigner (repeat for each)
+        #[arg(long = "share", required = true)]
+        shares: Vec<String>,
+
+        /// File to write the partial signature to, for the other cosigners
+        #[arg(long, default_value = "musig-partial.json")]
+        output: String,
+    },
+    /// Add up every cosigner's partial signature into the input's signature
+    Aggregate {
+        #[arg(long, default_value = "musig.session")]
+        session: String,
+
+        /// Partial signature file from another cosigner (repeat for each)
+        #[arg(long = "share", required = true)]
+        shares: Vec<String>,
+
+        /// File to write the PSBT with the input signed to
+        #[arg(long, default_value = "musig.psbt")]
+        output: String,
+    },
+}
+
+// One cosigner's progress, kept on their machine between steps
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    psbt: String,
+    input: usize,
+    // Every cosigner's key in aggregation order, ours among them
+    keys: Vec<String>,
+    key: String,
+    nonce: String,
+    // Dropped before the partial signature is written, so it can never sign twice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret_nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    partial_signature: Option<String>,
+}
+
+// What cosigners pass each other: a nonce, then a partial signature
+#[derive(Debug, Serialize, Deserialize)]
+struct Share {
+    key: String,
+    nonce: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    partial_signature: Option<String>,
+}
+
+fn parse_key(key: &str) -> Result<PublicKey, CliError> {
+    PublicKey::from_str(key).map_err(|e| format!("Invalid public key {}: {}", key, e).into())
+}
+
+fn parse_secret_key(wif: &str) -> Result<SecretKey, CliError> {
+    Ok(PrivateKey::from_wif(wif).map_err(|e| format!("Invalid secret key: {}", e))?.inner)
+}
+
+// The cosigners' keys sorted, aggregated and tweaked for a key-path-only taproot output
+fn key_agg(keys: &[String]) -> Result<(Vec<PublicKey>, KeyAgg), CliError> {
+    let mut keys = keys.iter().map(|key| parse_key(key)).collect::<Result<Vec<_>, _>>()?;
+    musig::sort_keys(&mut keys);
+    keys.dedup();
+    let agg = KeyAgg::new(&keys)?.tweak_for_taproot(None)?;
+    Ok((keys, agg))
+}
+
+fn output_script(agg: &KeyAgg) -> ScriptBuf {
+    ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(agg.output_key()))
+}
+
+// Taproot sighashes commit to every spent output, so build-tx's PSBT has to carry them all
+fn sighash(psbt: &Psbt, input: usize) -> Result<[u8; 32], CliError> {
+    let prevouts = psbt.inputs.iter()
+        .enumerate()
+        .map(|(index, input)| input.witness_utxo.clone().ok_or(format!("Input {} is missing its spent output", index)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sighash = SighashCache::new(&psbt.unsigned_tx)
+        .taproot_key_spend_signature_hash(input, &Prevouts::All(&prevouts), TapSighashType::Default)
+        .map_err(|e| format!("Failed to compute the sighash of input {}: {}", input, e))?;
+    Ok(sighash.to_byte_array())
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &str) -> Result<T, CliError> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid {}: {}", path, e).into())
+}
+
+fn write_json<T: Serialize>(path: &str, value: &T) -> Result<(), CliError> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("Error writing {}: {}", path, e))?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Sessions hold a secret nonce, so only their owner gets to read them
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+        .and_then(|mut file| file.write_all((json + "\n").as_bytes()))
+        .map_err(|e| format!("Error writing {}: {}", path, e).into())
+}
+
+impl Session {
+    fn load(path: &str) -> Result<(Self, Psbt, Vec<PublicKey>, KeyAgg), CliError> {
+        let session: Session = read_json(path)?;
+        let psbt = Psbt::from_str(&session.psbt).map_err(|e| format!("Invalid PSBT in {}: {}", path, e))?;
+        let (keys, agg) = key_agg(&session.keys)?;
+        Ok((session, psbt, keys, agg))
+    }
+
+    fn own_share(&self) -> Share {
+        Share { key: self.key.clone(), nonce: self.nonce.clone(), partial_signature: self.partial_signature.clone() }
+    }
+}
+
+// Every cosigner's share, ours included, in the aggregation order of their keys
+fn collect_shares(session: &Session, keys: &[PublicKey], paths: &[String]) -> Result<Vec<(PublicKey, Share)>, CliError> {
+    let mut shares = vec![(parse_key(&session.key)?, session.own_share())];
+    for path in paths {
+        let share: Share = read_json(path)?;
+        let key = parse_key(&share.key)?;
+        if !keys.contains(&key) {
+            return Err(format!("{} is from {}, who isn't one of this session's cosigners", path, key).into());
+        }
+        if shares.iter().any(|(seen, _)| *seen == key) {
+            return Err(format!("{} repeats the share of {}", path, key).into());
+        }
+        shares.push((key, share));
+    }
+    if let Some(missing) = keys.iter().find(|key| !shares.iter().any(|(seen, _)| seen == *key)) {
+        return Err(format!("Missing the share of cosigner {}", missing).into());
+    }
+    shares.sort_by_key(|(key, _)| keys.iter().position(|k| k == key));
+    Ok(shares)
+}
+
+fn aggregate_nonce(shares: &[(PublicKey, Share)]) -> Result<(Vec<PublicNonce>, AggregateNonce), CliError> {
+    let nonces = shares.iter()
+        .map(|(_, share)| share.nonce.parse::<PublicNonce>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let aggregate = AggregateNonce::new(&nonces)?;
+    Ok((nonces, aggregate))
+}
+
+fn parse_partial(key: &PublicKey, share: &Share) -> Result<PartialSignature, CliError> {
+    let partial = share.partial_signature.as_deref()
+        .ok_or_else(|| format!("Cosigner {} hasn't signed yet", key))?;
+    PartialSignature::from_str(partial).map_err(|e| format!("Invalid partial signature from {}: {}", key, e).into())
+}
+
+pub fn run(command: MusigCommand, network: Network) -> Result<(), CliError> {
+    match command {
+        MusigCommand::Address { keys } => {
+            let (_, agg) = key_agg(&keys)?;
+            let address = Address::p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(agg.output_key()), network);
+            println!("Internal key: {}", agg.internal_key());
+            println!("Address: {}", address);
+        }
+        MusigCommand::Nonce { psbt: psbt_path, input, secret_key, cosigners, session: session_path, share } => {
+            let contents = fs::read_to_string(&psbt_path)
+                .map_err(|e| format!("Error reading PSBT file {}: {}", psbt_path, e))?;
+            let psbt = Psbt::from_str(&qr::read_psbt(&contents)?)
+                .map_err(|e| format!("Invalid PSBT in {}: {}", psbt_path, e))?;
+            let secret_key = parse_secret_key(&secret_key)?;
+            let key = secret_key.public_key(&bitcoin::secp256k1::Secp256k1::signing_only());
+
+            let mut all_keys = cosigners;
+            all_keys.push(key.to_string());
+            let (keys, agg) = key_agg(&all_keys)?;
+            if keys.len() < 2 {
+                return Err("Pass the other cosigners' keys with --cosigner".into());
+            }
+            let spent = psbt.inputs.get(input)
+                .ok_or_else(|| format!("The PSBT has no input {}", input))?
+                .witness_utxo.as_ref()
+                .ok_or_else(|| format!("Input {} is missing its spent output", input))?;
+            if spent.script_pubkey != output_script(&agg) {
+                return Err(format!("Input {} isn't paid to these cosigners' shared key {}", input, agg.output_key()).into());
+            }
+
+            let (secret_nonce, nonce) = musig::generate_nonce(&secret_key, &agg, &sighash(&psbt, input)?)?;
+            let session = Session {
+                psbt: psbt.to_string(),
+                input,
+                keys: keys.iter().map(PublicKey::to_string).collect(),
+                key: key.to_string(),
+                nonce: nonce.to_string(),
+                secret_nonce: Some(hex::encode(secret_nonce.to_bytes())),
+                partial_signature: None,
+            };
+            write_json(&session_path, &session)?;
+            write_json(&share, &session.own_share())?;
+            println!("Session written to: {}", session_path);
+            println!("Nonce written to: {}", share);
+            println!("\nSend {} to the other cosigners and run `musig sign` with theirs.", share);
+        }
+        MusigCommand::Sign { session: session_path, secret_key, shares, output } => {
+            let (mut session, psbt, keys, agg) = Session::load(&session_path)?;
+            let secret_key = parse_secret_key(&secret_key)?;
+            let secret_nonce = session.secret_nonce.take()
+                .ok_or("This session's nonce has already signed; start a new one with `musig nonce`")?;
+            let secret_nonce = hex::decode(&secret_nonce)
+                .map_err(|e| format!("Invalid secret nonce in {}: {}", session_path, e))?;
+            let secret_nonce = SecretNonce::from_bytes(&secret_nonce)?;
+
+            let shares = collect_shares(&session, &keys, &shares)?;
+            let (_, aggregate) = aggregate_nonce(&shares)?;
+            // Forget the nonce before anything is signed with it, so a failure can't lead to reuse
+            write_json(&session_path, &session)?;
+            let partial = musig::partial_sign(secret_nonce, &secret_key, &agg, &aggregate, &sighash(&psbt, session.input)?)?;
+            session.partial_signature = Some(partial.to_string());
+            write_json(&session_path, &session)?;
+            write_json(&output, &session.own_share())?;
+            println!("Partial signature written to: {}", output);
+            println!("\nSend {} to the other cosigners; any of you can run `musig aggregate` once all are in.", output);
+        }
+        MusigCommand::Aggregate { session: session_path, shares, output } => {
+            let (session, mut psbt, keys, agg) = Session::load(&session_path)?;
+            let message = sighash(&psbt, session.input)?;
+            let shares = collect_shares(&session, &keys, &shares)?;
+            let (nonces, aggregate) = aggregate_nonce(&shares)?;
+            let mut partials = Vec::new();
+            for ((key, share), nonce) in shares.iter().zip(&nonces) {
+                let partial = parse_partial(key, share)?;
+                musig::verify_partial(&partial, key, nonce, &agg, &aggregate, &message)?;
+                partials.push(partial);
+            }
+            let signature = musig::aggregate_partials(&partials, &agg, &aggregate, &message)?;
+
+            let input = &mut psbt.inputs[session.input];
+            input.tap_key_sig = Some(taproot::Signature { signature, sighash_type: TapSighashType::Default });
+            input.tap_internal_key = Some(agg.internal_key());
+            fs::write(&output, psbt.to_string()).map_err(|e| format!("Error writing PSBT file {}: {}", output, e))?;
+            println!("Input {} signed by all {} cosigners", session.input, keys.len());
+            println!("PSBT written to: {}", output);
+            println!("\nSign the other inputs, then finalize with `combine`.");
+        }
+    }
+    Ok(())
+}
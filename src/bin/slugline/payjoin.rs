@@ -0,0 +1,46 @@
+use bitcoin::psbt::Psbt;
+use slugline::searcher::client::SearcherClient;
+use slugline::searcher::{SubmitPsbtRequest, PROTOCOL_VERSION};
+use std::fs;
+
+use crate::error::CliError;
+use crate::qr::{self, QrFormat};
+
+pub fn run(
+    client: SearcherClient,
+    psbt_path: &str,
+    payment_hash: Option<&str>,
+    output: &str,
+    qr: Option<QrFormat>,
+) -> Result<(), CliError> {
+    let psbt = fs::read_to_string(psbt_path)
+        .map_err(|e| format!("Error reading PSBT file {}: {}", psbt_path, e))?;
+    let psbt = qr::read_psbt(&psbt)?;
+
+    let request = SubmitPsbtRequest {
+        psbt,
+        payment_hash: payment_hash.map(str::to_string),
+        cancel_token: None,
+        commit_psbt: None,
+        payment_inputs: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+    };
+
+    println!("Requesting payjoin from: {}", client.base_url());
+
+    let response = client.payjoin(&request)?;
+    let Some(psbt) = response.psbt.filter(|_| response.success) else {
+        return Err(CliError::Failed(response.message));
+    };
+
+    fs::write(output, &psbt).map_err(|e| format!("Error writing PSBT file {}: {}", output, e))?;
+
+    println!("\nSearcher fee: {} sats", response.fee.unwrap_or_default());
+    println!("Payjoin PSBT written to: {}", output);
+    if let Some(format) = qr {
+        let psbt: Psbt = psbt.parse().map_err(|e| format!("Searcher returned an invalid PSBT: {}", e))?;
+        qr::show(&qr::psbt_parts(&psbt, format))?;
+    }
+    println!("\nSign your inputs and broadcast it as a single transaction, no package relay needed.");
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use bitcoin::psbt::Psbt;
+use clap::ValueEnum;
+use data_encoding::BASE32_NOPAD;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{EcLevel, QrCode};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::CliError;
+
+// Bytes of the PSBT's CBOR in each UR part, and base32 characters in each BBQr part (a
+// multiple of 8, so every part decodes on its own). Both keep the codes small enough to
+// scan from a terminal.
+const UR_FRAGMENT_LEN: usize = 120;
+const BBQR_PART_CHARS: usize = 320;
+const FRAME_MILLIS: u64 = 400;
+
+// The first and last letters of the 256 bytewords, in byte order
+const BYTEWORDS: &str = "aeadaoaxaaahamatayasbkbdbnbtbabsbebybgbwbbbzcmchcscfcycwcecackctcxclcpcndkdadsdidedtdrdndwdpdmdldyeheyeoeeecenemetesftfrfnfsfmfhfzfpfwfxfyfefgflfdgagegrgsgtglgwgdgygmgughgohfhghdhkhthphhhlhyhehnhsidiaieihiyioisinimjejzjnjtjljojsjpjkjykpkoktkskkknkgkekikblblalylflslrlplnltloldlelulklgmnmymhmemomumwmdmtmsmknlnyndnsntnnnenboyoeotoxonolospdptpkpypspmplpepfpaprqdqzrerprlrorhrdrkrfryrnrsrtsesasrssskswstspsosgsbsfsntotktitttdtetytltbtstptatnuyuoutueurvtvyvovlvevwvavdvswlwdwmwpwewywswtwnwzwfwkykynylyaytzszoztzczezm";
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum QrFormat {
+    // Blockchain Commons UR, read by Keystone, Passport, Jade and Sparrow
+    Ur,
+    // Coinkite BBQr, read by Coldcard Q
+    Bbqr,
+}
+
+fn cbor_header(major: u8, value: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    cbor_header(2, bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn read_cbor_header(data: &mut &[u8]) -> Option<(u8, u64)> {
+    let (&first, rest) = data.split_first()?;
+    let len = match first & 0x1f {
+        info @ 0..=23 => {
+            *data = rest;
+            return Some((first >> 5, info.into()));
+        }
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let value = rest.get(..len)?.iter().fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+    *data = &rest[len..];
+    Some((first >> 5, value))
+}
+
+fn read_cbor_uint(data: &mut &[u8]) -> Option<u64> {
+    read_cbor_header(data).filter(|(major, _)| *major == 0).map(|(_, value)| value)
+}
+
+fn read_cbor_bytes<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let (major, len) = read_cbor_header(data)?;
+    let len = usize::try_from(len).ok().filter(|len| major == 2 && *len <= data.len())?;
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Some(bytes)
+}
+
+// Minimal bytewords with the CRC-32 appended, as UR parts carry them
+fn bytewords(data: &[u8]) -> String {
+    let checksum = crc32fast::hash(data).to_be_bytes();
+    data.iter().chain(&checksum).map(|&byte| &BYTEWORDS[usize::from(byte) * 2..usize::from(byte) * 2 + 2]).collect()
+}
+
+fn from_bytewords(words: &str) -> Option<Vec<u8>> {
+    let words = words.as_bytes();
+    if !words.len().is_multiple_of(2) || words.len() < 8 {
+        return None;
+    }
+    let bytes = words.chunks(2)
+        .map(|pair| BYTEWORDS.as_bytes().chunks(2).position(|word| word == pair).map(|byte| byte as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    let (data, checksum) = bytes.split_at(bytes.len() - 4);
+    (crc32fast::hash(data).to_be_bytes() == checksum).then(|| data.to_vec())
+}
+
+// The PSBT as QR code contents, one per frame
+pub fn psbt_parts(psbt: &Psbt, format: QrFormat) -> Vec<String> {
+    let psbt = psbt.serialize();
+    match format {
+        QrFormat::Ur => {
+            let mut message = Vec::new();
+            cbor_bytes(&psbt, &mut message);
+            if message.len() <= UR_FRAGMENT_LEN {
+                return vec![format!("ur:crypto-psbt/{}", bytewords(&message)).to_uppercase()];
+            }
+            // Only the pure fragments of the fountain code, which every decoder takes
+            let count = message.len().div_ceil(UR_FRAGMENT_LEN);
+            let fragment_len = message.len().div_ceil(count);
+            let checksum = crc32fast::hash(&message);
+            message.chunks(fragment_len)
+                .enumerate()
+                .map(|(i, fragment)| {
+                    let mut padded = fragment.to_vec();
+                    padded.resize(fragment_len, 0);
+                    let mut part = Vec::new();
+                    cbor_header(4, 5, &mut part);
+                    for value in [i as u64 + 1, count as u64, message.len() as u64, checksum.into()] {
+                        cbor_header(0, value, &mut part);
+                    }
+                    cbor_bytes(&padded, &mut part);
+                    format!("ur:crypto-psbt/{}-{}/{}", i + 1, count, bytewords(&part)).to_uppercase()
+                })
+                .collect()
+        }
+        QrFormat::Bbqr => {
+            let encoded = BASE32_NOPAD.encode(&psbt);
+            let parts: Vec<&[u8]> = encoded.as_bytes().chunks(BBQR_PART_CHARS).collect();
+            parts.iter()
+                .enumerate()
+                .map(|(i, part)| {
+                    format!("B$2P{}{}{}", base36(parts.len()), base36(i), String::from_utf8_lossy(part))
+                })
+                .collect()
+        }
+    }
+}
+
+fn base36(n: usize) -> String {
+    let digit = |d: usize| char::from_digit(d as u32, 36).unwrap_or('0').to_ascii_uppercase();
+    [digit(n / 36 % 36), digit(n % 36)].iter().collect()
+}
+
+// Scanned UR or BBQr parts, one per line in any order, put back together. None when the
+// contents aren't QR parts at all.
+pub fn decode(contents: &str) -> Result<Option<Vec<u8>>, CliError> {
+    let lines: Vec<&str> = contents.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    match lines.first() {
+        Some(line) if line.to_ascii_lowercase().starts_with("ur:") => decode_ur(&lines).map(Some),
+        Some(line) if line.starts_with("B$") => decode_bbqr(&lines).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn decode_ur(lines: &[&str]) -> Result<Vec<u8>, CliError> {
+    let mut fragments = BTreeMap::new();
+    let mut expected = None;
+    for line in lines {
+        let line = line.to_ascii_lowercase();
+        let mut path = line.trim_start_matches("ur:").split('/');
+        let kind = path.next().unwrap_or_default();
+        if kind != "crypto-psbt" && kind != "psbt" {
+            return Err(format!("Expected a UR PSBT, got ur:{}", kind).into());
+        }
+        let invalid = || format!("Invalid UR part {}", line);
+        match (path.next(), path.next()) {
+            // A single part holds the whole PSBT
+            (Some(words), None) => {
+                let message = from_bytewords(words).ok_or_else(invalid)?;
+                return Ok(read_cbor_bytes(&mut message.as_slice()).ok_or_else(invalid)?.to_vec());
+            }
+            (Some(_), Some(words)) => {
+                let part = from_bytewords(words).ok_or_else(invalid)?;
+                let mut data = part.as_slice();
+                if read_cbor_header(&mut data) != Some((4, 5)) {
+                    return Err(invalid().into());
+                }
+                let fields: Vec<u64> = (0..4).map(|_| read_cbor_uint(&mut data)).collect::<Option<_>>().ok_or_else(invalid)?;
+                let fragment = read_cbor_bytes(&mut data).ok_or_else(invalid)?;
+                let (seq, count, len, checksum) = (fields[0], fields[1], fields[2], fields[3]);
+                if *expected.get_or_insert((count, len, checksum)) != (count, len, checksum) {
+                    return Err("UR parts come from different PSBTs".into());
+                }
+                // Mixed fragments past the pure ones need the full fountain decoder
+                if seq <= count {
+                    fragments.insert(seq, fragment.to_vec());
+                }
+            }
+            _ => return Err(invalid().into()),
+        }
+    }
+
+    let Some((count, len, checksum)) = expected else {
+        return Err("No UR parts".into());
+    };
+    let missing: Vec<String> = (1..=count).filter(|seq| !fragments.contains_key(seq)).map(|seq| seq.to_string()).collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing UR parts {} of {}", missing.join(", "), count).into());
+    }
+    let mut message: Vec<u8> = fragments.into_values().flatten().collect();
+    message.truncate(usize::try_from(len).unwrap_or(usize::MAX));
+    if u64::from(crc32fast::hash(&message)) != checksum {
+        return Err("UR parts don't add up to the PSBT they describe".into());
+    }
+    Ok(read_cbor_bytes(&mut message.as_slice()).ok_or("Invalid UR PSBT")?.to_vec())
+}
+
+fn decode_bbqr(lines: &[&str]) -> Result<Vec<u8>, CliError> {
+    let mut parts = BTreeMap::new();
+    let mut expected = None;
+    for line in lines {
+        let invalid = || format!("Invalid BBQr part {}", line);
+        let header = line.get(..8).ok_or_else(invalid)?;
+        let (encoding, kind) = (&header[2..3], &header[3..4]);
+        let total = usize::from_str_radix(&header[4..6], 36).map_err(|_| invalid())?;
+        let index = usize::from_str_radix(&header[6..8], 36).map_err(|_| invalid())?;
+        if *expected.get_or_insert((encoding.to_string(), kind.to_string(), total)) != (encoding.to_string(), kind.to_string(), total) {
+            return Err("BBQr parts come from different files".into());
+        }
+        parts.insert(index, line[8..].to_string());
+    }
+
+    let Some((encoding, kind, total)) = expected else {
+        return Err("No BBQr parts".into());
+    };
+    if kind != "P" && kind != "T" {
+        return Err(format!("Expected a BBQr PSBT or transaction, got file type {}", kind).into());
+    }
+    let missing: Vec<String> = (0..total).filter(|i| !parts.contains_key(i)).map(|i| (i + 1).to_string()).collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing BBQr parts {} of {}", missing.join(", "), total).into());
+    }
+    let data: String = parts.into_values().collect();
+    match encoding.as_str() {
+        "2" => BASE32_NOPAD.decode(data.as_bytes()).map_err(|e| format!("Invalid BBQr base32: {}", e).into()),
+        "H" => hex::decode(&data).map_err(|e| format!("Invalid BBQr hex: {}", e).into()),
+        _ => Err(format!("Unsupported BBQr encoding {}, export it uncompressed", encoding).into()),
+    }
+}
+
+// A PSBT file's contents as base64, whether it holds base64 or scanned QR parts
+pub fn read_psbt(contents: &str) -> Result<String, CliError> {
+    match decode(contents)? {
+        Some(bytes) => Ok(Psbt::deserialize(&bytes).map_err(|e| format!("Scanned PSBT is invalid: {}", e))?.to_string()),
+        None => Ok(contents.trim().to_string()),
+    }
+}
+
+fn render(contents: &str) -> Result<String, CliError> {
+    let code = QrCode::with_error_correction_level(contents.as_bytes(), EcLevel::L)
+        .map_err(|e| format!("Cannot make a QR code: {}", e))?;
+    // Light modules drawn as blocks, so the code reads right on a dark terminal
+    Ok(code.render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+// Shows the codes in the terminal, cycling through them until Enter is pressed when there
+// are several. Without a terminal to animate in, they're printed one after another.
+pub fn show(parts: &[String]) -> Result<(), CliError> {
+    let frames = parts.iter().map(|part| render(part)).collect::<Result<Vec<_>, _>>()?;
+    let interactive = std::io::stdout().is_terminal() && std::io::stdin().is_terminal();
+    if frames.len() == 1 || !interactive {
+        for (i, frame) in frames.iter().enumerate() {
+            println!("\nQR code {} of {}:\n{}", i + 1, frames.len(), frame);
+        }
+        return Ok(());
+    }
+
+    println!("\nShowing {} QR codes in turn, press Enter once the signer has read them all", frames.len());
+    let done = Arc::new(AtomicBool::new(false));
+    std::thread::spawn({
+        let done = done.clone();
+        move || {
+            let _ = std::io::stdin().read_line(&mut String::new());
+            done.store(true, Ordering::Relaxed);
+        }
+    });
+
+    let mut stdout = std::io::stdout();
+    let mut shown = 0;
+    for (i, frame) in frames.iter().enumerate().cycle() {
+        // Clear the previous frame, which the last BBQr part can be shorter than
+        if shown > 0 {
+            print!("\x1b[{}A\x1b[J", shown);
+        }
+        println!("Part {} of {}\n{}", i + 1, frames.len(), frame);
+        stdout.flush().map_err(|e| format!("Cannot write to the terminal: {}", e))?;
+        shown = frame.lines().count() + 1;
+        std::thread::sleep(Duration::from_millis(FRAME_MILLIS));
+        if done.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use slugline::runes::RuneAmount;
+use slugline::searcher::client::SearcherClient;
+use slugline::searcher::{QuoteRequest, PROTOCOL_VERSION};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::CliError;
+use crate::qr;
+
+pub fn run(
+    client: SearcherClient,
+    psbt_path: Option<&str>,
+    vsize: Option<u64>,
+    lightning: bool,
+    show_qr: bool,
+) -> Result<(), CliError> {
+    // Read the PSBT so the searcher can size it, otherwise quote the given vsize
+    let psbt = match psbt_path {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("Error reading PSBT file {}: {}", path, e))?;
+            Some(qr::read_psbt(&contents)?)
+        }
+        None => None,
+    };
+
+    let request = QuoteRequest { psbt, vsize, lightning, protocol_version: Some(PROTOCOL_VERSION) };
+
+    println!("Requesting quote from: {}", client.base_url());
+
+    let quote = client.quote(&request)?;
+
+    if !quote.success {
+        return Err(format!("Searcher refused to quote: {}", quote.message).into());
+    }
+
+    if quote.sponsored {
+        println!("\nQuote:");
+        println!("{}", quote.message);
+        if let Some(package_vsize) = quote.package_vsize {
+            println!("Package vsize: {} vbytes", package_vsize);
+        }
+        if let Some(anchor_script) = &quote.anchor_script {
+            println!("Anchor script: {}", anchor_script);
+        }
+        println!("\nSubmit the parent as is; it doesn't need a rune input.");
+        return Ok(());
+    }
+
+    if let (Some(invoice), Some(payment_hash)) = (&quote.invoice, &quote.payment_hash) {
+        println!("\nQuote:");
+        println!("Amount: {} sats", quote.amount_sat.unwrap_or_default());
+        if let Some(package_vsize) = quote.package_vsize {
+            println!("Package vsize: {} vbytes", package_vsize);
+        }
+        println!("Invoice: {}", invoice);
+        // Upper case fits the QR code's alphanumeric mode, which wallets read the same
+        if show_qr {
+            qr::show(&[format!("lightning:{}", invoice).to_uppercase()])?;
+        }
+        println!("Payment hash: {}", payment_hash);
+        if let Some(anchor_script) = &quote.anchor_script {
+            println!("Anchor script: {}", anchor_script);
+        }
+        println!("\nPay the invoice, build the parent with `build-tx --lightning`, and submit it with this payment hash.");
+        return Ok(());
+    }
+
+    let rune = quote.rune.unwrap_or_default();
+    // Amounts from searchers that predate rune_divisibility are shown in units
+    let rune_amount = RuneAmount::new(quote.rune_amount.unwrap_or_default(), quote.rune_divisibility.unwrap_or_default());
+
+    println!("\nQuote:");
+    println!("Rune: {}", rune);
+    println!("Rune amount: {}", rune_amount);
+    if let (Some(rate), Some(timestamp)) = (quote.exchange_rate, quote.rate_timestamp) {
+        println!("Exchange rate: {} USD per rune (as of {})", rate, timestamp);
+    }
+    println!("Rune address: {}", quote.rune_address.as_deref().unwrap_or("<none>"));
+    if let Some(ordinal_address) = &quote.ordinal_address {
+        println!(
+            "Also accepts an inscription from {} sent to {}",
+            quote.inscription_collection.as_deref().unwrap_or("any collection"),
+            ordinal_address
+        );
+    }
+    if let Some(fee_rate) = quote.fee_rate {
+        println!("Fee rate: {} sat/vB", fee_rate);
+    }
+    if let Some(package_vsize) = quote.package_vsize {
+        println!("Package vsize: {} vbytes", package_vsize);
+    }
+    if let Some(multiplier) = quote.price_multiplier {
+        println!("Size tier: x{}", multiplier);
+    }
+    if let Some(expires_at) = quote.expires_at {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        println!("Expires at: {} (in {} seconds)", expires_at, expires_at.saturating_sub(now));
+    }
+
+    println!("\nbuild-tx arguments:");
+    println!("--rune-amount {}", rune_amount);
+    if let Some(anchor_script) = &quote.anchor_script {
+        println!("--anchor-script {}", anchor_script);
+    }
+    if let (Some(amount), Some(delay)) = (quote.deposit_amount, quote.deposit_delay) {
+        println!("--deposit-amount {} --deposit-delay {} --refund-pubkey <your key>", amount, delay);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use bitcoin::{psbt::Psbt, Network, Transaction, Txid};
+use bitcoincore_rpc::{Client, RpcApi};
+use slugline::anchor::{find_anchor, AnchorPolicy};
+use slugline::error::BuildError;
+use slugline::policy;
+use slugline::rpc::{self, parse_network};
+use slugline::rune_index::RuneIndex;
+use slugline::runes::{self, validate_rune_input};
+use slugline::searcher::jobs::{self, Job, Payment};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::error::CliError;
+
+// Everything replay needs from the chain besides the job itself
+pub struct Chain<'a> {
+    pub bitcoind_host: &'a str,
+    pub bitcoind_user: Option<&'a str>,
+    pub bitcoind_password: Option<&'a str>,
+    pub network: &'a str,
+    pub ord_server: &'a str,
+    pub http: reqwest::Client,
+    pub rune_indexes: Vec<Arc<dyn RuneIndex>>,
+}
+
+// Runs a stored job's parent and child back through validation against the chain as it is
+// now, printing what each step finds. Nothing is broadcast: packages are only tested
+// against the mempool.
+pub fn run(job_store: &Path, job: &str, min_payment_confirmations: u32, chain: Chain) -> Result<(), CliError> {
+    let jobs = jobs::read(job_store)?;
+    let job = jobs.values()
+        .find(|stored| {
+            stored.id.to_string() == job
+                || stored.parent_txid.as_deref() == Some(job)
+                || stored.child_txid.as_deref() == Some(job)
+        })
+        .ok_or_else(|| format!("No job {} in {}", job, job_store.display()))?;
+    print_job(job);
+
+    let network = parse_network(chain.network);
+    let client = rpc::connect(chain.bitcoind_host, chain.bitcoind_user, chain.bitcoind_password, network, None)
+        .map_err(BuildError::from)?;
+
+    println!("\nReplay:");
+    let held = match &job.held_psbt {
+        Some(psbt) => Some(Psbt::from_str(psbt).map_err(|e| format!("Invalid held PSBT: {}", e))?),
+        None => None,
+    };
+    let parent = match (&held, job.parent_txid.as_deref()) {
+        (Some(psbt), _) => {
+            println!("[ok] parent: held as a PSBT until its commit confirms");
+            check("standardness", "parent is within relay limits".to_string(), policy::check_standard(psbt));
+            check("parent fee", "parent pays no fee".to_string(), policy::check_parent(psbt));
+            Some(psbt.clone().extract_tx_unchecked_fee_rate())
+        }
+        (None, Some(txid)) => fetch("parent", &client, txid),
+        (None, None) => {
+            println!("[FAIL] parent: the job never got as far as a parent");
+            None
+        }
+    };
+    let Some(parent) = parent else {
+        return Ok(());
+    };
+    let child = job.child_txid.as_deref().and_then(|txid| fetch("child", &client, txid));
+
+    check("anchor", "first output is a zero-value anchor".to_string(), find_anchor(&parent, AnchorPolicy::Either).map(|_| ()));
+    if let Some(child) = &child {
+        check("package", "child spends every dust output".to_string(), policy::check_package(&parent, child));
+    }
+
+    // A spent input means something else got in first: a replacement, or a conflicting spend
+    // that evicted the package
+    let inputs: Vec<_> = parent.input.iter().map(|input| input.previous_output).collect();
+    let confirmed = client.get_raw_transaction_info(&parent.compute_txid(), None)
+        .is_ok_and(|info| info.confirmations.unwrap_or_default() > 0);
+    match rpc::missing_outputs(&client, &inputs) {
+        _ if confirmed => println!("[ok] parent inputs: spent by the parent, which is confirmed"),
+        Ok(missing) if missing.is_empty() => println!("[ok] parent inputs: all unspent"),
+        Ok(missing) => println!(
+            "[FAIL] parent inputs: {} missing or spent by another transaction",
+            missing.iter().map(|outpoint| outpoint.to_string()).collect::<Vec<_>>().join(", "),
+        ),
+        Err(e) => println!("[warn] parent inputs: unknown: {}", e),
+    }
+
+    match (&job.payment, &job.payment_hash) {
+        (Some(Payment::Lightning { amount_sat }), _) => println!("[ok] payment: {} sats over Lightning, settled when the job ran", amount_sat),
+        (Some(Payment::Inscription), _) => println!("[ok] payment: an inscription, checked when the job ran"),
+        (Some(Payment::Sponsored { template, amount_sat }), _) => println!("[ok] payment: sponsored by {} for {} sats", template, amount_sat),
+        (None, Some(payment_hash)) => println!("[warn] payment: Lightning invoice {} was never accepted", payment_hash),
+        (Some(Payment::Rune { .. }), _) | (None, None) => {
+            if job.payment.is_none() {
+                println!("[warn] payment: none recorded yet, checking for runes");
+            }
+            check_rune_payment(&parent, job, min_payment_confirmations, network, &chain)?;
+        }
+    }
+
+    match child {
+        _ if confirmed => println!("[ok] mempool: the parent is confirmed"),
+        Some(child) => check_mempool(&client, &parent, &child),
+        None => println!("[warn] mempool: no child to test the package with"),
+    }
+    Ok(())
+}
+
+// Rune payments are checked as they would be now, so runes ord has since seen move show up
+fn check_rune_payment(parent: &Transaction, job: &Job, min_payment_confirmations: u32, network: Network, chain: &Chain) -> Result<(), CliError> {
+    let payment_inputs = job.payment_inputs.clone().unwrap_or_else(|| runes::last_input(parent));
+    let payment = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?.block_on(validate_rune_input(
+        &chain.http,
+        parent,
+        &payment_inputs,
+        min_payment_confirmations,
+        network,
+        chain.ord_server,
+        &chain.rune_indexes,
+    ));
+    match payment {
+        Ok(amount) => println!("[ok] payment: {} units of the rune in inputs {:?}", amount.units, payment_inputs),
+        Err(e) => println!("[FAIL] payment: {} ({})", e, e.reason()),
+    }
+    Ok(())
+}
+
+fn print_job(job: &Job) {
+    println!("Job: {}", job.id);
+    println!("Status: {}", format!("{:?}", job.status).to_lowercase());
+    println!("Parent: {}", job.parent_txid.as_deref().unwrap_or("?"));
+    println!("Child: {}", job.child_txid.as_deref().unwrap_or("?"));
+    if let Some(reason) = &job.failure_reason {
+        println!("Failure: {} ({})", job.failure_message.as_deref().unwrap_or("?"), reason);
+    }
+    if !job.log.is_empty() {
+        println!("\nLog:");
+        for event in &job.log {
+            println!("  {} {}: {}", event.at, event.step, event.message);
+        }
+    }
+}
+
+fn check<E: std::fmt::Display>(name: &str, passed: String, result: Result<(), E>) {
+    match result {
+        Ok(()) => println!("[ok] {}: {}", name, passed),
+        Err(e) => println!("[FAIL] {}: {}", name, e),
+    }
+}
+
+// bitcoind only has transactions in its mempool, or confirmed ones with -txindex
+fn fetch(name: &str, client: &Client, txid: &str) -> Option<Transaction> {
+    let found = Txid::from_str(txid)
+        .map_err(|e| e.to_string())
+        .and_then(|txid| client.get_raw_transaction(&txid, None).map_err(|e| e.to_string()));
+    match found {
+        Ok(tx) => {
+            println!("[ok] {}: {} found", name, txid);
+            Some(tx)
+        }
+        Err(e) => {
+            println!("[FAIL] {}: {} not in the mempool or chain: {}", name, txid, e);
+            None
+        }
+    }
+}
+
+// Whether the package is still in the mempool paying enough to stay there, or whether
+// bitcoind would take it back now and why not
+fn check_mempool(client: &Client, parent: &Transaction, child: &Transaction) {
+    let min_fee = client.call::<serde_json::Value>("getmempoolinfo", &[])
+        .ok()
+        .and_then(|info| info.get("mempoolminfee").and_then(|fee| fee.as_f64()));
+    if let Ok(entry) = client.get_mempool_entry(&child.compute_txid()) {
+        let fee_rate = entry.fees.ancestor.to_sat() as f64 / entry.ancestor_size.max(1) as f64;
+        match min_fee {
+            // mempoolminfee is in BTC/kvB
+            Some(min_fee) if fee_rate < min_fee * 100_000.0 => println!(
+                "[FAIL] mempool: in the mempool at {:.2} sat/vB, under the {:.2} sat/vB minimum so next to be evicted",
+                fee_rate,
+                min_fee * 100_000.0,
+            ),
+            _ => println!("[ok] mempool: in the mempool at {:.2} sat/vB", fee_rate),
+        }
+        return;
+    }
+
+    // A zero-fee parent only relays with its child, which test_package takes into account
+    match rpc::test_package(client, &[parent, child]) {
+        Ok(rejections) => {
+            for (name, rejection) in ["parent", "child"].iter().zip(rejections) {
+                match rejection {
+                    None => println!("[ok] mempool: {} would be accepted", name),
+                    Some(reason) => println!("[FAIL] mempool: {} would be rejected: {}", name, reason),
+                }
+            }
+        }
+        Err(e) => println!("[warn] mempool: testmempoolaccept failed: {}", e),
+    }
+    if let Some(min_fee) = min_fee {
+        println!("Mempool minimum fee: {:.2} sat/vB", min_fee * 100_000.0);
+    }
+}
@@ -0,0 +1,32 @@
+use slugline::searcher::jobs::{self, Payment};
+use std::path::Path;
+
+use crate::address_book::AddressBook;
+use crate::error::CliError;
+
+// Which job paid what to which rotated address, for bookkeeping
+pub fn run(job_store: &Path, book: &AddressBook) -> Result<(), CliError> {
+    let jobs = jobs::read(job_store)?;
+    let payments: Vec<_> = jobs.values()
+        .filter_map(|job| match (&job.rune_address, &job.payment) {
+            (Some(address), Some(Payment::Rune { amount })) => Some((job, address, amount)),
+            _ => None,
+        })
+        .collect();
+
+    println!("{} rune payments to rotated addresses\n", payments.len());
+    println!("{:>6} {:>12} {:>10} {:<10} {:<64}  ADDRESS", "JOB", "RECEIVED", "AMOUNT", "STATUS", "PARENT");
+    for (job, address, amount) in payments {
+        println!(
+            "{:>6} {:>12} {:>10} {:<10} {:<64}  {}",
+            job.id,
+            job.received_at,
+            amount,
+            format!("{:?}", job.status).to_lowercase(),
+            job.parent_txid.as_deref().unwrap_or("?"),
+            book.show(address),
+        );
+    }
+
+    Ok(())
+}
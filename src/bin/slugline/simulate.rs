@@ -0,0 +1,337 @@
+use bitcoin::{psbt::Psbt, Address, Amount, Network};
+use bitcoincore_rpc::{Client, RpcApi, json};
+use serde::Deserialize;
+use slugline::anchor::create_p2a_script;
+use slugline::build_tx;
+use slugline::ord::fetch_utxos;
+use slugline::rpc::{self, parse_network};
+use slugline::runes::RUNE_NAME;
+use slugline::error::BuildError;
+use slugline::searcher::client::SearcherClient;
+use slugline::searcher::{SubmitPsbtRequest, PROTOCOL_VERSION};
+use std::error::Error;
+use std::fs;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::CliError;
+
+const DEMO_WALLET: &str = "slugline-demo";
+const SEARCHER_WALLET: &str = "searcher";
+const ORD_WALLET: &str = "ord";
+const SEARCHER_URL: &str = "http://127.0.0.1:3000";
+
+// Rune amount the demo user sends to their runes address
+const DEMO_RUNE_AMOUNT: u64 = 1000;
+
+// Payment the demo user makes to the destination
+const DEMO_PAYMENT: u64 = 100_000;
+
+#[derive(Debug, Deserialize)]
+struct ReceiveResponse {
+    addresses: Vec<String>,
+}
+
+// Everything the demo needs to talk to bitcoind and ord
+struct Demo<'a> {
+    bitcoind_host: &'a str,
+    bitcoind_user: Option<&'a str>,
+    bitcoind_password: Option<&'a str>,
+    ord_server: &'a str,
+    ord_binary: &'a str,
+    http: reqwest::blocking::Client,
+    node: Client,
+    demo: Client,
+    searcher: Client,
+    mining_address: Address,
+}
+
+fn step(number: u32, description: &str) {
+    println!("\n==> Step {}: {}", number, description);
+}
+
+fn ensure_wallet(node: &Client, wallet_name: &str) -> Result<(), Box<dyn Error>> {
+    if node.list_wallets()?.iter().any(|w| w == wallet_name) {
+        println!("Wallet {} already loaded", wallet_name);
+        return Ok(());
+    }
+
+    if node.load_wallet(wallet_name).is_ok() {
+        println!("Loaded wallet {}", wallet_name);
+        return Ok(());
+    }
+
+    node.create_wallet(wallet_name, None, None, None, None)?;
+    println!("Created wallet {}", wallet_name);
+    Ok(())
+}
+
+fn new_address(client: &Client, label: &str) -> Result<Address, Box<dyn Error>> {
+    Ok(client
+        .get_new_address(Some(label), Some(json::AddressType::Bech32))?
+        .require_network(Network::Regtest)?)
+}
+
+impl Demo<'_> {
+    fn mine(&self, blocks: u64) -> Result<(), Box<dyn Error>> {
+        self.node.generate_to_address(blocks, &self.mining_address)?;
+        println!("Mined {} block(s), height is now {}", blocks, self.node.get_block_count()?);
+        self.wait_for_ord()
+    }
+
+    // ord indexes asynchronously, so wait until it has caught up with bitcoind
+    fn wait_for_ord(&self) -> Result<(), Box<dyn Error>> {
+        let target = self.node.get_block_count()?;
+        let url = format!("{}/blockcount", self.ord_server);
+        let started = Instant::now();
+
+        loop {
+            let height: u64 = self.http.get(&url).send()?.text()?.trim().parse()?;
+            if height >= target {
+                return Ok(());
+            }
+            if started.elapsed() > Duration::from_secs(60) {
+                return Err(format!("ord is stuck at height {} (bitcoind is at {})", height, target).into());
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    fn ord(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(self.ord_binary);
+        command
+            .arg("--regtest")
+            .arg("--bitcoin-rpc-url")
+            .arg(rpc::rpc_url(self.bitcoind_host, Network::Regtest, None));
+        if let Some(user) = self.bitcoind_user {
+            command.arg("--bitcoin-rpc-username").arg(user);
+        }
+        if let Some(pass) = self.bitcoind_password {
+            command.arg("--bitcoin-rpc-password").arg(pass);
+        }
+        command
+            .arg("wallet")
+            .arg("--name")
+            .arg(ORD_WALLET)
+            .arg("--server-url")
+            .arg(self.ord_server)
+            .args(args);
+        command
+    }
+
+    fn ord_output(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        let output = self.ord(args).output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "ord {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn rune_exists(&self) -> Result<bool, Box<dyn Error>> {
+        let url = format!("{}/rune/{}", self.ord_server, RUNE_NAME);
+        let response = self.http
+            .get(&url)
+            .header("Accept", "application/json")
+            .send()?;
+        Ok(response.status().is_success())
+    }
+
+    fn etch(&self) -> Result<(), Box<dyn Error>> {
+        if self.rune_exists()? {
+            println!("{} is already etched, reusing it", RUNE_NAME);
+            return Ok(());
+        }
+
+        // ord requires an inscription alongside the etching
+        let dir = std::env::temp_dir().join("slugline-simulate");
+        fs::create_dir_all(&dir)?;
+        let inscription = dir.join("slugline.txt");
+        fs::write(&inscription, "slugline demo rune")?;
+        let batch = dir.join("batch.yaml");
+        fs::write(&batch, format!(
+            "mode: separate-outputs\n\
+             etching:\n  rune: {}\n  divisibility: 0\n  premine: '1000000'\n  supply: '1000000'\n  symbol: '$'\n\
+             inscriptions:\n  - file: {}\n",
+            RUNE_NAME,
+            inscription.display()
+        ))?;
+
+        // The etching reveal only goes out once the commit matures, so keep mining until ord is done
+        let batch = batch.display().to_string();
+        let mut etching = self.ord(&["batch", "--fee-rate", "1", "--batch", &batch])
+            .stdout(Stdio::null())
+            .spawn()?;
+        loop {
+            if let Some(status) = etching.try_wait()? {
+                if !status.success() {
+                    return Err("ord failed to etch the demo rune".into());
+                }
+                break;
+            }
+            self.node.generate_to_address(1, &self.mining_address)?;
+            thread::sleep(Duration::from_secs(1));
+        }
+
+        self.mine(1)?;
+        println!("Etched {}", RUNE_NAME);
+        Ok(())
+    }
+
+    fn start_searcher(&self, client: &SearcherClient) -> Result<Child, Box<dyn Error>> {
+        let mut command = Command::new(std::env::current_exe()?);
+        command
+            .arg("--network").arg("regtest")
+            .arg("--bitcoind-host").arg(self.bitcoind_host)
+            .arg("--ord-server").arg(self.ord_server);
+        if let Some(user) = self.bitcoind_user {
+            command.arg("--bitcoind-user").arg(user);
+        }
+        if let Some(pass) = self.bitcoind_password {
+            command.arg("--bitcoind-password").arg(pass);
+        }
+        let searcher = command
+            .arg("run-searcher")
+            .arg("--wallet").arg(SEARCHER_WALLET)
+            .arg("--fee-rate").arg("10")
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        // Wait for the searcher to come up
+        let started = Instant::now();
+        while client.health().is_err() {
+            if started.elapsed() > Duration::from_secs(30) {
+                return Err("Searcher did not start within 30 seconds".into());
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+
+        Ok(searcher)
+    }
+}
+
+fn simulate(demo: &Demo) -> Result<(), Box<dyn Error>> {
+    step(2, "Funding the demo user, searcher, and ord wallets");
+    demo.mine(101)?;
+    let btc_address = new_address(&demo.demo, "slugline-demo-btc")?;
+    let runes_address = new_address(&demo.demo, "slugline-demo-runes")?;
+    let destination_address = new_address(&demo.demo, "slugline-demo-destination")?;
+    let searcher_address = new_address(&demo.searcher, "slugline-demo-searcher")?;
+    let receive: ReceiveResponse = serde_json::from_str(&demo.ord_output(&["receive"])?)?;
+    let ord_address = Address::from_str(receive.addresses.first().ok_or("ord returned no receive address")?)?
+        .require_network(Network::Regtest)?;
+    for (address, btc) in [(&btc_address, 1.0), (&searcher_address, 1.0), (&ord_address, 1.0)] {
+        let txid = demo.demo.send_to_address(address, Amount::from_btc(btc)?, None, None, None, None, None, None)?;
+        println!("Sent {} BTC to {} ({})", btc, address, txid);
+    }
+    demo.mine(1)?;
+
+    step(3, &format!("Etching the demo rune {}", RUNE_NAME));
+    demo.etch()?;
+
+    step(4, &format!("Sending {} {} to the demo runes address", DEMO_RUNE_AMOUNT, RUNE_NAME));
+    let outgoing = format!("{}:{}", DEMO_RUNE_AMOUNT, RUNE_NAME);
+    let runes_address_str = runes_address.to_string();
+    demo.ord_output(&["send", "--fee-rate", "1", &runes_address_str, &outgoing])?;
+    demo.mine(1)?;
+
+    step(5, "Building the zero-fee parent transaction");
+    let utxos = fetch_utxos(&demo.http, demo.ord_server, &btc_address.to_string())?;
+    let selected = build_tx::select_utxos(&utxos, DEMO_PAYMENT)?;
+    let rune_utxos = build_tx::fetch_rune_utxos(&demo.http, demo.ord_server, &runes_address.to_string())?;
+    let parent = build_tx::build_transaction(
+        &selected,
+        Some(rune_utxos.first().ok_or(BuildError::NoRuneUtxos)?),
+        &btc_address.to_string(),
+        &destination_address.to_string(),
+        DEMO_PAYMENT,
+        create_p2a_script(),
+        Network::Regtest,
+    )?;
+    println!("Built parent {} with {} inputs and {} outputs", parent.compute_txid(), parent.input.len(), parent.output.len());
+    let psbt = Psbt::from_unsigned_tx(parent)?;
+
+    step(6, "Signing the parent with the demo wallet");
+    let signed = demo.demo.wallet_process_psbt(&psbt.to_string(), Some(true), None, None)?;
+    if !signed.complete {
+        return Err("Demo wallet could not fully sign the parent".into());
+    }
+    println!("Parent signed");
+
+    step(7, "Starting a searcher and submitting the PSBT");
+    let client = SearcherClient::new(SEARCHER_URL);
+    let mut searcher = demo.start_searcher(&client)?;
+    let submitted = client.submit_psbt(&SubmitPsbtRequest {
+        psbt: signed.psbt.clone(),
+        payment_hash: None,
+        cancel_token: None,
+        commit_psbt: None,
+        payment_inputs: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+    });
+    let _ = searcher.kill();
+    let submitted = submitted?;
+    if !submitted.success {
+        return Err(format!("Searcher rejected the package: {}", submitted.message).into());
+    }
+    let txids = submitted.package_txids.unwrap_or_default();
+    println!("Searcher anchored the parent: {}", txids.join(" <- "));
+
+    step(8, "Mining the package");
+    demo.mine(1)?;
+    for txid in &txids {
+        let info = demo.node.get_raw_transaction_info(&FromStr::from_str(txid)?, None)?;
+        println!("{} has {} confirmation(s)", txid, info.confirmations.unwrap_or(0));
+    }
+
+    println!("\nDone! The parent paid no fee in BTC; the searcher paid it in exchange for {}.", RUNE_NAME);
+    Ok(())
+}
+
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    ord_server: &str,
+    ord_binary: &str,
+    http: reqwest::blocking::Client,
+) -> Result<(), CliError> {
+    if parse_network(network) != Network::Regtest {
+        return Err("simulate only runs against regtest (pass --network regtest)".into());
+    }
+
+    step(1, "Connecting to bitcoind and preparing wallets");
+    let demo = (|| -> Result<Demo, Box<dyn Error>> {
+        let node = rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, Network::Regtest, None)?;
+        ensure_wallet(&node, DEMO_WALLET)?;
+        ensure_wallet(&node, SEARCHER_WALLET)?;
+        let demo = rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, Network::Regtest, Some(DEMO_WALLET))?;
+        let searcher = rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, Network::Regtest, Some(SEARCHER_WALLET))?;
+        let mining_address = new_address(&demo, "slugline-demo-mining")?;
+
+        let demo = Demo {
+            bitcoind_host,
+            bitcoind_user,
+            bitcoind_password,
+            ord_server,
+            ord_binary,
+            http,
+            node,
+            demo,
+            searcher,
+            mining_address,
+        };
+        // ord refuses to create a wallet that already exists, which is fine
+        let _ = demo.ord(&["create"]).stdout(Stdio::null()).stderr(Stdio::null()).status();
+        Ok(demo)
+    })();
+
+    demo.and_then(|demo| simulate(&demo))
+        .map_err(|e| format!("Simulation failed: {}", e).into())
+}
@@ -0,0 +1,83 @@
+use slugline::error::ClientError;
+use slugline::runes::RuneAmount;
+use slugline::searcher::client::SearcherClient;
+use std::collections::HashMap;
+
+use crate::error::CliError;
+
+fn format_uptime(secs: u64) -> String {
+    format!("{}d {}h {}m {}s", secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60)
+}
+
+pub fn run(client: SearcherClient) -> Result<(), CliError> {
+    let health = client.health().map_err(|source| match source {
+        // The searcher answered, we just can't trust what it said
+        ClientError::Unverified(_) => CliError::Client(source),
+        source => CliError::Unreachable {
+            url: client.base_url().to_string(),
+            source,
+        },
+    })?;
+
+    let metrics = client.metrics().unwrap_or_else(|e| {
+        eprintln!("Warning: {}", e);
+        HashMap::new()
+    });
+    let metric = |name: &str| {
+        metrics.get(name).map(|v| v.to_string()).unwrap_or_else(|| "?".to_string())
+    };
+
+    println!("Status: {} ({})", health.status, health.message);
+    println!("Uptime: {}", format_uptime(health.uptime_secs));
+    println!("Pending jobs: {}", health.pending_jobs);
+    println!(
+        "Wallet UTXOs: {}",
+        health.wallet_utxos.map(|n| n.to_string()).unwrap_or_else(|| "?".to_string())
+    );
+    match (health.last_block_height, &health.last_block_hash) {
+        (Some(height), Some(hash)) => println!("Last block: {} ({})", height, hash),
+        _ => println!("Last block: ?"),
+    }
+    for node in &health.nodes {
+        let height = node.last_block_height.map(|height| height.to_string()).unwrap_or_else(|| "?".to_string());
+        println!("Node {} ({}): {}, block {}", node.node, node.role, node.status, height);
+        if let Some(rejection) = &node.last_rejection {
+            println!("  Last refused a package: {}", rejection);
+        }
+    }
+    println!(
+        "Submissions: {}, Packages submitted: {}",
+        metric("slugline_submissions_total"),
+        metric("slugline_packages_submitted_total")
+    );
+
+    // Searchers from before /info still report their health
+    match client.info() {
+        Ok(info) => {
+            println!("Network: {}", info.network);
+            println!("Payment methods: {}", info.payment_methods.join(", "));
+            for rune in &info.runes {
+                match (rune.price_per_vbyte, rune.usd_per_vbyte) {
+                    (_, Some(usd)) => println!("Price: ${} per vbyte in {}", usd, rune.name),
+                    (Some(price), None) => println!(
+                        "Price: {} {} per vbyte",
+                        RuneAmount::new(price, rune.divisibility.unwrap_or_default()),
+                        rune.name
+                    ),
+                    (None, None) => println!("Accepts: {}", rune.name),
+                }
+            }
+            if let Some(contact) = &info.contact {
+                println!("Contact: {}", contact);
+            }
+        }
+        Err(e) => eprintln!("Warning: {}", e),
+    }
+
+    // Non-zero exit lets cron scripts alert on anything but a healthy searcher
+    if health.status != "ok" {
+        return Err(CliError::Unhealthy(health.status));
+    }
+
+    Ok(())
+}
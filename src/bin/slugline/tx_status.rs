@@ -0,0 +1,65 @@
+use bitcoin::Txid;
+use bitcoincore_rpc::RpcApi;
+use slugline::chain::EsploraBackend;
+use slugline::error::BuildError;
+use slugline::rpc::{self, parse_network};
+use std::str::FromStr;
+
+use crate::error::CliError;
+
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    txid: &str,
+    mempool_api: Option<&str>,
+) -> Result<(), CliError> {
+    let txid = Txid::from_str(txid).map_err(|e| format!("Invalid txid {}: {}", txid, e))?;
+
+    // bitcoind only knows mempool transactions, or confirmed ones with -txindex
+    let from_core = rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, parse_network(network), None)
+        .and_then(|client| client.get_raw_transaction_info(&txid, None));
+
+    let url = match (from_core, mempool_api) {
+        (Ok(info), _) => {
+            println!("Transaction: {}", txid);
+            match (info.confirmations, info.blockhash) {
+                (Some(confirmations), Some(hash)) if confirmations > 0 => {
+                    println!("Status: confirmed ({} confirmations)", confirmations);
+                    println!("Block: {}", hash);
+                }
+                _ => println!("Status: unconfirmed"),
+            }
+            return Ok(());
+        }
+        (Err(e), None) => return Err(BuildError::from(e).into()),
+        (Err(e), Some(url)) => {
+            eprintln!("Warning: bitcoind lookup failed ({}), asking {}", e, url);
+            url
+        }
+    };
+
+    let mempool = EsploraBackend::new(url);
+    let status = mempool.tx_status(&txid).map_err(BuildError::from)?;
+
+    println!("Transaction: {}", txid);
+    if status.confirmed {
+        println!("Status: confirmed in block {}", status.block_height.map(|h| h.to_string()).unwrap_or_else(|| "?".to_string()));
+        if let Some(hash) = status.block_hash {
+            println!("Block: {}", hash);
+        }
+        return Ok(());
+    }
+
+    println!("Status: unconfirmed");
+    // Shows whether the package is paying enough to get mined soon
+    match mempool.recommended_fees() {
+        Ok(fees) => println!(
+            "Recommended fees: {} sat/vB next block, {} within 30 minutes, {} within an hour",
+            fees.fastest_fee, fees.half_hour_fee, fees.hour_fee,
+        ),
+        Err(e) => eprintln!("Warning: {}", e),
+    }
+    Ok(())
+}
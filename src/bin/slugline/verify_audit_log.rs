@@ -0,0 +1,19 @@
+use slugline::searcher::audit;
+use std::path::Path;
+
+use crate::error::CliError;
+
+// Walk the searcher's audit log, failing at the first entry that was altered, dropped or
+// reordered
+pub fn run(audit_log: &Path, checkpoint: Option<&str>) -> Result<(), CliError> {
+    let log = audit::verify(audit_log, checkpoint)?;
+
+    println!("Audit log {} verified: {} entries", audit_log.display(), log.entries);
+    if let Some(checkpoint) = checkpoint {
+        println!("Contains checkpoint {}", checkpoint);
+    }
+    // Kept somewhere else, this lets the next check notice the log being rewritten from scratch
+    println!("Head: {}", log.head);
+
+    Ok(())
+}
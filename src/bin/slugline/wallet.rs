@@ -0,0 +1,376 @@
+use bitcoin::{
+    absolute,
+    transaction::{OutPoint, Transaction, TxIn, TxOut},
+    Amount, Network, ScriptBuf, Sequence, Txid, Witness,
+};
+use bitcoincore_rpc::{Client, RpcApi, json};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use slugline::rpc::{self, parse_network};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::error::CliError;
+
+// Estimated vbytes for the split transaction: overhead, P2WPKH input, P2WPKH output
+const TX_OVERHEAD_VSIZE: u64 = 11;
+const P2WPKH_INPUT_VSIZE: u64 = 68;
+const P2WPKH_OUTPUT_VSIZE: u64 = 31;
+
+#[derive(Subcommand, Debug)]
+pub enum WalletCommand {
+    /// Create a descriptor wallet for the searcher
+    Create,
+    /// Show a fresh receive address for funding the wallet
+    FundAddress,
+    /// Split a coin into several fee-bump UTXOs
+    Split {
+        /// Number of UTXOs to create
+        #[arg(long, default_value = "10")]
+        count: u64,
+
+        /// Outpoint (txid:vout) to split, defaults to the largest UTXO
+        #[arg(long)]
+        outpoint: Option<String>,
+
+        /// Fee rate in sat/vB for the split transaction
+        #[arg(long, default_value = "1.0")]
+        fee_rate: f64,
+    },
+    /// Show the wallet balance
+    Balance,
+    /// List reserved and free UTXOs
+    Utxos,
+    /// Write the wallet's descriptors to a backup file
+    ExportDescriptors {
+        /// File to write the descriptors to
+        #[arg(long)]
+        output: String,
+
+        /// Leave the private keys out, for a watch-only copy
+        #[arg(long)]
+        public_only: bool,
+    },
+    /// Restore descriptors from an export-descriptors backup
+    ImportDescriptors {
+        /// Backup written by export-descriptors
+        #[arg(long)]
+        input: String,
+
+        /// Create the wallet first, for restoring onto a new node
+        #[arg(long)]
+        create: bool,
+    },
+}
+
+// What export-descriptors writes and import-descriptors reads back
+#[derive(Debug, Serialize, Deserialize)]
+struct DescriptorBackup {
+    wallet: String,
+    network: String,
+    private: bool,
+    descriptors: Vec<ExportedDescriptor>,
+}
+
+// One entry of listdescriptors, keeping what importdescriptors needs to pick up where
+// the wallet left off
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedDescriptor {
+    desc: String,
+    timestamp: u64,
+    active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    internal: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    range: Option<[u64; 2]>,
+    // Newer nodes list both, older ones only next
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next_index: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListDescriptorsResult {
+    wallet_name: String,
+    descriptors: Vec<ExportedDescriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockedOutpoint {
+    txid: Txid,
+    vout: u32,
+}
+
+fn connect(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: Network,
+    wallet_name: Option<&str>,
+) -> Result<Client, Box<dyn Error>> {
+    rpc::connect(bitcoind_host, bitcoind_user, bitcoind_password, network, wallet_name).map_err(|e| {
+        let rpc_url = rpc::rpc_url(bitcoind_host, network, wallet_name);
+        format!("Failed to connect to Bitcoin Core at {} (network: {:?}): {}", rpc_url, network, e).into()
+    })
+}
+
+fn create(client: &Client, wallet_name: &str, disable_private_keys: bool, blank: bool) -> Result<(), Box<dyn Error>> {
+    // createwallet wallet_name disable_private_keys blank passphrase avoid_reuse descriptors
+    let result: json::LoadWalletResult = client.call(
+        "createwallet",
+        &[
+            wallet_name.into(),
+            disable_private_keys.into(),
+            blank.into(),
+            "".into(),
+            false.into(),
+            true.into(),
+        ],
+    )?;
+
+    println!("Created descriptor wallet: {}", result.name);
+    if let Some(warning) = result.warning.filter(|w| !w.is_empty()) {
+        println!("Warning: {}", warning);
+    }
+
+    Ok(())
+}
+
+fn fund_address(client: &Client, network: Network) -> Result<(), Box<dyn Error>> {
+    let address = client
+        .get_new_address(Some("slugline-funding"), Some(json::AddressType::Bech32))?
+        .require_network(network)?;
+
+    println!("Send funds to: {}", address);
+
+    Ok(())
+}
+
+fn split(
+    client: &Client,
+    network: Network,
+    count: u64,
+    outpoint: Option<&str>,
+    fee_rate: f64,
+) -> Result<(), Box<dyn Error>> {
+    if count == 0 {
+        return Err("Count must be at least 1".into());
+    }
+
+    let unspent = client.list_unspent(Some(1), None, None, None, None)?;
+
+    // Pick the requested coin, or the largest one we have
+    let coin = match outpoint {
+        Some(outpoint) => {
+            let outpoint = OutPoint::from_str(outpoint)?;
+            unspent.iter()
+                .find(|u| u.txid == outpoint.txid && u.vout == outpoint.vout)
+                .ok_or_else(|| format!("UTXO not found in wallet: {}", outpoint))?
+        }
+        None => unspent.iter()
+            .max_by_key(|u| u.amount)
+            .ok_or("No UTXOs available in wallet")?,
+    };
+
+    let vsize = TX_OVERHEAD_VSIZE + P2WPKH_INPUT_VSIZE + P2WPKH_OUTPUT_VSIZE * count;
+    let fee = (vsize as f64 * fee_rate).ceil() as u64;
+    let per_output = coin.amount.to_sat().saturating_sub(fee) / count;
+
+    println!("Splitting {}:{} ({} sats) into {} outputs of {} sats", coin.txid, coin.vout, coin.amount.to_sat(), count, per_output);
+    println!("Estimated vsize: {} vbytes, Fee: {} sats", vsize, fee);
+
+    // Outputs below the dust limit would never relay
+    if per_output < 546 {
+        return Err(format!("Split outputs of {} sats would be dust", per_output).into());
+    }
+
+    let mut outputs = Vec::new();
+    for _ in 0..count {
+        let address = client
+            .get_new_address(Some("slugline-fee-bump"), Some(json::AddressType::Bech32))?
+            .require_network(network)?;
+        outputs.push(TxOut {
+            value: Amount::from_sat(per_output),
+            script_pubkey: address.script_pubkey(),
+        });
+    }
+
+    let tx = Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: coin.txid, vout: coin.vout },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::default(),
+        }],
+        output: outputs,
+    };
+
+    let sign_result = client.sign_raw_transaction_with_wallet(&tx, None, None)?;
+    if !sign_result.complete {
+        if let Some(errors) = &sign_result.errors {
+            for error in errors {
+                eprintln!("Signing error: {:?}", error);
+            }
+        }
+        return Err("Failed to fully sign split transaction".into());
+    }
+
+    let txid = client.send_raw_transaction(&sign_result.hex)?;
+    println!("Broadcast split transaction: {}", txid);
+
+    Ok(())
+}
+
+fn balance(client: &Client) -> Result<(), Box<dyn Error>> {
+    let balances = client.get_balances()?;
+
+    println!("Trusted: {} sats", balances.mine.trusted.to_sat());
+    println!("Untrusted pending: {} sats", balances.mine.untrusted_pending.to_sat());
+    println!("Immature: {} sats", balances.mine.immature.to_sat());
+
+    Ok(())
+}
+
+fn utxos(client: &Client) -> Result<(), Box<dyn Error>> {
+    let unspent = client.list_unspent(Some(0), None, None, None, None)?;
+    let locked: Vec<LockedOutpoint> = client.call("listlockunspent", &[])?;
+
+    // listunspent hides locked coins, so reserved ones are reported from listlockunspent
+    println!("Free UTXOs ({}):", unspent.len());
+    for utxo in &unspent {
+        println!("  - {}:{} ({} sats, {} confirmations)", utxo.txid, utxo.vout, utxo.amount.to_sat(), utxo.confirmations);
+    }
+
+    println!("Reserved UTXOs ({}):", locked.len());
+    for outpoint in &locked {
+        println!("  - {}:{}", outpoint.txid, outpoint.vout);
+    }
+
+    Ok(())
+}
+
+// Every descriptor of the wallet, the internal ones rotated rune addresses and change come
+// from included, so a restored wallet recognizes past payments
+fn export_descriptors(client: &Client, network: Network, output: &str, public_only: bool) -> Result<(), Box<dyn Error>> {
+    let listed: ListDescriptorsResult = client.call("listdescriptors", &[(!public_only).into()])?;
+    let backup = DescriptorBackup {
+        wallet: listed.wallet_name,
+        network: network.to_string(),
+        private: !public_only,
+        descriptors: listed.descriptors,
+    };
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Private descriptors are the wallet's keys, so only their owner gets to read them
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let json = serde_json::to_string_pretty(&backup)?;
+    options.open(output)?.write_all((json + "\n").as_bytes())?;
+
+    println!("Exported {} descriptors of wallet {} to {}", backup.descriptors.len(), backup.wallet, output);
+    if public_only {
+        println!("Private keys left out, the backup can only restore a watch-only wallet");
+    }
+
+    Ok(())
+}
+
+fn import_descriptors(client: &Client, network: Network, backup: &DescriptorBackup) -> Result<(), Box<dyn Error>> {
+    if backup.network != network.to_string() {
+        return Err(format!("Backup is of a {} wallet, not {}", backup.network, network).into());
+    }
+
+    let requests = backup.descriptors.iter()
+        .map(|descriptor| {
+            let mut request = json!({
+                "desc": descriptor.desc,
+                "timestamp": descriptor.timestamp,
+                "active": descriptor.active,
+            });
+            if let Some(internal) = descriptor.internal {
+                request["internal"] = internal.into();
+            }
+            if let Some(range) = descriptor.range {
+                request["range"] = json!(range);
+            }
+            // Carry on handing out addresses after the last one used, not from the start
+            if let Some(next) = descriptor.next_index.or(descriptor.next) {
+                request["next_index"] = next.into();
+            }
+            request
+        })
+        .collect::<Vec<_>>();
+
+    println!("Importing {} descriptors, rescanning from their creation...", requests.len());
+    let results: Vec<serde_json::Value> = client.call("importdescriptors", &[requests.into()])?;
+    let mut failed = 0;
+    for (descriptor, result) in backup.descriptors.iter().zip(&results) {
+        if result.get("success").and_then(|success| success.as_bool()) != Some(true) {
+            failed += 1;
+            let message = result.pointer("/error/message").and_then(|message| message.as_str()).unwrap_or("unknown error");
+            eprintln!("Failed to import {}: {}", descriptor.desc.split('#').next().unwrap_or_default(), message);
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} descriptors failed to import", failed, results.len()).into());
+    }
+
+    println!("Imported {} descriptors", results.len());
+    Ok(())
+}
+
+pub fn run(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: &str,
+    wallet_name: &str,
+    command: &WalletCommand,
+) -> Result<(), CliError> {
+    let network = parse_network(network);
+
+    // createwallet has to go to the node endpoint, everything else to the wallet
+    let wallet_path = match command {
+        WalletCommand::Create => None,
+        _ => Some(wallet_name),
+    };
+
+    // Read before anything is created, so a bad backup doesn't leave an empty wallet behind
+    let backup = match command {
+        WalletCommand::ImportDescriptors { input, create: create_wallet } => {
+            let text = fs::read_to_string(input).map_err(|e| format!("Error reading {}: {}", input, e))?;
+            let backup: DescriptorBackup = serde_json::from_str(&text).map_err(|e| format!("Invalid backup {}: {}", input, e))?;
+            if *create_wallet {
+                let node = connect(bitcoind_host, bitcoind_user, bitcoind_password, network, None)?;
+                create(&node, wallet_name, !backup.private, true)?;
+            }
+            Some(backup)
+        }
+        _ => None,
+    };
+
+    let client = connect(bitcoind_host, bitcoind_user, bitcoind_password, network, wallet_path)?;
+    let result = match command {
+        WalletCommand::Create => create(&client, wallet_name, false, false),
+        WalletCommand::FundAddress => fund_address(&client, network),
+        WalletCommand::Split { count, outpoint, fee_rate } => {
+            split(&client, network, *count, outpoint.as_deref(), *fee_rate)
+        }
+        WalletCommand::Balance => balance(&client),
+        WalletCommand::Utxos => utxos(&client),
+        WalletCommand::ExportDescriptors { output, public_only } => export_descriptors(&client, network, output, *public_only),
+        WalletCommand::ImportDescriptors { .. } => import_descriptors(&client, network, backup.as_ref().ok_or("No backup read")?),
+    };
+
+    Ok(result?)
+}
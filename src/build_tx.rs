@@ -1,59 +1,24 @@
 use bitcoin::{
     absolute,
     address::Address,
-    opcodes::all::OP_PUSHNUM_1,
-    psbt::Psbt,
-    script::{Builder, PushBytesBuf},
     transaction::{OutPoint, Transaction, TxIn, TxOut},
-    Amount, Network, ScriptBuf, Sequence, Txid, Witness,
+    Amount, Network, ScriptBuf, Sequence, Witness,
 };
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::error::Error;
 use std::str::FromStr;
 
-// Module-level constant for the rune we're working with
-const RUNE_NAME: &str = "TESTSLUGLINERUNE";
+use crate::error::BuildError;
+use crate::ord::{fetch_utxos, Utxo};
+use crate::runes::RUNE_NAME;
 
-#[derive(Debug, Deserialize, Serialize)]
-struct RuneInfo {
-    amount: u64,
-    divisibility: u8,
-    symbol: String,
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct Utxo {
-    address: String,
-    confirmations: u32,
-    indexed: bool,
-    inscriptions: Vec<String>,
-    outpoint: String,
-    runes: HashMap<String, RuneInfo>,
-    sat_ranges: Option<Vec<String>>,
-    script_pubkey: String,
-    spent: bool,
-    transaction: String,
-    value: u64,
-}
-
-fn parse_network(network_str: &str) -> Network {
-    match network_str {
-        "testnet" | "testnet4" => Network::Testnet,
-        "signet" => Network::Signet,
-        "regtest" => Network::Regtest,
-        _ => Network::Bitcoin,
-    }
-}
-
-fn build_transaction(
+pub fn build_transaction(
     selected_utxos: &[&Utxo],
-    rune_utxos: &[Utxo],
+    rune_utxo: Option<&Utxo>,
     btc_address: &str,
     destination_address: &str,
     amount: u64,
+    anchor_script: ScriptBuf,
     network: Network,
-) -> Result<Transaction, Box<dyn Error>> {
+) -> Result<Transaction, BuildError> {
     // Parse addresses
     let dest_addr = Address::from_str(destination_address)?
         .require_network(network)?;
@@ -63,68 +28,37 @@ fn build_transaction(
     // Calculate total input value from BTC UTXOs
     let btc_input: u64 = selected_utxos.iter().map(|u| u.value).sum();
     
-    // Check if we have at least one rune UTXO
-    if rune_utxos.is_empty() {
-        return Err("No rune UTXOs available for fee payment".into());
-    }
-    
     // Create inputs from selected UTXOs
     let mut inputs = Vec::new();
     for utxo in selected_utxos {
-        // Parse outpoint in format "txid:vout"
-        let parts: Vec<&str> = utxo.outpoint.split(':').collect();
-        if parts.len() != 2 {
-            return Err(format!("Invalid outpoint format: {}", utxo.outpoint).into());
-        }
-        let txid = Txid::from_str(parts[0])?;
-        let vout: u32 = parts[1].parse()?;
-        
-        let outpoint = OutPoint {
-            txid,
-            vout,
-        };
-        
         inputs.push(TxIn {
-            previous_output: outpoint,
+            previous_output: parse_outpoint(&utxo.outpoint)?,
             script_sig: ScriptBuf::new(), // Empty for now, will be signed later
             sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             witness: Witness::default(),
         });
     }
     
-    // Add rune UTXO as input (just one for now)
-    let rune_utxo = &rune_utxos[0];
-    let parts: Vec<&str> = rune_utxo.outpoint.split(':').collect();
-    if parts.len() != 2 {
-        return Err(format!("Invalid rune outpoint format: {}", rune_utxo.outpoint).into());
+    // Add rune UTXO as the last input, unless the fee is paid over Lightning
+    if let Some(rune_utxo) = rune_utxo {
+        inputs.push(TxIn {
+            previous_output: parse_outpoint(&rune_utxo.outpoint)?,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::default(),
+        });
     }
-    let txid = Txid::from_str(parts[0])?;
-    let vout: u32 = parts[1].parse()?;
-    
-    inputs.push(TxIn {
-        previous_output: OutPoint { txid, vout },
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        witness: Witness::default(),
-    });
     
     // Calculate total input including rune UTXO value
-    let total_input = btc_input + rune_utxo.value;
+    let total_input = btc_input + rune_utxo.map_or(0, |u| u.value);
     
     // Create outputs
     let mut outputs = Vec::new();
     
-    // First output: P2A (Pay-to-Anchor) with 0 sats
-    // Create P2A script: OP_1 <0x4e73>
-    let push_bytes = PushBytesBuf::try_from(&[0x4e, 0x73]).unwrap();
-    let p2a_script = Builder::new()
-        .push_opcode(OP_PUSHNUM_1)
-        .push_slice(push_bytes)
-        .into_script();
-    
+    // First output: the anchor with 0 sats, P2A unless the searcher handed out a keyed one
     outputs.push(TxOut {
         value: Amount::from_sat(0),
-        script_pubkey: p2a_script,
+        script_pubkey: anchor_script,
     });
     
     // Second output: destination output
@@ -154,32 +88,76 @@ fn build_transaction(
     Ok(tx)
 }
 
-fn fetch_utxos(ord_server: &str, address: &str) -> Result<Vec<Utxo>, Box<dyn Error>> {
-    let url = format!("{}/outputs/{}", ord_server, address);
-    println!("Fetching UTXOs from: {}", url);
-    
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .get(&url)
-        .header("Accept", "application/json")
-        .send()?;
-    
-    if !response.status().is_success() {
-        return Err(format!("Failed to fetch UTXOs: {}", response.status()).into());
+// A BIP 21 payment request, like bitcoin:bc1q...?amount=0.001&label=Alice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentUri {
+    pub address: String,
+    pub amount_sat: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+impl FromStr for PaymentUri {
+    type Err = BuildError;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        let invalid = BuildError::InvalidUri;
+        let rest = uri.get(..8)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("bitcoin:"))
+            .map(|_| &uri[8..])
+            .ok_or_else(|| invalid(format!("{} doesn't start with bitcoin:", uri)))?;
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        if address.is_empty() {
+            return Err(invalid("no address".to_string()));
+        }
+
+        let mut request = PaymentUri { address: address.to_string(), amount_sat: None, label: None, message: None };
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode(value).ok_or_else(|| invalid(format!("bad percent-encoding in {}", param)))?;
+            match key {
+                "amount" => {
+                    let amount = Amount::from_str_in(&value, bitcoin::Denomination::Bitcoin)
+                        .map_err(|e| invalid(format!("bad amount {}: {}", value, e)))?;
+                    request.amount_sat = Some(amount.to_sat());
+                }
+                "label" => request.label = Some(value),
+                "message" => request.message = Some(value),
+                // Parameters a wallet must understand to pay correctly, like req-pj
+                key if key.starts_with("req-") => return Err(invalid(format!("unsupported required parameter {}", key))),
+                _ => {}
+            }
+        }
+        Ok(request)
     }
-    
-    let utxos: Vec<Utxo> = response.json()?;
-    
-    // Filter out spent UTXOs
-    let unspent_utxos: Vec<Utxo> = utxos.into_iter()
-        .filter(|u| !u.spent)
-        .collect();
-    
-    Ok(unspent_utxos)
 }
 
-fn fetch_rune_utxos(ord_server: &str, address: &str) -> Result<Vec<Utxo>, Box<dyn Error>> {
-    let utxos = fetch_utxos(ord_server, address)?;
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            byte => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// ord reports outpoints as "txid:vout"
+fn parse_outpoint(outpoint: &str) -> Result<OutPoint, BuildError> {
+    OutPoint::from_str(outpoint).map_err(|_| BuildError::InvalidOutpoint(outpoint.to_string()))
+}
+
+pub fn fetch_rune_utxos(http: &reqwest::blocking::Client, ord_server: &str, address: &str) -> Result<Vec<Utxo>, BuildError> {
+    let utxos = fetch_utxos(http, ord_server, address)?;
     
     // Filter to only UTXOs containing our target rune
     let rune_utxos: Vec<Utxo> = utxos.into_iter()
@@ -189,10 +167,61 @@ fn fetch_rune_utxos(ord_server: &str, address: &str) -> Result<Vec<Utxo>, Box<dy
     Ok(rune_utxos)
 }
 
-fn select_utxos(utxos: &[Utxo], target_amount: u64) -> Result<Vec<&Utxo>, String> {
+// Sats a block's subsidy starts with halve every 210,000 blocks from 50 BTC
+const HALVING_INTERVAL: u64 = 210_000;
+const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+
+// What rides on a rune UTXO besides its runes that paying it to a searcher would give
+// away: inscriptions, or the first sat of a block when ord indexes sat ranges
+pub fn valuable_contents(utxo: &Utxo) -> Option<String> {
+    if !utxo.inscriptions.is_empty() {
+        return Some(format!("inscriptions {}", utxo.inscriptions.join(", ")));
+    }
+    let rare: Vec<&str> = utxo.sat_ranges.iter()
+        .flatten()
+        .filter(|range| {
+            range.split_once('-')
+                .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+                .is_some_and(|(start, end)| holds_rare_sat(start, end))
+        })
+        .map(String::as_str)
+        .collect();
+    (!rare.is_empty()).then(|| format!("rare sats in {}", rare.join(", ")))
+}
+
+// Whether the sats numbered start up to but not including end hold the first sat of a
+// block, which ord rates uncommon or rarer
+fn holds_rare_sat(start: u64, end: u64) -> bool {
+    let mut epoch_start = 0u64;
+    let mut subsidy = INITIAL_SUBSIDY;
+    while subsidy > 0 && epoch_start < end {
+        let epoch_end = epoch_start + subsidy * HALVING_INTERVAL;
+        if start < epoch_end {
+            let from = start.max(epoch_start);
+            let first = epoch_start + (from - epoch_start).div_ceil(subsidy) * subsidy;
+            if first < end.min(epoch_end) {
+                return true;
+            }
+        }
+        epoch_start = epoch_end;
+        subsidy /= 2;
+    }
+    false
+}
+
+// BIP 69's input order: previous txid as it's displayed, then output index. Sorting by it
+// before selecting also settles ties between equal values the same way on every backend.
+pub fn bip69_key(utxo: &Utxo) -> (&str, u32) {
+    match utxo.outpoint.rsplit_once(':') {
+        Some((txid, vout)) => (txid, vout.parse().unwrap_or_default()),
+        None => (&utxo.outpoint, 0),
+    }
+}
+
+pub fn select_utxos(utxos: &[Utxo], target_amount: u64) -> Result<Vec<&Utxo>, BuildError> {
     // Sort UTXOs by value in descending order
     let mut sorted_utxos: Vec<&Utxo> = utxos.iter().collect();
-    sorted_utxos.sort_by(|a, b| b.value.cmp(&a.value));
+    sorted_utxos.sort_by_key(|u| std::cmp::Reverse(u.value));
     
     let mut selected = Vec::new();
     let mut accumulated = 0u64;
@@ -206,133 +235,8 @@ fn select_utxos(utxos: &[Utxo], target_amount: u64) -> Result<Vec<&Utxo>, String
         }
     }
     
-    Err(format!(
-        "Insufficient funds. Available: {} sats, Required: {} sats",
-        accumulated, target_amount
-    ))
+    Err(BuildError::InsufficientFunds {
+        available: accumulated,
+        required: target_amount,
+    })
 }
-
-pub fn run(
-    _bitcoind_host: &str,
-    _bitcoind_user: Option<&str>,
-    _bitcoind_password: Option<&str>,
-    network: &str,
-    ord_server: &str,
-    btc_address: &str,
-    runes_address: &str,
-    destination_address: &str,
-    amount: u64,
-) {
-    println!("Building transaction...");
-    println!("BTC address: {}", btc_address);
-    println!("Runes address: {}", runes_address);
-    println!("Destination address: {}", destination_address);
-    println!("Amount: {} sats", amount);
-    println!("Network: {}", network);
-    
-    // Fetch BTC UTXOs
-    match fetch_utxos(ord_server, btc_address) {
-        Ok(utxos) => {
-            println!("Found {} UTXOs", utxos.len());
-            
-            // Calculate total balance
-            let total_balance: u64 = utxos.iter().map(|u| u.value).sum();
-            println!("Total balance: {} sats", total_balance);
-            
-            // Select UTXOs
-            match select_utxos(&utxos, amount) {
-                Ok(selected) => {
-                    println!("\nSelected {} UTXOs for transaction:", selected.len());
-                    let mut selected_total = 0u64;
-                    for utxo in &selected {
-                        println!("  - {} ({} sats)", utxo.outpoint, utxo.value);
-                        selected_total += utxo.value;
-                    }
-                    println!("Selected total: {} sats", selected_total);
-                    
-                    // Fetch Rune UTXOs
-                    println!("\nFetching rune UTXOs from runes address...");
-                    match fetch_rune_utxos(ord_server, runes_address) {
-                        Ok(rune_utxos) => {
-                            println!("Found {} UTXOs containing {}", rune_utxos.len(), RUNE_NAME);
-                            
-                            for utxo in &rune_utxos {
-                                if let Some(rune_info) = utxo.runes.get(RUNE_NAME) {
-                                    println!("  - {} ({} sats, {} {} runes)", 
-                                        utxo.outpoint, 
-                                        utxo.value, 
-                                        rune_info.amount,
-                                        rune_info.symbol
-                                    );
-                                }
-                            }
-                            
-                            // Build the transaction
-                            let network = parse_network(network);
-                            match build_transaction(&selected, &rune_utxos, btc_address, destination_address, amount, network) {
-                        Ok(tx) => {
-                            println!("\nTransaction created successfully!");
-                            println!("Transaction ID: {}", tx.compute_txid());
-                            println!("Version: {}", tx.version);
-                            println!("Inputs: {}", tx.input.len());
-                            println!("Outputs: {}", tx.output.len());
-                            
-                            // Show output details
-                            for (i, output) in tx.output.iter().enumerate() {
-                                let desc = match i {
-                                    0 => " (P2A anchor)",
-                                    1 => " (destination)",
-                                    2 => " (change)",
-                                    _ => "",
-                                };
-                                println!("  Output {}: {} sats{}", i, output.value.to_sat(), desc);
-                            }
-                            
-                            // Calculate fee
-                            let total_inputs = selected_total + rune_utxos[0].value;
-                            let total_outputs: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
-                            let fee = total_inputs - total_outputs;
-                            println!("Total inputs: {} sats", total_inputs);
-                            println!("Total outputs: {} sats", total_outputs);
-                            println!("Fee: {} sats", fee);
-                            
-                            println!("\nRaw transaction hex:");
-                            println!("{}", bitcoin::consensus::encode::serialize_hex(&tx));
-                            
-                            // Convert to PSBT
-                            let psbt = match Psbt::from_unsigned_tx(tx) {
-                                Ok(psbt) => psbt,
-                                Err(e) => {
-                                    eprintln!("Error creating PSBT: {}", e);
-                                    std::process::exit(1);
-                                }
-                            };
-                            
-                            // Output PSBT in base64 format
-                            println!("\nPSBT (base64):");
-                            println!("{}", psbt.to_string());
-                        }
-                        Err(e) => {
-                            eprintln!("Error building transaction: {}", e);
-                            std::process::exit(1);
-                        }
-                    }
-                        }
-                        Err(e) => {
-                            eprintln!("Error fetching rune UTXOs: {}", e);
-                            std::process::exit(1);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("Error fetching UTXOs: {}", e);
-            std::process::exit(1);
-        }
-    }
-}
\ No newline at end of file
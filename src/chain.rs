@@ -0,0 +1,361 @@
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{deserialize_hex, serialize_hex};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{Address, BlockHash, Network, OutPoint, ScriptBuf, Transaction, Txid};
+use bitcoincore_rpc::{json, Client, RpcApi};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+
+use crate::error::ChainError;
+use crate::filters::CompactFilterBackend;
+use crate::ord::Utxo;
+
+// Plain bitcoin held by an address, as seen by a chain backend
+#[derive(Debug, Clone)]
+pub struct ChainUtxo {
+    pub outpoint: OutPoint,
+    pub value: u64,
+    pub script_pubkey: ScriptBuf,
+    // Zero while unconfirmed
+    pub confirmations: u32,
+}
+
+impl ChainUtxo {
+    // The backends know nothing about runes or inscriptions, so those are left empty
+    pub fn into_ord_utxo(self, address: &str) -> Utxo {
+        Utxo {
+            address: address.to_string(),
+            confirmations: self.confirmations,
+            indexed: false,
+            inscriptions: Vec::new(),
+            outpoint: self.outpoint.to_string(),
+            runes: HashMap::new(),
+            sat_ranges: None,
+            script_pubkey: self.script_pubkey.to_hex_string(),
+            spent: false,
+            transaction: self.outpoint.txid.to_string(),
+            value: self.value,
+        }
+    }
+}
+
+// Where chain data comes from when there's no full node. Rune data still comes from ord.
+pub trait ChainBackend {
+    fn utxos(&self, address: &Address) -> Result<Vec<ChainUtxo>, ChainError>;
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError>;
+    fn tip(&self) -> Result<(u64, BlockHash), ChainError>;
+}
+
+fn confirmations(tip: u64, height: Option<u64>) -> u32 {
+    match height {
+        Some(height) if height > 0 && height <= tip => (tip - height + 1) as u32,
+        _ => 0,
+    }
+}
+
+pub struct CoreBackend {
+    client: Client,
+}
+
+impl CoreBackend {
+    pub fn new(client: Client) -> Self {
+        CoreBackend { client }
+    }
+}
+
+impl ChainBackend for CoreBackend {
+    // scantxoutset works without a wallet, at the cost of a full UTXO set scan
+    fn utxos(&self, address: &Address) -> Result<Vec<ChainUtxo>, ChainError> {
+        let tip = self.client.get_block_count()?;
+        let result = self.client.scan_tx_out_set_blocking(&[json::ScanTxOutRequest::Single(format!("addr({})", address))])?;
+        Ok(result.unspents
+            .into_iter()
+            .map(|u| ChainUtxo {
+                outpoint: OutPoint { txid: u.txid, vout: u.vout },
+                value: u.amount.to_sat(),
+                script_pubkey: u.script_pub_key,
+                confirmations: confirmations(tip, Some(u.height)),
+            })
+            .collect())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        Ok(self.client.send_raw_transaction(tx)?)
+    }
+
+    fn tip(&self) -> Result<(u64, BlockHash), ChainError> {
+        let height = self.client.get_block_count()?;
+        Ok((height, self.client.get_block_hash(height)?))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TxStatus {
+    pub confirmed: bool,
+    pub block_height: Option<u64>,
+    pub block_hash: Option<BlockHash>,
+    pub block_time: Option<u64>,
+}
+
+// mempool.space's fee recommendations, in sat/vB
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendedFees {
+    pub fastest_fee: f64,
+    pub half_hour_fee: f64,
+    pub hour_fee: f64,
+    pub economy_fee: f64,
+    pub minimum_fee: f64,
+}
+
+impl RecommendedFees {
+    // The recommendation matching a confirmation target in blocks
+    pub fn for_target(&self, blocks: u16) -> f64 {
+        match blocks {
+            0..=1 => self.fastest_fee,
+            2..=3 => self.half_hour_fee,
+            4..=6 => self.hour_fee,
+            _ => self.economy_fee,
+        }
+    }
+}
+
+// Fee rate buckets of the mempool, highest first: (sat/vB, vbytes paying about that much)
+#[derive(Debug, Clone, Deserialize)]
+pub struct MempoolSummary {
+    pub count: u64,
+    pub vsize: u64,
+    pub fee_histogram: Vec<(f64, u64)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraUtxo {
+    txid: Txid,
+    vout: u32,
+    value: u64,
+    status: TxStatus,
+}
+
+// A Blockstream/mempool.space style REST API
+pub struct EsploraBackend {
+    url: String,
+    http: reqwest::blocking::Client,
+}
+
+impl EsploraBackend {
+    pub fn new(url: &str) -> Self {
+        EsploraBackend {
+            url: url.trim_end_matches('/').to_string(),
+            http: reqwest::blocking::Client::new(),
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<reqwest::blocking::Response, ChainError> {
+        let url = format!("{}{}", self.url, path);
+        let response = self.http.get(&url).send()?;
+        if !response.status().is_success() {
+            return Err(ChainError::Status { url, status: response.status() });
+        }
+        Ok(response)
+    }
+
+    pub fn tx_status(&self, txid: &Txid) -> Result<TxStatus, ChainError> {
+        Ok(self.get(&format!("/tx/{}/status", txid))?.json()?)
+    }
+
+    // Only mempool.space instances serve this, plain Esplora doesn't
+    pub fn recommended_fees(&self) -> Result<RecommendedFees, ChainError> {
+        Ok(self.get("/v1/fees/recommended")?.json()?)
+    }
+
+    pub fn mempool(&self) -> Result<MempoolSummary, ChainError> {
+        Ok(self.get("/mempool")?.json()?)
+    }
+}
+
+impl ChainBackend for EsploraBackend {
+    fn utxos(&self, address: &Address) -> Result<Vec<ChainUtxo>, ChainError> {
+        let (tip, _) = self.tip()?;
+        let utxos: Vec<EsploraUtxo> = self.get(&format!("/address/{}/utxo", address))?.json()?;
+        Ok(utxos
+            .into_iter()
+            .map(|u| ChainUtxo {
+                outpoint: OutPoint { txid: u.txid, vout: u.vout },
+                value: u.value,
+                script_pubkey: address.script_pubkey(),
+                confirmations: confirmations(tip, u.status.block_height),
+            })
+            .collect())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        let url = format!("{}/tx", self.url);
+        let response = self.http.post(&url).body(serialize_hex(tx)).send()?;
+        let status = response.status();
+        let body = response.text()?;
+        if !status.is_success() {
+            return Err(ChainError::Rejected(body));
+        }
+        Txid::from_str(body.trim()).map_err(|_| ChainError::Malformed(format!("Invalid txid: {}", body)))
+    }
+
+    fn tip(&self) -> Result<(u64, BlockHash), ChainError> {
+        let height = self.get("/blocks/tip/height")?.text()?;
+        let hash = self.get("/blocks/tip/hash")?.text()?;
+        Ok((
+            height.trim().parse().map_err(|_| ChainError::Malformed(format!("Invalid height: {}", height)))?,
+            BlockHash::from_str(hash.trim()).map_err(|_| ChainError::Malformed(format!("Invalid block hash: {}", hash)))?,
+        ))
+    }
+}
+
+// An Electrum server over plain TCP (host:port)
+pub struct ElectrumBackend {
+    address: String,
+}
+
+impl ElectrumBackend {
+    pub fn new(address: &str) -> Self {
+        ElectrumBackend {
+            address: address.to_string(),
+        }
+    }
+
+    // One connection per call keeps this simple; Electrum speaks newline-delimited JSON-RPC
+    fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, ChainError> {
+        let mut stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+
+        let request = json!({ "jsonrpc": "2.0", "id": 0, "method": method, "params": params });
+        writeln!(stream, "{}", request)?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let response: serde_json::Value = serde_json::from_str(&line)?;
+
+        match response.get("error") {
+            Some(error) if !error.is_null() => Err(ChainError::Rejected(error.to_string())),
+            _ => Ok(response["result"].clone()),
+        }
+    }
+}
+
+// Electrum indexes outputs by the reversed sha256 of their script
+fn script_hash(script: &ScriptBuf) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+#[derive(Debug, Deserialize)]
+struct ElectrumUtxo {
+    tx_hash: Txid,
+    tx_pos: u32,
+    value: u64,
+    height: u64,
+}
+
+impl ChainBackend for ElectrumBackend {
+    fn utxos(&self, address: &Address) -> Result<Vec<ChainUtxo>, ChainError> {
+        let (tip, _) = self.tip()?;
+        let script_pubkey = address.script_pubkey();
+        let result = self.call("blockchain.scripthash.listunspent", json!([script_hash(&script_pubkey)]))?;
+        let utxos: Vec<ElectrumUtxo> = serde_json::from_value(result)?;
+        Ok(utxos
+            .into_iter()
+            .map(|u| ChainUtxo {
+                outpoint: OutPoint { txid: u.tx_hash, vout: u.tx_pos },
+                value: u.value,
+                script_pubkey: script_pubkey.clone(),
+                confirmations: confirmations(tip, Some(u.height)),
+            })
+            .collect())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        let result = self.call("blockchain.transaction.broadcast", json!([serialize_hex(tx)]))?;
+        let txid = result.as_str().unwrap_or_default();
+        Txid::from_str(txid).map_err(|_| ChainError::Malformed(format!("Invalid txid: {}", result)))
+    }
+
+    fn tip(&self) -> Result<(u64, BlockHash), ChainError> {
+        let result = self.call("blockchain.headers.subscribe", json!([]))?;
+        let height = result["height"].as_u64()
+            .ok_or_else(|| ChainError::Malformed("Tip without height".to_string()))?;
+        let header: Header = deserialize_hex(result["hex"].as_str().unwrap_or_default())
+            .map_err(|e| ChainError::Malformed(format!("Invalid header: {}", e)))?;
+        Ok((height, header.block_hash()))
+    }
+}
+
+// Which backend to use, parsed from "core", "esplora:<url>", "electrum:<host:port>"
+// or "filters:<host:port>[@<start height>]"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainSource {
+    Core,
+    Esplora(String),
+    Electrum(String),
+    Filters { peer: String, start_height: u32 },
+}
+
+impl FromStr for ChainSource {
+    type Err = ChainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            _ if s == "core" => Ok(ChainSource::Core),
+            Some(("esplora", url)) if !url.is_empty() => Ok(ChainSource::Esplora(url.to_string())),
+            Some(("electrum", address)) if !address.is_empty() => Ok(ChainSource::Electrum(address.to_string())),
+            Some(("filters", peer)) if !peer.is_empty() => match peer.split_once('@') {
+                Some((peer, height)) => Ok(ChainSource::Filters {
+                    peer: peer.to_string(),
+                    start_height: height.parse().map_err(|_| ChainError::InvalidBackend(s.to_string()))?,
+                }),
+                None => Ok(ChainSource::Filters { peer: peer.to_string(), start_height: 0 }),
+            },
+            _ => Err(ChainError::InvalidBackend(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ChainSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainSource::Core => write!(f, "core"),
+            ChainSource::Esplora(url) => write!(f, "esplora:{}", url),
+            ChainSource::Electrum(address) => write!(f, "electrum:{}", address),
+            ChainSource::Filters { peer, start_height } => write!(f, "filters:{}@{}", peer, start_height),
+        }
+    }
+}
+
+impl ChainSource {
+    pub fn connect(
+        &self,
+        bitcoind_host: &str,
+        bitcoind_user: Option<&str>,
+        bitcoind_password: Option<&str>,
+        network: Network,
+    ) -> Result<Box<dyn ChainBackend>, ChainError> {
+        info!("Using {} chain backend", self);
+        Ok(match self {
+            ChainSource::Core => Box::new(CoreBackend::new(crate::rpc::connect(
+                bitcoind_host,
+                bitcoind_user,
+                bitcoind_password,
+                network,
+                None,
+            )?)),
+            ChainSource::Esplora(url) => Box::new(EsploraBackend::new(url)),
+            ChainSource::Electrum(address) => Box::new(ElectrumBackend::new(address)),
+            ChainSource::Filters { peer, start_height } => Box::new(CompactFilterBackend::new(peer, network, *start_height)),
+        })
+    }
+}
@@ -0,0 +1,763 @@
+use axum::http::StatusCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum OrdError {
+    #[error("Request to ord failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("ord returned {status} for {url}")]
+    Status { url: String, status: reqwest::StatusCode },
+
+    #[error("Unexpected ord response: {0}")]
+    Malformed(String),
+
+    #[error("UTXO not found for outpoint: {0}")]
+    UtxoNotFound(String),
+}
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("Invalid address: {0}")]
+    Address(#[from] bitcoin::address::ParseError),
+
+    #[error("Invalid outpoint format: {0}")]
+    InvalidOutpoint(String),
+
+    #[error("Invalid bitcoin: URI: {0}")]
+    InvalidUri(String),
+
+    #[error("No rune UTXOs available for fee payment")]
+    NoRuneUtxos,
+
+    #[error("Every rune UTXO also carries something worth more than its runes, like {outpoint} with {contents}")]
+    ValuableRuneUtxos { outpoint: String, contents: String },
+
+    #[error("Insufficient funds. Available: {available} sats, Required: {required} sats")]
+    InsufficientFunds { available: u64, required: u64 },
+
+    #[error("Failed to create PSBT: {0}")]
+    Psbt(#[from] bitcoin::psbt::Error),
+
+    #[error(transparent)]
+    Ord(#[from] OrdError),
+
+    #[error("Bitcoin Core RPC failed: {0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+
+    #[error("Invalid descriptor: {0}")]
+    Descriptor(String),
+
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+}
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("Invalid PSBT: {0}")]
+    InvalidPsbt(#[from] bitcoin::psbt::PsbtParseError),
+
+    // Boxed because the error carries the whole PSBT
+    #[error("Failed to extract transaction from PSBT: {0}")]
+    Extract(Box<bitcoin::psbt::ExtractTxError>),
+
+    #[error("Transaction has no outputs")]
+    NoOutputs,
+
+    #[error("First output is not a P2A output")]
+    MissingAnchor,
+
+    #[error("First output is not a P2WPKH anchor")]
+    MissingKeyedAnchor,
+
+    #[error("Anchor pays {0}, which is not a key this searcher handed out")]
+    UnknownAnchorKey(String),
+
+    #[error("Anchor output value is not 0")]
+    NonZeroAnchor,
+
+    #[error("Package of {vsize} vbytes is larger than the {max} vbytes this searcher sponsors")]
+    PackageTooLarge { vsize: u64, max: u64 },
+
+    #[error("Transaction has no inputs")]
+    NoInputs,
+
+    #[error("Input {0} does not exist or is already spent")]
+    MissingInput(String),
+
+    #[error("Payment input does not contain {0} rune")]
+    MissingRune(String),
+
+    #[error("Invalid payment input: {0}")]
+    PaymentInput(String),
+
+    #[error("Payment input {outpoint} has {confirmations} confirmations, the searcher needs {min}")]
+    UnconfirmedPayment { outpoint: String, confirmations: u32, min: u32 },
+
+    #[error("Parent's runestone is a cenotaph, which would burn the rune payment: {0}")]
+    Cenotaph(crate::runestone::Flaw),
+
+    #[error("Parent's runestone sends {anchored} of the {paid} {rune} payment to the anchor", rune = crate::runes::RUNE_NAME)]
+    MisroutedPayment { anchored: crate::runes::RuneAmount, paid: crate::runes::RuneAmount },
+
+    #[error("Parent inputs would lose assets: {}", .0.iter().map(|finding| format!("input {} ({}) {}", finding.input, finding.outpoint, finding.problem)).collect::<Vec<_>>().join("; "))]
+    Inputs(Vec<crate::runes::InputFinding>),
+
+    #[error("No inscription from {0} is transferred to the searcher")]
+    MissingInscription(String),
+
+    #[error("Parent has {0} dust outputs, only the anchor may be dust")]
+    MultipleDustOutputs(usize),
+
+    #[error("Parent with dust outputs must pay zero fee, pays {0} sats")]
+    ParentPaysFee(u64),
+
+    #[error("Cannot compute parent fee: {0}")]
+    UnknownFee(String),
+
+    #[error("Parent pays {fee} sats in fees, more than the {max} sats this searcher allows")]
+    ParentFee { fee: u64, max: u64 },
+
+    #[error("Dust output {0} of the parent is not spent by the child")]
+    UnspentDust(u32),
+
+    #[error("Rune lookup failed: {0}")]
+    Ord(#[from] OrdError),
+
+    #[error("ord and {index} disagree about the runes in {outpoint}")]
+    IndexesDisagree { outpoint: String, index: String },
+
+    #[error("PSBT is for a different network: {0}")]
+    WrongNetwork(String),
+
+    #[error("Parent does not spend commit transaction {0}")]
+    UnrelatedCommit(String),
+
+    #[error("Commit pays {rate:.2} sat/vB, below the {min:.2} sat/vB it needs to confirm on its own")]
+    CommitFeeRate { rate: f64, min: f64 },
+
+    #[error("Parent lacks the deposit this searcher requires: {0}")]
+    MissingDeposit(String),
+
+    #[error("Parent is non-standard: {0}")]
+    NonStandard(String),
+
+    #[error("Parent breaks rule {rule}: {reason}")]
+    Rule { rule: String, reason: String },
+
+    #[error("Payment of {paid} {rune} is less than the {price} the package costs", rune = crate::runes::RUNE_NAME)]
+    Underpaid { paid: crate::runes::RuneAmount, price: crate::runes::RuneAmount },
+
+    #[error("Payment of {paid} rune units is less than the {required} it takes to pay {rate:.2} sat/vB and stay within {depth} vbytes of the top of the mempool")]
+    BelowMempoolDepth { paid: u64, required: u64, rate: f64, depth: u64 },
+}
+
+impl From<bitcoin::psbt::ExtractTxError> for ValidationError {
+    fn from(e: bitcoin::psbt::ExtractTxError) -> Self {
+        ValidationError::Extract(Box::new(e))
+    }
+}
+
+impl ValidationError {
+    // Stable name for grouping failures in statistics
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ValidationError::InvalidPsbt(_) => "invalid_psbt",
+            ValidationError::Extract(_) => "invalid_psbt",
+            ValidationError::NoOutputs => "no_outputs",
+            ValidationError::MissingAnchor => "missing_anchor",
+            ValidationError::MissingKeyedAnchor => "missing_keyed_anchor",
+            ValidationError::UnknownAnchorKey(_) => "unknown_anchor_key",
+            ValidationError::NonZeroAnchor => "non_zero_anchor",
+            ValidationError::PackageTooLarge { .. } => "package_too_large",
+            ValidationError::NoInputs => "no_inputs",
+            ValidationError::MissingInput(_) => "missing_input",
+            ValidationError::MissingRune(_) => "missing_rune",
+            ValidationError::PaymentInput(_) => "invalid_payment_input",
+            ValidationError::UnconfirmedPayment { .. } => "unconfirmed_payment",
+            ValidationError::Cenotaph(_) => "cenotaph",
+            ValidationError::MisroutedPayment { .. } => "misrouted_payment",
+            ValidationError::Inputs(_) => "misrouted_inputs",
+            ValidationError::MissingInscription(_) => "missing_inscription",
+            ValidationError::MultipleDustOutputs(_)
+            | ValidationError::ParentPaysFee(_)
+            | ValidationError::UnknownFee(_)
+            | ValidationError::UnspentDust(_) => "ephemeral_dust",
+            ValidationError::ParentFee { .. } => "parent_fee",
+            ValidationError::Ord(_) => "ord_unavailable",
+            ValidationError::IndexesDisagree { .. } => "indexes_disagree",
+            ValidationError::WrongNetwork(_) => "wrong_network",
+            ValidationError::UnrelatedCommit(_) => "unrelated_commit",
+            ValidationError::CommitFeeRate { .. } => "commit_fee_rate",
+            ValidationError::Rule { .. } => "rule_violation",
+            ValidationError::NonStandard(_) => "non_standard",
+            ValidationError::MissingDeposit(_) => "missing_deposit",
+            ValidationError::Underpaid { .. } => "underpaid",
+            ValidationError::BelowMempoolDepth { .. } => "below_mempool_depth",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ValidationError::InvalidPsbt(_) | ValidationError::Extract(_) => StatusCode::BAD_REQUEST,
+            // We couldn't check the submission, which isn't the submitter's fault
+            ValidationError::Ord(_) | ValidationError::IndexesDisagree { .. } => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SearcherError {
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+
+    #[error("Either psbt or vsize is required")]
+    MissingQuoteInput,
+
+    #[error("Bitcoin Core RPC failed: {0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+
+    #[error("No UTXOs available in searcher wallet")]
+    NoWalletUtxos,
+
+    #[error("Failed to create CPFP transaction: {0}")]
+    Cpfp(String),
+
+    #[error("Failed to fully sign CPFP transaction")]
+    IncompleteSignature,
+
+    #[error("Package submission failed: {0}")]
+    PackageRejected(String),
+
+    #[error("Commit transaction rejected: {0}")]
+    CommitRejected(String),
+
+    #[error("Commit {0} left the mempool before confirming")]
+    CommitDropped(String),
+
+    #[error("Cannot payjoin: {0}")]
+    Payjoin(String),
+
+    #[error("Fee of {fee} sats is over the searcher's {cap} sat cap")]
+    FeeCap { fee: u64, cap: u64 },
+
+    #[error("No upstream searcher accepted the request: {0}")]
+    Upstream(String),
+
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+
+    #[error("Pricing unavailable: {0}")]
+    Pricing(#[from] PricingError),
+
+    #[error("Nostr announcement failed: {0}")]
+    Nostr(#[from] NostrError),
+
+    #[error(transparent)]
+    Tor(#[from] TorError),
+
+    #[error("Rune liquidation failed: {0}")]
+    Liquidation(#[from] LiquidationError),
+
+    #[error("Signing failed: {0}")]
+    Signer(#[from] SignerError),
+
+    #[error("Searcher I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Daemon(String),
+
+    #[error("Invalid searcher configuration: {0}")]
+    Config(String),
+
+    #[error("Searcher stopped before the job finished")]
+    Interrupted,
+
+    #[error("{0} left the mempool without confirming and could not be rebroadcast")]
+    Dropped(String),
+
+    #[error("Payjoin was never broadcast")]
+    PayjoinExpired,
+
+    #[error("No job for {0}")]
+    UnknownJob(String),
+
+    #[error("Cancel token does not match the job")]
+    CancelDenied,
+
+    #[error("Token does not match the job, so its log can't be read")]
+    LogDenied,
+
+    #[error("Job can no longer be cancelled: {0}")]
+    NotCancellable(String),
+
+    #[error("Job was cancelled by the submitter")]
+    Cancelled,
+
+    #[error("Parent {parent} is already anchored by job {job}")]
+    AlreadyAnchored { parent: String, job: u64 },
+
+    #[error("Parent spends the same inputs as {parent}, which job {job} anchored")]
+    ConflictingParent { parent: String, job: u64 },
+
+    #[error("Parent was replaced by {0}")]
+    Replaced(String),
+
+    #[error("Parent can't be rescued: {0}")]
+    NotRescuable(String),
+
+    #[error("All {capacity} wallet UTXOs able to fund a package are taken by {in_flight} jobs in flight")]
+    AtCapacity { in_flight: u64, capacity: u64 },
+
+    #[error("Searcher wallets are low on funds ({balance_sat} sats in {free_utxos} usable UTXOs), taking quotes only")]
+    LowFunds { balance_sat: u64, free_utxos: u64 },
+
+    #[error("Too many quotes or invalid submissions, banned until {until}")]
+    Banned { until: u64 },
+
+    #[error("Job took longer than {secs} seconds and was abandoned, retry in {retry_after} seconds")]
+    Timeout { secs: u64, retry_after: u64 },
+
+    // The child failed but the parent was handed back, or would relay alone and wasn't sent
+    #[error("{source}")]
+    ChildFailed { source: Box<SearcherError>, parent_hex: String, diagnosis: String },
+}
+
+impl SearcherError {
+    // Seconds a client should wait before sending the same request again
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            SearcherError::Timeout { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    // Per-input problems, for responses to list alongside the message
+    pub fn findings(&self) -> Option<Vec<crate::runes::InputFinding>> {
+        match self {
+            SearcherError::Validation(ValidationError::Inputs(findings)) => Some(findings.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            SearcherError::Validation(e) => e.reason(),
+            SearcherError::ChildFailed { source, .. } => source.reason(),
+            SearcherError::Payment(PaymentError::Http(_) | PaymentError::Node { .. } | PaymentError::Malformed(_)) => "lightning_unavailable",
+            SearcherError::Payment(PaymentError::NotConfigured) => "lightning_not_configured",
+            SearcherError::Payment(PaymentError::UnknownInvoice(_) | PaymentError::WrongParent(_)) => "invalid_invoice",
+            SearcherError::Payment(PaymentError::Unpaid(_) | PaymentError::Expired(_)) => "unpaid_invoice",
+            SearcherError::MissingQuoteInput => "missing_quote_input",
+            SearcherError::Rpc(_) => "bitcoind_unavailable",
+            SearcherError::NoWalletUtxos => "no_wallet_utxos",
+            SearcherError::Cpfp(_) => "cpfp_failed",
+            SearcherError::IncompleteSignature => "incomplete_signature",
+            SearcherError::PackageRejected(_) => "package_rejected",
+            SearcherError::CommitRejected(_) => "commit_rejected",
+            SearcherError::CommitDropped(_) => "commit_dropped",
+            SearcherError::Payjoin(_) => "payjoin_failed",
+            SearcherError::FeeCap { .. } => "fee_cap_exceeded",
+            SearcherError::Upstream(_) => "upstream_failed",
+            SearcherError::Pricing(_) => "pricing_unavailable",
+            SearcherError::Nostr(_) => "nostr_failed",
+            SearcherError::Tor(_) => "tor_failed",
+            SearcherError::Liquidation(_) => "liquidation_failed",
+            SearcherError::Signer(_) => "signer_failed",
+            SearcherError::Interrupted => "interrupted",
+            SearcherError::Dropped(_) => "dropped",
+            SearcherError::PayjoinExpired => "payjoin_expired",
+            SearcherError::UnknownJob(_) => "unknown_job",
+            SearcherError::CancelDenied => "cancel_denied",
+            SearcherError::LogDenied => "log_denied",
+            SearcherError::NotCancellable(_) => "not_cancellable",
+            SearcherError::Cancelled => "cancelled",
+            SearcherError::AlreadyAnchored { .. } => "already_anchored",
+            SearcherError::ConflictingParent { .. } => "conflicting_parent",
+            SearcherError::Replaced(_) => "replaced",
+            SearcherError::NotRescuable(_) => "not_rescuable",
+            SearcherError::Banned { .. } => "banned",
+            SearcherError::Timeout { .. } => "timeout",
+            SearcherError::AtCapacity { .. } => "at_capacity",
+            SearcherError::LowFunds { .. } => "low_funds",
+            SearcherError::Io(_) | SearcherError::Daemon(_) | SearcherError::Config(_) => "internal",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            SearcherError::Validation(e) => e.status_code(),
+            SearcherError::ChildFailed { source, .. } => source.status_code(),
+            SearcherError::Payment(e) => e.status_code(),
+            SearcherError::MissingQuoteInput => StatusCode::BAD_REQUEST,
+            SearcherError::Rpc(_) | SearcherError::Upstream(_) | SearcherError::Signer(_) => StatusCode::BAD_GATEWAY,
+            SearcherError::NoWalletUtxos
+            | SearcherError::AtCapacity { .. }
+            | SearcherError::LowFunds { .. }
+            | SearcherError::Timeout { .. }
+            | SearcherError::Pricing(_)
+            | SearcherError::FeeCap { .. } => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            SearcherError::PackageRejected(_)
+            | SearcherError::CommitRejected(_)
+            | SearcherError::Payjoin(_)
+            | SearcherError::NotRescuable(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            SearcherError::UnknownJob(_) => StatusCode::NOT_FOUND,
+            SearcherError::Banned { .. } => StatusCode::TOO_MANY_REQUESTS,
+            SearcherError::CancelDenied | SearcherError::LogDenied => StatusCode::FORBIDDEN,
+            SearcherError::NotCancellable(_)
+            | SearcherError::Cancelled
+            | SearcherError::AlreadyAnchored { .. }
+            | SearcherError::ConflictingParent { .. }
+            | SearcherError::Replaced(_)
+            | SearcherError::CommitDropped(_) => StatusCode::CONFLICT,
+            SearcherError::Cpfp(_)
+            | SearcherError::IncompleteSignature
+            | SearcherError::Nostr(_)
+            | SearcherError::Io(_)
+            | SearcherError::Daemon(_)
+            | SearcherError::Config(_)
+            | SearcherError::Interrupted
+            | SearcherError::Dropped(_)
+            | SearcherError::PayjoinExpired
+            | SearcherError::Liquidation(_)
+            | SearcherError::Tor(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PaymentError {
+    #[error("Request to Lightning node failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Lightning node at {url} failed: {message}")]
+    Node { url: String, message: String },
+
+    #[error("Unexpected Lightning node response: {0}")]
+    Malformed(String),
+
+    #[error("Lightning payments are not enabled on this searcher")]
+    NotConfigured,
+
+    #[error("Unknown invoice: {0}")]
+    UnknownInvoice(String),
+
+    #[error("Invoice {0} has not been paid")]
+    Unpaid(String),
+
+    #[error("Invoice {0} expired before it was paid")]
+    Expired(String),
+
+    #[error("Invoice {0} was quoted for a different parent transaction")]
+    WrongParent(String),
+}
+
+impl PaymentError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            PaymentError::Http(_) | PaymentError::Node { .. } | PaymentError::Malformed(_) => StatusCode::BAD_GATEWAY,
+            PaymentError::NotConfigured => StatusCode::BAD_REQUEST,
+            PaymentError::UnknownInvoice(_) => StatusCode::NOT_FOUND,
+            PaymentError::Unpaid(_) | PaymentError::Expired(_) => StatusCode::PAYMENT_REQUIRED,
+            PaymentError::WrongParent(_) => StatusCode::CONFLICT,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PricingError {
+    #[error("Request to rate provider failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Unexpected rate provider response: {0}")]
+    Malformed(String),
+
+    #[error("Invalid rate provider: {0} (expected static:<rate> or <url>#<json pointer>)")]
+    InvalidProvider(String),
+
+    #[error("Invalid price tier: {0} (expected <max parent vsize>:<multiplier>)")]
+    InvalidTier(String),
+
+    #[error("Exchange rate is {0} seconds old")]
+    Stale(u64),
+
+    #[error("No exchange rate available")]
+    NoRate,
+}
+
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("Notification request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("{sink} refused the notification: {status}")]
+    Refused { sink: String, status: reqwest::StatusCode },
+
+    #[error("Notification failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+#[derive(Debug, Error)]
+pub enum AmountError {
+    #[error("Invalid rune amount: {0}")]
+    Invalid(String),
+
+    #[error("Rune amount {amount} has more decimal places than the rune's divisibility of {divisibility}")]
+    TooPrecise { amount: String, divisibility: u8 },
+
+    #[error("Rune amount {0} is too large")]
+    Overflow(String),
+}
+
+#[derive(Debug, Error)]
+pub enum NostrError {
+    // Boxed because tungstenite errors can carry a whole HTTP response
+    #[error("Nostr relay connection failed: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+
+    #[error("Invalid Nostr message: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid Nostr key or signature: {0}")]
+    Key(#[from] bitcoin::secp256k1::Error),
+
+    #[error("Invalid Nostr event: {0}")]
+    InvalidEvent(String),
+
+    #[error("{relay} rejected event: {message}")]
+    Rejected { relay: String, message: String },
+}
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Request to searcher failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Searcher returned {status} for {url}")]
+    Status { url: String, status: reqwest::StatusCode },
+
+    #[error("Invalid response from searcher: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Searcher response failed verification: {0}")]
+    Unverified(#[from] SignatureError),
+}
+
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    #[error("missing or malformed {0} header")]
+    MissingHeader(String),
+
+    #[error("timestamp is {0}s away from our clock")]
+    Stale(u64),
+
+    #[error("nonce doesn't match the request, the response may be replayed")]
+    NonceMismatch,
+
+    #[error("signature doesn't match the response")]
+    BadSignature,
+}
+
+#[derive(Debug, Error)]
+pub enum HwiError {
+    #[error("Failed to run hwi: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unexpected hwi output: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("hwi failed: {0}")]
+    Command(String),
+
+    #[error("No hardware wallet found")]
+    NoDevice,
+
+    #[error("Hardware wallet unavailable: {0}")]
+    Device(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("Request to chain backend failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Chain backend returned {status} for {url}")]
+    Status { url: String, status: reqwest::StatusCode },
+
+    #[error("Bitcoin Core RPC failed: {0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+
+    #[error("Chain backend connection failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid chain backend response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unexpected chain backend response: {0}")]
+    Malformed(String),
+
+    #[error("Chain backend rejected the request: {0}")]
+    Rejected(String),
+
+    #[error("Invalid chain backend: {0} (expected core, esplora:<url>, electrum:<host:port> or filters:<host:port>[@<height>])")]
+    InvalidBackend(String),
+}
+
+#[derive(Debug, Error)]
+pub enum SignerError {
+    #[error("Request to external signer failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("External signer at {url} refused: {message}")]
+    Refused { url: String, message: String },
+
+    #[error("External signer returned an invalid PSBT: {0}")]
+    Malformed(String),
+
+    #[error("External signer changed the transaction to {0}")]
+    Changed(String),
+
+    #[error("Input {0} was not signed")]
+    Unsigned(usize),
+}
+
+#[derive(Debug, Error)]
+pub enum MultisigError {
+    #[error("No PSBTs to combine")]
+    NoPsbts,
+
+    #[error("PSBT {index} is for a different transaction, {txid}")]
+    Mismatch { index: usize, txid: String },
+
+    #[error("Failed to combine PSBTs: {0}")]
+    Combine(#[from] bitcoin::psbt::Error),
+
+    #[error("Input {index} has {signatures} of the {required} signatures it needs")]
+    MissingSignatures { index: usize, signatures: usize, required: usize },
+
+    #[error("Input {0} spends a script that can't be finalized here")]
+    Unsupported(usize),
+}
+
+#[derive(Debug, Error)]
+pub enum MusigError {
+    #[error("No keys to aggregate")]
+    NoKeys,
+
+    #[error("Key {0} isn't one of the cosigners' keys")]
+    UnknownKey(String),
+
+    #[error("The secret nonce wasn't made for key {0}")]
+    WrongNonce(String),
+
+    #[error("Invalid nonce: {0}")]
+    InvalidNonce(String),
+
+    #[error("Partial signature from {0} doesn't verify")]
+    InvalidPartialSignature(String),
+
+    #[error("Aggregated signature doesn't verify against the output key")]
+    InvalidSignature,
+
+    #[error("The {0} is the point at infinity")]
+    Infinity(&'static str),
+
+    #[error("The {0} is not below the curve order")]
+    OutOfRange(String),
+
+    #[error(transparent)]
+    Secp(#[from] bitcoin::secp256k1::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Audit log I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Line {line} of the audit log is malformed: {message}")]
+    Malformed { line: usize, message: String },
+
+    #[error("Line {line} of the audit log doesn't follow the entry before it")]
+    BrokenChain { line: usize },
+
+    #[error("Line {line} of the audit log was altered after it was written")]
+    AlteredEntry { line: usize },
+
+    #[error("Audit log has no entry with hash {0}, so the entries up to it were removed or rewritten")]
+    MissingCheckpoint(String),
+}
+
+#[derive(Debug, Error)]
+pub enum LiquidationError {
+    #[error("Bitcoin Core RPC failed: {0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+
+    #[error(transparent)]
+    Ord(#[from] OrdError),
+
+    #[error("Request to liquidation venue failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Failed to build offer: {0}")]
+    Offer(String),
+
+    #[error("Liquidation venue at {url} refused the offer: {message}")]
+    Venue { url: String, message: String },
+}
+
+#[derive(Debug, Error)]
+pub enum TorError {
+    #[error("Tor control connection failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Tor control authentication failed: {0}")]
+    Auth(String),
+
+    #[error("Tor control command failed: {0}")]
+    Command(String),
+}
+
+#[cfg(feature = "testkit")]
+#[derive(Debug, Error)]
+pub enum TestkitError {
+    #[error("Bitcoin Core RPC failed: {0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+
+    #[error(transparent)]
+    Ord(#[from] OrdError),
+
+    #[error("Faucet request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Testkit I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid address: {0}")]
+    Address(#[from] bitcoin::address::ParseError),
+
+    #[error("Unexpected ord wallet output: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("ord wallet failed: {0}")]
+    OrdWallet(String),
+
+    #[error("Faucet refused the request: {0}")]
+    Faucet(String),
+
+    #[error("Timed out waiting for {0}")]
+    Timeout(String),
+
+    #[error("{0}")]
+    Unsupported(String),
+}
@@ -0,0 +1,277 @@
+use bitcoincore_rpc::{Client, RpcApi};
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+use crate::chain::EsploraBackend;
+
+// Estimates younger than this are reused instead of asking again
+const REFRESH_SECS: u64 = 60;
+// A block's worth of mempool
+pub const BLOCK_VBYTES: u64 = 1_000_000;
+// A template this heavy is a full block, so the mempool runs deeper than it shows
+const FULL_TEMPLATE_WEIGHT: u64 = 3_960_000;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// bitcoind's estimate for confirming within `target` blocks, in sat/vB. None when
+// bitcoind doesn't have enough data yet, which is always the case on regtest.
+pub fn core_estimate(client: &Client, target: u16) -> Option<f64> {
+    match client.estimate_smart_fee(target, None) {
+        Ok(estimate) => estimate.fee_rate.map(|rate| rate.to_sat() as f64 / 1000.0),
+        Err(e) => {
+            error!("estimatesmartfee failed: {}", e);
+            None
+        }
+    }
+}
+
+// Dynamic fee rate: bitcoind's estimate, then a mempool.space instance, then a fixed fallback
+#[derive(Debug)]
+pub struct FeeEstimator {
+    target: u16,
+    mempool_api: Option<String>,
+    fallback: f64,
+    cached: Mutex<Option<(f64, u64)>>,
+}
+
+impl FeeEstimator {
+    pub fn new(target: u16, mempool_api: Option<String>, fallback: f64) -> Self {
+        FeeEstimator {
+            target,
+            mempool_api,
+            fallback,
+            cached: Mutex::new(None),
+        }
+    }
+
+    // Blocking, since both sources are. `core` is optional so this works without a node.
+    pub fn rate(&self, core: Option<&Client>) -> f64 {
+        if let Some((rate, timestamp)) = *self.cached.lock().unwrap_or_else(|e| e.into_inner())
+            && now().saturating_sub(timestamp) < REFRESH_SECS
+        {
+            return rate;
+        }
+
+        let from_core = core.and_then(|client| core_estimate(client, self.target));
+        let estimate = match (from_core, &self.mempool_api) {
+            (Some(rate), _) => Some((rate, "bitcoind".to_string())),
+            (None, Some(url)) => match EsploraBackend::new(url).recommended_fees() {
+                Ok(fees) => Some((fees.for_target(self.target), url.clone())),
+                Err(e) => {
+                    error!("Fee estimate from {} failed: {}", url, e);
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+
+        match estimate {
+            Some((rate, source)) => {
+                info!("Fee rate {} sat/vB for {} blocks from {}", rate, self.target, source);
+                *self.cached.lock().unwrap_or_else(|e| e.into_inner()) = Some((rate, now()));
+                rate
+            }
+            None => {
+                error!("No fee estimate available, using {} sat/vB", self.fallback);
+                self.fallback
+            }
+        }
+    }
+}
+
+// Fee rate buckets of the mempool, highest rate first, as (sat/vB, vbytes)
+#[derive(Debug, Clone, Default)]
+pub struct FeeHistogram {
+    pub buckets: Vec<(f64, u64)>,
+    // Whether there are more transactions below the last bucket than it shows
+    pub truncated: bool,
+}
+
+impl FeeHistogram {
+    // From getblocktemplate's transactions, which only cover the next block
+    pub fn from_template(template: &Value) -> Option<Self> {
+        let transactions = template.get("transactions")?.as_array()?;
+        let mut buckets = Vec::with_capacity(transactions.len());
+        let mut weight = 0;
+        for tx in transactions {
+            let fee = tx.get("fee")?.as_u64()?;
+            let tx_weight = tx.get("weight")?.as_u64()?;
+            let vsize = tx_weight.div_ceil(4).max(1);
+            buckets.push((fee as f64 / vsize as f64, vsize));
+            weight += tx_weight;
+        }
+        // Template order keeps packages together, the histogram wants rates
+        buckets.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Some(FeeHistogram { buckets, truncated: weight >= FULL_TEMPLATE_WEIGHT })
+    }
+
+    // The rate paid by the transaction `depth` vbytes from the top of the mempool. None
+    // when the mempool isn't that deep, so anything above the relay floor gets that far.
+    pub fn rate_at_depth(&self, depth: u64) -> Option<f64> {
+        let mut total = 0;
+        for (rate, vsize) in &self.buckets {
+            total += vsize;
+            if total >= depth {
+                return Some(*rate);
+            }
+        }
+        match self.truncated {
+            true => self.buckets.last().map(|(rate, _)| *rate),
+            false => None,
+        }
+    }
+}
+
+// Fee histogram from bitcoind's block template, then a mempool.space instance
+fn core_histogram(client: &Client) -> Option<FeeHistogram> {
+    match client.call::<Value>("getblocktemplate", &[json!({"rules": ["segwit"]})]) {
+        Ok(template) => FeeHistogram::from_template(&template),
+        Err(e) => {
+            error!("getblocktemplate failed: {}", e);
+            None
+        }
+    }
+}
+
+// The fee rate it takes to sit `depth` vbytes deep in the mempool, for pricing that
+// follows the mempool rather than a fixed sat/vB
+#[derive(Debug)]
+pub struct HistogramEstimator {
+    depth: u64,
+    mempool_api: Option<String>,
+    cached: Mutex<Option<(f64, u64)>>,
+}
+
+impl HistogramEstimator {
+    pub fn new(depth: u64, mempool_api: Option<String>) -> Self {
+        HistogramEstimator {
+            depth,
+            mempool_api,
+            cached: Mutex::new(None),
+        }
+    }
+
+    // Blocking like FeeEstimator. None when neither source has a histogram.
+    pub fn rate(&self, core: Option<&Client>) -> Option<f64> {
+        if let Some((rate, timestamp)) = *self.cached.lock().unwrap_or_else(|e| e.into_inner())
+            && now().saturating_sub(timestamp) < REFRESH_SECS
+        {
+            return Some(rate);
+        }
+
+        let from_core = core.and_then(core_histogram).map(|histogram| (histogram, "bitcoind".to_string()));
+        let histogram = from_core.or_else(|| {
+            let url = self.mempool_api.as_ref()?;
+            match EsploraBackend::new(url).mempool() {
+                Ok(mempool) => Some((FeeHistogram { buckets: mempool.fee_histogram, truncated: false }, url.clone())),
+                Err(e) => {
+                    error!("Mempool histogram from {} failed: {}", url, e);
+                    None
+                }
+            }
+        });
+        let (histogram, source) = histogram?;
+
+        // Nothing gets in below the mempool's minimum, however empty it is
+        let floor = core
+            .and_then(|client| client.get_mempool_info().ok())
+            .map_or(1.0, |info| info.mempool_min_fee.to_sat() as f64 / 1000.0);
+        let rate = histogram.rate_at_depth(self.depth).unwrap_or(floor).max(floor);
+        info!("Fee rate {} sat/vB at {} vbytes deep from {}", rate, self.depth, source);
+        *self.cached.lock().unwrap_or_else(|e| e.into_inner()) = Some((rate, now()));
+        Some(rate)
+    }
+}
+
+// What it takes to get into the next block, going by bitcoind's block template
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockFloor {
+    // Lowest rate of a package that made the template
+    pub rate: f64,
+    // Whether the template is a full block, so packages have to outbid that rate
+    pub full: bool,
+}
+
+impl BlockFloor {
+    // Each transaction nothing else in the template spends is a package with its
+    // in-template ancestors, which it was picked with, so a zero-fee parent is judged
+    // by its child
+    pub fn from_template(template: &Value) -> Option<Self> {
+        let transactions = template.get("transactions")?.as_array()?;
+        let mut txs = Vec::with_capacity(transactions.len());
+        for tx in transactions {
+            // Template dependencies are 1-based positions in the list
+            let depends = tx.get("depends")?.as_array()?
+                .iter()
+                .map(|depend| depend.as_u64().and_then(|i| usize::try_from(i).ok()?.checked_sub(1)))
+                .collect::<Option<Vec<_>>>()?;
+            txs.push((tx.get("fee")?.as_u64()?, tx.get("weight")?.as_u64()?, depends));
+        }
+
+        let mut spent = vec![false; txs.len()];
+        for (_, _, depends) in &txs {
+            for i in depends {
+                *spent.get_mut(*i)? = true;
+            }
+        }
+        let mut rate = None::<f64>;
+        for leaf in (0..txs.len()).filter(|i| !spent[*i]) {
+            let (mut fee, mut weight) = (0, 0);
+            let mut package = vec![leaf];
+            let mut seen = vec![false; txs.len()];
+            while let Some(i) = package.pop() {
+                if std::mem::replace(&mut seen[i], true) {
+                    continue;
+                }
+                fee += txs[i].0;
+                weight += txs[i].1;
+                package.extend(txs[i].2.iter().filter(|i| **i < txs.len()));
+            }
+            let package_rate = fee as f64 / weight.div_ceil(4).max(1) as f64;
+            rate = Some(rate.map_or(package_rate, |rate| rate.min(package_rate)));
+        }
+
+        let weight = txs.iter().map(|(_, weight, _)| weight).sum::<u64>();
+        Some(BlockFloor { rate: rate.unwrap_or(0.0), full: weight >= FULL_TEMPLATE_WEIGHT })
+    }
+
+    // Whether a package paying `rate` would be mined next, if it were broadcast now
+    pub fn includes(&self, rate: f64) -> bool {
+        !self.full || rate > self.rate
+    }
+}
+
+// bitcoind's next block template, reused for REFRESH_SECS since it's costly to make
+#[derive(Debug, Default)]
+pub struct NextBlock {
+    cached: Mutex<Option<(BlockFloor, u64)>>,
+}
+
+impl NextBlock {
+    // Blocking like FeeEstimator. None when bitcoind can't make a template, like while
+    // it's syncing or has no peers.
+    pub fn floor(&self, core: &Client) -> Option<BlockFloor> {
+        if let Some((floor, timestamp)) = *self.cached.lock().unwrap_or_else(|e| e.into_inner())
+            && now().saturating_sub(timestamp) < REFRESH_SECS
+        {
+            return Some(floor);
+        }
+        let floor = match core.call::<Value>("getblocktemplate", &[json!({"rules": ["segwit"]})]) {
+            Ok(template) => BlockFloor::from_template(&template)?,
+            Err(e) => {
+                error!("getblocktemplate failed: {}", e);
+                return None;
+            }
+        };
+        info!("Next block template takes packages over {} sat/vB (full: {})", floor.rate, floor.full);
+        *self.cached.lock().unwrap_or_else(|e| e.into_inner()) = Some((floor, now()));
+        Some(floor)
+    }
+}
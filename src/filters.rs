@@ -0,0 +1,490 @@
+use bitcoin::bip158::{BlockFilter, FilterHash, FilterHeader};
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{deserialize, serialize, Decodable};
+use bitcoin::constants::genesis_block;
+use bitcoin::hashes::Hash;
+use bitcoin::p2p::message::{NetworkMessage, RawNetworkMessage};
+use bitcoin::p2p::message_blockdata::{GetHeadersMessage, Inventory};
+use bitcoin::p2p::message_filter::{GetCFHeaders, GetCFilters};
+use bitcoin::p2p::message_network::VersionMessage;
+use bitcoin::p2p::{Address as PeerAddress, Magic, ServiceFlags};
+use bitcoin::pow::{CompactTarget, Work};
+use bitcoin::{Address, BlockHash, Network, OutPoint, Transaction, Txid};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+use crate::chain::{ChainBackend, ChainUtxo};
+use crate::error::ChainError;
+
+// Peers answer getheaders with at most this many headers, and getcfilters for at most this many blocks
+const MAX_HEADERS: usize = 2000;
+const MAX_FILTERS: u32 = 1000;
+
+// BIP 158 basic filter
+const BASIC_FILTER: u8 = 0;
+
+// Recent headers a locator lists one by one before it starts skipping back
+const LOCATOR_RECENT: usize = 10;
+
+// Headers synced from genesis, each one checked to link to the one before, carry the
+// difficulty the network calls for at its height, and meet it
+struct HeaderChain {
+    network: Network,
+    headers: Vec<Header>,
+    hashes: Vec<BlockHash>,
+}
+
+impl HeaderChain {
+    fn new(network: Network) -> Self {
+        let genesis = genesis_block(network).header;
+        HeaderChain { network, headers: vec![genesis], hashes: vec![genesis.block_hash()] }
+    }
+
+    // Headers saved by an earlier sync, checked again, up to the first one that fails
+    fn load(network: Network, path: &PathBuf) -> Self {
+        let mut chain = HeaderChain::new(network);
+        let Ok(bytes) = fs::read(path) else {
+            return chain;
+        };
+        for encoded in bytes.chunks_exact(80).skip(1) {
+            let pushed = deserialize::<Header>(encoded)
+                .map_err(|e| ChainError::Malformed(e.to_string()))
+                .and_then(|header| chain.push(header));
+            if let Err(e) = pushed {
+                error!("Discarding saved headers from height {}: {}", chain.headers.len(), e);
+                break;
+            }
+        }
+        chain
+    }
+
+    // Rewrites the file from `from` on, so a sync only appends what's new
+    fn save(&self, path: &PathBuf, from: usize) -> Result<(), ChainError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let from = from.min((file.metadata()?.len() / 80) as usize);
+        file.set_len(from as u64 * 80)?;
+        let encoded: Vec<u8> = self.headers[from..].iter().flat_map(serialize).collect();
+        file.write_all(&encoded)?;
+        Ok(())
+    }
+
+    fn tip(&self) -> (u32, BlockHash) {
+        ((self.hashes.len() - 1) as u32, self.hashes[self.hashes.len() - 1])
+    }
+
+    fn height_of(&self, hash: &BlockHash) -> Option<usize> {
+        self.hashes.iter().rposition(|known| known == hash)
+    }
+
+    // The latest hashes, then exponentially further back to genesis, so the peer can find
+    // where its chain leaves ours
+    fn locator(&self) -> Vec<BlockHash> {
+        let mut locator = Vec::new();
+        let mut height = self.hashes.len() - 1;
+        let mut step = 1;
+        while height > 0 {
+            locator.push(self.hashes[height]);
+            if locator.len() >= LOCATOR_RECENT {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+        }
+        locator.push(self.hashes[0]);
+        locator
+    }
+
+    fn work_after(&self, height: usize) -> Work {
+        self.headers[height + 1..].iter().fold(Work::from_be_bytes([0; 32]), |work, header| work + header.work())
+    }
+
+    fn truncate(&mut self, height: usize) {
+        self.headers.truncate(height + 1);
+        self.hashes.truncate(height + 1);
+    }
+
+    fn push(&mut self, header: Header) -> Result<(), ChainError> {
+        let (_, tip) = self.tip();
+        if header.prev_blockhash != tip {
+            return Err(ChainError::Malformed(format!("Header {} does not connect", header.block_hash())));
+        }
+        self.check_difficulty(&header)
+            .map_err(|e| ChainError::Malformed(format!("Header {}: {}", header.block_hash(), e)))?;
+        let hash = header.validate_pow(header.target())
+            .map_err(|e| ChainError::Malformed(format!("Header {}: {}", header.block_hash(), e)))?;
+        self.headers.push(header);
+        self.hashes.push(hash);
+        Ok(())
+    }
+
+    // The bits Bitcoin Core would expect: the previous header's, or a retarget every
+    // difficulty period. Networks that allow minimum difficulty blocks drop to the limit
+    // whenever blocks are slow, so there a header is only held to the proof of work limit.
+    fn check_difficulty(&self, header: &Header) -> Result<(), String> {
+        let params = self.network.params();
+        if header.target() > params.max_attainable_target {
+            return Err(format!("target {:?} is above the proof of work limit", header.bits));
+        }
+        if params.allow_min_difficulty_blocks {
+            return Ok(());
+        }
+        let height = self.headers.len() as u64;
+        let previous = &self.headers[self.headers.len() - 1];
+        let interval = params.difficulty_adjustment_interval();
+        let expected = match height % interval {
+            0 if !params.no_pow_retargeting => {
+                let first = &self.headers[(height - interval) as usize];
+                let timespan = (i64::from(previous.time) - i64::from(first.time)).max(0) as u64;
+                CompactTarget::from_next_work_required(previous.bits, timespan, params)
+            }
+            _ => previous.bits,
+        };
+        match header.bits == expected {
+            true => Ok(()),
+            false => Err(format!("bits {:?} where the network requires {:?}", header.bits, expected)),
+        }
+    }
+}
+
+// A connection to one P2P peer
+struct Peer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    magic: Magic,
+}
+
+impl Peer {
+    // Refuses peers without the `required` services. Peers only take transactions from
+    // connections that asked to `relay` them.
+    fn connect(address: &str, network: Network, required: ServiceFlags, relay: bool) -> Result<Peer, ChainError> {
+        let socket = address.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| ChainError::Malformed(format!("Cannot resolve {}", address)))?;
+        let writer = TcpStream::connect_timeout(&socket, Duration::from_secs(30))?;
+        writer.set_read_timeout(Some(Duration::from_secs(60)))?;
+
+        let mut peer = Peer {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+            magic: network.magic(),
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let mut version_message = VersionMessage::new(
+            ServiceFlags::NONE,
+            timestamp,
+            PeerAddress::new(&socket, ServiceFlags::NONE),
+            PeerAddress::new(&socket, ServiceFlags::NONE),
+            timestamp as u64,
+            "/slugline/".to_string(),
+            0,
+        );
+        version_message.relay = relay;
+        peer.send(NetworkMessage::Version(version_message))?;
+
+        let (mut version, mut verack) = (false, false);
+        while !(version && verack) {
+            match peer.receive()? {
+                NetworkMessage::Version(theirs) => {
+                    if !theirs.services.has(required) {
+                        return Err(ChainError::Rejected(format!("{} lacks services {}", address, required)));
+                    }
+                    peer.send(NetworkMessage::Verack)?;
+                    version = true;
+                }
+                NetworkMessage::Verack => verack = true,
+                _ => {}
+            }
+        }
+
+        info!("Connected to {}", address);
+        Ok(peer)
+    }
+
+    fn send(&mut self, message: NetworkMessage) -> Result<(), ChainError> {
+        let raw = RawNetworkMessage::new(self.magic, message);
+        self.writer.write_all(&serialize(&raw))?;
+        Ok(())
+    }
+
+    // Next message from the peer, answering keepalive pings along the way
+    fn receive(&mut self) -> Result<NetworkMessage, ChainError> {
+        loop {
+            let raw = RawNetworkMessage::consensus_decode(&mut bitcoin::io::FromStd::new(&mut self.reader))
+                .map_err(|e| ChainError::Malformed(format!("Invalid P2P message: {}", e)))?;
+            match raw.into_payload() {
+                NetworkMessage::Ping(nonce) => self.send(NetworkMessage::Pong(nonce))?,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    // Waits for the peer to answer a ping. Peers handle messages in order, so by then
+    // they have looked at everything sent before it.
+    fn ping(&mut self) -> Result<(), ChainError> {
+        let nonce = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or_default();
+        self.send(NetworkMessage::Ping(nonce))?;
+        loop {
+            if let NetworkMessage::Pong(pong) = self.receive()?
+                && pong == nonce
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    // Brings `chain` up to the peer's best chain, from where it left off. A peer that has
+    // reorganized away from our tip has to show more work on its side of the fork than on
+    // ours. Returns the lowest height that changed.
+    fn sync_headers(&mut self, chain: &mut HeaderChain) -> Result<usize, ChainError> {
+        let mut changed = chain.headers.len();
+        let mut fork: Option<(usize, Work)> = None;
+        let mut locator = chain.locator();
+        let mut first_batch = true;
+        loop {
+            self.send(NetworkMessage::GetHeaders(GetHeadersMessage::new(locator, BlockHash::all_zeros())))?;
+
+            let headers = loop {
+                if let NetworkMessage::Headers(headers) = self.receive()? {
+                    break headers;
+                }
+            };
+
+            let count = headers.len();
+            if let Some(first) = headers.first()
+                && first.prev_blockhash != chain.tip().1
+            {
+                // Only the first batch may start below our tip
+                let height = chain.height_of(&first.prev_blockhash)
+                    .filter(|_| first_batch)
+                    .ok_or_else(|| ChainError::Malformed(format!("Header {} does not connect", first.block_hash())))?;
+                info!("Peer reorganized from height {}", height + 1);
+                fork = Some((height, chain.work_after(height)));
+                chain.truncate(height);
+                changed = height + 1;
+            }
+            for header in headers {
+                chain.push(header)?;
+            }
+
+            if count < MAX_HEADERS {
+                break;
+            }
+            locator = vec![chain.tip().1];
+            first_batch = false;
+        }
+
+        if let Some((height, dropped)) = fork
+            && chain.work_after(height) <= dropped
+        {
+            return Err(ChainError::Rejected(format!("Peer reorganized from height {} onto a chain with less work", height + 1)));
+        }
+        info!("Synced {} headers", chain.headers.len());
+        Ok(changed)
+    }
+}
+
+// Hands a package to a peer, child first. A peer doing 1p1c package relay (Bitcoin Core
+// 28 and later) keeps the child as an orphan, then evaluates the two together when a parent
+// paying less than its minimum fee arrives. Peers don't acknowledge transactions, so the
+// ping answered after them only shows the peer has looked at them.
+pub fn announce_package(address: &str, network: Network, package: &[Transaction]) -> Result<(), ChainError> {
+    let mut peer = Peer::connect(address, network, ServiceFlags::NONE, true)?;
+    for tx in package.iter().rev() {
+        peer.send(NetworkMessage::Tx(tx.clone()))?;
+    }
+    peer.ping()?;
+    info!("Announced {} transactions to {}", package.len(), address);
+    Ok(())
+}
+
+// Neutrino-style light client: downloads compact block filters from a peer and only
+// fetches the blocks whose filters match the address being scanned.
+//
+// Everything comes from the one peer. Headers are checked for proof of work and the
+// network's difficulty, and filters against the filter headers the peer commits to, so a
+// peer can't hand out filters that disagree with its own chain. It can still hide
+// transactions from both, or serve a valid but stale chain, so the peer must be trusted.
+pub struct CompactFilterBackend {
+    peer: String,
+    network: Network,
+    start_height: u32,
+    // Where synced headers are kept between runs, so a sync resumes from the last tip
+    header_file: Option<PathBuf>,
+    headers: Mutex<Option<HeaderChain>>,
+}
+
+impl CompactFilterBackend {
+    pub fn new(peer: &str, network: Network, start_height: u32) -> Self {
+        CompactFilterBackend {
+            peer: peer.to_string(),
+            network,
+            start_height,
+            header_file: std::env::home_dir().map(|home| home.join(".slugline").join(format!("headers-{}", network))),
+            headers: Mutex::new(None),
+        }
+    }
+
+    // Syncs the header chain with the peer and runs `f` on it. A failed sync throws away
+    // what's in memory, so the next one starts again from what was saved.
+    fn with_headers<T>(&self, peer: &mut Peer, f: impl FnOnce(&HeaderChain) -> T) -> Result<T, ChainError> {
+        let mut headers = self.headers.lock().unwrap_or_else(|e| e.into_inner());
+        let chain = headers.get_or_insert_with(|| match &self.header_file {
+            Some(path) => HeaderChain::load(self.network, path),
+            None => HeaderChain::new(self.network),
+        });
+        let changed = match peer.sync_headers(chain) {
+            Ok(changed) => changed,
+            Err(e) => {
+                *headers = None;
+                return Err(e);
+            }
+        };
+        if let Some(path) = &self.header_file
+            && let Err(e) = chain.save(path, changed)
+        {
+            error!("Failed to save headers to {}: {}", path.display(), e);
+        }
+        Ok(f(chain))
+    }
+}
+
+// The peer's filter hashes for heights `start` to `stop`, checked to chain up from the
+// filter header before `start` to the one after `stop`. Returns that last filter header
+// alongside, for the next range to start from.
+fn filter_hashes(
+    peer: &mut Peer,
+    start: u32,
+    stop_hash: BlockHash,
+    previous: Option<FilterHeader>,
+) -> Result<(Vec<FilterHash>, FilterHeader), ChainError> {
+    peer.send(NetworkMessage::GetCFHeaders(GetCFHeaders { filter_type: BASIC_FILTER, start_height: start, stop_hash }))?;
+    let headers = loop {
+        if let NetworkMessage::CFHeaders(headers) = peer.receive()? {
+            break headers;
+        }
+    };
+    if headers.stop_hash != stop_hash {
+        return Err(ChainError::Malformed(format!("Unexpected filter headers up to block {}", headers.stop_hash)));
+    }
+    if previous.is_some_and(|previous| previous != headers.previous_filter_header) {
+        return Err(ChainError::Malformed(format!("Filter headers from height {} do not connect", start)));
+    }
+    let last = headers.filter_hashes.iter().fold(headers.previous_filter_header, |header, hash| hash.filter_header(&header));
+    Ok((headers.filter_hashes, last))
+}
+
+impl ChainBackend for CompactFilterBackend {
+    fn utxos(&self, address: &Address) -> Result<Vec<ChainUtxo>, ChainError> {
+        let mut peer = Peer::connect(&self.peer, self.network, ServiceFlags::COMPACT_FILTERS, false)?;
+        let start_height = self.start_height.max(1);
+        let (tip, hashes) = self.with_headers(&mut peer, |chain| {
+            (chain.tip().0, chain.hashes.get(start_height as usize..).unwrap_or_default().to_vec())
+        })?;
+        let hash_at = |height: u32| hashes[(height - start_height) as usize];
+        let script_pubkey = address.script_pubkey();
+
+        // Basic filters cover both output scripts and the scripts of spent outputs,
+        // so blocks that spend the address's coins match too
+        let mut matched = Vec::new();
+        let mut start = start_height;
+        let mut filter_header = None;
+        while start <= tip {
+            let stop = (start + MAX_FILTERS - 1).min(tip);
+            let (filter_hashes, last) = filter_hashes(&mut peer, start, hash_at(stop), filter_header)?;
+            filter_header = Some(last);
+            peer.send(NetworkMessage::GetCFilters(GetCFilters {
+                filter_type: BASIC_FILTER,
+                start_height: start,
+                stop_hash: hash_at(stop),
+            }))?;
+
+            for height in start..=stop {
+                let filter = loop {
+                    if let NetworkMessage::CFilter(filter) = peer.receive()? {
+                        break filter;
+                    }
+                };
+                if filter.block_hash != hash_at(height) {
+                    return Err(ChainError::Malformed(format!("Unexpected filter for block {}", filter.block_hash)));
+                }
+                if filter_hashes.get((height - start) as usize) != Some(&FilterHash::hash(&filter.filter)) {
+                    return Err(ChainError::Malformed(format!("Filter for block {} does not match its filter header", filter.block_hash)));
+                }
+                let found = BlockFilter::new(&filter.filter)
+                    .match_any(&filter.block_hash, std::iter::once(script_pubkey.as_bytes()))
+                    .map_err(|e| ChainError::Malformed(format!("Invalid filter: {}", e)))?;
+                if found {
+                    matched.push(height);
+                }
+            }
+            start = stop + 1;
+        }
+        info!("{} blocks matched filters for {}", matched.len(), address);
+
+        // Replay matching blocks in order, adding outputs to the address and removing spends
+        let mut utxos: BTreeMap<OutPoint, ChainUtxo> = BTreeMap::new();
+        for height in matched {
+            peer.send(NetworkMessage::GetData(vec![Inventory::WitnessBlock(hash_at(height))]))?;
+            let block = loop {
+                if let NetworkMessage::Block(block) = peer.receive()? {
+                    break block;
+                }
+            };
+            // The synced headers only vouch for a block whose hash and commitments match
+            if block.block_hash() != hash_at(height) {
+                return Err(ChainError::Malformed(format!("Asked for block {}, got {}", hash_at(height), block.block_hash())));
+            }
+            if !block.check_merkle_root() || !block.check_witness_commitment() {
+                return Err(ChainError::Malformed(format!("Block {} does not match the commitments in its header", hash_at(height))));
+            }
+
+            for tx in &block.txdata {
+                for input in &tx.input {
+                    utxos.remove(&input.previous_output);
+                }
+                let txid = tx.compute_txid();
+                for (vout, output) in tx.output.iter().enumerate() {
+                    if output.script_pubkey == script_pubkey {
+                        let outpoint = OutPoint { txid, vout: vout as u32 };
+                        utxos.insert(outpoint, ChainUtxo {
+                            outpoint,
+                            value: output.value.to_sat(),
+                            script_pubkey: output.script_pubkey.clone(),
+                            confirmations: tip - height + 1,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(utxos.into_values().collect())
+    }
+
+    // Peers don't acknowledge transactions, so this only confirms the peer looked at it.
+    // The connection asks for relay, or the peer would ignore the transaction.
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        let mut peer = Peer::connect(&self.peer, self.network, ServiceFlags::NONE, true)?;
+        peer.send(NetworkMessage::Tx(tx.clone()))?;
+        peer.ping()?;
+        info!("Sent {} to {}", tx.compute_txid(), self.peer);
+        Ok(tx.compute_txid())
+    }
+
+    fn tip(&self) -> Result<(u64, BlockHash), ChainError> {
+        let mut peer = Peer::connect(&self.peer, self.network, ServiceFlags::COMPACT_FILTERS, false)?;
+        let (height, hash) = self.with_headers(&mut peer, HeaderChain::tip)?;
+        Ok((height as u64, hash))
+    }
+}
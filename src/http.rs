@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+// Pooled connections are kept open this long between requests
+const POOL_IDLE_SECS: u64 = 90;
+const TCP_KEEPALIVE_SECS: u64 = 60;
+
+// Timeouts and proxy shared by every HTTP client slugline builds, so one slow ord
+// server can't hang a command or a searcher request forever
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub connect_timeout: Duration,
+    // Whole request, from connecting until the body is read
+    pub timeout: Duration,
+    pub proxy: Option<reqwest::Proxy>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+            proxy: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    // Clients are meant to be built once and cloned, which shares the connection pool.
+    // Like reqwest::Client::new, this panics only if the TLS backend can't be initialized.
+    pub fn client(&self) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .pool_idle_timeout(Duration::from_secs(POOL_IDLE_SECS))
+            .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE_SECS));
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        builder.build().expect("TLS backend cannot be initialized")
+    }
+
+    // Has to be called outside of an async runtime, as with any blocking reqwest client
+    pub fn blocking_client(&self) -> reqwest::blocking::Client {
+        let mut builder = reqwest::blocking::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .pool_idle_timeout(Duration::from_secs(POOL_IDLE_SECS))
+            .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE_SECS));
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        builder.build().expect("TLS backend cannot be initialized")
+    }
+}
+
+// A URL fit for logs: passwords and query values, which often carry API keys, are masked
+pub fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some("***"));
+    }
+    if parsed.query().is_some() {
+        let keys: Vec<String> = parsed.query_pairs().map(|(key, _)| key.into_owned()).collect();
+        parsed.query_pairs_mut().clear().extend_pairs(keys.iter().map(|key| (key, "***")));
+    }
+    parsed.to_string()
+}
@@ -0,0 +1,134 @@
+use bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{CompressedPublicKey, Network, ScriptBuf};
+use serde::Deserialize;
+use std::process::Command;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::error::HwiError;
+
+// A hardware wallet as reported by `hwi enumerate`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Device {
+    #[serde(rename = "type")]
+    pub device_type: String,
+    pub model: String,
+    pub path: String,
+    pub fingerprint: Option<String>,
+    pub error: Option<String>,
+}
+
+// Drives hardware wallets through the HWI command line tool
+#[derive(Debug, Clone)]
+pub struct Hwi {
+    binary: String,
+    chain: &'static str,
+}
+
+impl Hwi {
+    pub fn new(binary: &str, network: Network) -> Self {
+        let chain = match network {
+            Network::Bitcoin => "main",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+            _ => "test",
+        };
+        Hwi {
+            binary: binary.to_string(),
+            chain,
+        }
+    }
+
+    fn run(&self, device: Option<&Device>, args: &[&str]) -> Result<serde_json::Value, HwiError> {
+        let mut command = Command::new(&self.binary);
+        command.arg("--chain").arg(self.chain);
+        if let Some(device) = device {
+            command
+                .arg("--device-type").arg(&device.device_type)
+                .arg("--device-path").arg(&device.path);
+        }
+
+        let output = command.args(args).output()?;
+        if !output.status.success() {
+            return Err(HwiError::Command(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+
+        // HWI reports device errors as JSON on stdout with a zero exit status
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        if let Some(error) = result.get("error").and_then(|e| e.as_str()) {
+            return Err(HwiError::Command(error.to_string()));
+        }
+        Ok(result)
+    }
+
+    pub fn enumerate(&self) -> Result<Vec<Device>, HwiError> {
+        let devices = self.run(None, &["enumerate"])?;
+        Ok(serde_json::from_value(devices)?)
+    }
+
+    // The connected device to use, picked by fingerprint when more than one is plugged in
+    pub fn device(&self, fingerprint: Option<&str>) -> Result<Device, HwiError> {
+        let mut devices: Vec<Device> = self.enumerate()?
+            .into_iter()
+            .filter(|d| fingerprint.is_none() || d.fingerprint.as_deref() == fingerprint)
+            .collect();
+
+        match devices.len() {
+            0 => Err(HwiError::NoDevice),
+            1 => {
+                let device = devices.remove(0);
+                match &device.error {
+                    // Usually a locked device or one waiting for its PIN
+                    Some(error) => Err(HwiError::Device(format!("{} {}: {}", device.device_type, device.model, error))),
+                    None => Ok(device),
+                }
+            }
+            n => Err(HwiError::Device(format!("{} devices connected, pick one with its fingerprint", n))),
+        }
+    }
+
+    pub fn xpub(&self, device: &Device, path: &DerivationPath) -> Result<Xpub, HwiError> {
+        let result = self.run(Some(device), &["getxpub", &format!("m/{}", path)])?;
+        let xpub = result["xpub"].as_str()
+            .ok_or_else(|| HwiError::Command("getxpub returned no xpub".to_string()))?;
+        Xpub::from_str(xpub).map_err(|e| HwiError::Command(format!("Invalid xpub {}: {}", xpub, e)))
+    }
+
+    // The device shows the outputs and fee for confirmation before it signs
+    pub fn sign(&self, device: &Device, psbt: &Psbt) -> Result<Psbt, HwiError> {
+        info!("Sending PSBT to {} {} for signing", device.device_type, device.model);
+        let result = self.run(Some(device), &["signtx", &psbt.to_string()])?;
+        let signed = result["psbt"].as_str()
+            .ok_or_else(|| HwiError::Command("signtx returned no PSBT".to_string()))?;
+        Psbt::from_str(signed).map_err(|e| HwiError::Command(format!("Invalid signed PSBT: {}", e)))
+    }
+}
+
+// Tell the device which inputs belong to the key at this path. Handles P2WPKH and
+// BIP86 key-path P2TR inputs, returning how many inputs matched.
+pub fn add_key_origin(psbt: &mut Psbt, fingerprint: Fingerprint, path: &DerivationPath, xpub: &Xpub) -> usize {
+    let secp = Secp256k1::verification_only();
+    let public_key = xpub.public_key;
+    let (internal_key, _) = public_key.x_only_public_key();
+    let p2wpkh = ScriptBuf::new_p2wpkh(&CompressedPublicKey(public_key).wpubkey_hash());
+    let p2tr = ScriptBuf::new_p2tr(&secp, internal_key, None);
+
+    let mut matched = 0;
+    for input in &mut psbt.inputs {
+        let Some(utxo) = &input.witness_utxo else {
+            continue;
+        };
+
+        if utxo.script_pubkey == p2wpkh {
+            input.bip32_derivation.insert(public_key, (fingerprint, path.clone()));
+            matched += 1;
+        } else if utxo.script_pubkey == p2tr {
+            input.tap_internal_key = Some(internal_key);
+            input.tap_key_origins.insert(internal_key, (Vec::new(), (fingerprint, path.clone())));
+            matched += 1;
+        }
+    }
+    matched
+}
@@ -0,0 +1,66 @@
+use bitcoin::{transaction::Transaction, ScriptBuf};
+
+use crate::error::{OrdError, ValidationError};
+use crate::ord::{fetch_inscriptions, fetch_outputs_info};
+
+// Accept inscriptions (optionally only children of one collection) as payment
+#[derive(Debug, Clone)]
+pub struct InscriptionPayment {
+    pub collection: Option<String>,
+    pub address: String,
+    pub script_pubkey: ScriptBuf,
+}
+
+// Which output a sat at this offset into the inputs lands in, following ord's first-in-first-out rule
+pub(crate) fn output_for_offset(tx: &Transaction, offset: u64) -> Option<usize> {
+    let mut end = 0;
+    for (vout, output) in tx.output.iter().enumerate() {
+        end += output.value.to_sat();
+        if offset < end {
+            return Some(vout);
+        }
+    }
+    // Past the last output the sat goes to the miner as fee
+    None
+}
+
+pub async fn validate_inscription_payment(
+    http: &reqwest::Client,
+    tx: &Transaction,
+    ord_server: &str,
+    payment: &InscriptionPayment,
+) -> Result<(), ValidationError> {
+    let outpoints: Vec<_> = tx.input.iter().map(|input| input.previous_output).collect();
+    let outputs = fetch_outputs_info(http, ord_server, &outpoints).await?;
+    let inscription_ids: Vec<String> = outputs.iter().flat_map(|output| output.inscriptions.iter().cloned()).collect();
+    let mut inscriptions = fetch_inscriptions(http, ord_server, &inscription_ids).await?.into_iter();
+
+    let mut input_offset = 0;
+    for output in &outputs {
+        for inscription in inscriptions.by_ref().take(output.inscriptions.len()) {
+            if let Some(collection) = &payment.collection
+                && !inscription.parents.contains(collection)
+            {
+                continue;
+            }
+
+            let offset: u64 = inscription.satpoint
+                .rsplit(':')
+                .next()
+                .and_then(|offset| offset.parse().ok())
+                .ok_or_else(|| OrdError::Malformed(format!("Invalid satpoint: {}", inscription.satpoint)))?;
+
+            if let Some(vout) = output_for_offset(tx, input_offset + offset)
+                && tx.output[vout].script_pubkey == payment.script_pubkey
+            {
+                return Ok(());
+            }
+        }
+
+        input_offset += output.value;
+    }
+
+    Err(ValidationError::MissingInscription(
+        payment.collection.clone().unwrap_or_else(|| "any collection".to_string()),
+    ))
+}
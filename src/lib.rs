@@ -0,0 +1,35 @@
+//! Build rune-paid, zero-fee Bitcoin transactions and run searchers that anchor them with CPFP.
+
+pub mod anchor;
+pub mod build_tx;
+pub mod chain;
+pub mod error;
+pub mod fees;
+pub mod filters;
+pub mod http;
+pub mod hwi;
+pub mod inscriptions;
+pub mod liquidation;
+pub mod miniscript;
+pub mod multisig;
+pub mod musig;
+pub mod nostr;
+pub mod notify;
+pub mod ord;
+pub mod payments;
+pub mod policy;
+pub mod pricing;
+pub mod rpc;
+pub mod rules;
+pub mod rune_index;
+pub mod runes;
+pub mod runestone;
+pub mod searcher;
+pub mod signer;
+pub mod sponsorship;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+pub mod tor;
+pub mod watch;
@@ -0,0 +1,180 @@
+use bitcoin::{
+    psbt::Psbt,
+    sighash::EcdsaSighashType,
+    transaction::{Transaction, TxIn, TxOut, Version},
+    absolute, Address, Amount, Network, OutPoint, Sequence,
+};
+use bitcoincore_rpc::{Client, RpcApi};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::error::{LiquidationError, OrdError};
+use crate::ord::fetch_utxos;
+
+// Outputs below this can't be relayed, so asks are never smaller
+const DUST_LIMIT_SAT: u64 = 546;
+
+#[derive(Debug, Clone)]
+pub struct LiquidationConfig {
+    // Marketplace endpoint accepting signed PSBT sell offers
+    pub venue_url: String,
+    // Rune units to keep before selling the rest
+    pub threshold: u64,
+    // Asking price in sats per rune unit
+    pub price: f64,
+    // Stop selling once the wallet holds this many sats
+    pub float_target: Option<u64>,
+    // Payout addresses from the wallet must be for this network
+    pub network: Network,
+}
+
+// The body posted to the venue
+#[derive(Debug, Clone, Serialize)]
+pub struct Offer {
+    pub rune: String,
+    pub amount: u64,
+    pub price_sat: u64,
+    pub outpoint: String,
+    // Seller input signed SIGHASH_SINGLE|ANYONECANPAY against the payment output, so
+    // the buyer can add their own inputs and outputs around it
+    pub psbt: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct VenueResponse {
+    pub id: Option<String>,
+}
+
+// Build an offer selling everything in one rune UTXO for `price_sat`, paid to `payout`
+pub fn build_offer(
+    client: &Client,
+    rune: &str,
+    outpoint: OutPoint,
+    amount: u64,
+    price_sat: u64,
+    payout: &Address,
+) -> Result<Offer, LiquidationError> {
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: outpoint,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        }],
+        output: vec![TxOut { value: Amount::from_sat(price_sat), script_pubkey: payout.script_pubkey() }],
+    };
+    let psbt = Psbt::from_unsigned_tx(tx).map_err(|e| LiquidationError::Offer(e.to_string()))?;
+
+    // The wallet fills in the rune UTXO and signs it, provided it holds the rune address key
+    let processed = client.wallet_process_psbt(
+        &psbt.to_string(),
+        Some(true),
+        Some(EcdsaSighashType::SinglePlusAnyoneCanPay.into()),
+        None,
+    )?;
+    let signed = Psbt::from_str(&processed.psbt).map_err(|e| LiquidationError::Offer(e.to_string()))?;
+    let input = &signed.inputs[0];
+    if input.final_script_witness.is_none() && input.partial_sigs.is_empty() && input.tap_key_sig.is_none() {
+        return Err(LiquidationError::Offer(format!("wallet could not sign {}", outpoint)));
+    }
+
+    Ok(Offer {
+        rune: rune.to_string(),
+        amount,
+        price_sat,
+        outpoint: outpoint.to_string(),
+        psbt: signed.to_string(),
+    })
+}
+
+pub fn post_offer(http: &reqwest::blocking::Client, venue_url: &str, offer: &Offer) -> Result<VenueResponse, LiquidationError> {
+    let response = http.post(venue_url).json(offer).send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(LiquidationError::Venue {
+            url: venue_url.to_string(),
+            message: format!("{}: {}", status, response.text().unwrap_or_default()),
+        });
+    }
+    // Venues that don't return an ID still accepted the offer
+    Ok(response.json().unwrap_or_default())
+}
+
+// Offer rune UTXOs at `rune_address` for sale until only `threshold` units are left
+// unoffered. UTXOs already offered are locked in the wallet, so CPFP children don't
+// spend them, and skipped on later passes.
+pub fn liquidate(
+    client: &Client,
+    http: &reqwest::blocking::Client,
+    ord_server: &str,
+    rune_address: &str,
+    rune: &str,
+    config: &LiquidationConfig,
+    offered: &mut HashSet<OutPoint>,
+) -> Result<Vec<Offer>, LiquidationError> {
+    if let Some(target) = config.float_target {
+        let balance = client.get_balance(None, None)?.to_sat();
+        if balance >= target {
+            info!("Wallet holds {} sats, at or above the {} sat float target, not selling runes", balance, target);
+            return Ok(Vec::new());
+        }
+    }
+
+    // Only sell UTXOs carrying nothing but our rune, so nothing else goes with them
+    let mut utxos = Vec::new();
+    for utxo in fetch_utxos(http, ord_server, rune_address)? {
+        let outpoint = OutPoint::from_str(&utxo.outpoint)
+            .map_err(|e| OrdError::Malformed(format!("Invalid outpoint {}: {}", utxo.outpoint, e)))?;
+        if offered.contains(&outpoint) || !utxo.inscriptions.is_empty() || utxo.runes.len() != 1 {
+            continue;
+        }
+        if let Some(info) = utxo.runes.get(rune) {
+            utxos.push((outpoint, info.amount));
+        }
+    }
+
+    let balance: u64 = utxos.iter().map(|(_, amount)| amount).sum();
+    if balance <= config.threshold {
+        info!("Holding {} {}, below the {} liquidation threshold", balance, rune, config.threshold);
+        return Ok(Vec::new());
+    }
+
+    // Sell the largest UTXOs first, stopping before the balance would drop below the threshold
+    utxos.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+    let mut remaining = balance;
+    let mut offers = Vec::new();
+    for (outpoint, amount) in utxos {
+        if remaining - amount < config.threshold {
+            continue;
+        }
+        let price_sat = (amount as f64 * config.price).ceil() as u64;
+        if price_sat < DUST_LIMIT_SAT {
+            info!("Skipping {}: {} {} is only worth {} sats", outpoint, amount, rune, price_sat);
+            continue;
+        }
+
+        let payout = client.get_new_address(None, None)?
+            .require_network(config.network)
+            .map_err(|e| LiquidationError::Offer(format!("Wallet gave a payout address for the wrong network: {}", e)))?;
+        let offer = build_offer(client, rune, outpoint, amount, price_sat, &payout)?;
+        let response = post_offer(http, &config.venue_url, &offer)?;
+        info!(
+            "Offered {} {} from {} for {} sats{}",
+            amount,
+            rune,
+            outpoint,
+            price_sat,
+            response.id.map(|id| format!(" (offer {})", id)).unwrap_or_default(),
+        );
+
+        client.lock_unspent(&[outpoint])?;
+        offered.insert(outpoint);
+        remaining -= amount;
+        offers.push(offer);
+    }
+
+    Ok(offers)
+}
@@ -0,0 +1,195 @@
+use bitcoin::blockdata::opcodes::all::{
+    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CLTV, OP_CSV, OP_DUP,
+    OP_ELSE, OP_ENDIF, OP_HASH160, OP_IF, OP_NOTIF, OP_SIZE,
+};
+use bitcoin::psbt::{Input, Psbt};
+use bitcoin::script::Instruction;
+use bitcoin::{absolute, Script, Sequence};
+
+// Witness elements with their length prefix, taking the largest DER signature
+const SIGNATURE_SIZE: u64 = 1 + 72;
+const PUBKEY_SIZE: u64 = 1 + 33;
+const PREIMAGE_SIZE: u64 = 1 + 32;
+// 0x01 to take an IF's first branch, empty for the other
+const SELECTOR_SIZE: u64 = 1 + 1;
+// Item count, signature and key of a P2WPKH spend, assumed for inputs we know nothing about
+pub const P2WPKH_WITNESS_WEIGHT: u64 = 108;
+
+// Spend paths a script can have before it's not worth telling them apart
+const MAX_PATHS: usize = 1024;
+
+// One way to satisfy a P2WSH script: the side taken at every IF, what its witness weighs
+// and the timelocks it has to wait for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpendPath {
+    // Witness weight of this way of satisfying the script, the script itself included
+    pub witness_weight: u64,
+    pub older: Option<Sequence>,
+    pub after: Option<absolute::LockTime>,
+}
+
+impl SpendPath {
+    pub fn is_timelocked(&self) -> bool {
+        self.older.is_some() || self.after.is_some()
+    }
+
+    // Whether an input with `sequence` in a transaction locked to `lock_time` can take it
+    pub fn is_satisfied_by(&self, sequence: Sequence, lock_time: absolute::LockTime) -> bool {
+        let older = self.older.is_none_or(|older| {
+            match (older.to_relative_lock_time(), sequence.to_relative_lock_time()) {
+                (Some(older), Some(sequence)) => older.is_implied_by(sequence),
+                _ => false,
+            }
+        });
+        let after = self.after.is_none_or(|after| sequence.enables_absolute_lock_time() && after.is_implied_by(lock_time));
+        older && after
+    }
+
+    fn add(&mut self, other: &SpendPath) {
+        self.witness_weight += other.witness_weight;
+        self.older = self.older.max(other.older);
+        if let Some(after) = other.after {
+            self.add_after(after);
+        }
+    }
+
+    fn add_after(&mut self, after: absolute::LockTime) {
+        if self.after.is_none_or(|current| after.to_consensus_u32() > current.to_consensus_u32()) {
+            self.after = Some(after);
+        }
+    }
+}
+
+// What it takes to spend a P2WSH script, like the ones wsh() miniscript descriptors produce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptSpend {
+    // Every way through the script's IFs, in script order, the first side of each IF first
+    pub paths: Vec<SpendPath>,
+}
+
+impl ScriptSpend {
+    // Witness weight of the costliest path
+    pub fn max_witness_weight(&self) -> u64 {
+        self.paths.iter().map(|path| path.witness_weight).max().unwrap_or_default()
+    }
+
+    // Witness weight of the costliest path an input with `sequence` in a transaction locked
+    // to `lock_time` can take, or of any path if none of them fits
+    pub fn witness_weight(&self, sequence: Sequence, lock_time: absolute::LockTime) -> u64 {
+        self.paths.iter()
+            .filter(|path| path.is_satisfied_by(sequence, lock_time))
+            .map(|path| path.witness_weight)
+            .max()
+            .unwrap_or_else(|| self.max_witness_weight())
+    }
+
+    // The cheapest path that waits on a timelock, to spend through it on purpose
+    pub fn timelocked(&self) -> Option<&SpendPath> {
+        self.paths.iter().filter(|path| path.is_timelocked()).min_by_key(|path| path.witness_weight)
+    }
+}
+
+// Walks one branch of the script, returning every path through it with the bytes its
+// satisfaction pushes. Stops at the ELSE or ENDIF that closes it.
+fn branch_paths(instructions: &[Instruction], pos: &mut usize) -> Option<Vec<SpendPath>> {
+    let mut paths = vec![SpendPath::default()];
+    while let Some(instruction) = instructions.get(*pos) {
+        let previous = pos.checked_sub(1).and_then(|i| instructions.get(i));
+        let next = instructions.get(*pos + 1);
+        let mut step = SpendPath::default();
+        match instruction.opcode() {
+            Some(OP_IF) | Some(OP_NOTIF) => {
+                *pos += 1;
+                let mut sides = branch_paths(instructions, pos)?;
+                match instructions.get(*pos).and_then(Instruction::opcode) == Some(OP_ELSE) {
+                    true => {
+                        *pos += 1;
+                        sides.extend(branch_paths(instructions, pos)?);
+                    }
+                    false => sides.push(SpendPath::default()),
+                }
+                if instructions.get(*pos).and_then(Instruction::opcode) != Some(OP_ENDIF) {
+                    return None;
+                }
+                if paths.len() * sides.len() > MAX_PATHS {
+                    return None;
+                }
+                paths = paths.iter()
+                    .flat_map(|path| sides.iter().map(move |side| {
+                        let mut path = *path;
+                        path.add(side);
+                        path.witness_weight += SELECTOR_SIZE;
+                        path
+                    }))
+                    .collect();
+            }
+            Some(OP_ELSE) | Some(OP_ENDIF) => return Some(paths),
+            Some(OP_CHECKSIG) | Some(OP_CHECKSIGVERIFY) => step.witness_weight = SIGNATURE_SIZE,
+            Some(OP_CHECKMULTISIG) | Some(OP_CHECKMULTISIGVERIFY) => {
+                // m <n keys> n, and CHECKMULTISIG pops an extra empty element
+                let count = usize::try_from(previous?.script_num()?).ok()?;
+                let required = instructions.get(pos.checked_sub(count + 2)?)?.script_num()?;
+                step.witness_weight = 1 + u64::try_from(required).ok()? * SIGNATURE_SIZE;
+            }
+            // pk_h takes the key as well as the signature
+            Some(OP_HASH160) if previous.and_then(Instruction::opcode) == Some(OP_DUP) => step.witness_weight = PUBKEY_SIZE,
+            // Hash locks check the preimage is 32 bytes before hashing it
+            Some(OP_SIZE) if next.and_then(Instruction::script_num) == Some(32) => step.witness_weight = PREIMAGE_SIZE,
+            Some(OP_CSV) => step.older = Some(Sequence::from_consensus(u32::try_from(previous?.script_num()?).ok()?)),
+            Some(OP_CLTV) => step.add_after(absolute::LockTime::from_consensus(u32::try_from(previous?.script_num()?).ok()?)),
+            _ => {}
+        }
+        for path in &mut paths {
+            path.add(&step);
+        }
+        *pos += 1;
+    }
+    Some(paths)
+}
+
+// Plans each way through a witness script's IFs on its own, so a timelock or a costly
+// satisfaction in one branch doesn't carry over to the others. Branches combined with
+// BOOLOR or thresh() are all sized, so those paths are upper bounds. None when the script
+// doesn't parse, its IFs don't balance, or it has more than MAX_PATHS paths.
+pub fn analyze(witness_script: &Script) -> Option<ScriptSpend> {
+    let instructions = witness_script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let mut pos = 0;
+    let mut paths = branch_paths(&instructions, &mut pos)?;
+    if pos != instructions.len() {
+        return None;
+    }
+    let script = witness_script.len() as u64;
+    let script_push = bitcoin::VarInt(script).size() as u64 + script;
+    for path in &mut paths {
+        // One byte counts the elements, as a satisfaction never has more than 252
+        path.witness_weight += 1 + script_push;
+    }
+    Some(ScriptSpend { paths })
+}
+
+// The witness weight an input adds once signed: its final witness if it has one, the
+// costliest branch of its witness script that its sequence and the locktime allow, or a
+// P2WPKH spend
+pub fn input_witness_weight(input: &Input, sequence: Sequence, lock_time: absolute::LockTime) -> u64 {
+    if let Some(witness) = &input.final_script_witness {
+        return witness.size() as u64;
+    }
+    input.witness_script.as_deref()
+        .and_then(analyze)
+        .map_or(P2WPKH_WITNESS_WEIGHT, |spend| spend.witness_weight(sequence, lock_time))
+}
+
+// Estimate the vsize of a PSBT's transaction once every input is signed
+pub fn estimate_signed_vsize(psbt: &Psbt) -> u64 {
+    let mut weight = psbt.unsigned_tx.weight().to_wu();
+    if !psbt.inputs.is_empty() {
+        // Segwit marker and flag are only serialized once the tx has witnesses
+        weight += 2;
+        let tx = &psbt.unsigned_tx;
+        weight += psbt.inputs.iter()
+            .zip(&tx.input)
+            .map(|(input, txin)| input_witness_weight(input, txin.sequence, tx.lock_time))
+            .sum::<u64>();
+    }
+    weight.div_ceil(4)
+}
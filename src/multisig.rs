@@ -0,0 +1,153 @@
+use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1, OP_PUSHNUM_16};
+use bitcoin::psbt::Psbt;
+use bitcoin::script::Instruction;
+use bitcoin::{PublicKey, Script, ScriptBuf, TxOut, Witness};
+
+use crate::anchor::is_p2a;
+use crate::error::MultisigError;
+
+// How far along one input's signatures are
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputStatus {
+    Finalized,
+    // Ready to finalize, or still waiting on cosigners
+    Signed { signatures: usize, required: usize },
+    // A script this module can't finalize, left for the signer that made it
+    Unsupported,
+}
+
+impl InputStatus {
+    pub fn is_ready(&self) -> bool {
+        match self {
+            InputStatus::Finalized => true,
+            InputStatus::Signed { signatures, required } => signatures >= required,
+            InputStatus::Unsupported => false,
+        }
+    }
+}
+
+// Merges the signatures each cosigner added to their copy of the same PSBT
+pub fn combine(psbts: Vec<Psbt>) -> Result<Psbt, MultisigError> {
+    let mut psbts = psbts.into_iter();
+    let mut combined = psbts.next().ok_or(MultisigError::NoPsbts)?;
+    for (i, psbt) in psbts.enumerate() {
+        let txid = psbt.unsigned_tx.compute_txid();
+        if txid != combined.unsigned_tx.compute_txid() {
+            return Err(MultisigError::Mismatch { index: i + 1, txid: txid.to_string() });
+        }
+        combined.combine(psbt)?;
+    }
+    Ok(combined)
+}
+
+fn spent_output(psbt: &Psbt, index: usize) -> Option<&TxOut> {
+    let input = &psbt.inputs[index];
+    match (&input.witness_utxo, &input.non_witness_utxo) {
+        (Some(output), _) => Some(output),
+        (None, Some(prev)) => prev.output.get(psbt.unsigned_tx.input[index].previous_output.vout as usize),
+        (None, None) => None,
+    }
+}
+
+// The threshold and keys of a bare `m <keys> n OP_CHECKMULTISIG` script, like sortedmulti
+// and multi descriptors produce
+pub fn multisig_keys(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let small_int = |instruction: &Instruction| {
+        let op = instruction.opcode()?.to_u8();
+        (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&op).then(|| usize::from(op - OP_PUSHNUM_1.to_u8() + 1))
+    };
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (first, rest) = instructions.split_first()?;
+    let (last, rest) = rest.split_last()?;
+    let (count, keys) = rest.split_last()?;
+    if last.opcode() != Some(OP_CHECKMULTISIG) {
+        return None;
+    }
+    let (required, count) = (small_int(first)?, small_int(count)?);
+    let keys = keys.iter()
+        .map(|key| PublicKey::from_slice(key.push_bytes()?.as_bytes()).ok())
+        .collect::<Option<Vec<_>>>()?;
+    (keys.len() == count && required <= count).then_some((required, keys))
+}
+
+fn witness_script(psbt: &Psbt, index: usize, script_pubkey: &Script) -> Option<ScriptBuf> {
+    psbt.inputs[index].witness_script.clone()
+        .filter(|script| script_pubkey.is_p2wsh() && ScriptBuf::new_p2wsh(&script.wscript_hash()) == *script_pubkey)
+}
+
+pub fn status(psbt: &Psbt) -> Vec<InputStatus> {
+    (0..psbt.inputs.len())
+        .map(|index| {
+            let input = &psbt.inputs[index];
+            if input.final_script_witness.is_some() || input.final_script_sig.is_some() {
+                return InputStatus::Finalized;
+            }
+            let Some(script_pubkey) = spent_output(psbt, index).map(|output| &output.script_pubkey) else {
+                return InputStatus::Unsupported;
+            };
+            if is_p2a(script_pubkey) {
+                InputStatus::Signed { signatures: 0, required: 0 }
+            } else if script_pubkey.is_p2wpkh() {
+                InputStatus::Signed { signatures: input.partial_sigs.len().min(1), required: 1 }
+            } else if script_pubkey.is_p2tr() {
+                InputStatus::Signed { signatures: usize::from(input.tap_key_sig.is_some()), required: 1 }
+            } else if let Some((required, keys)) = witness_script(psbt, index, script_pubkey)
+                .and_then(|script| multisig_keys(&script))
+            {
+                let signatures = keys.iter().filter(|key| input.partial_sigs.contains_key(key)).count();
+                InputStatus::Signed { signatures, required }
+            } else {
+                InputStatus::Unsupported
+            }
+        })
+        .collect()
+}
+
+// Finalizes every input that has all the signatures it needs: P2WSH multisig, P2WPKH,
+// taproot key spends and P2A. Fails naming the first input that doesn't.
+pub fn finalize(psbt: &mut Psbt) -> Result<(), MultisigError> {
+    for (index, status) in status(psbt).into_iter().enumerate() {
+        match status {
+            InputStatus::Finalized => continue,
+            InputStatus::Unsupported => return Err(MultisigError::Unsupported(index)),
+            InputStatus::Signed { signatures, required } if signatures < required => {
+                return Err(MultisigError::MissingSignatures { index, signatures, required });
+            }
+            InputStatus::Signed { .. } => {}
+        }
+        let script_pubkey = spent_output(psbt, index).map(|output| output.script_pubkey.clone()).unwrap_or_default();
+        let witness_script = witness_script(psbt, index, &script_pubkey);
+        let input = &mut psbt.inputs[index];
+        let witness = if is_p2a(&script_pubkey) {
+            Witness::new()
+        } else if script_pubkey.is_p2wpkh()
+            && let Some((key, signature)) = input.partial_sigs.iter().next()
+        {
+            Witness::p2wpkh(signature, &key.inner)
+        } else if script_pubkey.is_p2tr()
+            && let Some(signature) = input.tap_key_sig
+        {
+            Witness::p2tr_key_spend(&signature)
+        } else if let Some(script) = witness_script
+            && let Some((required, keys)) = multisig_keys(&script)
+        {
+            // CHECKMULTISIG pops one element too many, and wants the signatures in key order
+            let mut witness = Witness::new();
+            witness.push([]);
+            for signature in keys.iter().filter_map(|key| input.partial_sigs.get(key)).take(required) {
+                witness.push(signature.to_vec());
+            }
+            witness.push(script.as_bytes());
+            witness
+        } else {
+            return Err(MultisigError::Unsupported(index));
+        };
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+        input.tap_key_sig = None;
+        input.witness_script = None;
+        input.bip32_derivation.clear();
+        input.tap_key_origins.clear();
+    }
+    Ok(())
+}
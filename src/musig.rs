@@ -0,0 +1,476 @@
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::constants::CURVE_ORDER;
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use bitcoin::secp256k1::{schnorr, Message, Parity, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::taproot::{TapNodeHash, TapTweakHash};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::MusigError;
+
+// MuSig2 (BIP 327) for cosigners sharing a taproot key: key aggregation with the taproot
+// tweak, two-nonce signing and partial signature aggregation
+
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in parts {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+// A number mod the curve order. SecretKey does the arithmetic but can't be zero, which
+// coefficients, tweaks and signatures can be, so zero is kept as None.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ModN(Option<SecretKey>);
+
+impl ModN {
+    const ZERO: ModN = ModN(None);
+
+    fn one() -> ModN {
+        ModN(SecretKey::from_slice(&Scalar::ONE.to_be_bytes()).ok())
+    }
+
+    // None unless the bytes are below the curve order
+    fn from_bytes(bytes: [u8; 32]) -> Option<ModN> {
+        (bytes < CURVE_ORDER).then(|| ModN(SecretKey::from_slice(&bytes).ok()))
+    }
+
+    // A hash mod the curve order. Hashes are below twice the order, so one subtraction does.
+    fn reduce(mut bytes: [u8; 32]) -> ModN {
+        if bytes >= CURVE_ORDER {
+            let mut borrow = 0;
+            for (byte, order) in bytes.iter_mut().zip(CURVE_ORDER).rev() {
+                let difference = i16::from(*byte) - i16::from(order) - borrow;
+                *byte = difference.rem_euclid(256) as u8;
+                borrow = i16::from(difference < 0);
+            }
+        }
+        ModN(SecretKey::from_slice(&bytes).ok())
+    }
+
+    fn to_bytes(self) -> [u8; 32] {
+        self.0.map_or([0; 32], |scalar| scalar.secret_bytes())
+    }
+
+    // Both are below the order, so the only sum add_tweak refuses is zero
+    fn add(self, other: ModN) -> ModN {
+        match (self.0, other.0) {
+            (None, _) => other,
+            (_, None) => self,
+            (Some(a), Some(b)) => ModN(a.add_tweak(&Scalar::from(b)).ok()),
+        }
+    }
+
+    fn mul(self, other: ModN) -> ModN {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => ModN(a.mul_tweak(&Scalar::from(b)).ok()),
+            _ => ModN::ZERO,
+        }
+    }
+
+    fn negate_if(self, negate: bool) -> ModN {
+        match negate {
+            true => ModN(self.0.map(SecretKey::negate)),
+            false => self,
+        }
+    }
+
+    // Times the generator, None being the point at infinity
+    fn point(self) -> Option<PublicKey> {
+        self.0.map(|scalar| scalar.public_key(&Secp256k1::signing_only()))
+    }
+}
+
+// Points are None at infinity, which sums and multiples can land on
+fn point_mul(point: Option<PublicKey>, scalar: ModN) -> Option<PublicKey> {
+    match (point, scalar.0) {
+        (Some(point), Some(scalar)) => point.mul_tweak(&Secp256k1::verification_only(), &Scalar::from(scalar)).ok(),
+        _ => None,
+    }
+}
+
+fn point_add(points: &[Option<PublicKey>]) -> Option<PublicKey> {
+    let points: Vec<&PublicKey> = points.iter().flatten().collect();
+    match points.is_empty() {
+        true => None,
+        false => PublicKey::combine_keys(&points).ok(),
+    }
+}
+
+fn point_negate_if(point: Option<PublicKey>, negate: bool) -> Option<PublicKey> {
+    match negate {
+        true => point.map(|point| point.negate(&Secp256k1::verification_only())),
+        false => point,
+    }
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.x_only_public_key().1 == Parity::Even
+}
+
+// 33 zero bytes for the point at infinity, as BIP 327 encodes aggregate nonces
+fn parse_point_ext(bytes: &[u8]) -> Result<Option<PublicKey>, MusigError> {
+    match bytes.iter().all(|byte| *byte == 0) {
+        true => Ok(None),
+        false => Ok(Some(PublicKey::from_slice(bytes)?)),
+    }
+}
+
+// Sorted by their compressed encoding, so every cosigner aggregates them in the same order
+pub fn sort_keys(keys: &mut [PublicKey]) {
+    keys.sort_by_key(PublicKey::serialize);
+}
+
+// The aggregate of the cosigners' keys, tracking what tweaks did to it
+#[derive(Debug, Clone)]
+pub struct KeyAgg {
+    keys: Vec<PublicKey>,
+    list_hash: [u8; 32],
+    second_key: Option<PublicKey>,
+    // The aggregate before any tweak
+    internal: XOnlyPublicKey,
+    // Q, gacc (only ever 1 or -1, so kept as whether it's negated) and tacc
+    aggregate: PublicKey,
+    negated: bool,
+    tweak: ModN,
+}
+
+impl KeyAgg {
+    // Aggregates the keys in the order given; sort them first with sort_keys
+    pub fn new(keys: &[PublicKey]) -> Result<Self, MusigError> {
+        let first = keys.first().ok_or(MusigError::NoKeys)?;
+        let encoded: Vec<u8> = keys.iter().flat_map(PublicKey::serialize).collect();
+        let mut agg = KeyAgg {
+            keys: keys.to_vec(),
+            list_hash: tagged_hash("KeyAgg list", &[&encoded]),
+            second_key: keys.iter().find(|key| *key != first).copied(),
+            internal: first.x_only_public_key().0,
+            aggregate: *first,
+            negated: false,
+            tweak: ModN::ZERO,
+        };
+        let weighted: Vec<_> = keys.iter().map(|key| point_mul(Some(*key), agg.coefficient(key))).collect();
+        agg.aggregate = point_add(&weighted).ok_or(MusigError::Infinity("aggregate key"))?;
+        agg.internal = agg.aggregate.x_only_public_key().0;
+        Ok(agg)
+    }
+
+    fn coefficient(&self, key: &PublicKey) -> ModN {
+        if Some(*key) == self.second_key {
+            return ModN::one();
+        }
+        ModN::reduce(tagged_hash("KeyAgg coefficient", &[&self.list_hash, &key.serialize()]))
+    }
+
+    // A signer's coefficient, for keys that are one of the cosigners'
+    fn session_coefficient(&self, key: &PublicKey) -> Result<ModN, MusigError> {
+        match self.keys.contains(key) {
+            true => Ok(self.coefficient(key)),
+            false => Err(MusigError::UnknownKey(key.to_string())),
+        }
+    }
+
+    // The key before any tweak, which goes in the PSBT as the taproot internal key
+    pub fn internal_key(&self) -> XOnlyPublicKey {
+        self.internal
+    }
+
+    // Adds tweak times the generator to the key. An x-only tweak applies to the even-y
+    // version of the key, as BIP 341 tweaks do; a plain one to the key as it is.
+    pub fn tweak(mut self, tweak: [u8; 32], x_only: bool) -> Result<Self, MusigError> {
+        let tweak = ModN::from_bytes(tweak).ok_or_else(|| MusigError::OutOfRange("tweak".to_string()))?;
+        let negate = x_only && !has_even_y(&self.aggregate);
+        self.aggregate = point_add(&[point_negate_if(Some(self.aggregate), negate), tweak.point()])
+            .ok_or(MusigError::Infinity("tweaked key"))?;
+        self.negated ^= negate;
+        self.tweak = tweak.add(self.tweak.negate_if(negate));
+        Ok(self)
+    }
+
+    // Applies the BIP 341 tweak, with no script tree for a key-path-only output
+    pub fn tweak_for_taproot(self, merkle_root: Option<TapNodeHash>) -> Result<Self, MusigError> {
+        let tweak = TapTweakHash::from_key_and_tweak(self.output_key(), merkle_root).to_byte_array();
+        self.tweak(tweak, true)
+    }
+
+    // The key the signature verifies against: the output key once tweaked for taproot
+    pub fn output_key(&self) -> XOnlyPublicKey {
+        self.aggregate.x_only_public_key().0
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+}
+
+// The two secret nonces, kept until this cosigner signs and never used twice
+#[derive(Clone)]
+pub struct SecretNonce {
+    k1: SecretKey,
+    k2: SecretKey,
+    key: PublicKey,
+}
+
+impl fmt::Debug for SecretNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretNonce").field("key", &self.key).finish_non_exhaustive()
+    }
+}
+
+impl SecretNonce {
+    // k1 || k2 || the signer's key, as BIP 327 serializes it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.k1.secret_bytes()[..], &self.k2.secret_bytes(), &self.key.serialize()].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MusigError> {
+        if bytes.len() != 97 {
+            return Err(MusigError::InvalidNonce(format!("secret nonce is {} bytes, expected 97", bytes.len())));
+        }
+        Ok(SecretNonce {
+            k1: SecretKey::from_slice(&bytes[..32])?,
+            k2: SecretKey::from_slice(&bytes[32..64])?,
+            key: PublicKey::from_slice(&bytes[64..])?,
+        })
+    }
+}
+
+// R1 || R2, shared with the other cosigners before anyone signs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicNonce {
+    r1: PublicKey,
+    r2: PublicKey,
+}
+
+impl fmt::Display for PublicNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", hex::encode(self.r1.serialize()), hex::encode(self.r2.serialize()))
+    }
+}
+
+impl FromStr for PublicNonce {
+    type Err = MusigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|e| MusigError::InvalidNonce(e.to_string()))?;
+        if bytes.len() != 66 {
+            return Err(MusigError::InvalidNonce(format!("public nonce is {} bytes, expected 66", bytes.len())));
+        }
+        Ok(PublicNonce {
+            r1: PublicKey::from_slice(&bytes[..33])?,
+            r2: PublicKey::from_slice(&bytes[33..])?,
+        })
+    }
+}
+
+// Makes a fresh nonce pair for signing `message` with `secret_key`. The secret half must be
+// kept private and thrown away once used.
+pub fn generate_nonce(secret_key: &SecretKey, agg: &KeyAgg, message: &[u8; 32]) -> Result<(SecretNonce, PublicNonce), MusigError> {
+    let mut random = [0u8; 32];
+    thread_rng().fill_bytes(&mut random);
+    let key = secret_key.public_key(&Secp256k1::signing_only());
+    nonce_gen(random, Some(secret_key), &key, Some(&agg.output_key()), Some(message), &[])
+}
+
+// BIP 327 NonceGen from the given randomness, which has to be fresh every time. Mixing in
+// the secret key keeps nonces apart even if the RNG repeats itself.
+pub fn nonce_gen(
+    random: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    key: &PublicKey,
+    aggregate_key: Option<&XOnlyPublicKey>,
+    message: Option<&[u8]>,
+    extra: &[u8],
+) -> Result<(SecretNonce, PublicNonce), MusigError> {
+    let seed = match secret_key {
+        Some(secret_key) => {
+            let aux = tagged_hash("MuSig/aux", &[&random]);
+            let mut seed = secret_key.secret_bytes();
+            seed.iter_mut().zip(aux).for_each(|(a, b)| *a ^= b);
+            seed
+        }
+        None => random,
+    };
+    let aggregate_key = aggregate_key.map(XOnlyPublicKey::serialize);
+    let aggregate_key: &[u8] = aggregate_key.as_ref().map_or(&[], |key| key);
+    let message = match message {
+        Some(message) => [&[1][..], &(message.len() as u64).to_be_bytes(), message].concat(),
+        None => vec![0],
+    };
+    let nonce = |i: u8| {
+        let hash = tagged_hash("MuSig/nonce", &[
+            &seed,
+            &[33],
+            &key.serialize(),
+            &[aggregate_key.len() as u8],
+            aggregate_key,
+            &message,
+            &(extra.len() as u32).to_be_bytes(),
+            extra,
+            &[i],
+        ]);
+        ModN::reduce(hash).0.ok_or_else(|| MusigError::InvalidNonce("nonce is zero".to_string()))
+    };
+    let secp = Secp256k1::signing_only();
+    let secret = SecretNonce { k1: nonce(0)?, k2: nonce(1)?, key: *key };
+    let public = PublicNonce { r1: secret.k1.public_key(&secp), r2: secret.k2.public_key(&secp) };
+    Ok((secret, public))
+}
+
+// The sum of every cosigner's nonces; either half can cancel out to the point at infinity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AggregateNonce {
+    r1: Option<PublicKey>,
+    r2: Option<PublicKey>,
+}
+
+impl AggregateNonce {
+    pub fn new(nonces: &[PublicNonce]) -> Result<Self, MusigError> {
+        if nonces.is_empty() {
+            return Err(MusigError::InvalidNonce("no nonces to aggregate".to_string()));
+        }
+        let r1: Vec<_> = nonces.iter().map(|nonce| Some(nonce.r1)).collect();
+        let r2: Vec<_> = nonces.iter().map(|nonce| Some(nonce.r2)).collect();
+        Ok(AggregateNonce { r1: point_add(&r1), r2: point_add(&r2) })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let encode = |point: Option<PublicKey>| point.map_or([0; 33], |point| point.serialize());
+        [encode(self.r1), encode(self.r2)].concat()
+    }
+}
+
+impl fmt::Display for AggregateNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.to_bytes()))
+    }
+}
+
+impl FromStr for AggregateNonce {
+    type Err = MusigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|e| MusigError::InvalidNonce(e.to_string()))?;
+        if bytes.len() != 66 {
+            return Err(MusigError::InvalidNonce(format!("aggregate nonce is {} bytes, expected 66", bytes.len())));
+        }
+        Ok(AggregateNonce { r1: parse_point_ext(&bytes[..33])?, r2: parse_point_ext(&bytes[33..])? })
+    }
+}
+
+// One cosigner's share of the signature, a number mod the curve order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSignature(ModN);
+
+impl fmt::Display for PartialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0.to_bytes()))
+    }
+}
+
+impl FromStr for PartialSignature {
+    type Err = MusigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes: [u8; 32] = hex::decode(s)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| MusigError::OutOfRange("partial signature".to_string()))?;
+        ModN::from_bytes(bytes).map(PartialSignature).ok_or_else(|| MusigError::OutOfRange("partial signature".to_string()))
+    }
+}
+
+// b, R and e for one message under one aggregate nonce
+struct SessionValues {
+    b: ModN,
+    r: PublicKey,
+    e: ModN,
+}
+
+fn session_values(agg: &KeyAgg, nonce: &AggregateNonce, message: &[u8; 32]) -> SessionValues {
+    let output_key = agg.output_key().serialize();
+    let b = ModN::reduce(tagged_hash("MuSig/noncecoef", &[&nonce.to_bytes(), &output_key, message]));
+    // Nonces cancelling out fall back to the generator, as BIP 327 says
+    let r = point_add(&[nonce.r1, point_mul(nonce.r2, b)])
+        .unwrap_or_else(|| ModN::one().point().expect("one times the generator is a point"));
+    let e = ModN::reduce(tagged_hash("BIP0340/challenge", &[&r.x_only_public_key().0.serialize(), &output_key, message]));
+    SessionValues { b, r, e }
+}
+
+// Whether the signing key has to be negated: the final key's parity, flipped by gacc
+fn key_negated(agg: &KeyAgg) -> bool {
+    !has_even_y(&agg.aggregate) ^ agg.negated
+}
+
+// This cosigner's share of the signature, checked before it's handed out. Takes the
+// secret nonce by value so it can't be used again.
+pub fn partial_sign(
+    nonce: SecretNonce,
+    secret_key: &SecretKey,
+    agg: &KeyAgg,
+    aggregate_nonce: &AggregateNonce,
+    message: &[u8; 32],
+) -> Result<PartialSignature, MusigError> {
+    let secp = Secp256k1::signing_only();
+    let key = secret_key.public_key(&secp);
+    if key != nonce.key {
+        return Err(MusigError::WrongNonce(key.to_string()));
+    }
+    let a = agg.session_coefficient(&key)?;
+    let values = session_values(agg, aggregate_nonce, message);
+    let odd_nonce = !has_even_y(&values.r);
+    let k1 = ModN(Some(nonce.k1)).negate_if(odd_nonce);
+    let k2 = ModN(Some(nonce.k2)).negate_if(odd_nonce);
+    let d = ModN(Some(*secret_key)).negate_if(key_negated(agg));
+    let partial = PartialSignature(k1.add(values.b.mul(k2)).add(values.e.mul(a).mul(d)));
+
+    let public = PublicNonce { r1: nonce.k1.public_key(&secp), r2: nonce.k2.public_key(&secp) };
+    verify_partial(&partial, &key, &public, agg, aggregate_nonce, message)?;
+    Ok(partial)
+}
+
+// Checks one cosigner's share against their key and nonce, so a bad one can be blamed
+pub fn verify_partial(
+    partial: &PartialSignature,
+    key: &PublicKey,
+    nonce: &PublicNonce,
+    agg: &KeyAgg,
+    aggregate_nonce: &AggregateNonce,
+    message: &[u8; 32],
+) -> Result<(), MusigError> {
+    let a = agg.session_coefficient(key)?;
+    let values = session_values(agg, aggregate_nonce, message);
+    let r = point_add(&[Some(nonce.r1), point_mul(Some(nonce.r2), values.b)]);
+    let r = point_negate_if(r, !has_even_y(&values.r));
+    let key_part = point_mul(Some(*key), values.e.mul(a).negate_if(key_negated(agg)));
+    match partial.0.point() == point_add(&[r, key_part]) {
+        true => Ok(()),
+        false => Err(MusigError::InvalidPartialSignature(key.to_string())),
+    }
+}
+
+// Adds up every cosigner's share into a BIP 340 signature for the output key, checking it
+// before handing it back
+pub fn aggregate_partials(
+    partials: &[PartialSignature],
+    agg: &KeyAgg,
+    aggregate_nonce: &AggregateNonce,
+    message: &[u8; 32],
+) -> Result<schnorr::Signature, MusigError> {
+    if partials.is_empty() {
+        return Err(MusigError::NoKeys);
+    }
+    let values = session_values(agg, aggregate_nonce, message);
+    let tweak = values.e.mul(agg.tweak.negate_if(!has_even_y(&agg.aggregate)));
+    let s = partials.iter().fold(tweak, |s, partial| s.add(partial.0));
+    let bytes = [values.r.x_only_public_key().0.serialize(), s.to_bytes()].concat();
+    let signature = schnorr::Signature::from_slice(&bytes)?;
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &Message::from_digest(*message), &agg.output_key())
+        .map_err(|_| MusigError::InvalidSignature)?;
+    Ok(signature)
+}
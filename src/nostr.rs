@@ -0,0 +1,179 @@
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{schnorr, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+use tungstenite::{stream::MaybeTlsStream, Message as WsMessage, WebSocket};
+
+use crate::error::NostrError;
+
+// NIP-78 application-specific data; replaceable per pubkey and "d" tag
+pub const ANNOUNCEMENT_KIND: u64 = 30078;
+pub const ANNOUNCEMENT_TAG: &str = "slugline-searcher";
+
+// NIP-01 short text note
+pub const NOTE_KIND: u64 = 1;
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+// What a searcher tells the world about itself
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Announcement {
+    pub url: String,
+    pub network: String,
+    pub runes: Vec<String>,
+    pub rune_price: u64,
+    pub usd_price: Option<f64>,
+    pub fee_rate: f64,
+    pub rune_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Event {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u64,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+impl Event {
+    // NIP-01: the id is the sha256 of the canonical serialization
+    fn compute_id(pubkey: &str, created_at: u64, kind: u64, tags: &[Vec<String>], content: &str) -> sha256::Hash {
+        let serialized = json!([0, pubkey, created_at, kind, tags, content]).to_string();
+        sha256::Hash::hash(serialized.as_bytes())
+    }
+
+    pub fn sign(keypair: &Keypair, kind: u64, tags: Vec<Vec<String>>, content: String) -> Event {
+        let secp = Secp256k1::new();
+        let pubkey = keypair.x_only_public_key().0.to_string();
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let id = Event::compute_id(&pubkey, created_at, kind, &tags, &content);
+        let sig = secp.sign_schnorr_no_aux_rand(&Message::from_digest(id.to_byte_array()), keypair);
+
+        Event {
+            id: id.to_string(),
+            pubkey,
+            created_at,
+            kind,
+            tags,
+            content,
+            sig: sig.to_string(),
+        }
+    }
+
+    pub fn verify(&self) -> Result<(), NostrError> {
+        let id = Event::compute_id(&self.pubkey, self.created_at, self.kind, &self.tags, &self.content);
+        if id.to_string() != self.id {
+            return Err(NostrError::InvalidEvent(format!("id mismatch for {}", self.id)));
+        }
+
+        let pubkey = XOnlyPublicKey::from_str(&self.pubkey)?;
+        let sig = schnorr::Signature::from_str(&self.sig)?;
+        Secp256k1::verification_only()
+            .verify_schnorr(&sig, &Message::from_digest(id.to_byte_array()), &pubkey)?;
+        Ok(())
+    }
+}
+
+pub fn parse_secret_key(secret_key: &str) -> Result<Keypair, NostrError> {
+    Ok(Keypair::from_seckey_str(&Secp256k1::new(), secret_key)?)
+}
+
+// A note tagging `recipient`, which their client shows as a notification. It isn't
+// encrypted, so it should say nothing /health doesn't.
+pub fn mention_event(keypair: &Keypair, recipient: &str, message: &str) -> Result<Event, NostrError> {
+    XOnlyPublicKey::from_str(recipient)?;
+    let tags = vec![vec!["p".to_string(), recipient.to_string()]];
+    Ok(Event::sign(keypair, NOTE_KIND, tags, message.to_string()))
+}
+
+pub fn announcement_event(keypair: &Keypair, announcement: &Announcement) -> Result<Event, NostrError> {
+    let mut tags = vec![
+        vec!["d".to_string(), format!("{}:{}", ANNOUNCEMENT_TAG, announcement.network)],
+        vec!["network".to_string(), announcement.network.clone()],
+    ];
+    for rune in &announcement.runes {
+        tags.push(vec!["t".to_string(), rune.clone()]);
+    }
+
+    Ok(Event::sign(keypair, ANNOUNCEMENT_KIND, tags, serde_json::to_string(announcement)?))
+}
+
+fn connect(relay: &str) -> Result<Socket, NostrError> {
+    let (socket, _) = tungstenite::connect(relay).map_err(|e| NostrError::WebSocket(Box::new(e)))?;
+    Ok(socket)
+}
+
+fn read(socket: &mut Socket) -> Result<Option<serde_json::Value>, NostrError> {
+    match socket.read().map_err(|e| NostrError::WebSocket(Box::new(e)))? {
+        WsMessage::Text(text) => Ok(Some(serde_json::from_str(&text)?)),
+        _ => Ok(None),
+    }
+}
+
+// Send an event and wait for the relay's OK
+pub fn publish(relay: &str, event: &Event) -> Result<(), NostrError> {
+    info!("Publishing event {} to {}", event.id, relay);
+    let mut socket = connect(relay)?;
+    socket
+        .send(WsMessage::Text(json!(["EVENT", event]).to_string()))
+        .map_err(|e| NostrError::WebSocket(Box::new(e)))?;
+
+    loop {
+        let Some(message) = read(&mut socket)? else { continue };
+        if message[0] == "OK" && message[1] == event.id.as_str() {
+            let _ = socket.close(None);
+            if message[2] == true {
+                return Ok(());
+            }
+            return Err(NostrError::Rejected {
+                relay: relay.to_string(),
+                message: message[3].as_str().unwrap_or_default().to_string(),
+            });
+        }
+    }
+}
+
+// Fetch the stored announcements for a rune, dropping any with bad signatures
+pub fn fetch_announcements(relay: &str, rune: &str, since: u64) -> Result<Vec<(Event, Announcement)>, NostrError> {
+    info!("Fetching announcements for {} from {}", rune, relay);
+    let mut socket = connect(relay)?;
+    let filter = json!({ "kinds": [ANNOUNCEMENT_KIND], "#t": [rune], "since": since });
+    socket
+        .send(WsMessage::Text(json!(["REQ", ANNOUNCEMENT_TAG, filter]).to_string()))
+        .map_err(|e| NostrError::WebSocket(Box::new(e)))?;
+
+    let mut announcements = Vec::new();
+    loop {
+        let Some(message) = read(&mut socket)? else { continue };
+        match message[0].as_str() {
+            Some("EVENT") => {
+                let event: Event = serde_json::from_value(message[2].clone())?;
+                if let Err(e) = event.verify() {
+                    info!("Ignoring invalid event from {}: {}", relay, e);
+                    continue;
+                }
+                match serde_json::from_str(&event.content) {
+                    Ok(announcement) => announcements.push((event, announcement)),
+                    Err(e) => info!("Ignoring malformed announcement {}: {}", event.id, e),
+                }
+            }
+            Some("EOSE") | Some("CLOSED") => break,
+            _ => {}
+        }
+    }
+
+    let _ = socket.send(WsMessage::Text(json!(["CLOSE", ANNOUNCEMENT_TAG]).to_string()));
+    let _ = socket.close(None);
+    Ok(announcements)
+}
@@ -0,0 +1,225 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+use crate::error::{NotifyError, SearcherError};
+use crate::http::redact_url;
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+// Something an operator should hear about without tailing logs, like "wallet_empty",
+// "bitcoind_unreachable", "submit_failures" or "large_job_confirmed"
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Notification {
+    pub event: String,
+    pub network: String,
+    pub message: String,
+}
+
+impl Notification {
+    fn text(&self) -> String {
+        format!("slugline ({}): {}", self.network, self.message)
+    }
+}
+
+// Where notifications go. Library users implement this to add channels; the CLI
+// offers the ones below.
+pub trait NotificationSink: fmt::Debug + Send + Sync {
+    // For logs, so without credentials
+    fn name(&self) -> String;
+
+    fn send<'a>(&'a self, http: &'a reqwest::Client, notification: &'a Notification) -> BoxFuture<'a, Result<(), NotifyError>>;
+}
+
+// "webhook:<url>", "telegram:<bot token>:<chat id>",
+// "matrix:<access token>@<homeserver url>/<room id>" or "email:<address>"
+pub fn parse_sink(spec: &str) -> Result<Arc<dyn NotificationSink>, SearcherError> {
+    // Only the kind, since the rest may hold a token
+    let invalid = || SearcherError::Config(format!(
+        "invalid notification sink {}: expected webhook:<url>, telegram:<bot token>:<chat id>, \
+         matrix:<access token>@<homeserver url>/<room id> or email:<address>",
+        spec.split(':').next().unwrap_or_default()
+    ));
+    match spec.split_once(':').ok_or_else(invalid)? {
+        ("webhook", url) if !url.is_empty() => Ok(Arc::new(Webhook { url: url.to_string() })),
+        ("telegram", rest) => {
+            let (token, chat_id) = rest.rsplit_once(':').filter(|(token, chat_id)| !token.is_empty() && !chat_id.is_empty()).ok_or_else(invalid)?;
+            Ok(Arc::new(Telegram { token: token.to_string(), chat_id: chat_id.to_string() }))
+        }
+        ("matrix", rest) => {
+            let (token, target) = rest.split_once('@').ok_or_else(invalid)?;
+            let (homeserver, room) = target.rsplit_once('/').filter(|(_, room)| !room.is_empty()).ok_or_else(invalid)?;
+            let homeserver = reqwest::Url::parse(homeserver).map_err(|_| invalid())?;
+            Ok(Arc::new(Matrix { homeserver, room: room.to_string(), token: token.to_string() }))
+        }
+        ("email", address) if address.contains('@') => Ok(Arc::new(Email { address: address.to_string() })),
+        _ => Err(invalid()),
+    }
+}
+
+fn check_status(sink: String, response: reqwest::Response) -> Result<(), NotifyError> {
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(NotifyError::Refused { sink, status }),
+    }
+}
+
+// POSTs the notification as JSON
+#[derive(Debug)]
+pub struct Webhook {
+    pub url: String,
+}
+
+impl NotificationSink for Webhook {
+    fn name(&self) -> String {
+        redact_url(&self.url)
+    }
+
+    fn send<'a>(&'a self, http: &'a reqwest::Client, notification: &'a Notification) -> BoxFuture<'a, Result<(), NotifyError>> {
+        Box::pin(async move {
+            check_status(self.name(), http.post(&self.url).json(notification).send().await?)
+        })
+    }
+}
+
+// A message from a bot to a chat it was added to
+pub struct Telegram {
+    pub token: String,
+    pub chat_id: String,
+}
+
+impl fmt::Debug for Telegram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Telegram").field("chat_id", &self.chat_id).finish_non_exhaustive()
+    }
+}
+
+impl NotificationSink for Telegram {
+    fn name(&self) -> String {
+        format!("telegram chat {}", self.chat_id)
+    }
+
+    fn send<'a>(&'a self, http: &'a reqwest::Client, notification: &'a Notification) -> BoxFuture<'a, Result<(), NotifyError>> {
+        Box::pin(async move {
+            let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, self.token);
+            let body = serde_json::json!({ "chat_id": self.chat_id, "text": notification.text() });
+            // The URL carries the bot token, so keep it out of errors
+            let response = http.post(url).json(&body).send().await.map_err(|e| e.without_url())?;
+            check_status(self.name(), response)
+        })
+    }
+}
+
+// A text message to a room the access token's user has joined
+pub struct Matrix {
+    pub homeserver: reqwest::Url,
+    pub room: String,
+    pub token: String,
+}
+
+impl fmt::Debug for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Matrix")
+            .field("homeserver", &self.homeserver.as_str())
+            .field("room", &self.room)
+            .finish_non_exhaustive()
+    }
+}
+
+// Matrix deduplicates sends by transaction id, so each one needs its own
+static MATRIX_TXN: AtomicU64 = AtomicU64::new(0);
+
+impl NotificationSink for Matrix {
+    fn name(&self) -> String {
+        format!("matrix room {}", self.room)
+    }
+
+    fn send<'a>(&'a self, http: &'a reqwest::Client, notification: &'a Notification) -> BoxFuture<'a, Result<(), NotifyError>> {
+        Box::pin(async move {
+            let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+            let txn = format!("slugline-{}-{}", started, MATRIX_TXN.fetch_add(1, Ordering::Relaxed));
+            let mut url = self.homeserver.clone();
+            url.path_segments_mut()
+                .map_err(|_| NotifyError::Failed(format!("{} is not a homeserver URL", self.homeserver)))?
+                .pop_if_empty()
+                .extend(["_matrix", "client", "v3", "rooms", &self.room, "send", "m.room.message", &txn]);
+            let body = serde_json::json!({ "msgtype": "m.text", "body": notification.text() });
+            check_status(self.name(), http.put(url).bearer_auth(&self.token).json(&body).send().await?)
+        })
+    }
+}
+
+// Mail handed to the local sendmail, which most servers' MTAs provide
+#[derive(Debug)]
+pub struct Email {
+    pub address: String,
+}
+
+impl NotificationSink for Email {
+    fn name(&self) -> String {
+        format!("email to {}", self.address)
+    }
+
+    fn send<'a>(&'a self, _http: &'a reqwest::Client, notification: &'a Notification) -> BoxFuture<'a, Result<(), NotifyError>> {
+        Box::pin(async move {
+            let mail = format!(
+                "To: {}\nSubject: slugline {} on {}\n\n{}\n",
+                self.address, notification.event, notification.network, notification.message
+            );
+            let mut child = tokio::process::Command::new("sendmail")
+                .args(["-i", "--", &self.address])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(mail.as_bytes()).await?;
+            }
+            let output = child.wait_with_output().await?;
+            if !output.status.success() {
+                return Err(NotifyError::Failed(format!(
+                    "sendmail exited with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            Ok(())
+        })
+    }
+}
+
+// Sends to every sink in the background, so callers never wait on a slow channel
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    sinks: Vec<Arc<dyn NotificationSink>>,
+    http: reqwest::Client,
+    network: String,
+}
+
+impl Notifier {
+    pub fn new(sinks: Vec<Arc<dyn NotificationSink>>, http: reqwest::Client, network: String) -> Self {
+        Notifier { sinks, http, network }
+    }
+
+    pub fn notify(&self, event: &str, message: String) {
+        if self.sinks.is_empty() {
+            return;
+        }
+        let notification = Notification { event: event.to_string(), network: self.network.clone(), message };
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            for sink in &notifier.sinks {
+                match sink.send(&notifier.http, &notification).await {
+                    Ok(()) => info!("Sent {} notification to {}", notification.event, sink.name()),
+                    Err(e) => error!("Failed to send {} notification to {}: {}", notification.event, sink.name(), e),
+                }
+            }
+        });
+    }
+}
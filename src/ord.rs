@@ -0,0 +1,219 @@
+use bitcoin::{transaction::OutPoint, Network, ScriptBuf};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::error::OrdError;
+use crate::runestone::RuneId;
+
+// Most requests one validation has in flight to ord at once, so a parent with many
+// inputs is checked about as fast as one with a few without flooding the server
+pub const MAX_CONCURRENT_LOOKUPS: usize = 8;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuneInfo {
+    pub amount: u64,
+    pub divisibility: u8,
+    pub symbol: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Utxo {
+    pub address: String,
+    pub confirmations: u32,
+    pub indexed: bool,
+    pub inscriptions: Vec<String>,
+    pub outpoint: String,
+    pub runes: HashMap<String, RuneInfo>,
+    pub sat_ranges: Option<Vec<String>>,
+    pub script_pubkey: String,
+    pub spent: bool,
+    pub transaction: String,
+    pub value: u64,
+}
+
+pub fn fetch_utxos(http: &reqwest::blocking::Client, ord_server: &str, address: &str) -> Result<Vec<Utxo>, OrdError> {
+    let url = format!("{}/outputs/{}", ord_server, address);
+    info!("Fetching UTXOs from: {}", url);
+
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    let utxos: Vec<Utxo> = response.json()?;
+
+    // Filter out spent UTXOs
+    let unspent_utxos: Vec<Utxo> = utxos.into_iter()
+        .filter(|u| !u.spent)
+        .collect();
+
+    Ok(unspent_utxos)
+}
+
+pub fn fetch_output(http: &reqwest::blocking::Client, ord_server: &str, outpoint: &OutPoint) -> Result<Utxo, OrdError> {
+    let url = format!("{}/output/{}", ord_server, outpoint);
+    info!("Fetching output from: {}", url);
+
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    Ok(response.json()?)
+}
+
+// Inscription details from ord's /inscription endpoint
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Inscription {
+    pub id: String,
+    #[serde(default)]
+    pub parents: Vec<String>,
+    // "txid:vout:offset"
+    pub satpoint: String,
+}
+
+pub async fn fetch_output_info(http: &reqwest::Client, ord_server: &str, outpoint: &OutPoint) -> Result<Utxo, OrdError> {
+    let url = format!("{}/output/{}", ord_server, outpoint);
+    info!("Fetching output from: {}", url);
+
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    Ok(response.json().await?)
+}
+
+// Outputs in the order given, looked up concurrently
+pub async fn fetch_outputs_info(http: &reqwest::Client, ord_server: &str, outpoints: &[OutPoint]) -> Result<Vec<Utxo>, OrdError> {
+    // Owned items, since futures borrowing from the iterator can't be spawned
+    stream::iter(outpoints.iter().copied())
+        .map(|outpoint| async move { fetch_output_info(http, ord_server, &outpoint).await })
+        .buffered(MAX_CONCURRENT_LOOKUPS)
+        .try_collect()
+        .await
+}
+
+pub async fn fetch_inscription(http: &reqwest::Client, ord_server: &str, inscription_id: &str) -> Result<Inscription, OrdError> {
+    let url = format!("{}/inscription/{}", ord_server, inscription_id);
+    info!("Fetching inscription from: {}", url);
+
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    Ok(response.json().await?)
+}
+
+// Inscriptions in the order given, looked up concurrently
+pub async fn fetch_inscriptions(http: &reqwest::Client, ord_server: &str, inscription_ids: &[String]) -> Result<Vec<Inscription>, OrdError> {
+    stream::iter(inscription_ids.iter().cloned())
+        .map(|inscription_id| async move { fetch_inscription(http, ord_server, &inscription_id).await })
+        .buffered(MAX_CONCURRENT_LOOKUPS)
+        .try_collect()
+        .await
+}
+
+pub async fn fetch_utxo_info(http: &reqwest::Client, outpoint: &OutPoint, network: Network, ord_server: &str) -> Result<Utxo, OrdError> {
+    // First, fetch the transaction to get the output script
+    let url = format!("{}/tx/{}", ord_server, outpoint.txid);
+    info!("Fetching transaction details from: {}", url);
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    let tx_data: serde_json::Value = response.json().await?;
+
+    // Get the output at the specified vout
+    let outputs = tx_data["transaction"]["output"]
+        .as_array()
+        .ok_or_else(|| OrdError::Malformed("No outputs in transaction".to_string()))?;
+
+    let output = outputs.get(outpoint.vout as usize)
+        .ok_or_else(|| OrdError::Malformed("Output index out of bounds".to_string()))?;
+
+    // Get the script_pubkey and convert to address
+    let script_hex = output["script_pubkey"]
+        .as_str()
+        .ok_or_else(|| OrdError::Malformed("No script_pubkey in output".to_string()))?;
+
+    let script_bytes = hex::decode(script_hex)
+        .map_err(|e| OrdError::Malformed(format!("Failed to decode script hex: {}", e)))?;
+
+    let script = ScriptBuf::from_bytes(script_bytes);
+
+    // Try to extract address from script
+    let address = bitcoin::Address::from_script(&script, network)
+        .map_err(|e| OrdError::Malformed(format!("Failed to derive address from script: {}", e)))?;
+
+    // Now fetch the UTXO info for this specific output
+    let url = format!("{}/outputs/{}", ord_server, address);
+    info!("Fetching UTXO info from: {}", url);
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    let utxos: Vec<Utxo> = response.json().await?;
+
+    // Find the specific UTXO matching our outpoint
+    let outpoint_str = format!("{}:{}", outpoint.txid, outpoint.vout);
+    utxos.into_iter()
+        .find(|u| u.outpoint == outpoint_str)
+        .ok_or(OrdError::UtxoNotFound(outpoint_str))
+}
+
+// The rune's ID, which edicts name it by
+pub async fn fetch_rune_id(http: &reqwest::Client, ord_server: &str, rune: &str) -> Result<RuneId, OrdError> {
+    let url = format!("{}/rune/{}", ord_server, rune);
+    info!("Fetching rune from: {}", url);
+    let response = http
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(OrdError::Status { url, status: response.status() });
+    }
+
+    let rune_data: serde_json::Value = response.json().await?;
+    let id = rune_data["id"]
+        .as_str()
+        .ok_or_else(|| OrdError::Malformed(format!("No id for rune {}", rune)))?;
+    id.split_once(':')
+        .and_then(|(block, index)| Some((block.parse().ok()?, index.parse().ok()?)))
+        .ok_or_else(|| OrdError::Malformed(format!("Invalid rune ID: {}", id)))
+}
@@ -0,0 +1,155 @@
+use bitcoin::base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::error::PaymentError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightningBackend {
+    Lnd,
+    Cln,
+}
+
+#[derive(Debug, Clone)]
+pub struct LightningConfig {
+    pub backend: LightningBackend,
+    pub url: String,
+    // LND macaroon (hex) or CLN rune
+    pub auth: String,
+    // Sats per vbyte of sponsored package, defaults to the searcher's fee rate
+    pub price: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub payment_request: String,
+    pub payment_hash: String,
+    pub amount_sat: u64,
+    pub expires_at: u64,
+}
+
+// Talks to the searcher's Lightning node over its REST API
+#[derive(Debug, Clone)]
+pub struct LightningNode {
+    config: LightningConfig,
+    http: reqwest::Client,
+}
+
+impl LightningNode {
+    pub fn new(config: LightningConfig, http: reqwest::Client) -> Self {
+        LightningNode { config, http }
+    }
+
+    pub fn price(&self, fee_rate: f64) -> u64 {
+        self.config.price.unwrap_or(fee_rate.ceil() as u64)
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value) -> Result<serde_json::Value, PaymentError> {
+        let url = format!("{}{}", self.config.url.trim_end_matches('/'), path);
+        let request = match self.config.backend {
+            LightningBackend::Lnd => self.http.post(&url).header("Grpc-Metadata-macaroon", &self.config.auth),
+            LightningBackend::Cln => self.http.post(&url).header("Rune", &self.config.auth),
+        };
+        self.send(url, request.json(&body)).await
+    }
+
+    async fn get(&self, path: &str) -> Result<serde_json::Value, PaymentError> {
+        let url = format!("{}{}", self.config.url.trim_end_matches('/'), path);
+        let request = self.http.get(&url).header("Grpc-Metadata-macaroon", &self.config.auth);
+        self.send(url, request).await
+    }
+
+    async fn send(&self, url: String, request: reqwest::RequestBuilder) -> Result<serde_json::Value, PaymentError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(PaymentError::Node {
+                url,
+                message: format!("{}: {}", status, response.text().await.unwrap_or_default()),
+            });
+        }
+        Ok(response.json().await?)
+    }
+
+    pub async fn create_invoice(&self, amount_sat: u64, memo: &str, expiry: u64) -> Result<Invoice, PaymentError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let (payment_request, payment_hash) = match self.config.backend {
+            LightningBackend::Lnd => {
+                let result = self.post("/v1/invoices", json!({
+                    "value": amount_sat.to_string(),
+                    "memo": memo,
+                    "expiry": expiry.to_string(),
+                })).await?;
+                // LND returns the hash base64 encoded
+                let r_hash = STANDARD
+                    .decode(result["r_hash"].as_str().unwrap_or_default())
+                    .map_err(|e| PaymentError::Malformed(format!("Invalid r_hash: {}", e)))?;
+                (result["payment_request"].as_str().map(str::to_string), Some(hex::encode(r_hash)))
+            }
+            LightningBackend::Cln => {
+                let result = self.post("/v1/invoice", json!({
+                    "amount_msat": amount_sat * 1000,
+                    // CLN labels must be unique
+                    "label": format!("slugline-{}", now.as_nanos()),
+                    "description": memo,
+                    "expiry": expiry,
+                })).await?;
+                (
+                    result["bolt11"].as_str().map(str::to_string),
+                    result["payment_hash"].as_str().map(str::to_string),
+                )
+            }
+        };
+
+        let (Some(payment_request), Some(payment_hash)) = (payment_request, payment_hash) else {
+            return Err(PaymentError::Malformed("Invoice response missing fields".to_string()));
+        };
+
+        info!("Created {} sat invoice {}", amount_sat, payment_hash);
+        Ok(Invoice {
+            payment_request,
+            payment_hash,
+            amount_sat,
+            expires_at: now.as_secs() + expiry,
+        })
+    }
+
+    // Cancels an unpaid invoice, so it can't be paid after all. Fails for paid invoices.
+    pub async fn cancel_invoice(&self, payment_hash: &str) -> Result<(), PaymentError> {
+        match self.config.backend {
+            LightningBackend::Lnd => {
+                let hash = hex::decode(payment_hash)
+                    .map_err(|e| PaymentError::Malformed(format!("Invalid payment hash: {}", e)))?;
+                self.post("/v2/invoices/cancel", json!({ "payment_hash": STANDARD.encode(hash) })).await?;
+            }
+            LightningBackend::Cln => {
+                let result = self.post("/v1/listinvoices", json!({ "payment_hash": payment_hash })).await?;
+                let label = result["invoices"][0]["label"].as_str()
+                    .ok_or_else(|| PaymentError::UnknownInvoice(payment_hash.to_string()))?
+                    .to_string();
+                self.post("/v1/delinvoice", json!({ "label": label, "status": "unpaid" })).await?;
+            }
+        }
+        info!("Cancelled invoice {}", payment_hash);
+        Ok(())
+    }
+
+    pub async fn is_paid(&self, payment_hash: &str) -> Result<bool, PaymentError> {
+        match self.config.backend {
+            LightningBackend::Lnd => {
+                let result = self.get(&format!("/v1/invoice/{}", payment_hash)).await?;
+                Ok(result["state"] == "SETTLED")
+            }
+            LightningBackend::Cln => {
+                let result = self.post("/v1/listinvoices", json!({ "payment_hash": payment_hash })).await?;
+                Ok(result["invoices"]
+                    .as_array()
+                    .is_some_and(|invoices| invoices.iter().any(|i| i["status"] == "paid")))
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use bitcoin::{
+    policy::{MAX_STANDARD_TX_SIGOPS_COST, MAX_STANDARD_TX_WEIGHT},
+    psbt::Psbt,
+    transaction::{Transaction, TxOut},
+    Amount, OutPoint,
+};
+use std::collections::HashMap;
+use tracing::error;
+
+use crate::error::ValidationError;
+
+// Bitcoin Core's relay limits beyond what the bitcoin crate exports
+const MAX_TRUC_VSIZE: u64 = 10_000;
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+const MAX_STANDARD_WITNESS_STACK_ITEM_SIZE: usize = 80;
+
+// How strictly to apply the ephemeral dust rules relayed by newer Bitcoin Core releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PolicyMode {
+    Off,
+    // Log violations but sponsor the package anyway
+    #[default]
+    Warn,
+    Enforce,
+}
+
+impl PolicyMode {
+    pub fn apply(self, result: Result<(), ValidationError>) -> Result<(), ValidationError> {
+        match (self, result) {
+            (PolicyMode::Off, _) | (_, Ok(())) => Ok(()),
+            (PolicyMode::Warn, Err(e)) => {
+                error!("Ephemeral dust policy violation (not enforced): {}", e);
+                Ok(())
+            }
+            (PolicyMode::Enforce, Err(e)) => Err(e),
+        }
+    }
+}
+
+fn dust_outputs(tx: &Transaction) -> Vec<u32> {
+    tx.output.iter()
+        .enumerate()
+        .filter(|(_, output)| output.value < output.script_pubkey.minimal_non_dust())
+        .map(|(vout, _)| vout as u32)
+        .collect()
+}
+
+// A parent with dust may only have one dust output and must pay no fee of its own
+pub fn check_parent(psbt: &Psbt) -> Result<(), ValidationError> {
+    let dust = dust_outputs(&psbt.unsigned_tx);
+    if dust.is_empty() {
+        return Ok(());
+    }
+    if dust.len() > 1 {
+        return Err(ValidationError::MultipleDustOutputs(dust.len()));
+    }
+
+    let fee = psbt.fee().map_err(|e| ValidationError::UnknownFee(e.to_string()))?;
+    if fee != Amount::ZERO {
+        return Err(ValidationError::ParentPaysFee(fee.to_sat()));
+    }
+    Ok(())
+}
+
+// Every dust output of the parent has to be spent by the child in the same package
+pub fn check_package(parent: &Transaction, child: &Transaction) -> Result<(), ValidationError> {
+    let parent_txid = parent.compute_txid();
+    for vout in dust_outputs(parent) {
+        let spent = child.input.iter()
+            .any(|input| input.previous_output.txid == parent_txid && input.previous_output.vout == vout);
+        if !spent {
+            return Err(ValidationError::UnspentDust(vout));
+        }
+    }
+    Ok(())
+}
+
+// The limits relay policy puts on a signed parent, checked before anything is looked up so
+// a parent bitcoind would refuse fails with the limit it breaks
+pub fn check_standard(psbt: &Psbt) -> Result<(), ValidationError> {
+    let non_standard = |reason: String| Err(ValidationError::NonStandard(reason));
+    let spent: HashMap<OutPoint, TxOut> = psbt.unsigned_tx.input.iter()
+        .zip(&psbt.inputs)
+        .filter_map(|(txin, input)| {
+            let output = match (&input.witness_utxo, &input.non_witness_utxo) {
+                (Some(output), _) => output.clone(),
+                (None, Some(prev)) => prev.output.get(txin.previous_output.vout as usize)?.clone(),
+                (None, None) => return None,
+            };
+            Some((txin.previous_output, output))
+        })
+        .collect();
+    let tx = psbt.clone().extract_tx_unchecked_fee_rate();
+
+    let weight = tx.weight().to_wu();
+    if weight > u64::from(MAX_STANDARD_TX_WEIGHT) {
+        return non_standard(format!("weight {} is over the {} limit", weight, MAX_STANDARD_TX_WEIGHT));
+    }
+    let vsize = tx.vsize() as u64;
+    if vsize > MAX_TRUC_VSIZE {
+        return non_standard(format!("{} vbytes is over the {} vbyte limit for TRUC transactions", vsize, MAX_TRUC_VSIZE));
+    }
+    let sigops = tx.total_sigop_cost(|outpoint| spent.get(outpoint).cloned());
+    if sigops > MAX_STANDARD_TX_SIGOPS_COST as usize {
+        return non_standard(format!("sigop cost {} is over the {} limit", sigops, MAX_STANDARD_TX_SIGOPS_COST));
+    }
+
+    for (index, input) in tx.input.iter().enumerate() {
+        if input.script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            return non_standard(format!(
+                "input {} scriptSig is {} bytes, over the {} byte limit",
+                index,
+                input.script_sig.len(),
+                MAX_STANDARD_SCRIPTSIG_SIZE
+            ));
+        }
+        if !input.script_sig.is_push_only() {
+            return non_standard(format!("input {} scriptSig is not push-only", index));
+        }
+
+        let Some(prevout) = spent.get(&input.previous_output) else {
+            continue;
+        };
+        if prevout.script_pubkey.is_p2wsh()
+            && let Some((script, stack)) = input.witness.to_vec().split_last()
+        {
+            if script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+                return non_standard(format!(
+                    "input {} witness script is {} bytes, over the {} byte limit",
+                    index,
+                    script.len(),
+                    MAX_STANDARD_P2WSH_SCRIPT_SIZE
+                ));
+            }
+            if stack.len() > MAX_STANDARD_P2WSH_STACK_ITEMS {
+                return non_standard(format!(
+                    "input {} has {} witness stack items, over the {} limit",
+                    index,
+                    stack.len(),
+                    MAX_STANDARD_P2WSH_STACK_ITEMS
+                ));
+            }
+            if let Some(item) = stack.iter().find(|item| item.len() > MAX_STANDARD_WITNESS_STACK_ITEM_SIZE) {
+                return non_standard(format!(
+                    "input {} has a {} byte witness stack item, over the {} byte limit",
+                    index,
+                    item.len(),
+                    MAX_STANDARD_WITNESS_STACK_ITEM_SIZE
+                ));
+            }
+        }
+        if prevout.script_pubkey.is_p2tr() && input.witness.taproot_annex().is_some() {
+            return non_standard(format!("input {} has a taproot annex", index));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+use crate::error::PricingError;
+
+// Rates younger than this are reused instead of asking the providers again
+const REFRESH_SECS: u64 = 60;
+
+// Where to get the USD price of one rune unit
+#[derive(Debug, Clone)]
+pub enum RateProvider {
+    // A fixed rate, useful as the last link of a chain
+    Static(f64),
+    // A JSON endpoint and the JSON pointer to the rate in its response
+    Http { url: String, pointer: String },
+}
+
+impl FromStr for RateProvider {
+    type Err = PricingError;
+
+    // "static:<rate>" or "<url>#<json pointer>"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rate) = s.strip_prefix("static:") {
+            return rate.parse()
+                .map(RateProvider::Static)
+                .map_err(|_| PricingError::InvalidProvider(s.to_string()));
+        }
+
+        match s.rsplit_once('#') {
+            Some((url, pointer)) if pointer.starts_with('/') => Ok(RateProvider::Http {
+                url: url.to_string(),
+                pointer: pointer.to_string(),
+            }),
+            _ => Err(PricingError::InvalidProvider(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for RateProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RateProvider::Static(rate) => write!(f, "static:{}", rate),
+            RateProvider::Http { url, pointer } => write!(f, "{}#{}", url, pointer),
+        }
+    }
+}
+
+// A price multiplier for parents up to a size, so big parents can cost more per vbyte
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceTier {
+    pub max_vsize: u64,
+    pub multiplier: f64,
+}
+
+impl FromStr for PriceTier {
+    type Err = PricingError;
+
+    // "<max parent vsize>:<multiplier>"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PricingError::InvalidTier(s.to_string());
+        let (max_vsize, multiplier) = s.split_once(':').ok_or_else(invalid)?;
+        let tier = PriceTier {
+            max_vsize: max_vsize.parse().map_err(|_| invalid())?,
+            multiplier: multiplier.parse().map_err(|_| invalid())?,
+        };
+        if !(tier.multiplier > 0.0 && tier.multiplier.is_finite()) {
+            return Err(invalid());
+        }
+        Ok(tier)
+    }
+}
+
+// The multiplier of the smallest tier the parent fits in. Parents bigger than every
+// tier pay the largest tier's, and without tiers everything pays the base price.
+pub fn tier_multiplier(tiers: &[PriceTier], parent_vsize: u64) -> f64 {
+    tiers.iter()
+        .filter(|tier| parent_vsize <= tier.max_vsize)
+        .min_by_key(|tier| tier.max_vsize)
+        .or_else(|| tiers.iter().max_by_key(|tier| tier.max_vsize))
+        .map_or(1.0, |tier| tier.multiplier)
+}
+
+// A package's price in rune units or sats, from a price per vbyte and its tier's multiplier
+pub fn price_package(package_vsize: u64, price_per_vbyte: u64, multiplier: f64) -> u64 {
+    (package_vsize as f64 * price_per_vbyte as f64 * multiplier).ceil() as u64
+}
+
+// Rune prices that follow the mempool: the fee rate `depth_vbytes` deep, plus a margin,
+// converted to runes at a fixed number of sats per whole rune
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistogramPricing {
+    pub sats_per_rune: f64,
+    pub margin_percent: f64,
+    pub depth_vbytes: u64,
+}
+
+impl HistogramPricing {
+    // Rune units per vbyte at a fee rate, fractional so small rates don't round to nothing
+    pub fn units_per_vbyte(&self, fee_rate: f64, divisibility: u8) -> f64 {
+        fee_rate * (1.0 + self.margin_percent / 100.0) / self.sats_per_rune * 10f64.powi(divisibility.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExchangeRate {
+    pub usd_per_rune: f64,
+    pub timestamp: u64,
+    pub source: String,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl RateProvider {
+    async fn fetch(&self, http: &reqwest::Client) -> Result<f64, PricingError> {
+        match self {
+            RateProvider::Static(rate) => Ok(*rate),
+            RateProvider::Http { url, pointer } => {
+                let response = http
+                    .get(url)
+                    .header("Accept", "application/json")
+                    .send()
+                    .await?;
+
+                if !response.status().is_success() {
+                    return Err(PricingError::Malformed(format!("{} returned {}", url, response.status())));
+                }
+
+                let body: serde_json::Value = response.json().await?;
+                // Feeds disagree on whether prices are numbers or strings
+                let rate = match body.pointer(pointer) {
+                    Some(serde_json::Value::Number(n)) => n.as_f64(),
+                    Some(serde_json::Value::String(s)) => s.parse().ok(),
+                    _ => None,
+                };
+                rate.filter(|rate| *rate > 0.0)
+                    .ok_or_else(|| PricingError::Malformed(format!("No positive rate at {} in {}", pointer, url)))
+            }
+        }
+    }
+}
+
+// Tries each provider in order, caching the first rate that comes back
+#[derive(Debug)]
+pub struct PriceFeed {
+    providers: Vec<RateProvider>,
+    max_age: u64,
+    cached: Mutex<Option<ExchangeRate>>,
+    http: reqwest::Client,
+}
+
+impl PriceFeed {
+    pub fn new(providers: Vec<RateProvider>, max_age: u64, http: reqwest::Client) -> Self {
+        PriceFeed {
+            providers,
+            max_age,
+            cached: Mutex::new(None),
+            http,
+        }
+    }
+
+    pub async fn rate(&self) -> Result<ExchangeRate, PricingError> {
+        let cached = self.cached.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(rate) = &cached
+            && now().saturating_sub(rate.timestamp) < REFRESH_SECS
+        {
+            return Ok(rate.clone());
+        }
+
+        for provider in &self.providers {
+            match provider.fetch(&self.http).await {
+                Ok(usd_per_rune) => {
+                    let rate = ExchangeRate {
+                        usd_per_rune,
+                        timestamp: now(),
+                        source: provider.to_string(),
+                    };
+                    info!("Exchange rate {} USD per rune from {}", usd_per_rune, rate.source);
+                    *self.cached.lock().unwrap_or_else(|e| e.into_inner()) = Some(rate.clone());
+                    return Ok(rate);
+                }
+                Err(e) => error!("Rate provider {} failed: {}", provider, e),
+            }
+        }
+
+        // Every provider failed, fall back to the last rate while it's within bounds
+        match cached {
+            Some(rate) if now().saturating_sub(rate.timestamp) <= self.max_age => Ok(rate),
+            Some(rate) => Err(PricingError::Stale(now().saturating_sub(rate.timestamp))),
+            None => Err(PricingError::NoRate),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use bitcoin::{transaction::OutPoint, Network, Transaction};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+
+pub fn parse_network(network_str: &str) -> Network {
+    match network_str {
+        "testnet" => Network::Testnet,
+        "testnet4" => Network::Testnet4,
+        "signet" => Network::Signet,
+        "regtest" => Network::Regtest,
+        _ => Network::Bitcoin,
+    }
+}
+
+pub fn rpc_port(network: Network) -> u16 {
+    match network {
+        Network::Bitcoin => 8332,
+        Network::Testnet => 18332,
+        Network::Testnet4 => 48332,
+        Network::Signet => 38332,
+        Network::Regtest => 18443,
+        _ => 8332, // Default to mainnet port
+    }
+}
+
+// The `chain` bitcoind reports in getblockchaininfo. Testnet, testnet4 and signet share
+// an address prefix, so this is the only way to tell them apart.
+pub fn chain_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "main",
+        Network::Testnet => "test",
+        Network::Testnet4 => "testnet4",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+        _ => "unknown",
+    }
+}
+
+// The `chain` ord reports in /status
+pub fn ord_chain_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "mainnet",
+        Network::Testnet => "testnet",
+        Network::Testnet4 => "testnet4",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+        _ => "unknown",
+    }
+}
+
+pub fn rpc_url(bitcoind_host: &str, network: Network, wallet_name: Option<&str>) -> String {
+    match wallet_name {
+        Some(wallet_name) => format!("http://{}:{}/wallet/{}", bitcoind_host, rpc_port(network), wallet_name),
+        None => format!("http://{}:{}", bitcoind_host, rpc_port(network)),
+    }
+}
+
+pub fn auth(bitcoind_user: Option<&str>, bitcoind_password: Option<&str>) -> Auth {
+    match (bitcoind_user, bitcoind_password) {
+        (Some(user), Some(pass)) => Auth::UserPass(user.to_string(), pass.to_string()),
+        _ => Auth::None,
+    }
+}
+
+pub fn connect(
+    bitcoind_host: &str,
+    bitcoind_user: Option<&str>,
+    bitcoind_password: Option<&str>,
+    network: Network,
+    wallet_name: Option<&str>,
+) -> Result<Client, bitcoincore_rpc::Error> {
+    let rpc_url = rpc_url(bitcoind_host, network, wallet_name);
+    Client::new(&rpc_url, auth(bitcoind_user, bitcoind_password))
+}
+
+// bitcoind's version number, like 280000 for 28.0, and its user agent. Asked without the
+// typed result, whose warnings field newer versions changed the shape of.
+pub fn node_version(client: &Client) -> Result<(usize, String), bitcoincore_rpc::Error> {
+    let info = client.call::<serde_json::Value>("getnetworkinfo", &[])?;
+    let version = info.get("version").and_then(|version| version.as_u64()).unwrap_or_default() as usize;
+    let subversion = info.get("subversion").and_then(|subversion| subversion.as_str()).unwrap_or_default();
+    Ok((version, subversion.to_string()))
+}
+
+// Which of these outputs are missing or already spent, counting the mempool. Asked in
+// one batch, so checking a parent's inputs costs one round trip however many there are.
+pub fn missing_outputs(client: &Client, outpoints: &[OutPoint]) -> Result<Vec<OutPoint>, bitcoincore_rpc::Error> {
+    if outpoints.is_empty() {
+        return Ok(Vec::new());
+    }
+    let params = outpoints.iter()
+        .map(|outpoint| serde_json::value::to_raw_value(&serde_json::json!([outpoint.txid, outpoint.vout, true])))
+        .collect::<Result<Vec<_>, _>>()?;
+    let jsonrpc = client.get_jsonrpc_client();
+    let requests: Vec<_> = params.iter().map(|params| jsonrpc.build_request("gettxout", Some(params))).collect();
+
+    let mut missing = Vec::new();
+    for (outpoint, response) in outpoints.iter().zip(jsonrpc.send_batch(&requests)?) {
+        let response = response
+            .ok_or_else(|| bitcoincore_rpc::Error::ReturnedError(format!("No gettxout response for {}", outpoint)))?;
+        if response.result::<Option<serde_json::Value>>()?.is_none() {
+            missing.push(*outpoint);
+        }
+    }
+    Ok(missing)
+}
+
+// Reasons bitcoind gives a transaction that pays too little on its own
+const FEE_REJECTIONS: [&str; 2] = ["min relay fee not met", "mempool min fee not met"];
+
+// testmempoolaccept on a package, with why each transaction would be rejected or None if
+// it would be accepted. bitcoind tests each transaction at its own fee rate, so a zero-fee
+// parent fails on fees even when its child pays for both. Those fee rejections are let
+// through when the package's own rate clears the mempool minimum, along with later
+// transactions bitcoind didn't get to or turned away for spending the rejected one.
+pub fn test_package(client: &Client, package: &[&Transaction]) -> Result<Vec<Option<String>>, bitcoincore_rpc::Error> {
+    let hexes: Vec<String> = package.iter().map(|tx| bitcoin::consensus::encode::serialize_hex(*tx)).collect();
+    let results = client.call::<Vec<serde_json::Value>>("testmempoolaccept", &[serde_json::json!(hexes)])?;
+    let mut rejections: Vec<Option<String>> = results.iter()
+        .map(|result| match result["allowed"] == true {
+            true => None,
+            false => Some(
+                result["reject-reason"].as_str()
+                    .or(result["package-error"].as_str())
+                    .unwrap_or("rejected")
+                    .to_string(),
+            ),
+        })
+        .collect();
+
+    let is_fee = |reason: &String| FEE_REJECTIONS.iter().any(|fee| reason.starts_with(fee));
+    let fee_only = rejections.iter().enumerate().all(|(i, rejection)| match rejection {
+        None => true,
+        Some(reason) if is_fee(reason) => true,
+        Some(_) if i == 0 => false,
+        Some(_) => results[i]["reject-reason"].as_str().is_none_or(|reason| reason == "missing-inputs"),
+    });
+    if !fee_only || !rejections.iter().flatten().any(is_fee) {
+        return Ok(rejections);
+    }
+    if let Some(rate) = package_fee_rate(client, package)? {
+        let info = client.call::<serde_json::Value>("getmempoolinfo", &[])?;
+        // In BTC/kvB
+        let min_rate = ["mempoolminfee", "minrelaytxfee"].iter()
+            .filter_map(|field| info[field].as_f64())
+            .fold(0.0, f64::max) * 100_000.0;
+        if rate >= min_rate {
+            rejections.iter_mut().for_each(|rejection| *rejection = None);
+        }
+    }
+    Ok(rejections)
+}
+
+// Fees over vsize across the package, taking input values from earlier transactions of
+// the package or the UTXO set. None if an input can't be found.
+fn package_fee_rate(client: &Client, package: &[&Transaction]) -> Result<Option<f64>, bitcoincore_rpc::Error> {
+    let mut fee: i64 = 0;
+    for (i, tx) in package.iter().enumerate() {
+        for input in &tx.input {
+            let outpoint = input.previous_output;
+            let in_package = package[..i].iter()
+                .find(|earlier| earlier.compute_txid() == outpoint.txid)
+                .and_then(|earlier| earlier.output.get(outpoint.vout as usize))
+                .map(|output| output.value);
+            let value = match in_package {
+                Some(value) => value,
+                None => match client.get_tx_out(&outpoint.txid, outpoint.vout, Some(true))? {
+                    Some(output) => output.value,
+                    None => return Ok(None),
+                },
+            };
+            fee += value.to_sat() as i64;
+        }
+        fee -= tx.output.iter().map(|output| output.value.to_sat() as i64).sum::<i64>();
+    }
+    let vsize: usize = package.iter().map(|tx| tx.vsize()).sum();
+    Ok(Some(fee as f64 / vsize.max(1) as f64))
+}
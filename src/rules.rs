@@ -0,0 +1,167 @@
+use bitcoin::{
+    consensus::encode::serialize_hex,
+    opcodes::all::{OP_PUSHNUM_13, OP_RETURN},
+    script::Instruction,
+    transaction::Transaction,
+    Address, Network, ScriptBuf,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{SearcherError, ValidationError};
+
+// How long a rule command gets before the parent is refused
+const RULE_COMMAND_TIMEOUT_SECS: u64 = 10;
+
+// An operator's own check on a parent, run before it is sponsored or paid for. Library
+// users implement this to add rules; the CLI offers the ones below.
+pub trait ParentRule: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    // Why the parent breaks the rule, if it does
+    fn check(&self, tx: &Transaction) -> Result<(), String>;
+}
+
+pub fn check_rules(rules: &[Arc<dyn ParentRule>], tx: &Transaction) -> Result<(), ValidationError> {
+    for rule in rules {
+        rule.check(tx).map_err(|reason| ValidationError::Rule { rule: rule.name().to_string(), reason })?;
+    }
+    Ok(())
+}
+
+// "destinations=<address>,<address>", "no-op-return" or "command=<path>"
+pub fn parse_rule(spec: &str, network: Network) -> Result<Arc<dyn ParentRule>, SearcherError> {
+    let invalid = |reason: String| SearcherError::Config(format!("invalid rule {}: {}", spec, reason));
+    match spec.split_once('=') {
+        Some(("destinations", addresses)) => {
+            let scripts = addresses.split(',')
+                .map(|address| {
+                    Address::from_str(address)
+                        .and_then(|address| address.require_network(network))
+                        .map(|address| address.script_pubkey())
+                        .map_err(|e| invalid(format!("{}: {}", address, e)))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Arc::new(Destinations { scripts }))
+        }
+        Some(("command", program)) if !program.is_empty() => Ok(Arc::new(RuleCommand { program: program.to_string() })),
+        None if spec == "no-op-return" => Ok(Arc::new(NoOpReturn)),
+        _ => Err(invalid("expected destinations=<addresses>, no-op-return or command=<path>".to_string())),
+    }
+}
+
+// At least one output must pay one of these scripts
+#[derive(Debug)]
+pub struct Destinations {
+    pub scripts: HashSet<ScriptBuf>,
+}
+
+impl ParentRule for Destinations {
+    fn name(&self) -> &str {
+        "destinations"
+    }
+
+    fn check(&self, tx: &Transaction) -> Result<(), String> {
+        match tx.output.iter().any(|output| self.scripts.contains(&output.script_pubkey)) {
+            true => Ok(()),
+            false => Err("no output pays an allowed destination".to_string()),
+        }
+    }
+}
+
+// No OP_RETURN outputs besides a runestone
+#[derive(Debug)]
+pub struct NoOpReturn;
+
+impl ParentRule for NoOpReturn {
+    fn name(&self) -> &str {
+        "no-op-return"
+    }
+
+    fn check(&self, tx: &Transaction) -> Result<(), String> {
+        for (vout, output) in tx.output.iter().enumerate() {
+            let mut instructions = output.script_pubkey.instructions();
+            if instructions.next() == Some(Ok(Instruction::Op(OP_RETURN)))
+                && instructions.next() != Some(Ok(Instruction::Op(OP_PUSHNUM_13)))
+            {
+                return Err(format!("output {} is an OP_RETURN that isn't a runestone", vout));
+            }
+        }
+        Ok(())
+    }
+}
+
+// An external program gets the parent's hex on stdin and accepts it by exiting 0. Any
+// other exit refuses it, with the first line the program printed as the reason.
+#[derive(Debug)]
+pub struct RuleCommand {
+    pub program: String,
+}
+
+impl ParentRule for RuleCommand {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn check(&self, tx: &Transaction) -> Result<(), String> {
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not run: {}", e))?;
+
+        // Writing and reading happen on their own threads, so a program that prints more
+        // than a pipe holds, or doesn't read the parent, can't block past the timeout
+        let stdin = child.stdin.take();
+        let hex = serialize_hex(tx);
+        let writer = std::thread::spawn(move || stdin.map_or(Ok(()), |mut stdin| stdin.write_all(hex.as_bytes())));
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if started.elapsed() > Duration::from_secs(RULE_COMMAND_TIMEOUT_SECS) => {
+                    break Err(format!("no answer within {} seconds", RULE_COMMAND_TIMEOUT_SECS));
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(e) => break Err(e.to_string()),
+            }
+        };
+        if status.is_err() {
+            let _ = child.kill();
+        }
+        // Reaped however it ended, so a long-running searcher doesn't collect zombies
+        let _ = child.wait();
+        if status?.success() {
+            return Ok(());
+        }
+
+        let output = [stdout, stderr].map(|reader| reader.join().unwrap_or_default()).concat();
+        if let Some(line) = output.lines().find(|line| !line.trim().is_empty()) {
+            return Err(line.trim().to_string());
+        }
+        match writer.join() {
+            Ok(Err(e)) => Err(format!("could not write the parent: {}", e)),
+            _ => Err("refused".to_string()),
+        }
+    }
+}
+
+// Everything a pipe gives until it closes
+fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        output
+    })
+}
@@ -0,0 +1,125 @@
+use bitcoin::OutPoint;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::error::{OrdError, SearcherError};
+use crate::http::redact_url;
+use crate::ord::{fetch_output_info, RuneInfo};
+
+// Somewhere to ask which runes an output holds. Payment validation reads confirmations
+// and runes from ord, then asks every other index configured and refuses payments they
+// don't all agree on, so one buggy index can't get a searcher to accept runes that
+// aren't there.
+pub trait RuneIndex: fmt::Debug + Send + Sync {
+    // For logs and errors, so without credentials
+    fn name(&self) -> String;
+
+    // Keyed by spaced rune name, as ord reports them
+    fn runes<'a>(&'a self, http: &'a reqwest::Client, outpoint: &'a OutPoint) -> BoxFuture<'a, Result<HashMap<String, RuneInfo>, OrdError>>;
+}
+
+// "ord:<url>" or "unisat:[<api key>@]<url>"
+pub fn parse_index(spec: &str) -> Result<Arc<dyn RuneIndex>, SearcherError> {
+    // Only the kind, since the rest may hold a key
+    let invalid = || SearcherError::Config(format!(
+        "invalid rune index {}: expected ord:<url> or unisat:[<api key>@]<url>",
+        spec.split(':').next().unwrap_or_default()
+    ));
+    match spec.split_once(':').ok_or_else(invalid)? {
+        ("ord", url) if !url.is_empty() => Ok(Arc::new(OrdServer { url: url.trim_end_matches('/').to_string() })),
+        ("unisat", rest) => {
+            let (api_key, url) = match rest.split_once('@') {
+                Some((api_key, url)) if !api_key.contains("://") => (Some(api_key.to_string()), url),
+                _ => (None, rest),
+            };
+            if url.is_empty() {
+                return Err(invalid());
+            }
+            Ok(Arc::new(Unisat { url: url.trim_end_matches('/').to_string(), api_key }))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+// Rune amounts by name, which is all the indexes have to agree on
+pub fn amounts(runes: &HashMap<String, RuneInfo>) -> BTreeMap<&str, u64> {
+    runes.iter().map(|(name, rune)| (name.as_str(), rune.amount)).collect()
+}
+
+// Another ord server, for checking one ord against another
+#[derive(Debug)]
+pub struct OrdServer {
+    pub url: String,
+}
+
+impl RuneIndex for OrdServer {
+    fn name(&self) -> String {
+        format!("ord {}", redact_url(&self.url))
+    }
+
+    fn runes<'a>(&'a self, http: &'a reqwest::Client, outpoint: &'a OutPoint) -> BoxFuture<'a, Result<HashMap<String, RuneInfo>, OrdError>> {
+        Box::pin(async move { Ok(fetch_output_info(http, &self.url, outpoint).await?.runes) })
+    }
+}
+
+// UniSat's open API, which indexes runes independently of ord
+#[derive(Debug)]
+pub struct Unisat {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnisatResponse<T> {
+    code: i64,
+    msg: String,
+    data: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UnisatBalance {
+    // In the rune's smallest units, as a string since they can outgrow JSON numbers
+    amount: String,
+    spaced_rune: String,
+    divisibility: u8,
+    #[serde(default)]
+    symbol: String,
+}
+
+impl RuneIndex for Unisat {
+    fn name(&self) -> String {
+        format!("unisat {}", redact_url(&self.url))
+    }
+
+    fn runes<'a>(&'a self, http: &'a reqwest::Client, outpoint: &'a OutPoint) -> BoxFuture<'a, Result<HashMap<String, RuneInfo>, OrdError>> {
+        Box::pin(async move {
+            let url = format!("{}/v1/indexer/runes/utxo/{}/{}/balance", self.url, outpoint.txid, outpoint.vout);
+            info!("Fetching rune balance from: {}", redact_url(&url));
+            let mut request = http.get(&url).header("Accept", "application/json");
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(OrdError::Status { url: redact_url(&url), status: response.status() });
+            }
+            let response: UnisatResponse<Vec<UnisatBalance>> = response.json().await?;
+            let balances = match (response.code, response.data) {
+                (0, Some(balances)) => balances,
+                _ => return Err(OrdError::Malformed(format!("unisat returned {}: {}", response.code, response.msg))),
+            };
+            balances.into_iter()
+                .map(|balance| {
+                    let amount = balance.amount.parse()
+                        .map_err(|_| OrdError::Malformed(format!("unisat amount {} for {}", balance.amount, balance.spaced_rune)))?;
+                    Ok((balance.spaced_rune, RuneInfo { amount, divisibility: balance.divisibility, symbol: balance.symbol }))
+                })
+                .collect()
+        })
+    }
+}
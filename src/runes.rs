@@ -0,0 +1,299 @@
+use bitcoin::{
+    psbt::{raw::ProprietaryKey, Psbt},
+    transaction::{OutPoint, Transaction},
+    Network,
+};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::error::{AmountError, OrdError, ValidationError};
+use crate::inscriptions::output_for_offset;
+use crate::ord::{fetch_inscriptions, fetch_outputs_info, fetch_rune_id, fetch_utxo_info, Utxo};
+use crate::rune_index::{self, RuneIndex};
+use crate::runestone;
+
+// Module-level constant for the rune we're working with
+pub const RUNE_NAME: &str = "TESTSLUGLINERUNE";
+
+// An amount of a rune, counted in its smallest units but read and written with the
+// rune's divisibility, so 1.5 of a rune with divisibility 2 is 150 units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuneAmount {
+    pub units: u64,
+    pub divisibility: u8,
+}
+
+impl RuneAmount {
+    pub fn new(units: u64, divisibility: u8) -> Self {
+        RuneAmount { units, divisibility }
+    }
+
+    // A decimal like "1.5". Places past the divisibility are refused rather than rounded,
+    // since either way of rounding a payment surprises someone.
+    pub fn parse(amount: &str, divisibility: u8) -> Result<Self, AmountError> {
+        let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(AmountError::Invalid(amount.to_string()));
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > usize::from(divisibility) {
+            return Err(AmountError::TooPrecise { amount: amount.to_string(), divisibility });
+        }
+
+        let overflow = || AmountError::Overflow(amount.to_string());
+        let scale = 10u128.checked_pow(divisibility.into()).ok_or_else(overflow)?;
+        let whole = match whole {
+            "" => 0,
+            whole => whole.parse::<u128>().map_err(|_| overflow())?,
+        };
+        let fraction = match fraction {
+            "" => 0,
+            fraction => format!("{:0<width$}", fraction, width = usize::from(divisibility))
+                .parse::<u128>()
+                .map_err(|_| overflow())?,
+        };
+        let units = whole.checked_mul(scale).and_then(|units| units.checked_add(fraction)).ok_or_else(overflow)?;
+        Ok(RuneAmount::new(u64::try_from(units).map_err(|_| overflow())?, divisibility))
+    }
+
+    // A computed number of units, like a price per vbyte times a size, rounded up so a
+    // quote never asks for less than the price
+    pub fn from_units_ceil(units: f64, divisibility: u8) -> Self {
+        RuneAmount::new(units.ceil() as u64, divisibility)
+    }
+}
+
+impl fmt::Display for RuneAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(scale) = 10u128.checked_pow(self.divisibility.into()) else {
+            return write!(f, "{}", self.units);
+        };
+        let units = u128::from(self.units);
+        write!(f, "{}", units / scale)?;
+        if units % scale > 0 {
+            let fraction = format!("{:0>width$}", units % scale, width = usize::from(self.divisibility));
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+// PSBT inputs paying the searcher carry a proprietary "slugline" field whose value is
+// the version of this convention, so build-tx and searchers agree on which inputs pay
+const MARKER_PREFIX: &[u8] = b"slugline";
+const MARKER_SUBTYPE_PAYMENT: u8 = 0;
+pub const PAYMENT_MARKER_VERSION: u8 = 1;
+
+fn payment_marker() -> ProprietaryKey {
+    ProprietaryKey { prefix: MARKER_PREFIX.to_vec(), subtype: MARKER_SUBTYPE_PAYMENT, key: Vec::new() }
+}
+
+pub fn mark_payment_input(psbt: &mut Psbt, index: usize) {
+    if let Some(input) = psbt.inputs.get_mut(index) {
+        input.proprietary.insert(payment_marker(), vec![PAYMENT_MARKER_VERSION]);
+    }
+}
+
+// Which inputs pay: the ones the submitter declared, else the ones marked in the PSBT,
+// else the last input as before there were markers
+pub fn payment_inputs(psbt: &Psbt, declared: Option<&[usize]>) -> Result<Vec<usize>, ValidationError> {
+    let inputs = psbt.unsigned_tx.input.len();
+    let mut payment_inputs = match declared {
+        Some(declared) => declared.to_vec(),
+        None => {
+            let mut marked = Vec::new();
+            for (index, input) in psbt.inputs.iter().enumerate() {
+                match input.proprietary.get(&payment_marker()).map(Vec::as_slice) {
+                    None => {}
+                    Some([PAYMENT_MARKER_VERSION]) => marked.push(index),
+                    Some(version) => {
+                        return Err(ValidationError::PaymentInput(format!(
+                            "input {} is marked with unsupported version {:?}",
+                            index, version,
+                        )));
+                    }
+                }
+            }
+            marked
+        }
+    };
+    if payment_inputs.is_empty() {
+        payment_inputs.extend(inputs.checked_sub(1));
+    }
+    payment_inputs.sort_unstable();
+    payment_inputs.dedup();
+    if let Some(index) = payment_inputs.iter().find(|index| **index >= inputs) {
+        return Err(ValidationError::PaymentInput(format!("input {} does not exist", index)));
+    }
+    Ok(payment_inputs)
+}
+
+// Transactions seen without a PSBT, like those in the mempool, pay with their last input
+pub fn last_input(tx: &Transaction) -> Vec<usize> {
+    tx.input.len().checked_sub(1).into_iter().collect()
+}
+
+// Something wrong with one of the parent's inputs
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputFinding {
+    pub input: usize,
+    pub outpoint: String,
+    pub problem: String,
+}
+
+// Payment inputs have to come from transactions with at least `min_confirmations`, so a
+// submitter can't pay with runes from a transaction that is later replaced
+pub async fn validate_rune_input(
+    http: &reqwest::Client,
+    tx: &Transaction,
+    payment_inputs: &[usize],
+    min_confirmations: u32,
+    network: Network,
+    ord_server: &str,
+    indexes: &[Arc<dyn RuneIndex>],
+) -> Result<RuneAmount, ValidationError> {
+    if tx.input.is_empty() {
+        return Err(ValidationError::NoInputs);
+    }
+
+    // Every payment input has to carry the rune
+    let outpoints: Vec<OutPoint> = payment_inputs.iter()
+        .filter_map(|index| tx.input.get(*index))
+        .map(|input| input.previous_output)
+        .collect();
+    let lookups = outpoints.iter().map(|outpoint| fetch_utxo_info(http, outpoint, network, ord_server));
+    let utxos = try_join_all(lookups).await?;
+    cross_check(http, &outpoints, &utxos, indexes).await?;
+    let mut amount = RuneAmount::new(0, 0);
+    for utxo_info in utxos {
+        // ord only indexes blocks, so runes in an unconfirmed output would look missing
+        if utxo_info.confirmations < min_confirmations {
+            return Err(ValidationError::UnconfirmedPayment {
+                outpoint: utxo_info.outpoint,
+                confirmations: utxo_info.confirmations,
+                min: min_confirmations,
+            });
+        }
+        // The amount the input carries, which is what the submitter pays
+        let rune = utxo_info.runes.get(RUNE_NAME)
+            .ok_or_else(|| ValidationError::MissingRune(RUNE_NAME.to_string()))?;
+        amount = RuneAmount::new(amount.units + rune.amount, rune.divisibility);
+    }
+    if amount.units == 0 {
+        return Err(ValidationError::MissingRune(RUNE_NAME.to_string()));
+    }
+
+    // A cenotaph burns the runes the parent spends instead of passing them to the anchor
+    if let Some(flaw) = runestone::cenotaph(tx) {
+        return Err(ValidationError::Cenotaph(flaw));
+    }
+
+    // A valid runestone routes the runes itself, and could send the payment elsewhere with
+    // an edict or pointer. Only the payment inputs' runes are counted, which can only make
+    // less of it look like it reaches the anchor.
+    if let Some(runestone) = runestone::runestone(tx) {
+        let id = match runestone.edicts.is_empty() {
+            true => None,
+            false => Some(fetch_rune_id(http, ord_server, RUNE_NAME).await?),
+        };
+        let anchored = runestone.allocated(tx, id, amount.units.into(), 0);
+        if anchored < u128::from(amount.units) {
+            return Err(ValidationError::MisroutedPayment {
+                anchored: RuneAmount::new(anchored as u64, amount.divisibility),
+                paid: amount,
+            });
+        }
+    }
+
+    let findings = check_inputs(http, tx, payment_inputs, ord_server).await?;
+    if !findings.is_empty() {
+        return Err(ValidationError::Inputs(findings));
+    }
+
+    Ok(amount)
+}
+
+// Every other index has to see the same runes in the payment inputs as ord does. One that
+// can't be reached fails validation like ord would, since the point is not to trust ord alone.
+async fn cross_check(
+    http: &reqwest::Client,
+    outpoints: &[OutPoint],
+    utxos: &[Utxo],
+    indexes: &[Arc<dyn RuneIndex>],
+) -> Result<(), ValidationError> {
+    for index in indexes {
+        for (outpoint, utxo) in outpoints.iter().zip(utxos) {
+            let runes = index.runes(http, outpoint).await?;
+            if rune_index::amounts(&runes) != rune_index::amounts(&utxo.runes) {
+                error!(
+                    "ord and {} disagree about the runes in {}: {:?} and {:?}",
+                    index.name(),
+                    outpoint,
+                    rune_index::amounts(&utxo.runes),
+                    rune_index::amounts(&runes)
+                );
+                return Err(ValidationError::IndexesDisagree { outpoint: outpoint.to_string(), index: index.name() });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Look at every input for assets the submitter would lose. Without a runestone all of a
+// parent's runes go to its first output, the anchor, along with the payment. Inscriptions
+// follow their sats, and sats past the last output go to the miner.
+pub async fn check_inputs(
+    http: &reqwest::Client,
+    tx: &Transaction,
+    payment_inputs: &[usize],
+    ord_server: &str,
+) -> Result<Vec<InputFinding>, OrdError> {
+    let outpoints: Vec<_> = tx.input.iter().map(|input| input.previous_output).collect();
+    let outputs = fetch_outputs_info(http, ord_server, &outpoints).await?;
+    let inscription_ids: Vec<String> = outputs.iter().flat_map(|output| output.inscriptions.iter().cloned()).collect();
+    let mut inscriptions = fetch_inscriptions(http, ord_server, &inscription_ids).await?.into_iter();
+    let routes_runes = runestone::has_runestone(tx);
+
+    let mut findings = Vec::new();
+    let mut input_offset = 0;
+    for (input, output) in outputs.iter().enumerate() {
+        let mut finding = |problem: String| findings.push(InputFinding {
+            input,
+            outpoint: output.outpoint.clone(),
+            problem,
+        });
+
+        if !routes_runes {
+            let mut runes: Vec<_> = output.runes.iter()
+                .filter(|(name, _)| !payment_inputs.contains(&input) || name.as_str() != RUNE_NAME)
+                .collect();
+            runes.sort_by_key(|(name, _)| name.as_str());
+            for (name, rune) in runes {
+                finding(format!(
+                    "carries {} {}, which would go to the anchor with the payment",
+                    RuneAmount::new(rune.amount, rune.divisibility),
+                    name
+                ));
+            }
+        }
+
+        for inscription in inscriptions.by_ref().take(output.inscriptions.len()) {
+            let offset: u64 = inscription.satpoint
+                .rsplit(':')
+                .next()
+                .and_then(|offset| offset.parse().ok())
+                .ok_or_else(|| OrdError::Malformed(format!("Invalid satpoint: {}", inscription.satpoint)))?;
+            if output_for_offset(tx, input_offset + offset).is_none() {
+                finding(format!("carries inscription {}, which would be lost to fees", inscription.id));
+            }
+        }
+
+        input_offset += output.value;
+    }
+    Ok(findings)
+}
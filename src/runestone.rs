@@ -0,0 +1,328 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use bitcoin::{
+    opcodes::all::{OP_PUSHNUM_13, OP_RETURN},
+    script::Instruction,
+    transaction::Transaction,
+};
+
+// Runestone tags and flags, as numbered by the runes protocol
+const TAG_BODY: u128 = 0;
+const TAG_FLAGS: u128 = 2;
+const TAG_RUNE: u128 = 4;
+const TAG_PREMINE: u128 = 6;
+const TAG_CAP: u128 = 8;
+const TAG_AMOUNT: u128 = 10;
+const TAG_HEIGHT_START: u128 = 12;
+const TAG_HEIGHT_END: u128 = 14;
+const TAG_OFFSET_START: u128 = 16;
+const TAG_OFFSET_END: u128 = 18;
+const TAG_MINT: u128 = 20;
+const TAG_POINTER: u128 = 22;
+
+const FLAG_ETCHING: u32 = 0;
+const FLAG_TERMS: u32 = 1;
+const FLAG_TURBO: u32 = 2;
+
+// Why a runestone is a cenotaph. A cenotaph burns every rune the transaction spends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flaw {
+    EdictOutput,
+    EdictRuneId,
+    InvalidScript,
+    Opcode,
+    SupplyOverflow,
+    TrailingIntegers,
+    TruncatedField,
+    UnrecognizedEvenTag,
+    UnrecognizedFlag,
+    Varint,
+}
+
+impl fmt::Display for Flaw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Flaw::EdictOutput => "edict output greater than transaction output count",
+            Flaw::EdictRuneId => "invalid rune ID in edict",
+            Flaw::InvalidScript => "invalid script in OP_RETURN",
+            Flaw::Opcode => "non-pushdata opcode in OP_RETURN",
+            Flaw::SupplyOverflow => "supply overflows u128",
+            Flaw::TrailingIntegers => "trailing integers in body",
+            Flaw::TruncatedField => "field with missing value",
+            Flaw::UnrecognizedEvenTag => "unrecognized even tag",
+            Flaw::UnrecognizedFlag => "unrecognized flag",
+            Flaw::Varint => "invalid varint",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+// A rune ID as block and transaction index
+pub type RuneId = (u64, u32);
+
+// Moves `amount` of a rune to an output. An amount of 0 moves all that's left, and an
+// output equal to the transaction's output count splits the runes across its outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edict {
+    pub id: RuneId,
+    pub amount: u128,
+    pub output: u32,
+}
+
+// The parts of a valid runestone that say where the runes a transaction spends go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Runestone {
+    pub edicts: Vec<Edict>,
+    pub pointer: Option<u32>,
+}
+
+impl Runestone {
+    // How much of `balance`, the units of rune `id` the transaction spends, lands on
+    // `output`, following ord: edicts in order, then what's left to the pointer or the
+    // first output that isn't an OP_RETURN. Runes minted or etched here aren't counted.
+    pub fn allocated(&self, tx: &Transaction, id: Option<RuneId>, mut balance: u128, output: usize) -> u128 {
+        let destinations: Vec<usize> = tx.output.iter()
+            .enumerate()
+            .filter(|(_, out)| !out.script_pubkey.is_op_return())
+            .map(|(index, _)| index)
+            .collect();
+        let mut allocated = 0;
+        let mut allocate = |balance: &mut u128, amount: u128, to: usize| {
+            *balance -= amount;
+            if to == output {
+                allocated += amount;
+            }
+        };
+
+        for edict in self.edicts.iter().filter(|edict| Some(edict.id) == id) {
+            let to = edict.output as usize;
+            if to != tx.output.len() {
+                let amount = if edict.amount == 0 { balance } else { edict.amount.min(balance) };
+                allocate(&mut balance, amount, to);
+            } else if !destinations.is_empty() {
+                let count = destinations.len() as u128;
+                let (share, remainder) = (balance / count, balance % count);
+                for (i, to) in destinations.iter().enumerate() {
+                    let amount = match edict.amount {
+                        0 if (i as u128) < remainder => share + 1,
+                        0 => share,
+                        amount => amount.min(balance),
+                    };
+                    allocate(&mut balance, amount, *to);
+                }
+            }
+        }
+
+        let rest = self.pointer.map(|pointer| pointer as usize).or(destinations.first().copied());
+        if rest == Some(output) {
+            allocated += balance;
+        }
+        allocated
+    }
+}
+
+// The first flaw that makes the transaction's runestone a cenotaph, or None if it has
+// no runestone or a valid one
+pub fn cenotaph(tx: &Transaction) -> Option<Flaw> {
+    decipher(tx)?.err()
+}
+
+// The transaction's runestone, if it has one and it isn't a cenotaph
+pub fn runestone(tx: &Transaction) -> Option<Runestone> {
+    decipher(tx)?.ok()
+}
+
+fn decipher(tx: &Transaction) -> Option<Result<Runestone, Flaw>> {
+    let payload = match payload(tx)? {
+        Ok(payload) => payload,
+        Err(flaw) => return Some(Err(flaw)),
+    };
+    let Some(integers) = integers(&payload) else {
+        return Some(Err(Flaw::Varint));
+    };
+
+    let (mut flaws, edicts, mut fields) = message(tx, &integers);
+
+    let mut flags = take(&mut fields, TAG_FLAGS, 1, |_| Some(())).map(|values| values[0]).unwrap_or_default();
+    if take_flag(&mut flags, FLAG_ETCHING) {
+        let premine = take(&mut fields, TAG_PREMINE, 1, |_| Some(())).map(|values| values[0]);
+        take(&mut fields, TAG_RUNE, 1, |_| Some(()));
+        if take_flag(&mut flags, FLAG_TERMS) {
+            let cap = take(&mut fields, TAG_CAP, 1, |_| Some(())).map(|values| values[0]);
+            let amount = take(&mut fields, TAG_AMOUNT, 1, |_| Some(())).map(|values| values[0]);
+            for tag in [TAG_HEIGHT_START, TAG_HEIGHT_END, TAG_OFFSET_START, TAG_OFFSET_END] {
+                take(&mut fields, tag, 1, |values| u64::try_from(values[0]).ok().map(|_| ()));
+            }
+            let supply = cap.unwrap_or_default()
+                .checked_mul(amount.unwrap_or_default())
+                .and_then(|minted| minted.checked_add(premine.unwrap_or_default()));
+            if supply.is_none() {
+                flaws.push(Flaw::SupplyOverflow);
+            }
+        }
+        take_flag(&mut flags, FLAG_TURBO);
+    }
+    take(&mut fields, TAG_MINT, 2, |values| {
+        rune_id(u64::try_from(values[0]).ok()?, u32::try_from(values[1]).ok()?).map(|_| ())
+    });
+    let pointer = take(&mut fields, TAG_POINTER, 1, |values| {
+        (values[0] < tx.output.len() as u128).then_some(())
+    });
+
+    if flags != 0 {
+        flaws.push(Flaw::UnrecognizedFlag);
+    }
+    // Odd tags may be ignored, even tags must be understood
+    if fields.keys().any(|tag| tag % 2 == 0) {
+        flaws.push(Flaw::UnrecognizedEvenTag);
+    }
+
+    match flaws.into_iter().next() {
+        Some(flaw) => Some(Err(flaw)),
+        None => Some(Ok(Runestone { edicts, pointer: pointer.map(|values| values[0] as u32) })),
+    }
+}
+
+// Whether the transaction carries a runestone at all, valid or not
+pub fn has_runestone(tx: &Transaction) -> bool {
+    payload(tx).is_some()
+}
+
+// The data pushed after OP_RETURN OP_13 in the first output carrying it
+fn payload(tx: &Transaction) -> Option<Result<Vec<u8>, Flaw>> {
+    for output in &tx.output {
+        let mut instructions = output.script_pubkey.instructions();
+        if instructions.next() != Some(Ok(Instruction::Op(OP_RETURN))) {
+            continue;
+        }
+        if instructions.next() != Some(Ok(Instruction::Op(OP_PUSHNUM_13))) {
+            continue;
+        }
+
+        let mut payload = Vec::new();
+        for instruction in instructions {
+            match instruction {
+                Ok(Instruction::PushBytes(push)) => payload.extend_from_slice(push.as_bytes()),
+                Ok(Instruction::Op(_)) => return Some(Err(Flaw::Opcode)),
+                Err(_) => return Some(Err(Flaw::InvalidScript)),
+            }
+        }
+        return Some(Ok(payload));
+    }
+    None
+}
+
+// LEB128 integers, None if one is unterminated or doesn't fit in a u128
+fn integers(payload: &[u8]) -> Option<Vec<u128>> {
+    let mut integers = Vec::new();
+    let mut i = 0;
+    while i < payload.len() {
+        let (value, length) = varint(&payload[i..])?;
+        integers.push(value);
+        i += length;
+    }
+    Some(integers)
+}
+
+fn varint(buffer: &[u8]) -> Option<(u128, usize)> {
+    let mut n = 0u128;
+    for (i, &byte) in buffer.iter().enumerate() {
+        if i > 18 {
+            return None;
+        }
+        let value = u128::from(byte & 0b0111_1111);
+        if i == 18 && value & 0b0111_1100 != 0 {
+            return None;
+        }
+        n |= value << (7 * i);
+        if byte & 0b1000_0000 == 0 {
+            return Some((n, i + 1));
+        }
+    }
+    None
+}
+
+// Split the integers into tagged fields and check the edicts in the body
+fn message(tx: &Transaction, integers: &[u128]) -> (Vec<Flaw>, Vec<Edict>, HashMap<u128, VecDeque<u128>>) {
+    let mut flaws = Vec::new();
+    let mut edicts = Vec::new();
+    let mut fields: HashMap<u128, VecDeque<u128>> = HashMap::new();
+
+    for i in (0..integers.len()).step_by(2) {
+        let tag = integers[i];
+        if tag == TAG_BODY {
+            let (mut block, mut index) = (0u64, 0u32);
+            for edict in integers[i + 1..].chunks(4) {
+                if edict.len() != 4 {
+                    flaws.push(Flaw::TrailingIntegers);
+                    break;
+                }
+                // Rune ids are delta-encoded against the previous edict
+                let Some(next) = next_rune_id(block, index, edict[0], edict[1]) else {
+                    flaws.push(Flaw::EdictRuneId);
+                    break;
+                };
+                // An output equal to the count splits the runes across all outputs
+                if edict[3] > tx.output.len() as u128 {
+                    flaws.push(Flaw::EdictOutput);
+                    break;
+                }
+                edicts.push(Edict { id: next, amount: edict[2], output: edict[3] as u32 });
+                (block, index) = next;
+            }
+            break;
+        }
+
+        let Some(&value) = integers.get(i + 1) else {
+            flaws.push(Flaw::TruncatedField);
+            break;
+        };
+        fields.entry(tag).or_default().push_back(value);
+    }
+
+    (flaws, edicts, fields)
+}
+
+// ord's RuneId::new: block 0 only has the etching-less ID 0:0, so 0:1 and up are invalid
+fn rune_id(block: u64, index: u32) -> Option<(u64, u32)> {
+    (block > 0 || index == 0).then_some((block, index))
+}
+
+fn next_rune_id(block: u64, index: u32, block_delta: u128, index_delta: u128) -> Option<(u64, u32)> {
+    let next_block = block.checked_add(u64::try_from(block_delta).ok()?)?;
+    let next_index = if block_delta == 0 {
+        index.checked_add(u32::try_from(index_delta).ok()?)?
+    } else {
+        u32::try_from(index_delta).ok()?
+    };
+    rune_id(next_block, next_index)
+}
+
+// Remove a field's first `count` values if they're present and `valid` accepts them.
+// Fields left behind are what decide whether an even tag went unrecognized.
+fn take(
+    fields: &mut HashMap<u128, VecDeque<u128>>,
+    tag: u128,
+    count: usize,
+    valid: impl Fn(&[u128]) -> Option<()>,
+) -> Option<Vec<u128>> {
+    let queue = fields.get_mut(&tag)?;
+    if queue.len() < count {
+        return None;
+    }
+    let values: Vec<u128> = queue.iter().take(count).copied().collect();
+    valid(&values)?;
+    queue.drain(..count);
+    if queue.is_empty() {
+        fields.remove(&tag);
+    }
+    Some(values)
+}
+
+fn take_flag(flags: &mut u128, flag: u32) -> bool {
+    let mask = 1u128 << flag;
+    let set = *flags & mask != 0;
+    *flags &= !mask;
+    set
+}
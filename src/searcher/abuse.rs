@@ -0,0 +1,172 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use bitcoin::Txid;
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+use crate::error::SearcherError;
+
+// Too few quotes to judge a client's ratio by
+const MIN_QUOTES_JUDGED: u64 = 20;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// When a client counts as abusing the API, and what to do about it
+#[derive(Debug, Clone, Default)]
+pub struct AbuseConfig {
+    // Quotes and dry runs per submission before a client is flagged
+    pub max_quote_ratio: Option<f64>,
+    // Submissions refused for the client's own fault before it is flagged
+    pub max_invalid: Option<u64>,
+    pub window_secs: u64,
+    // Ban flagged clients for this long, or only log them
+    pub ban_secs: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Activity {
+    window_start: u64,
+    quotes: u64,
+    submissions: u64,
+    invalid: u64,
+    banned_until: Option<u64>,
+}
+
+// Per-client counts, keyed by IP address since the API has no accounts
+#[derive(Debug)]
+pub struct AbuseTracker {
+    config: AbuseConfig,
+    clients: Mutex<HashMap<IpAddr, Activity>>,
+    // Who asked for a quote for each parent, until the quote expires
+    quoted: Mutex<HashMap<Txid, (IpAddr, u64)>>,
+    pub flagged: AtomicU64,
+}
+
+impl AbuseTracker {
+    pub fn new(config: AbuseConfig) -> Self {
+        AbuseTracker {
+            config,
+            clients: Mutex::new(HashMap::new()),
+            quoted: Mutex::new(HashMap::new()),
+            flagged: AtomicU64::new(0),
+        }
+    }
+
+    // When the client's ban ends, if it is banned
+    pub fn banned_until(&self, client: IpAddr, now: u64) -> Option<u64> {
+        let clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients.get(&client)?.banned_until.filter(|until| *until > now)
+    }
+
+    pub fn record_quote(&self, client: IpAddr, now: u64) {
+        self.record(client, now, |activity| activity.quotes += 1);
+    }
+
+    // Submissions refused with a 4xx were the client's fault: a bad PSBT, a missing
+    // payment, a parent that was already anchored
+    pub fn record_submission(&self, client: IpAddr, now: u64, invalid: bool) {
+        self.record(client, now, |activity| {
+            activity.submissions += 1;
+            activity.invalid += u64::from(invalid);
+        });
+    }
+
+    fn record(&self, client: IpAddr, now: u64, change: impl FnOnce(&mut Activity)) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget clients whose window ran out and who aren't banned
+        clients.retain(|_, activity| {
+            activity.window_start + self.config.window_secs > now || activity.banned_until.is_some_and(|until| until > now)
+        });
+        let activity = clients.entry(client).or_insert_with(|| Activity { window_start: now, ..Default::default() });
+        if activity.window_start + self.config.window_secs <= now {
+            *activity = Activity { window_start: now, banned_until: activity.banned_until, ..Default::default() };
+        }
+        change(activity);
+
+        let Some(reason) = self.abuse(activity) else {
+            return;
+        };
+        self.flagged.fetch_add(1, Ordering::Relaxed);
+        match self.config.ban_secs {
+            Some(ban_secs) => {
+                error!("Banning {} for {} seconds: {}", client, ban_secs, reason);
+                *activity = Activity { window_start: now, banned_until: Some(now + ban_secs), ..Default::default() };
+            }
+            None => {
+                error!("Flagging {}: {}", client, reason);
+                // Start over so the same activity isn't flagged on every request
+                *activity = Activity { window_start: now, ..Default::default() };
+            }
+        }
+    }
+
+    fn abuse(&self, activity: &Activity) -> Option<String> {
+        if let Some(max) = self.config.max_invalid
+            && activity.invalid >= max
+        {
+            return Some(format!("{} invalid submissions", activity.invalid));
+        }
+        let ratio = activity.quotes as f64 / activity.submissions.max(1) as f64;
+        if let Some(max) = self.config.max_quote_ratio
+            && activity.quotes >= MIN_QUOTES_JUDGED
+            && ratio > max
+        {
+            return Some(format!("{} quotes for {} submissions", activity.quotes, activity.submissions));
+        }
+        None
+    }
+
+    pub fn record_quoted_parent(&self, parent: Txid, client: IpAddr, expires_at: u64) {
+        let mut quoted = self.quoted.lock().unwrap_or_else(|e| e.into_inner());
+        quoted.retain(|_, (_, expires_at)| *expires_at > now());
+        quoted.insert(parent, (client, expires_at));
+    }
+
+    // The client that was quoted for this parent, to record on its job
+    pub fn quoted_by(&self, parent: Txid) -> Option<IpAddr> {
+        let quoted = self.quoted.lock().unwrap_or_else(|e| e.into_inner());
+        quoted.get(&parent).map(|(client, _)| *client)
+    }
+}
+
+// Refuse banned clients, then count what the request did once it has been answered.
+// Requests without a peer address, like ones from tests, pass untracked.
+pub async fn guard(
+    State(tracker): State<Arc<AbuseTracker>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ConnectInfo(peer)) = connect_info else {
+        return next.run(request).await;
+    };
+    let client = peer.ip();
+    if let Some(until) = tracker.banned_until(client, now()) {
+        info!("Refusing banned client {}", client);
+        let e = SearcherError::Banned { until };
+        return (e.status_code(), Json(json!({ "success": false, "message": e.to_string() }))).into_response();
+    }
+
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    let status = response.status();
+    match path.as_str() {
+        "/quote" | "/validate-psbt" if status.is_success() => tracker.record_quote(client, now()),
+        "/submit-psbt" | "/payjoin" | "/rescue" => {
+            tracker.record_submission(client, now(), status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS);
+        }
+        _ => {}
+    }
+    response
+}
@@ -0,0 +1,151 @@
+use bitcoin::hashes::{sha256, Hash};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+use crate::error::AuditError;
+
+use super::jobs::{Job, JobKind, JobStatus, Payment};
+
+// What the first entry follows
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// A decision about a job, as it was made
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub at: u64,
+    pub job: u64,
+    pub kind: JobKind,
+    // accepted, rejected, cancelled or confirmed
+    pub decision: String,
+    pub parent_txid: Option<String>,
+    pub child_txid: Option<String>,
+    pub payment: Option<Payment>,
+    pub fee_sat: Option<u64>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+// A record chained to the entry before it: `hash` covers the previous hash and the
+// record, so changing, dropping or reordering a line breaks every hash after it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditEntry {
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    // The decision a job's new status records, if it's one worth recording
+    pub fn for_job(job: &Job) -> Option<Self> {
+        let decision = match job.status {
+            JobStatus::Pending => return None,
+            JobStatus::Submitted => "accepted",
+            JobStatus::Failed => "rejected",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Confirmed => "confirmed",
+        };
+        Some(AuditRecord {
+            seq: 0,
+            at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            job: job.id,
+            kind: job.kind,
+            decision: decision.to_string(),
+            parent_txid: job.parent_txid.clone(),
+            child_txid: job.child_txid.clone(),
+            payment: job.payment.clone(),
+            fee_sat: job.fee_sat,
+            reason: job.failure_reason.clone(),
+            message: job.failure_message.clone(),
+        })
+    }
+}
+
+fn entry_hash(prev_hash: &str, record: &AuditRecord) -> Result<String, serde_json::Error> {
+    let record = serde_json::to_string(record)?;
+    Ok(sha256::Hash::hash(format!("{}{}", prev_hash, record).as_bytes()).to_string())
+}
+
+// How far a log checks out: its length and the hash of its last entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedLog {
+    pub entries: u64,
+    pub head: String,
+}
+
+// Checks every entry follows the one before and still hashes to what was written, and
+// that a hash noted from an earlier check is still in the chain
+pub fn verify(path: &Path, checkpoint: Option<&str>) -> Result<VerifiedLog, AuditError> {
+    let mut log = VerifiedLog { entries: 0, head: GENESIS_HASH.to_string() };
+    let mut found = checkpoint.is_none_or(|checkpoint| checkpoint == GENESIS_HASH);
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line_number = i + 1;
+        let entry: AuditEntry = serde_json::from_str(&line?)
+            .map_err(|e| AuditError::Malformed { line: line_number, message: e.to_string() })?;
+        if entry.prev_hash != log.head || entry.record.seq != log.entries + 1 {
+            return Err(AuditError::BrokenChain { line: line_number });
+        }
+        let hash = entry_hash(&entry.prev_hash, &entry.record)
+            .map_err(|e| AuditError::Malformed { line: line_number, message: e.to_string() })?;
+        if hash != entry.hash {
+            return Err(AuditError::AlteredEntry { line: line_number });
+        }
+        found |= checkpoint == Some(entry.hash.as_str());
+        log = VerifiedLog { entries: entry.record.seq, head: entry.hash };
+    }
+    match found {
+        true => Ok(log),
+        false => Err(AuditError::MissingCheckpoint(checkpoint.unwrap_or_default().to_string())),
+    }
+}
+
+// An append-only log of the searcher's decisions. Each entry is synced to disk before
+// the next is written.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    head: Mutex<VerifiedLog>,
+}
+
+impl AuditLog {
+    // Refuses to carry on from a log that doesn't verify, so a broken chain is noticed
+    // rather than built on
+    pub fn open(path: PathBuf) -> Result<Self, AuditError> {
+        // A new log starts from the genesis hash
+        let head = match path.exists() {
+            true => verify(&path, None)?,
+            false => VerifiedLog { entries: 0, head: GENESIS_HASH.to_string() },
+        };
+        Ok(AuditLog { path, head: Mutex::new(head) })
+    }
+
+    pub fn head(&self) -> VerifiedLog {
+        self.head.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // Losing an entry shouldn't fail the job it describes, but it is logged loudly
+    pub fn record(&self, mut record: AuditRecord) {
+        let mut head = self.head.lock().unwrap_or_else(|e| e.into_inner());
+        record.seq = head.entries + 1;
+        let job = record.job;
+        let written = entry_hash(&head.head, &record)
+            .map_err(std::io::Error::other)
+            .and_then(|hash| {
+                let entry = AuditEntry { record, prev_hash: head.head.clone(), hash };
+                let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+                writeln!(file, "{}", serde_json::to_string(&entry).map_err(std::io::Error::other)?)?;
+                file.sync_data()?;
+                Ok(entry)
+            });
+        match written {
+            Ok(entry) => *head = VerifiedLog { entries: entry.record.seq, head: entry.hash },
+            Err(e) => error!("Failed to write audit entry for job {} to {}: {}", job, self.path.display(), e),
+        }
+    }
+}
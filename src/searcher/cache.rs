@@ -0,0 +1,138 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+// Largest request body looked at for the cache key, the same as axum's default limit
+const MAX_KEY_BODY: usize = 2 * 1024 * 1024;
+// Past this many live entries new responses aren't cached, so varied requests can't grow it without bound
+const MAX_ENTRIES: usize = 1024;
+
+#[derive(Debug, Clone)]
+struct Cached {
+    at: Instant,
+    headers: HeaderMap,
+    body: Bytes,
+    etag: String,
+}
+
+// Successful responses of one endpoint, by method, URI and request body, reused for `ttl`
+// so pollers don't have the searcher recompute them. `public` says whether shared caches
+// like CDNs may keep them too; private ones are also keyed by client address.
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    public: bool,
+    entries: Mutex<HashMap<sha256::Hash, Cached>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration, public: bool) -> Arc<Self> {
+        Arc::new(ResponseCache { ttl, public, entries: Mutex::new(HashMap::new()) })
+    }
+
+    fn cache_control(&self) -> HeaderValue {
+        let scope = if self.public { "public" } else { "private" };
+        HeaderValue::from_str(&format!("{}, max-age={}", scope, self.ttl.as_secs()))
+            .unwrap_or(HeaderValue::from_static("no-cache"))
+    }
+
+    fn get(&self, key: &sha256::Hash) -> Option<Cached> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.get(key).filter(|cached| cached.at.elapsed() < self.ttl).cloned()
+    }
+
+    fn insert(&self, key: sha256::Hash, cached: Cached) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, cached| cached.at.elapsed() < self.ttl);
+        if entries.len() < MAX_ENTRIES {
+            entries.insert(key, cached);
+        }
+    }
+}
+
+fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(&sha256::Hash::hash(body)[..16]))
+}
+
+// If-None-Match may list several tags, weak or not, or "*"
+fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers.get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+fn respond(cache: &ResponseCache, request_headers: &HeaderMap, cached: Cached) -> Response {
+    let mut response = match matches(request_headers, &cached.etag) {
+        true => StatusCode::NOT_MODIFIED.into_response(),
+        false => {
+            let mut response = Response::new(Body::from(cached.body));
+            *response.headers_mut() = cached.headers;
+            response
+        }
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, cache.cache_control());
+    if let Ok(etag) = HeaderValue::from_str(&cached.etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    response
+}
+
+// Middleware answering from the cache when it can, with an ETag so clients polling with
+// If-None-Match get a bodiless 304 when nothing changed. Handlers opt a response out of
+// caching with `Cache-Control: no-store`, and only 200s are kept.
+pub async fn cache(State(cache): State<Arc<ResponseCache>>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_KEY_BODY).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let mut engine = sha256::Hash::engine();
+    engine.input(format!("{}\n{}\n", parts.method, parts.uri).as_bytes());
+    engine.input(&body);
+    if !cache.public
+        && let Some(ConnectInfo(peer)) = parts.extensions.get::<ConnectInfo<SocketAddr>>()
+    {
+        engine.input(peer.ip().to_string().as_bytes());
+    }
+    let key = sha256::Hash::from_engine(engine);
+    let request_headers = parts.headers.clone();
+
+    if let Some(cached) = cache.get(&key) {
+        return respond(&cache, &request_headers, cached);
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let no_store = response.headers()
+        .get(header::CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("no-store"));
+    if response.status() != StatusCode::OK || no_store {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to buffer response for caching: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let cached = Cached { at: Instant::now(), headers: parts.headers, etag: etag(&body), body };
+    cache.insert(key, cached.clone());
+    respond(&cache, &request_headers, cached)
+}
@@ -0,0 +1,211 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::error::ClientError;
+
+use super::signing::{self, NONCE_HEADER};
+use super::{
+    CancelResponse, HealthResponse, InfoResponse, JobLogResponse, PayjoinResponse, QuoteRequest, QuoteResponse, RescueRequest, SubmitPsbtRequest,
+    SubmitPsbtResponse, ValidatePsbtResponse,
+};
+
+// Blocking client for a searcher's HTTP API
+#[derive(Debug, Clone)]
+pub struct SearcherClient {
+    base_url: String,
+    http: reqwest::blocking::Client,
+    response_key: Option<String>,
+}
+
+impl SearcherClient {
+    pub fn new(base_url: &str) -> Self {
+        SearcherClient::with_http(base_url, reqwest::blocking::Client::new())
+    }
+
+    // Use a configured client, e.g. one going through Tor to reach an onion searcher
+    pub fn with_http(base_url: &str, http: reqwest::blocking::Client) -> Self {
+        SearcherClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http,
+            response_key: None,
+        }
+    }
+
+    // Require every response to be signed with this key, rejecting tampered or replayed ones
+    pub fn with_response_key(mut self, key: Option<String>) -> Self {
+        self.response_key = key;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn quote(&self, request: &QuoteRequest) -> Result<QuoteResponse, ClientError> {
+        let url = format!("{}/quote", self.base_url);
+        info!("Requesting quote from: {}", url);
+
+        let request = self.http
+            .post(&url)
+            .header("Accept", "application/json")
+            .json(request);
+
+        self.read_json(url, "/quote", request)
+    }
+
+    pub fn submit_psbt(&self, request: &SubmitPsbtRequest) -> Result<SubmitPsbtResponse, ClientError> {
+        let url = format!("{}/submit-psbt", self.base_url);
+        info!("Submitting PSBT to: {}", url);
+
+        let request = self.http
+            .post(&url)
+            .header("Accept", "application/json")
+            .json(request);
+
+        self.read_json(url, "/submit-psbt", request)
+    }
+
+    // Have the searcher anchor a parent already in the mempool without a child
+    pub fn rescue(&self, request: &RescueRequest) -> Result<SubmitPsbtResponse, ClientError> {
+        let url = format!("{}/rescue", self.base_url);
+        info!("Asking for rescue at: {}", url);
+
+        let request = self.http
+            .post(&url)
+            .header("Accept", "application/json")
+            .json(request);
+
+        self.read_json(url, "/rescue", request)
+    }
+
+    pub fn validate_psbt(&self, request: &SubmitPsbtRequest) -> Result<ValidatePsbtResponse, ClientError> {
+        let url = format!("{}/validate-psbt", self.base_url);
+        info!("Validating PSBT with: {}", url);
+
+        let request = self.http
+            .post(&url)
+            .header("Accept", "application/json")
+            .json(request);
+
+        self.read_json(url, "/validate-psbt", request)
+    }
+
+    pub fn payjoin(&self, request: &SubmitPsbtRequest) -> Result<PayjoinResponse, ClientError> {
+        let url = format!("{}/payjoin", self.base_url);
+        info!("Requesting payjoin from: {}", url);
+
+        let request = self.http
+            .post(&url)
+            .header("Accept", "application/json")
+            .json(request);
+
+        self.read_json(url, "/payjoin", request)
+    }
+
+    // Cancel the job for a parent using the token it was submitted with
+    pub fn cancel(&self, parent_txid: &str, cancel_token: &str) -> Result<CancelResponse, ClientError> {
+        let path = format!("/jobs/{}", parent_txid);
+        let url = format!("{}{}", self.base_url, path);
+        info!("Cancelling job at: {}", url);
+
+        let request = self.http
+            .delete(&url)
+            .header("Accept", "application/json")
+            .bearer_auth(cancel_token);
+
+        self.read_json(url, &path, request)
+    }
+
+    // What the searcher did with the job for a parent, readable with its cancel token
+    pub fn job_log(&self, parent_txid: &str, cancel_token: &str) -> Result<JobLogResponse, ClientError> {
+        let path = format!("/jobs/{}/log", parent_txid);
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.http
+            .get(&url)
+            .header("Accept", "application/json")
+            .bearer_auth(cancel_token);
+
+        self.read_json(url, &path, request)
+    }
+
+    pub fn info(&self) -> Result<InfoResponse, ClientError> {
+        let url = format!("{}/info", self.base_url);
+        let request = self.http
+            .get(&url)
+            .header("Accept", "application/json");
+
+        self.read_json(url, "/info", request)
+    }
+
+    pub fn health(&self) -> Result<HealthResponse, ClientError> {
+        let url = format!("{}/health", self.base_url);
+        let request = self.http
+            .get(&url)
+            .header("Accept", "application/json");
+
+        // A degraded searcher answers 503 but still reports its state
+        self.read_json(url, "/health", request)
+    }
+
+    pub fn metrics(&self) -> Result<HashMap<String, u64>, ClientError> {
+        let url = format!("{}/metrics", self.base_url);
+        let (status, body) = self.send("/metrics", self.http.get(&url))?;
+
+        if !status.is_success() {
+            return Err(ClientError::Status { url, status });
+        }
+
+        // Prometheus text format: skip comments, read "name value" samples
+        let metrics = String::from_utf8_lossy(&body)
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let name = parts.next()?;
+                let value = parts.next()?.parse().ok()?;
+                Some((name.to_string(), value))
+            })
+            .collect();
+
+        Ok(metrics)
+    }
+
+    // Send a request, checking the response's signature when we have a key
+    fn send(
+        &self,
+        path: &str,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> Result<(reqwest::StatusCode, Vec<u8>), ClientError> {
+        let Some(key) = &self.response_key else {
+            let response = request.send()?;
+            return Ok((response.status(), response.bytes()?.to_vec()));
+        };
+
+        let nonce = signing::new_nonce();
+        let response = request.header(NONCE_HEADER, &nonce).send()?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes()?.to_vec();
+
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        signing::verify(key, header, &nonce, path, &body)?;
+        Ok((status, body))
+    }
+
+    // Searchers answer failures with a JSON body explaining why, so prefer that
+    // over the bare status and only fall back when the body isn't ours
+    fn read_json<T: DeserializeOwned>(
+        &self,
+        url: String,
+        path: &str,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> Result<T, ClientError> {
+        let (status, body) = self.send(path, request)?;
+        match serde_json::from_slice(&body) {
+            Ok(body) => Ok(body),
+            Err(_) if !status.is_success() => Err(ClientError::Status { url, status }),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+// Sizes of a P2WPKH spend and output, and the transaction around them
+const INPUT_VSIZE: u64 = 68;
+const OUTPUT_VSIZE: u64 = 31;
+const TX_OVERHEAD_VSIZE: u64 = 11;
+// Keeps a consolidation well under the standard transaction size
+const MAX_INPUTS: usize = 200;
+
+// Merging the change children leave behind back into UTXOs big enough to fund a child,
+// checked every `interval` seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsolidationConfig {
+    // Size of the UTXOs to rebuild; confirmed ones under it count as change
+    pub target_sat: u64,
+    // Change UTXOs that have to pile up before they're worth merging
+    pub min_inputs: usize,
+    // Only consolidate while the searcher's fee rate is at or under this
+    pub max_fee_rate: f64,
+    pub interval: u64,
+}
+
+// A consolidation to make: the change it spends and the UTXOs it makes of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub inputs: Vec<OutPoint>,
+    pub outputs: Vec<u64>,
+    pub fee: u64,
+}
+
+// Picks the wallet's change, skipping UTXOs that would cost more to spend than they hold,
+// and splits it into as many target sized UTXOs as it covers. None when there isn't
+// enough change to bother.
+pub fn plan(unspent: &[ListUnspentResultEntry], config: &ConsolidationConfig, fee_rate: f64) -> Option<Plan> {
+    let input_fee = (INPUT_VSIZE as f64 * fee_rate).ceil() as u64;
+    let mut change = unspent.iter()
+        .filter(|utxo| utxo.spendable && utxo.amount.to_sat() < config.target_sat && utxo.amount.to_sat() > input_fee)
+        .collect::<Vec<_>>();
+    // Largest first, so a capped consolidation merges the most sats
+    change.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
+    change.truncate(MAX_INPUTS);
+    if change.len() < config.min_inputs.max(2) {
+        return None;
+    }
+
+    let total = change.iter().map(|utxo| utxo.amount.to_sat()).sum::<u64>();
+    let fee_for = |count: u64| {
+        let vsize = TX_OVERHEAD_VSIZE + INPUT_VSIZE * change.len() as u64 + OUTPUT_VSIZE * count;
+        (vsize as f64 * fee_rate).ceil() as u64
+    };
+    // Outputs under the target would only count as change again next time
+    let mut count = (total / config.target_sat.max(1)).max(1);
+    while count > 1 && total.saturating_sub(fee_for(count)) / count < config.target_sat {
+        count -= 1;
+    }
+    let fee = fee_for(count);
+    let remaining = total.checked_sub(fee)?;
+    // Evenly, with the odd sats on the first output
+    let mut outputs = vec![remaining / count; count as usize];
+    outputs[0] += remaining % count;
+
+    Some(Plan {
+        inputs: change.iter().map(|utxo| OutPoint { txid: utxo.txid, vout: utxo.vout }).collect(),
+        outputs,
+        fee,
+    })
+}
+
+// The unsigned consolidation, paying each output to the script beside it
+pub fn transaction(plan: &Plan, scripts: Vec<ScriptBuf>) -> Transaction {
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: plan.inputs.iter()
+            .map(|outpoint| TxIn {
+                previous_output: *outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: plan.outputs.iter()
+            .zip(scripts)
+            .map(|(value, script_pubkey)| TxOut { value: Amount::from_sat(*value), script_pubkey })
+            .collect(),
+    }
+}
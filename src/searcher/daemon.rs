@@ -0,0 +1,131 @@
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::error;
+
+use crate::error::SearcherError;
+
+// Detach from the terminal: fork, start a new session, and send output to the log file.
+// Has to happen before any threads exist, so before tracing or the tokio runtime start.
+#[cfg(unix)]
+pub fn daemonize(log_file: Option<&Path>) -> Result<(), SearcherError> {
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    let log = match log_file {
+        Some(path) => OpenOptions::new().create(true).append(true).open(path)?,
+        None => OpenOptions::new().write(true).open("/dev/null")?,
+    };
+    let null = fs::File::open("/dev/null")?;
+
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error().into()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // Fork again so the daemon can never reacquire a controlling terminal
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error().into()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        libc::dup2(null.as_raw_fd(), 0);
+        libc::dup2(log.as_raw_fd(), 1);
+        libc::dup2(log.as_raw_fd(), 2);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_log_file: Option<&Path>) -> Result<(), SearcherError> {
+    Err(SearcherError::Daemon("--daemon is only supported on Unix".to_string()))
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+// Holds the PID file for as long as the searcher runs, removing it on the way out
+#[derive(Debug)]
+pub struct PidFile(PathBuf);
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<PidFile, SearcherError> {
+        if let Ok(contents) = fs::read_to_string(path)
+            && let Ok(pid) = contents.trim().parse::<u32>()
+            && pid != std::process::id()
+            && process_alive(pid)
+        {
+            return Err(SearcherError::Daemon(format!("Already running as pid {} ({})", pid, path.display())));
+        }
+
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(PidFile(path.to_path_buf()))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// Whether we were started by systemd with Type=notify
+pub fn notify_enabled() -> bool {
+    std::env::var_os("NOTIFY_SOCKET").is_some()
+}
+
+// Send a state change ("READY=1", "STATUS=...", "WATCHDOG=1") to systemd. A no-op outside systemd.
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        // A leading @ means a socket in the abstract namespace
+        match path.as_bytes().strip_prefix(b"@") {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                socket.send_to_addr(state.as_bytes(), &address)
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => Err(std::io::Error::other("abstract sockets are Linux only")),
+            None => socket.send_to(state.as_bytes(), &path),
+        }
+    });
+
+    if let Err(e) = sent {
+        error!("sd_notify failed: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+// How often to ping systemd's watchdog: half of WatchdogSec, as sd_watchdog_enabled recommends
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec / 2))
+}
@@ -0,0 +1,234 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use super::{QuoteRequest, RescueRequest, SchemaErrorResponse, SubmitPsbtRequest, SUPPORTED_PROTOCOL_VERSIONS};
+
+// A v3 parent may not exceed 10,000 vB, but its PSBT can carry full previous
+// transactions for each input, so allow far more than the parent itself needs
+pub const MAX_PSBT_BASE64_LEN: usize = 1_000_000;
+const TRUC_MAX_VSIZE: u64 = 10_000;
+// Long enough that a token can't be guessed, short enough not to bloat the job log
+const MIN_CANCEL_TOKEN_LEN: usize = 16;
+const MAX_CANCEL_TOKEN_LEN: usize = 128;
+
+// Request bodies the searcher accepts, with the checks serde alone can't express
+pub trait Schema {
+    // A valid payload, shown alongside errors
+    fn example() -> serde_json::Value;
+
+    // The offending field and what's wrong with it
+    fn validate(&self) -> Result<(), (&'static str, String)>;
+}
+
+// A JSON body extractor that explains what's wrong with a request, where axum's own
+// Json rejection only returns a bare status and message
+pub struct ValidJson<T>(pub T);
+
+type Rejection = (StatusCode, Json<SchemaErrorResponse>);
+
+fn reject<T: Schema>(status: StatusCode, message: String, field: Option<String>, expected: Option<String>) -> Rejection {
+    (status, Json(SchemaErrorResponse {
+        success: false,
+        message,
+        field,
+        expected,
+        example: T::example(),
+    }))
+}
+
+fn json_type(value: &serde_json::Value) -> Option<String> {
+    let name = match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    };
+    Some(name.to_string())
+}
+
+// Turn serde's error into the field at fault and what it should have been
+fn describe<T: Schema>(e: serde_path_to_error::Error<serde_json::Error>) -> Rejection {
+    let detail = e.inner().to_string();
+    if e.inner().classify() != serde_json::error::Category::Data {
+        // Not valid JSON at all, so there's no field to point at
+        return reject::<T>(StatusCode::BAD_REQUEST, format!("Malformed JSON: {}", detail), None, None);
+    }
+
+    // "invalid type: integer `5`, expected a string at line 1 column 10"
+    let reason = detail.split(" at line ").next().unwrap_or(&detail);
+
+    // Missing and unknown fields are reported on the enclosing object, with the name in backticks
+    let path = e.path().to_string();
+    let field = if path != "." {
+        Some(path)
+    } else {
+        reason.split('`').nth(1)
+            .filter(|_| reason.starts_with("missing field") || reason.starts_with("unknown field"))
+            .map(str::to_string)
+    };
+    let expected = reason.split(", expected ").nth(1).map(str::to_string)
+        .or_else(|| field.as_deref().and_then(|field| json_type(&T::example()[field])));
+
+    let message = match &field {
+        Some(field) => format!("Invalid request body at {}: {}", field, reason),
+        None => format!("Invalid request body: {}", reason),
+    };
+    reject::<T>(StatusCode::UNPROCESSABLE_ENTITY, message, field, expected)
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Schema,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = request.headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+        if !is_json {
+            return Err(reject::<T>(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected a JSON body with Content-Type: application/json".to_string(),
+                None,
+                None,
+            ));
+        }
+
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(|e| reject::<T>(e.status(), e.body_text(), None, None))?;
+
+        let mut deserializer = serde_json::Deserializer::from_slice(&body);
+        let value: T = serde_path_to_error::deserialize(&mut deserializer).map_err(describe::<T>)?;
+
+        value.validate().map_err(|(field, reason)| {
+            let expected = json_type(&T::example()[field]);
+            reject::<T>(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Invalid request body at {}: {}", field, reason),
+                Some(field.to_string()),
+                expected,
+            )
+        })?;
+
+        Ok(ValidJson(value))
+    }
+}
+
+// Cheap checks before handing the PSBT to the parser
+fn validate_psbt(psbt: &str) -> Result<(), String> {
+    if psbt.is_empty() {
+        return Err("PSBT is empty".to_string());
+    }
+    if psbt.len() > MAX_PSBT_BASE64_LEN {
+        return Err(format!("PSBT is {} characters, the limit is {}", psbt.len(), MAX_PSBT_BASE64_LEN));
+    }
+    if !psbt.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=') {
+        return Err("PSBT must be base64 encoded".to_string());
+    }
+    Ok(())
+}
+
+fn validate_protocol_version(version: Option<u32>) -> Result<(), (&'static str, String)> {
+    match version {
+        Some(version) if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => Err((
+            "protocol_version",
+            format!("protocol version {} is not supported, use one of {:?}", version, SUPPORTED_PROTOCOL_VERSIONS),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl Schema for SubmitPsbtRequest {
+    fn example() -> serde_json::Value {
+        json!({
+            "psbt": "cHNidP8BAH0CAAAAAeFH5Kf...",
+            "payment_hash": "0001020304050607080910111213141516171819202122232425262728293031",
+        })
+    }
+
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        validate_protocol_version(self.protocol_version)?;
+        validate_psbt(&self.psbt).map_err(|reason| ("psbt", reason))?;
+        if let Some(commit_psbt) = &self.commit_psbt {
+            validate_psbt(commit_psbt).map_err(|reason| ("commit_psbt", reason))?;
+        }
+        validate_payment_fields(self.payment_hash.as_deref(), self.payment_inputs.as_deref(), self.cancel_token.as_deref())
+    }
+}
+
+fn is_hex_hash(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn validate_payment_fields(
+    payment_hash: Option<&str>,
+    payment_inputs: Option<&[usize]>,
+    cancel_token: Option<&str>,
+) -> Result<(), (&'static str, String)> {
+    if payment_hash.is_some_and(|payment_hash| !is_hex_hash(payment_hash)) {
+        return Err(("payment_hash", "payment hash must be 64 hex characters".to_string()));
+    }
+    if payment_inputs.is_some_and(<[usize]>::is_empty) {
+        return Err(("payment_inputs", "payment inputs must list at least one input".to_string()));
+    }
+    if let Some(token) = cancel_token
+        && !(MIN_CANCEL_TOKEN_LEN..=MAX_CANCEL_TOKEN_LEN).contains(&token.len())
+    {
+        return Err((
+            "cancel_token",
+            format!("cancel token must be {} to {} characters", MIN_CANCEL_TOKEN_LEN, MAX_CANCEL_TOKEN_LEN),
+        ));
+    }
+    Ok(())
+}
+
+impl Schema for RescueRequest {
+    fn example() -> serde_json::Value {
+        json!({
+            "txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            "payment_inputs": [1],
+        })
+    }
+
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        validate_protocol_version(self.protocol_version)?;
+        if !is_hex_hash(&self.txid) {
+            return Err(("txid", "txid must be 64 hex characters".to_string()));
+        }
+        validate_payment_fields(self.payment_hash.as_deref(), self.payment_inputs.as_deref(), self.cancel_token.as_deref())
+    }
+}
+
+impl Schema for QuoteRequest {
+    fn example() -> serde_json::Value {
+        json!({ "psbt": "cHNidP8BAH0CAAAAAeFH5Kf...", "vsize": 200, "lightning": false })
+    }
+
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        validate_protocol_version(self.protocol_version)?;
+        match (&self.psbt, self.vsize) {
+            (Some(psbt), _) => validate_psbt(psbt).map_err(|reason| ("psbt", reason)),
+            (None, Some(0)) => Err(("vsize", "vsize must be positive".to_string())),
+            (None, Some(vsize)) if vsize > TRUC_MAX_VSIZE => Err((
+                "vsize",
+                format!("v3 parents are limited to {} vB", TRUC_MAX_VSIZE),
+            )),
+            (None, Some(_)) => Ok(()),
+            (None, None) => Err(("psbt", "either psbt or vsize is required".to_string())),
+        }
+    }
+}
@@ -0,0 +1,736 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+use crate::error::SearcherError;
+
+use super::audit::{AuditLog, AuditRecord};
+use super::{ConfirmationTimes, DailyPackages, StatsResponse};
+
+const SECS_PER_DAY: u64 = 86_400;
+// The job store file is rewritten with one line per job once it has this many lines per
+// job since it last was, and at least COMPACT_MIN_LINES
+const COMPACT_LINES_PER_JOB: usize = 8;
+const COMPACT_MIN_LINES: usize = 1024;
+// How long a quoted invoice is kept past its expiry, so one paid just before it expired
+// can still be submitted with
+const INVOICE_KEEP_SECS: u64 = SECS_PER_DAY;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Package,
+    Payjoin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    // Package accepted by bitcoind, or payjoin signed and handed back
+    Submitted,
+    Confirmed,
+    Failed,
+    // Abandoned by the submitter before broadcast, or its child replaced after
+    Cancelled,
+}
+
+// How the submitter paid for the sponsorship
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Payment {
+    Rune { amount: u64 },
+    Lightning { amount_sat: u64 },
+    Inscription,
+    // Paid for by the searcher's sponsorship budget
+    Sponsored { template: String, amount_sat: u64 },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub received_at: u64,
+    pub parent_txid: Option<String>,
+    pub child_txid: Option<String>,
+    pub payment: Option<Payment>,
+    // Searcher wallet that funded it
+    pub wallet: Option<String>,
+    // Parent and child together, or the whole payjoin
+    pub vsize: Option<u64>,
+    pub fee_sat: Option<u64>,
+    pub submitted_at: Option<u64>,
+    // Block time of the block that confirmed it
+    pub confirmed_at: Option<u64>,
+    pub failure_reason: Option<String>,
+    pub failure_message: Option<String>,
+    // Wallet UTXO a payjoin locked, to unlock if the job never completes
+    pub locked_utxo: Option<String>,
+    // SHA-256 of the submitter's cancel token
+    pub cancel_hash: Option<String>,
+    // Outpoints the parent spends, to spot a later parent replacing this one
+    #[serde(default)]
+    pub parent_inputs: Vec<String>,
+    // wtxid of the parent as submitted, then as last seen in the mempool
+    #[serde(default)]
+    pub parent_wtxid: Option<String>,
+    // Fresh address the child paid this job's runes to, when addresses rotate
+    pub rune_address: Option<String>,
+    // A parent spending an unconfirmed commit is held as a PSBT until the commit confirms
+    pub commit_txid: Option<String>,
+    pub held_psbt: Option<String>,
+    pub payment_hash: Option<String>,
+    #[serde(default)]
+    pub payment_inputs: Option<Vec<usize>>,
+    // IP address that submitted the job, and the one quoted for its parent
+    #[serde(default)]
+    pub client: Option<String>,
+    #[serde(default)]
+    pub quote_client: Option<String>,
+    // Deposit a keyed parent carried, for the searcher to claim
+    #[serde(default)]
+    pub deposit: Option<Deposit>,
+    // What the searcher did with it, for the submitter to read back
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log: Vec<JobEvent>,
+    // Sats the searcher's wallet paid in fees, once bitcoind accepted the package or the
+    // payjoin was signed, and what the child sent to the revenue address
+    #[serde(default)]
+    pub fees_spent_sat: Option<u64>,
+    #[serde(default)]
+    pub revenue_sat: Option<u64>,
+    // Replacement generation: how many times the child has been replaced
+    #[serde(default)]
+    pub replacements: u32,
+    // Whether the package last paid enough for bitcoind's next block template
+    #[serde(default)]
+    pub next_block: Option<bool>,
+    // Broadcast nodes that accepted the package as well as the main one
+    #[serde(default)]
+    pub broadcast_to: Vec<String>,
+}
+
+// A Lightning invoice handed out by /quote, bound to the parent it pays for
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Invoice {
+    pub payment_hash: String,
+    pub parent: Option<String>,
+    pub expires_at: u64,
+    pub amount_sat: u64,
+    // Fee rate the invoice was priced at
+    pub fee_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Deposit {
+    pub outpoint: String,
+    pub amount_sat: u64,
+    pub witness_script: String,
+}
+
+// One step of a job, in the order it happened
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JobEvent {
+    pub at: u64,
+    pub step: String,
+    pub message: String,
+}
+
+impl Job {
+    pub fn fee_rate(&self) -> Option<f64> {
+        match (self.fee_sat, self.vsize) {
+            (Some(fee), Some(vsize)) if vsize > 0 => Some(fee as f64 / vsize as f64),
+            _ => None,
+        }
+    }
+
+    // The transaction whose confirmation settles the job
+    pub fn tracked_txid(&self) -> Option<&str> {
+        match self.kind {
+            JobKind::Package => self.child_txid.as_deref(),
+            JobKind::Payjoin => self.parent_txid.as_deref(),
+        }
+    }
+}
+
+// Every submission the searcher has handled, and the Lightning invoices it has quoted.
+// With a path, both are appended to a JSON Lines file as they change and reloaded on
+// start, the last line for each winning. Each job line carries only the log events added
+// since the job's previous line, and the file is compacted to a line per job and invoice
+// on start and whenever it has grown well past that.
+#[derive(Debug)]
+pub struct JobStore {
+    file: Option<Mutex<StoreFile>>,
+    entries: Mutex<Entries>,
+    // Where each change of status is recorded as a decision
+    audit: Option<AuditLog>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    jobs: BTreeMap<u64, Job>,
+    // By payment hash, until INVOICE_KEEP_SECS past their expiry
+    invoices: BTreeMap<String, Invoice>,
+}
+
+impl Entries {
+    fn prune_invoices(&mut self, now: u64) {
+        self.invoices.retain(|_, invoice| invoice.expires_at + INVOICE_KEEP_SECS > now);
+    }
+
+    fn lines(&mut self) -> Result<Vec<String>, serde_json::Error> {
+        let jobs = self.jobs.values_mut().map(|job| line(job, 0));
+        let invoices = self.invoices.values().map(invoice_line);
+        jobs.chain(invoices).collect()
+    }
+}
+
+#[derive(Debug)]
+struct StoreFile {
+    path: PathBuf,
+    // Lines appended since the file was last compacted
+    lines: usize,
+}
+
+// A job as one line of the job store: everything but its log, then its log events from
+// `log_from` on. Lines from before logs were split this way carry the whole log.
+#[derive(Deserialize)]
+struct Line {
+    #[serde(default)]
+    log_from: usize,
+    #[serde(flatten)]
+    job: Job,
+}
+
+// A line of the job store, a quoted invoice or a job. Invoice lines are tried first, as
+// job lines never have an invoice field.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Invoice { invoice: Invoice },
+    Job(Box<Line>),
+}
+
+#[derive(Serialize)]
+struct LineRef<'a> {
+    log_from: usize,
+    log: &'a [JobEvent],
+    #[serde(flatten)]
+    job: &'a Job,
+}
+
+// What a change has the job store file do, worked out with the jobs locked and done
+// after they're unlocked
+enum FileWrite {
+    Append(String),
+    Compact(Vec<String>),
+}
+
+fn line(job: &mut Job, log_from: usize) -> Result<String, serde_json::Error> {
+    let log = std::mem::take(&mut job.log);
+    let line = serde_json::to_string(&LineRef { log_from, log: log.get(log_from..).unwrap_or_default(), job });
+    job.log = log;
+    line
+}
+
+fn invoice_line(invoice: &Invoice) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&serde_json::json!({ "invoice": invoice }))
+}
+
+impl StoreFile {
+    fn write(&mut self, write: FileWrite) -> std::io::Result<()> {
+        match write {
+            FileWrite::Append(line) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+                writeln!(file, "{}", line)?;
+                self.lines += 1;
+            }
+            FileWrite::Compact(lines) => {
+                let compacted = self.path.with_extension("tmp");
+                let mut file = File::create(&compacted)?;
+                for line in &lines {
+                    writeln!(file, "{}", line)?;
+                }
+                file.sync_all()?;
+                fs::rename(&compacted, &self.path)?;
+                self.lines = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl JobStore {
+    pub fn in_memory() -> Self {
+        JobStore { file: None, entries: Mutex::new(Entries::default()), audit: None }
+    }
+
+    pub fn open(path: PathBuf) -> Result<Self, SearcherError> {
+        let mut entries = read_entries(&path)?;
+        entries.prune_invoices(unix_now());
+        let lines = entries.lines().map_err(std::io::Error::other)?;
+        let mut file = StoreFile { path, lines: 0 };
+        file.write(FileWrite::Compact(lines))?;
+
+        info!(
+            "Loaded {} jobs and {} invoices from {}",
+            entries.jobs.len(), entries.invoices.len(), file.path.display(),
+        );
+        Ok(JobStore { file: Some(Mutex::new(file)), entries: Mutex::new(entries), audit: None })
+    }
+
+    pub fn with_audit_log(self, audit: AuditLog) -> Self {
+        JobStore { audit: Some(audit), ..self }
+    }
+
+    pub fn start(&self, kind: JobKind, received_at: u64) -> u64 {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let id = entries.jobs.keys().next_back().map_or(1, |id| id + 1);
+        let job = Job {
+            id,
+            kind,
+            status: JobStatus::Pending,
+            received_at,
+            parent_txid: None,
+            child_txid: None,
+            payment: None,
+            wallet: None,
+            vsize: None,
+            fee_sat: None,
+            submitted_at: None,
+            confirmed_at: None,
+            failure_reason: None,
+            failure_message: None,
+            locked_utxo: None,
+            cancel_hash: None,
+            parent_inputs: Vec::new(),
+            parent_wtxid: None,
+            rune_address: None,
+            commit_txid: None,
+            held_psbt: None,
+            payment_hash: None,
+            payment_inputs: None,
+            client: None,
+            quote_client: None,
+            deposit: None,
+            log: Vec::new(),
+            fees_spent_sat: None,
+            revenue_sat: None,
+            replacements: 0,
+            next_block: None,
+            broadcast_to: Vec::new(),
+        };
+        entries.jobs.insert(id, job);
+        self.record_job(entries, id, 0);
+        id
+    }
+
+    pub fn update(&self, id: u64, change: impl FnOnce(&mut Job)) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(job) = entries.jobs.get_mut(&id) {
+            let (status, logged) = (job.status, job.log.len());
+            change(job);
+            self.audit(status, job);
+            self.record_job(entries, id, logged);
+        }
+    }
+
+    // Apply a change only if the job is still in `from`, so a submission and a cancel
+    // racing each other can't both win
+    pub fn transition(&self, id: u64, from: JobStatus, change: impl FnOnce(&mut Job)) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.jobs.get_mut(&id) {
+            Some(job) if job.status == from => {
+                let logged = job.log.len();
+                change(job);
+                self.audit(from, job);
+                self.record_job(entries, id, logged);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn log(&self, id: u64, step: &str, message: String) {
+        self.update(id, |job| job.log.push(event(step, message)));
+    }
+
+    // A cancelled job keeps its status when the submission it interrupted unwinds
+    pub fn fail(&self, id: u64, e: &SearcherError) {
+        self.update(id, |job| {
+            job.log.push(event("failed", e.to_string()));
+            if job.status == JobStatus::Cancelled {
+                return;
+            }
+            job.status = JobStatus::Failed;
+            job.failure_reason = Some(e.reason().to_string());
+            job.failure_message = Some(e.to_string());
+        });
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).jobs.get(&id).cloned()
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).jobs.values().cloned().collect()
+    }
+
+    pub fn earnings(&self, since: u64) -> Earnings {
+        earnings(self.entries.lock().unwrap_or_else(|e| e.into_inner()).jobs.values(), since)
+    }
+
+    // Expired invoices are let go of as new ones are quoted
+    pub fn quote_invoice(&self, invoice: Invoice) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.prune_invoices(unix_now());
+        let payment_hash = invoice.payment_hash.clone();
+        entries.invoices.insert(payment_hash.clone(), invoice);
+        self.record(entries, &payment_hash, |entries| invoice_line(&entries.invoices[&payment_hash]));
+    }
+
+    pub fn invoice(&self, payment_hash: &str) -> Option<Invoice> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).invoices.get(payment_hash).cloned()
+    }
+
+    pub fn invoices(&self) -> Vec<Invoice> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).invoices.values().cloned().collect()
+    }
+
+    // The invoice as changed, or None if it was never quoted or has been let go of
+    pub fn update_invoice(&self, payment_hash: &str, change: impl FnOnce(&mut Invoice)) -> Option<Invoice> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let invoice = entries.invoices.get_mut(payment_hash)?;
+        change(invoice);
+        let invoice = invoice.clone();
+        self.record(entries, payment_hash, |_| invoice_line(&invoice));
+        Some(invoice)
+    }
+
+    // Called with the jobs locked, so entries go in the order the changes were made
+    fn audit(&self, previous: JobStatus, job: &Job) {
+        if let Some(audit) = &self.audit
+            && job.status != previous
+            && let Some(record) = AuditRecord::for_job(job)
+        {
+            audit.record(record);
+        }
+    }
+
+    fn record_job(&self, entries: MutexGuard<Entries>, id: u64, log_from: usize) {
+        self.record(entries, &format!("job {}", id), |entries| match entries.jobs.get_mut(&id) {
+            Some(job) => line(job, log_from),
+            None => Ok(String::new()),
+        });
+    }
+
+    // Hands the file over before the entries are unlocked, so lines go in the order the
+    // changes were made but other jobs aren't held up while they're written. Losing a
+    // record shouldn't fail the submission it describes.
+    fn record(
+        &self,
+        mut entries: MutexGuard<Entries>,
+        name: &str,
+        line: impl FnOnce(&mut Entries) -> Result<String, serde_json::Error>,
+    ) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        let count = entries.jobs.len() + entries.invoices.len();
+        let write = match file.lines >= COMPACT_MIN_LINES.max(count * COMPACT_LINES_PER_JOB) {
+            true => entries.lines().map(FileWrite::Compact),
+            false => line(&mut entries).map(FileWrite::Append),
+        };
+        drop(entries);
+        if let Err(e) = write.map_err(std::io::Error::other).and_then(|write| file.write(write)) {
+            error!("Failed to record {} in {}: {}", name, file.path.display(), e);
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn event(step: &str, message: String) -> JobEvent {
+    JobEvent { at: unix_now(), step: step.to_string(), message }
+}
+
+// The latest state of every job in a job store file, without touching the file, so it
+// can be read while a searcher is appending to it
+pub fn read(path: &Path) -> Result<BTreeMap<u64, Job>, SearcherError> {
+    Ok(read_entries(path)?.jobs)
+}
+
+fn read_entries(path: &Path) -> Result<Entries, SearcherError> {
+    let mut jobs = BTreeMap::<u64, Job>::new();
+    let mut invoices = BTreeMap::new();
+    match File::open(path) {
+        Ok(file) => {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // A crash mid-write leaves a truncated last line, which is safe to skip
+                match serde_json::from_str::<Record>(&line) {
+                    Ok(Record::Invoice { invoice }) => {
+                        invoices.insert(invoice.payment_hash.clone(), invoice);
+                    }
+                    Ok(Record::Job(line)) => {
+                        let Line { log_from, mut job } = *line;
+                        if let Some(previous) = jobs.remove(&job.id) {
+                            let mut log = previous.log;
+                            log.truncate(log_from);
+                            log.append(&mut job.log);
+                            job.log = log;
+                        }
+                        jobs.insert(job.id, job);
+                    }
+                    Err(e) => error!("Skipping line {} of {}: {}", i + 1, path.display(), e),
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Entries { jobs, invoices })
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+// Days since the Unix epoch to a proleptic Gregorian YYYY-MM-DD date
+pub fn format_day(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// A YYYY-MM-DD date to days since the Unix epoch, the inverse of format_day
+pub fn parse_day(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    // Rejects days past the end of the month, like 2025-02-30
+    (days >= 0 && format_day(days as u64) == date).then_some(days as u64)
+}
+
+// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+// Sats spent sponsoring jobs submitted on the UTC day containing `now`
+pub fn sponsored_today(jobs: &[Job], now: u64) -> u64 {
+    jobs.iter()
+        .filter(|job| job.submitted_at.is_some_and(|at| at / SECS_PER_DAY == now / SECS_PER_DAY))
+        .filter_map(|job| match &job.payment {
+            Some(Payment::Sponsored { amount_sat, .. }) => Some(*amount_sat),
+            _ => None,
+        })
+        .sum()
+}
+
+// What the searcher paid out and took in, over jobs received since `since`. Payments
+// count once the job was sponsored; fees count whenever the wallet paid them.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Earnings {
+    pub jobs: u64,
+    pub packages_submitted: u64,
+    pub payjoins_signed: u64,
+    pub fees_spent_sat: u64,
+    // Part of fees_spent_sat paid from the sponsorship budget
+    pub sponsored_sat: u64,
+    // In the rune's smallest units
+    pub runes_earned: u64,
+    pub lightning_earned_sat: u64,
+    pub inscriptions_earned: u64,
+    pub revenue_sat: u64,
+}
+
+pub fn earnings<'a>(jobs: impl IntoIterator<Item = &'a Job>, since: u64) -> Earnings {
+    let mut earnings = Earnings::default();
+    for job in jobs.into_iter().filter(|job| job.received_at >= since) {
+        earnings.jobs += 1;
+        if let Some(fee) = job.fees_spent_sat {
+            earnings.fees_spent_sat += fee;
+            match job.kind {
+                JobKind::Package => earnings.packages_submitted += 1,
+                JobKind::Payjoin => earnings.payjoins_signed += 1,
+            }
+        }
+        earnings.revenue_sat += job.revenue_sat.unwrap_or_default();
+        if !matches!(job.status, JobStatus::Submitted | JobStatus::Confirmed) {
+            continue;
+        }
+        match &job.payment {
+            Some(Payment::Rune { amount }) => earnings.runes_earned += amount,
+            Some(Payment::Lightning { amount_sat }) => earnings.lightning_earned_sat += amount_sat,
+            Some(Payment::Inscription) => earnings.inscriptions_earned += 1,
+            Some(Payment::Sponsored { amount_sat, .. }) => earnings.sponsored_sat += amount_sat,
+            None => {}
+        }
+    }
+    earnings
+}
+
+// A job the searcher's wallet paid fees for, as a line in an accounting ledger. Payments
+// are only income for jobs that were sponsored and not cancelled, as in earnings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LedgerEntry {
+    pub job: u64,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    // When the fee was paid: the package accepted or the payjoin signed
+    pub paid_at: u64,
+    pub confirmed_at: Option<u64>,
+    pub parent_txid: Option<String>,
+    pub child_txid: Option<String>,
+    pub wallet: Option<String>,
+    pub fee_sat: u64,
+    // Part of fee_sat paid from the sponsorship budget
+    pub sponsored_sat: u64,
+    pub payment_method: Option<String>,
+    // In the rune's smallest units
+    pub rune_amount: u64,
+    pub lightning_sat: u64,
+    pub revenue_sat: u64,
+}
+
+// Ledger entries for fees paid from `from` up to `to`, in the order they were paid
+pub fn ledger<'a>(jobs: impl IntoIterator<Item = &'a Job>, from: u64, to: u64) -> Vec<LedgerEntry> {
+    let mut entries: Vec<LedgerEntry> = jobs.into_iter()
+        .filter_map(|job| {
+            let fee_sat = job.fees_spent_sat?;
+            let paid_at = job.submitted_at.unwrap_or(job.received_at);
+            if paid_at < from || paid_at >= to {
+                return None;
+            }
+            let income = matches!(job.status, JobStatus::Submitted | JobStatus::Confirmed);
+            let payment = job.payment.as_ref().filter(|_| income);
+            let mut entry = LedgerEntry {
+                job: job.id,
+                kind: job.kind,
+                status: job.status,
+                paid_at,
+                confirmed_at: job.confirmed_at,
+                parent_txid: job.parent_txid.clone(),
+                child_txid: job.child_txid.clone(),
+                wallet: job.wallet.clone(),
+                fee_sat,
+                sponsored_sat: 0,
+                payment_method: None,
+                rune_amount: 0,
+                lightning_sat: 0,
+                revenue_sat: job.revenue_sat.unwrap_or_default(),
+            };
+            let method = match payment {
+                Some(Payment::Rune { amount }) => {
+                    entry.rune_amount = *amount;
+                    "rune"
+                }
+                Some(Payment::Lightning { amount_sat }) => {
+                    entry.lightning_sat = *amount_sat;
+                    "lightning"
+                }
+                Some(Payment::Inscription) => "inscription",
+                Some(Payment::Sponsored { amount_sat, .. }) => {
+                    entry.sponsored_sat = *amount_sat;
+                    "sponsored"
+                }
+                None => return Some(entry),
+            };
+            entry.payment_method = Some(method.to_string());
+            Some(entry)
+        })
+        .collect();
+    entries.sort_by_key(|entry| (entry.paid_at, entry.job));
+    entries
+}
+
+pub fn stats(jobs: &[Job], since: u64) -> StatsResponse {
+    let jobs: Vec<&Job> = jobs.iter().filter(|job| job.received_at >= since).collect();
+    let sponsored: Vec<&Job> = jobs.iter()
+        .copied()
+        .filter(|job| matches!(job.status, JobStatus::Submitted | JobStatus::Confirmed))
+        .collect();
+
+    let mut per_day: BTreeMap<u64, u64> = BTreeMap::new();
+    for job in &sponsored {
+        *per_day.entry(job.submitted_at.unwrap_or(job.received_at) / SECS_PER_DAY).or_default() += 1;
+    }
+
+    let mut confirmation_secs: Vec<u64> = sponsored.iter()
+        .filter_map(|job| Some(job.confirmed_at?.saturating_sub(job.submitted_at?)))
+        .collect();
+    confirmation_secs.sort_unstable();
+    let confirmation_times = (!confirmation_secs.is_empty()).then(|| ConfirmationTimes {
+        count: confirmation_secs.len(),
+        min_secs: confirmation_secs[0],
+        median_secs: percentile(&confirmation_secs, 50),
+        p90_secs: percentile(&confirmation_secs, 90),
+        max_secs: confirmation_secs[confirmation_secs.len() - 1],
+    });
+
+    let mut failures: BTreeMap<String, u64> = BTreeMap::new();
+    for job in &jobs {
+        if let Some(reason) = &job.failure_reason {
+            *failures.entry(reason.clone()).or_default() += 1;
+        }
+    }
+
+    StatsResponse {
+        jobs: jobs.len(),
+        sponsored: sponsored.len(),
+        confirmed: sponsored.iter().filter(|job| job.status == JobStatus::Confirmed).count(),
+        failed: jobs.iter().filter(|job| job.status == JobStatus::Failed).count(),
+        cancelled: jobs.iter().filter(|job| job.status == JobStatus::Cancelled).count(),
+        packages_per_day: per_day.into_iter()
+            .map(|(day, packages)| DailyPackages { date: format_day(day), packages })
+            .collect(),
+        average_fee_rate: average(sponsored.iter().filter_map(|job| job.fee_rate())),
+        average_rune_payment: average(sponsored.iter().filter_map(|job| match job.payment {
+            Some(Payment::Rune { amount }) => Some(amount as f64),
+            _ => None,
+        })),
+        average_lightning_payment_sat: average(sponsored.iter().filter_map(|job| match job.payment {
+            Some(Payment::Lightning { amount_sat }) => Some(amount_sat as f64),
+            _ => None,
+        })),
+        confirmation_times,
+        failures,
+        sponsored_sat: sponsored.iter().filter_map(|job| match &job.payment {
+            Some(Payment::Sponsored { amount_sat, .. }) => Some(*amount_sat),
+            _ => None,
+        }).sum(),
+    }
+}
@@ -0,0 +1,240 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+    Router,
+};
+use hyper_util::rt::TokioIo;
+use ipnet::IpNet;
+use socket2::{Domain, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tower::Service;
+use tracing::{error, info};
+
+use crate::error::SearcherError;
+
+// How long a connection gets to send its PROXY header before it's dropped
+const PROXY_HEADER_TIMEOUT_SECS: u64 = 5;
+
+// The longest v1 header, "PROXY TCP6" with two full IPv6 addresses and ports
+const PROXY_V1_MAX_LEN: usize = 107;
+
+const PROXY_V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+const LISTEN_BACKLOG: i32 = 1024;
+
+// Binds every address. An IPv6 wildcard like [::] accepts IPv4 too, unless an IPv4
+// address is also listed on the same port, which then gets those connections.
+pub fn bind(addrs: &[SocketAddr]) -> io::Result<Vec<TcpListener>> {
+    addrs.iter()
+        .map(|addr| {
+            let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, None)?;
+            if addr.is_ipv6() {
+                let ipv4_too = addrs.iter().any(|other| other.is_ipv4() && other.port() == addr.port());
+                socket.set_only_v6(ipv4_too)?;
+            }
+            #[cfg(unix)]
+            socket.set_reuse_address(true)?;
+            socket.set_nonblocking(true)?;
+            socket.bind(&(*addr).into())?;
+            socket.listen(LISTEN_BACKLOG)?;
+            TcpListener::from_std(socket.into())
+        })
+        .collect()
+}
+
+// Where a local service like Tor reaches the first listener, since a wildcard can't be dialed
+pub fn local_target(addrs: &[SocketAddr]) -> Option<SocketAddr> {
+    let mut addr = *addrs.first()?;
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+        IpAddr::V6(ip) if ip.is_unspecified() => addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+        _ => {}
+    }
+    Some(addr)
+}
+
+// Serves `app` on every listener with each request's peer address as ConnectInfo. With
+// `proxy_protocol`, each connection has to open with a PROXY header naming the client.
+pub async fn serve(listeners: Vec<TcpListener>, app: Router, proxy_protocol: bool) -> io::Result<()> {
+    let servers = listeners.into_iter().map(|listener| {
+        let app = app.clone();
+        async move {
+            match proxy_protocol {
+                true => serve_proxied(listener, app).await,
+                false => axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await,
+            }
+        }
+    });
+    futures::future::try_join_all(servers).await?;
+    Ok(())
+}
+
+async fn serve_proxied(listener: TcpListener, app: Router) -> io::Result<()> {
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Like running out of file descriptors, which passes as connections close
+                error!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let app = app.clone();
+        tokio::spawn(async move {
+            let header = tokio::time::timeout(Duration::from_secs(PROXY_HEADER_TIMEOUT_SECS), read_proxy_header(&mut stream));
+            let client = match header.await {
+                Ok(Ok(client)) => client.unwrap_or(peer),
+                Ok(Err(e)) => {
+                    info!("Dropping connection from {} without a valid PROXY header: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    info!("Dropping connection from {} that sent no PROXY header", peer);
+                    return;
+                }
+            };
+            let service = hyper::service::service_fn(move |mut request: Request<hyper::body::Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(client));
+                // A Router is always ready, so there's no need to poll it first
+                app.clone().call(request)
+            });
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await
+            {
+                info!("Connection from {} ended with an error: {}", client, e);
+            }
+        });
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// The client address from a PROXY protocol v1 or v2 header, read without consuming
+// anything past it. None for health checks the proxy makes itself (v2 LOCAL, v1 UNKNOWN).
+pub async fn read_proxy_header(stream: &mut TcpStream) -> io::Result<Option<SocketAddr>> {
+    let mut signature = [0u8; 12];
+    stream.read_exact(&mut signature[..5]).await?;
+    if &signature[..5] == b"PROXY" {
+        let mut line = b"PROXY".to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= PROXY_V1_MAX_LEN {
+                return Err(invalid("PROXY v1 header too long"));
+            }
+            line.push(stream.read_u8().await?);
+        }
+        return parse_proxy_v1(&line);
+    }
+
+    stream.read_exact(&mut signature[5..]).await?;
+    if signature != PROXY_V2_SIGNATURE {
+        return Err(invalid("not a PROXY header"));
+    }
+    let version_command = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let mut addresses = vec![0u8; stream.read_u16().await? as usize];
+    stream.read_exact(&mut addresses).await?;
+    parse_proxy_v2(version_command, family, &addresses)
+}
+
+// "PROXY TCP4 <client> <proxy> <client port> <proxy port>\r\n"
+fn parse_proxy_v1(line: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line).map_err(|_| invalid("PROXY v1 header isn't ASCII"))?;
+    let fields: Vec<&str> = line.trim_end().split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", client, _, port, _] => {
+            let ip: IpAddr = client.parse().map_err(|_| invalid("bad client address in PROXY v1 header"))?;
+            let port: u16 = port.parse().map_err(|_| invalid("bad client port in PROXY v1 header"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("malformed PROXY v1 header")),
+    }
+}
+
+fn parse_proxy_v2(version_command: u8, family: u8, addresses: &[u8]) -> io::Result<Option<SocketAddr>> {
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    match version_command & 0x0f {
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("unsupported PROXY v2 command")),
+    }
+    let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+    match family >> 4 {
+        1 if addresses.len() >= 12 => {
+            let ip: [u8; 4] = addresses[..4].try_into().map_err(|_| invalid("short PROXY v2 address"))?;
+            Ok(Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port(8))))
+        }
+        2 if addresses.len() >= 36 => {
+            let ip: [u8; 16] = addresses[..16].try_into().map_err(|_| invalid("short PROXY v2 address"))?;
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port(32))))
+        }
+        // Unix sockets and unspecified families carry no client IP
+        0 | 3 => Ok(None),
+        _ => Err(invalid("short PROXY v2 address")),
+    }
+}
+
+// Proxies whose X-Forwarded-For is believed
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(pub Vec<IpNet>);
+
+// An IP address or CIDR block
+pub fn parse_proxy(spec: &str) -> Result<IpNet, SearcherError> {
+    spec.parse::<IpNet>()
+        .or_else(|_| spec.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| SearcherError::Config(format!("invalid trusted proxy {}: expected an IP address or CIDR block", spec)))
+}
+
+impl TrustedProxies {
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+
+    // Walks X-Forwarded-For back from the connection, past trusted proxies, to the first
+    // address none of them vouches for. Anything before it could be made up by the client.
+    fn client(&self, peer: IpAddr, request: &Request) -> IpAddr {
+        let mut client = peer;
+        let forwarded = request.headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim())
+            .collect::<Vec<_>>();
+        for hop in forwarded.into_iter().rev() {
+            if !self.trusts(client) {
+                break;
+            }
+            match hop.parse::<IpAddr>() {
+                Ok(ip) => client = ip,
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+// Middleware replacing the peer address with the client named by trusted proxies, so
+// rate limiting, bans and job records see clients rather than the proxy in front
+pub async fn client_ip(State(proxies): State<Arc<TrustedProxies>>, mut request: Request, next: Next) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let client = proxies.client(peer.ip(), &request);
+        if client != peer.ip() {
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::new(client, peer.port())));
+        }
+    }
+    next.run(request).await
+}
@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::runes::InputFinding;
+
+pub mod abuse;
+pub mod audit;
+pub mod cache;
+pub mod client;
+pub mod consolidate;
+pub mod daemon;
+pub mod extract;
+pub mod jobs;
+pub mod listen;
+pub mod preflight;
+pub mod quotes;
+pub mod relay;
+pub mod server;
+pub mod signing;
+
+// Version of the submit API this build speaks, bumped when a change would break older
+// clients. Requests without a version are taken to speak version 1.
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u32] = &[1];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubmitPsbtRequest {
+    pub psbt: String,
+    // Set when the fee was paid over Lightning instead of with a rune input
+    pub payment_hash: Option<String>,
+    // Secret chosen by the submitter that lets them cancel the job later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_token: Option<String>,
+    // A transaction the parent spends, like an inscription commit, broadcast first and
+    // paying its own fee, with the parent anchored once it confirms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_psbt: Option<String>,
+    // Indices of the inputs carrying the rune payment, when not marked in the PSBT
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_inputs: Option<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+}
+
+// A parent already in the mempool without a child, like one another searcher failed to
+// anchor, for /rescue to take over. Answered with a SubmitPsbtResponse.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RescueRequest {
+    pub txid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_token: Option<String>,
+    // Indices of the inputs carrying the rune payment, the last input when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_inputs: Option<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubmitPsbtResponse {
+    pub success: bool,
+    // Version the searcher answered with, missing from searchers that predate versions
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    pub message: String,
+    pub package_txids: Option<Vec<String>>,
+    // Set when no child could be made: why, and the parent for the submitter to broadcast
+    #[serde(default)]
+    pub diagnosis: Option<String>,
+    #[serde(default)]
+    pub parent_hex: Option<String>,
+    // Inputs that would lose runes or inscriptions, when that's why it failed
+    #[serde(default)]
+    pub findings: Option<Vec<InputFinding>>,
+    // Seconds to wait before submitting again, when the searcher gave up for now
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+    // Whether the package pays enough to make bitcoind's next block template, when known
+    #[serde(default)]
+    pub next_block: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PayjoinResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    pub message: String,
+    // The parent with the searcher's input signed, for the submitter to sign and broadcast
+    pub psbt: Option<String>,
+    pub fee: Option<u64>,
+    #[serde(default)]
+    pub findings: Option<Vec<InputFinding>>,
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuoteRequest {
+    pub psbt: Option<String>,
+    pub vsize: Option<u64>,
+    #[serde(default)]
+    pub lightning: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct QuoteResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    pub message: String,
+    pub rune: Option<String>,
+    // In the rune's smallest units; rune_divisibility places make one whole rune
+    pub rune_amount: Option<u64>,
+    #[serde(default)]
+    pub rune_divisibility: Option<u8>,
+    pub rune_address: Option<String>,
+    pub fee_rate: Option<f64>,
+    pub package_vsize: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub invoice: Option<String>,
+    pub payment_hash: Option<String>,
+    pub amount_sat: Option<u64>,
+    pub inscription_collection: Option<String>,
+    pub ordinal_address: Option<String>,
+    // USD per rune unit used to price a USD-denominated quote, and when it was fetched
+    pub exchange_rate: Option<f64>,
+    pub rate_timestamp: Option<u64>,
+    // The parent matches a sponsored script and needs no payment
+    #[serde(default)]
+    pub sponsored: bool,
+    // Hex P2WPKH script to use as the parent's zero-value anchor in place of P2A
+    #[serde(default)]
+    pub anchor_script: Option<String>,
+    // Sats the parent has to deposit to the anchor key, refundable after this many blocks
+    #[serde(default)]
+    pub deposit_amount: Option<u64>,
+    #[serde(default)]
+    pub deposit_delay: Option<u16>,
+    // Size tier applied to the per-vbyte price, and the largest package the searcher takes
+    #[serde(default)]
+    pub price_multiplier: Option<f64>,
+    #[serde(default)]
+    pub max_package_vsize: Option<u64>,
+}
+
+// What the searcher would do with a PSBT, from /validate-psbt
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ValidatePsbtResponse {
+    // Every check passed and bitcoind would accept the package
+    pub success: bool,
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    pub message: String,
+    pub parent_txid: Option<String>,
+    pub parent_vsize: Option<u64>,
+    pub payment: Option<jobs::Payment>,
+    // Searcher wallet the child would be funded from
+    pub wallet: Option<String>,
+    pub child_txid: Option<String>,
+    pub child_vsize: Option<u64>,
+    pub child_fee: Option<u64>,
+    // sat/vB across parent and child
+    pub package_fee_rate: Option<f64>,
+    pub mempool_accepted: Option<bool>,
+    pub reject_reason: Option<String>,
+    pub findings: Option<Vec<InputFinding>>,
+}
+
+// Outcome of DELETE /jobs/{txid}
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CancelResponse {
+    pub success: bool,
+    pub message: String,
+    // Transaction that replaced an already broadcast child
+    pub replacement_txid: Option<String>,
+}
+
+// Sent to /quotes/events subscribers when fees spike past what standing quotes were
+// priced at
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QuoteEvent {
+    // Always quotes_expired for now
+    pub event: String,
+    pub at: u64,
+    pub fee_rate: f64,
+    // Quotes whose fee_rate is under this no longer stand
+    pub stale_below: f64,
+    // Lightning invoices cancelled at the node, which can no longer be paid
+    pub cancelled_invoices: Vec<String>,
+}
+
+// A job's steps from GET /jobs/{txid}/log
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JobLogResponse {
+    pub success: bool,
+    pub message: String,
+    pub status: Option<jobs::JobStatus>,
+    pub failure_reason: Option<String>,
+    pub events: Vec<jobs::JobEvent>,
+}
+
+// Returned instead of the endpoint's usual response when the request body doesn't match its schema
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SchemaErrorResponse {
+    pub success: bool,
+    pub message: String,
+    pub field: Option<String>,
+    // Expected JSON type, or the accepted field names for an unknown field
+    pub expected: Option<String>,
+    pub example: serde_json::Value,
+}
+
+// What the searcher supports, from /info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InfoResponse {
+    // Newest version spoken, then every version accepted in requests
+    pub protocol_version: u32,
+    pub protocol_versions: Vec<u32>,
+    pub network: String,
+    pub runes: Vec<RuneTerms>,
+    // rune, lightning, inscription and sponsorship, as configured
+    pub payment_methods: Vec<String>,
+    pub max_package_vsize: Option<u64>,
+    // Missing when each parent is quoted its own address
+    pub rune_address: Option<String>,
+    pub contact: Option<String>,
+}
+
+// A rune the searcher accepts and what it charges per vbyte of package, before size tiers
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuneTerms {
+    pub name: String,
+    // Set when priced in runes, or in USD converted at quote time
+    pub price_per_vbyte: Option<u64>,
+    pub usd_per_vbyte: Option<f64>,
+    // Decimal places of the rune, for showing unit amounts like price_per_vbyte
+    #[serde(default)]
+    pub divisibility: Option<u8>,
+    // Confirmations the transaction funding a payment input needs
+    #[serde(default)]
+    pub min_confirmations: Option<u32>,
+}
+
+// Posted to the low funds webhook when the searcher stops or resumes taking submissions,
+// with event "low_funds" or "funds_restored"
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FundsAlert {
+    pub event: String,
+    pub network: String,
+    pub balance_sat: u64,
+    pub free_utxos: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub message: String,
+    pub uptime_secs: u64,
+    pub pending_jobs: u64,
+    pub wallet_utxos: Option<usize>,
+    pub last_block_height: Option<u64>,
+    pub last_block_hash: Option<String>,
+    #[serde(default)]
+    pub nodes: Vec<NodeHealth>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NodeHealth {
+    pub node: String,
+    // "primary" for the node holding the wallets, "broadcast" for the ones only sent packages
+    pub role: String,
+    // "ok" or "unreachable"
+    pub status: String,
+    pub last_block_height: Option<u64>,
+    // Why it last refused a package, until it next accepts one
+    pub last_rejection: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DailyPackages {
+    // UTC date, YYYY-MM-DD
+    pub date: String,
+    pub packages: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConfirmationTimes {
+    pub count: usize,
+    pub min_secs: u64,
+    pub median_secs: u64,
+    pub p90_secs: u64,
+    pub max_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StatsResponse {
+    pub jobs: usize,
+    // Packages accepted by bitcoind plus signed payjoins
+    pub sponsored: usize,
+    pub confirmed: usize,
+    pub failed: usize,
+    #[serde(default)]
+    pub cancelled: usize,
+    pub packages_per_day: Vec<DailyPackages>,
+    // sat/vB across parent and child
+    pub average_fee_rate: Option<f64>,
+    pub average_rune_payment: Option<f64>,
+    pub average_lightning_payment_sat: Option<f64>,
+    pub confirmation_times: Option<ConfirmationTimes>,
+    pub failures: BTreeMap<String, u64>,
+    // Child fees paid from the sponsorship budget
+    pub sponsored_sat: u64,
+}
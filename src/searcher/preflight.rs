@@ -0,0 +1,200 @@
+use bitcoincore_rpc::{jsonrpc, Client, RpcApi};
+use std::fmt;
+
+use crate::http::redact_url;
+use crate::rpc;
+
+use super::server::Config;
+
+// First Bitcoin Core version that relays TRUC (v3) transactions and takes zero-fee
+// parents in packages
+pub const MIN_BITCOIND_VERSION: usize = 280000;
+
+// Blocks ord may trail bitcoind by, since it indexes a block after bitcoind has it
+const MAX_ORD_LAG_BLOCKS: u64 = 1;
+
+// Wallet RPC error for a wallet that isn't loaded
+const RPC_WALLET_NOT_FOUND: i32 = -18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    // The searcher starts, but degraded until it's fixed
+    Warn,
+    // The searcher refuses to start
+    Fail,
+}
+
+// One line of the report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let outcome = match self.outcome {
+            Outcome::Pass => "ok",
+            Outcome::Warn => "warn",
+            Outcome::Fail => "FAIL",
+        };
+        write!(f, "[{}] {}: {}", outcome, self.name, self.detail)
+    }
+}
+
+// What the searcher found out about its bitcoind, wallets and ord before starting
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn add(&mut self, name: impl Into<String>, outcome: Outcome, detail: String) {
+        self.checks.push(Check { name: name.into(), outcome, detail });
+    }
+
+    pub fn failures(&self) -> Vec<&Check> {
+        self.checks.iter().filter(|check| check.outcome == Outcome::Fail).collect()
+    }
+}
+
+// Checks everything the searcher needs before it takes jobs. Nothing being up yet only
+// degrades it, /health reports that until they are. Running against the wrong chain, a
+// bitcoind that can't relay the packages, or a wallet that doesn't exist fails.
+pub fn preflight(config: &Config) -> Report {
+    let mut report = Report::default();
+    let height = rpc::connect(
+        &config.bitcoind_host,
+        config.bitcoind_user.as_deref(),
+        config.bitcoind_password.as_deref(),
+        config.network,
+        None,
+    )
+    .map_err(|e| e.to_string())
+    .and_then(|client| check_bitcoind(config, &client, &mut report));
+    match height {
+        Ok(height) => {
+            check_wallets(config, &mut report);
+            check_ord(config, Some(height), &mut report);
+        }
+        Err(e) => {
+            report.add("bitcoind", Outcome::Warn, format!("unreachable at {}: {}", redact_url(&config.bitcoind_host), e));
+            report.add("wallets", Outcome::Warn, "not checked without bitcoind".to_string());
+            check_ord(config, None, &mut report);
+        }
+    }
+    report
+}
+
+// The chain's height, once bitcoind has answered at all
+fn check_bitcoind(config: &Config, client: &Client, report: &mut Report) -> Result<u64, String> {
+    let info = client.call::<serde_json::Value>("getblockchaininfo", &[]).map_err(|e| e.to_string())?;
+    let blocks = info.get("blocks").and_then(|blocks| blocks.as_u64()).unwrap_or_default();
+    let syncing = info.get("initialblockdownload").and_then(|syncing| syncing.as_bool()).unwrap_or_default();
+    // Testnet, testnet4 and signet addresses look alike, so only asking tells these apart
+    let chain = info.get("chain").and_then(|chain| chain.as_str()).unwrap_or_default();
+    match chain == rpc::chain_name(config.network) {
+        true if syncing => report.add("bitcoind", Outcome::Warn, format!("still syncing {} at block {}", chain, blocks)),
+        true => report.add("bitcoind", Outcome::Pass, format!("on {} at block {}", chain, blocks)),
+        false => report.add("bitcoind", Outcome::Fail, format!("bitcoind is on {} but --network is {}", chain, config.network)),
+    }
+
+    // Without TRUC relay the children themselves are non-standard. A P2P peer doing
+    // package relay can still take packages, otherwise nothing gets out.
+    let peer = config.package_peer.as_deref();
+    match rpc::node_version(client) {
+        Ok((version, subversion)) if version >= MIN_BITCOIND_VERSION => {
+            report.add("bitcoind version", Outcome::Pass, format!("{} relays TRUC transactions", subversion));
+        }
+        Ok((_, subversion)) => match peer {
+            Some(peer) => report.add(
+                "bitcoind version",
+                Outcome::Warn,
+                format!("{} predates TRUC relay, packages go to peer {}", subversion, peer),
+            ),
+            None => report.add(
+                "bitcoind version",
+                Outcome::Fail,
+                format!("{} predates TRUC relay, upgrade to 28.0 or later or set --package-peer", subversion),
+            ),
+        },
+        Err(e) => report.add("bitcoind version", Outcome::Warn, format!("unknown: {}", e)),
+    }
+
+    // bitcoind answers help for a method it doesn't have with text, not an error
+    match client.call::<String>("help", &["submitpackage".into()]) {
+        Ok(help) if !help.starts_with("help: unknown command") => {
+            report.add("submitpackage", Outcome::Pass, "available".to_string());
+        }
+        _ => match peer {
+            Some(peer) => report.add("submitpackage", Outcome::Warn, format!("unavailable, packages go to peer {}", peer)),
+            None => report.add(
+                "submitpackage",
+                Outcome::Warn,
+                "unavailable, parent and child go one at a time so zero-fee parents can't be sponsored".to_string(),
+            ),
+        },
+    }
+    Ok(blocks)
+}
+
+fn check_wallets(config: &Config, report: &mut Report) {
+    for wallet in &config.wallets {
+        let name = format!("wallet {}", wallet);
+        let balances = rpc::connect(
+            &config.bitcoind_host,
+            config.bitcoind_user.as_deref(),
+            config.bitcoind_password.as_deref(),
+            config.network,
+            Some(wallet),
+        )
+        .and_then(|client| client.get_balances());
+        match balances {
+            Ok(balances) if balances.mine.trusted.to_sat() == 0 => {
+                report.add(name, Outcome::Warn, "no confirmed funds to pay for children with".to_string());
+            }
+            Ok(balances) => report.add(name, Outcome::Pass, format!("{} spendable", balances.mine.trusted)),
+            Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e))) if e.code == RPC_WALLET_NOT_FOUND => {
+                report.add(name, Outcome::Fail, format!("not loaded in bitcoind, create or load it first: {}", e.message));
+            }
+            Err(e) => report.add(name, Outcome::Warn, format!("unknown: {}", e)),
+        }
+    }
+}
+
+fn check_ord(config: &Config, bitcoind_height: Option<u64>, report: &mut Report) {
+    let http = config.http.blocking_client();
+    let status = http
+        .get(format!("{}/status", config.ord_server))
+        .header("Accept", "application/json")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json::<serde_json::Value>());
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            report.add("ord", Outcome::Warn, format!("unreachable at {}: {}", redact_url(&config.ord_server), e));
+            return;
+        }
+    };
+    if let Some(chain) = status.get("chain").and_then(|chain| chain.as_str())
+        && chain != rpc::ord_chain_name(config.network)
+    {
+        report.add("ord", Outcome::Fail, format!("ord is on {} but --network is {}", chain, config.network));
+        return;
+    }
+
+    // Runes in blocks ord hasn't indexed would look missing
+    let height = status.get("height").and_then(|height| height.as_u64());
+    match (height, bitcoind_height) {
+        (Some(height), Some(tip)) if height + MAX_ORD_LAG_BLOCKS < tip => report.add(
+            "ord",
+            Outcome::Warn,
+            format!("at block {}, {} behind bitcoind, so recent rune payments look missing", height, tip - height),
+        ),
+        (Some(height), _) => report.add("ord", Outcome::Pass, format!("on {} at block {}", rpc::ord_chain_name(config.network), height)),
+        (None, _) => report.add("ord", Outcome::Pass, format!("on {}", rpc::ord_chain_name(config.network))),
+    }
+}
@@ -0,0 +1,113 @@
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use std::io::Cursor;
+use std::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::info;
+use tungstenite::protocol::frame::coding::{Control, Data, OpCode};
+use tungstenite::protocol::frame::{Frame, FrameHeader};
+
+use super::QuoteEvent;
+
+// Events a subscriber can fall behind by before it misses some
+const EVENT_BUFFER: usize = 16;
+
+// Subscribers only send pings and closes, so anything bigger is dropped
+const MAX_CLIENT_FRAME: usize = 4096;
+
+// The fee rates standing quotes were priced at, until they expire, and the clients to
+// tell when they're withdrawn
+#[derive(Debug)]
+pub struct QuoteBook {
+    issued: Mutex<Vec<(f64, u64)>>,
+    events: broadcast::Sender<QuoteEvent>,
+}
+
+impl Default for QuoteBook {
+    fn default() -> Self {
+        QuoteBook { issued: Mutex::new(Vec::new()), events: broadcast::channel(EVENT_BUFFER).0 }
+    }
+}
+
+impl QuoteBook {
+    pub fn issue(&self, fee_rate: f64, expires_at: u64) {
+        self.issued.lock().unwrap_or_else(|e| e.into_inner()).push((fee_rate, expires_at));
+    }
+
+    // Forgets the standing quotes priced under `stale_below`, saying whether there were any
+    pub fn withdraw_below(&self, stale_below: f64, now: u64) -> bool {
+        let mut issued = self.issued.lock().unwrap_or_else(|e| e.into_inner());
+        issued.retain(|(_, expires_at)| *expires_at > now);
+        let standing = issued.len();
+        issued.retain(|(fee_rate, _)| *fee_rate >= stale_below);
+        issued.len() < standing
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<QuoteEvent> {
+        self.events.subscribe()
+    }
+
+    // How many subscribers it went out to
+    pub fn publish(&self, event: QuoteEvent) -> usize {
+        self.events.send(event).unwrap_or(0)
+    }
+}
+
+// Sends events to an upgraded WebSocket connection as text messages until the client
+// goes away. Pings are answered and a close is returned; other messages are ignored.
+pub async fn stream(upgraded: Upgraded, mut events: broadcast::Receiver<QuoteEvent>) {
+    let mut socket = TokioIo::new(upgraded);
+    let mut received = Vec::new();
+    loop {
+        let mut replies = Vec::new();
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => match serde_json::to_vec(&event) {
+                    Ok(text) => replies.push(Frame::message(text, OpCode::Data(Data::Text), true)),
+                    Err(_) => continue,
+                },
+                Err(RecvError::Lagged(missed)) => info!("Quote event subscriber missed {} events", missed),
+                Err(RecvError::Closed) => replies.push(Frame::close(None)),
+            },
+            read = socket.read_buf(&mut received) => {
+                if !matches!(read, Ok(read) if read > 0) || received.len() > MAX_CLIENT_FRAME {
+                    return;
+                }
+                while let Some((opcode, payload)) = next_frame(&mut received) {
+                    match opcode {
+                        OpCode::Control(Control::Ping) => replies.push(Frame::pong(payload)),
+                        OpCode::Control(Control::Close) => replies.push(Frame::close(None)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for reply in replies {
+            let closing = reply.header().opcode == OpCode::Control(Control::Close);
+            let mut bytes = Vec::new();
+            if reply.format(&mut bytes).is_err() || socket.write_all(&bytes).await.is_err() || closing {
+                return;
+            }
+        }
+    }
+}
+
+// Takes the first whole frame a client sent off the front of `received`, unmasked
+fn next_frame(received: &mut Vec<u8>) -> Option<(OpCode, Vec<u8>)> {
+    let mut cursor = Cursor::new(&received[..]);
+    let (header, length) = FrameHeader::parse(&mut cursor).ok()??;
+    let start = cursor.position() as usize;
+    let end = start.checked_add(usize::try_from(length).ok()?)?;
+    if received.len() < end {
+        return None;
+    }
+    let mut payload = received[start..end].to_vec();
+    if let Some(mask) = header.mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    received.drain(..end);
+    Some((header.opcode, payload))
+}
@@ -0,0 +1,301 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use bitcoin::psbt::Psbt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+use super::client::SearcherClient;
+use super::extract::ValidJson;
+use super::signing;
+use super::{HealthResponse, QuoteRequest, QuoteResponse, SubmitPsbtRequest, SubmitPsbtResponse, PROTOCOL_VERSION};
+use crate::error::{ClientError, SearcherError, ValidationError};
+use crate::http::{redact_url, HttpConfig};
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub upstreams: Vec<String>,
+    pub race: bool,
+    pub http: HttpConfig,
+    // Key upstream responses must be signed with
+    pub upstream_key: Option<String>,
+    // Key to sign the relay's own responses with
+    pub response_key: Option<String>,
+}
+
+#[derive(Debug)]
+struct RelayState {
+    config: Config,
+    started_at: Instant,
+}
+
+fn upstream_client(config: &Config, url: &str) -> SearcherClient {
+    SearcherClient::with_http(url, config.http.blocking_client()).with_response_key(config.upstream_key.clone())
+}
+
+// Call every upstream at once, returning results in the configured order.
+// SearcherClient is blocking, so each call gets its own blocking thread.
+async fn each_upstream<T, F>(upstreams: &[String], config: &Config, call: F) -> Vec<(String, Result<T, ClientError>)>
+where
+    T: Send + 'static,
+    F: Fn(&SearcherClient) -> Result<T, ClientError> + Clone + Send + 'static,
+{
+    let mut calls = JoinSet::new();
+    for (i, url) in upstreams.iter().enumerate() {
+        let url = url.clone();
+        let call = call.clone();
+        let config = config.clone();
+        calls.spawn_blocking(move || (i, url.clone(), call(&upstream_client(&config, &url))));
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = calls.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => error!("Upstream call panicked: {}", e),
+        }
+    }
+
+    results.sort_by_key(|(i, _, _)| *i);
+    results.into_iter().map(|(_, url, result)| (url, result)).collect()
+}
+
+// Successful quotes, cheapest first
+async fn quotes(state: &RelayState, request: &QuoteRequest) -> Vec<(String, QuoteResponse)> {
+    let request = request.clone();
+    let mut quotes: Vec<(String, QuoteResponse)> = each_upstream(&state.config.upstreams, &state.config, move |client| client.quote(&request))
+        .await
+        .into_iter()
+        .filter_map(|(url, result)| match result {
+            Ok(quote) if quote.success => Some((url, quote)),
+            Ok(quote) => {
+                info!("{} refused to quote: {}", url, quote.message);
+                None
+            }
+            Err(e) => {
+                error!("Failed to get quote from {}: {}", url, e);
+                None
+            }
+        })
+        .collect();
+
+    quotes.sort_by_key(|(_, quote)| quote.rune_amount.or(quote.amount_sat).unwrap_or(u64::MAX));
+    quotes
+}
+
+async fn handle_quote(
+    State(state): State<Arc<RelayState>>,
+    ValidJson(payload): ValidJson<QuoteRequest>,
+) -> (StatusCode, Json<QuoteResponse>) {
+    info!("Relaying quote request to {} upstreams", state.config.upstreams.len());
+
+    match quote(&state, &payload).await {
+        Ok(response) => (StatusCode::OK, Json(response)),
+        Err(e) => {
+            error!("Quote failed: {}", e);
+            (e.status_code(), Json(QuoteResponse {
+                success: false,
+                protocol_version: Some(PROTOCOL_VERSION),
+                message: e.to_string(),
+                ..Default::default()
+            }))
+        }
+    }
+}
+
+async fn quote(state: &RelayState, payload: &QuoteRequest) -> Result<QuoteResponse, SearcherError> {
+    if payload.psbt.is_none() && payload.vsize.is_none() {
+        return Err(SearcherError::MissingQuoteInput);
+    }
+
+    let (url, mut quote) = quotes(state, payload).await
+        .into_iter()
+        .next()
+        .ok_or_else(|| SearcherError::Upstream("no upstream returned a quote".to_string()))?;
+
+    info!("Cheapest quote is {:?} from {}", quote.rune_amount.or(quote.amount_sat), url);
+    quote.message = format!("Quote from {}", url);
+    Ok(quote)
+}
+
+async fn handle_submit_psbt(
+    State(state): State<Arc<RelayState>>,
+    ValidJson(payload): ValidJson<SubmitPsbtRequest>,
+) -> (StatusCode, Json<SubmitPsbtResponse>) {
+    info!("Received PSBT submission to relay");
+
+    match submit_psbt(&state, &payload).await {
+        Ok(response) => (StatusCode::OK, Json(response)),
+        Err(e) => {
+            error!("Relayed submission failed: {}", e);
+            (e.status_code(), Json(SubmitPsbtResponse {
+                success: false,
+                protocol_version: Some(PROTOCOL_VERSION),
+                message: e.to_string(),
+                package_txids: None,
+                diagnosis: None,
+                parent_hex: None,
+                findings: e.findings(),
+                retry_after: e.retry_after(),
+                next_block: None,
+            }))
+        }
+    }
+}
+
+async fn submit_psbt(state: &RelayState, payload: &SubmitPsbtRequest) -> Result<SubmitPsbtResponse, SearcherError> {
+    // Reject garbage here rather than sending it to every upstream
+    Psbt::from_str(&payload.psbt).map_err(ValidationError::from)?;
+
+    // Try the cheapest upstreams first, then any that didn't quote
+    let mut candidates: Vec<String> = quotes(state, &QuoteRequest { psbt: Some(payload.psbt.clone()), ..Default::default() })
+        .await
+        .into_iter()
+        .map(|(url, _)| url)
+        .collect();
+    for url in &state.config.upstreams {
+        if !candidates.contains(url) {
+            candidates.push(url.clone());
+        }
+    }
+
+    let mut failures = Vec::new();
+    let mut accepted = None;
+
+    if state.config.race {
+        // Whichever upstream gets the package into the mempool first wins
+        let request = payload.clone();
+        for (url, result) in each_upstream(&candidates, &state.config, move |client| client.submit_psbt(&request)).await {
+            match result {
+                Ok(response) if response.success => {
+                    info!("{} accepted the package", url);
+                    accepted.get_or_insert((url, response));
+                }
+                Ok(response) => failures.push(format!("{}: {}", url, response.message)),
+                Err(e) => failures.push(format!("{}: {}", url, e)),
+            }
+        }
+    } else {
+        for url in candidates {
+            info!("Forwarding submission to {}", url);
+            let request = payload.clone();
+            let forward_url = url.clone();
+            let config = state.config.clone();
+            let result = tokio::task::spawn_blocking(move || upstream_client(&config, &forward_url).submit_psbt(&request))
+                .await
+                .map_err(|e| SearcherError::Upstream(e.to_string()))?;
+
+            match result {
+                Ok(response) if response.success => {
+                    accepted = Some((url, response));
+                    break;
+                }
+                Ok(response) => failures.push(format!("{}: {}", url, response.message)),
+                Err(e) => failures.push(format!("{}: {}", url, e)),
+            }
+        }
+    }
+
+    let (url, mut response) = accepted.ok_or_else(|| SearcherError::Upstream(failures.join(", ")))?;
+
+    response.message = if failures.is_empty() {
+        format!("{} (via {})", response.message, url)
+    } else {
+        format!("{} (via {}; failed: {})", response.message, url, failures.join(", "))
+    };
+    Ok(response)
+}
+
+async fn handle_health(State(state): State<Arc<RelayState>>) -> (StatusCode, Json<HealthResponse>) {
+    let upstreams = each_upstream(&state.config.upstreams, &state.config, |client| client.health()).await;
+
+    let mut healthy = 0;
+    let mut health = HealthResponse {
+        status: "ok".to_string(),
+        message: String::new(),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        pending_jobs: 0,
+        wallet_utxos: None,
+        last_block_height: None,
+        last_block_hash: None,
+        nodes: Vec::new(),
+    };
+
+    // Sum the upstreams' capacity and report the most recent block any of them has seen
+    for (url, result) in &upstreams {
+        match result {
+            Ok(upstream) if upstream.status == "ok" => {
+                healthy += 1;
+                health.pending_jobs += upstream.pending_jobs;
+                if let Some(utxos) = upstream.wallet_utxos {
+                    *health.wallet_utxos.get_or_insert(0) += utxos;
+                }
+                if upstream.last_block_height > health.last_block_height {
+                    health.last_block_height = upstream.last_block_height;
+                    health.last_block_hash = upstream.last_block_hash.clone();
+                }
+            }
+            Ok(upstream) => error!("Upstream {} is {}: {}", url, upstream.status, upstream.message),
+            Err(e) => error!("Upstream {} unreachable: {}", url, e),
+        }
+    }
+
+    health.message = format!("{}/{} upstream searchers healthy", healthy, upstreams.len());
+    if healthy == 0 {
+        health.status = "degraded".to_string();
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(health));
+    }
+    (StatusCode::OK, Json(health))
+}
+
+pub fn app(config: Config) -> Router {
+    let state = Arc::new(RelayState {
+        config,
+        started_at: Instant::now(),
+    });
+
+    let router = Router::new()
+        .route("/submit-psbt", post(handle_submit_psbt))
+        .route("/quote", post(handle_quote))
+        .route("/health", get(handle_health));
+    let router = match state.config.response_key.clone() {
+        Some(key) => router.layer(middleware::from_fn_with_state(Arc::new(key), signing::sign_responses)),
+        None => router,
+    };
+
+    router
+        .layer(tower_http::trace::TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+pub fn run(config: Config) -> Result<(), SearcherError> {
+    info!("Starting slugline relay...");
+    info!("Configuration:");
+    for url in &config.upstreams {
+        info!("  Upstream: {}", redact_url(url));
+    }
+    info!("  Race submissions: {}", config.race);
+
+    // Build the runtime
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    runtime.block_on(async {
+        let app = app(config);
+
+        let addr = "127.0.0.1:3000";
+        info!("Relay listening on {}", addr);
+
+        let listener = TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await?;
+        Ok(())
+    })
+}
@@ -0,0 +1,4029 @@
+use axum::{
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
+    Router,
+};
+use bitcoin::consensus::encode::deserialize_hex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{
+    absolute, address::NetworkUnchecked, psbt::{self, Psbt}, transaction::{Transaction, TxIn, TxOut, Version}, Address,
+    Amount, BlockHash, Network, NetworkKind, OutPoint, Sequence, Txid, Witness,
+};
+use bitcoincore_rpc::{jsonrpc, Client, RpcApi, json};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, info_span, Instrument, Span};
+
+use super::abuse::{self, AbuseConfig, AbuseTracker};
+use super::audit::AuditLog;
+use super::cache::{self, ResponseCache};
+use super::consolidate::{self, ConsolidationConfig};
+use super::daemon::{self, PidFile};
+use super::extract::ValidJson;
+use super::jobs::{self, Deposit, JobKind, JobStatus, JobStore, Payment};
+use super::listen::{self, TrustedProxies};
+use super::preflight::{self, Outcome};
+use super::quotes::{self, QuoteBook};
+use super::signing;
+use super::{
+    CancelResponse, FundsAlert, HealthResponse, InfoResponse, JobLogResponse, NodeHealth, PayjoinResponse, QuoteEvent, QuoteRequest, QuoteResponse, StatsResponse, SubmitPsbtRequest,
+    RescueRequest, RuneTerms, SubmitPsbtResponse, ValidatePsbtResponse, PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use crate::anchor::{create_cpfp_transaction, find_anchor, find_deposit, Anchor, AnchorPolicy, DepositTerms, KEYED_ANCHOR_EXTRA_VSIZE};
+use crate::error::{LiquidationError, NostrError, PaymentError, SearcherError, ValidationError};
+use crate::filters;
+use crate::fees::{FeeEstimator, HistogramEstimator, NextBlock};
+use crate::http::{redact_url, HttpConfig};
+use crate::inscriptions::{validate_inscription_payment, InscriptionPayment};
+use crate::liquidation::{self, LiquidationConfig};
+use crate::miniscript::estimate_signed_vsize;
+use crate::nostr::{self, Announcement};
+use crate::notify::{NotificationSink, Notifier};
+use crate::payments::{LightningConfig, LightningNode};
+use crate::policy::{self, PolicyMode};
+use crate::pricing::{price_package, tier_multiplier, ExchangeRate, HistogramPricing, PriceFeed, PriceTier, RateProvider};
+use crate::rpc;
+use crate::rules::{check_rules, ParentRule};
+use crate::rune_index::RuneIndex;
+use crate::runes::{self, validate_rune_input, RuneAmount, RUNE_NAME};
+use crate::signer::{self, ExternalSigner, SignerConfig};
+use crate::sponsorship::{self, Budget, Reservation, SponsorshipConfig};
+use crate::tor;
+
+// Estimated vsize of our CPFP child: P2A input + P2WPKH input + P2WPKH output
+const CHILD_VSIZE_ESTIMATE: u64 = 151;
+
+// A P2TR output: value, script length and a 34 byte script, the largest a rune or
+// revenue output takes
+const RUNE_OUTPUT_VSIZE: u64 = 43;
+
+const RUNE_ADDRESS_LABEL: &str = "slugline-rune";
+
+// Dust limit of a P2TR output, the largest a child's outputs need
+const OUTPUT_DUST_SAT: u64 = 330;
+
+// Announcements are refreshed so discover can tell live searchers from dead ones
+const ANNOUNCE_INTERVAL_SECS: u64 = 600;
+
+// Rune balances change only as payments arrive, so there's no point checking often
+const LIQUIDATION_INTERVAL_SECS: u64 = 600;
+
+// Fee rates above this are far more likely a typo than a real fee market
+const FAT_FINGER_FEE_RATE: f64 = 1000.0;
+
+// How long a payjoin's UTXO stays locked waiting for the submitter to broadcast
+const PAYJOIN_LOCK_SECS: u64 = 86_400;
+
+// How long a submission waits for its Lightning payment to settle
+const PAYMENT_WAIT_SECS: u64 = 30;
+
+// How often to check whether held parents' commits have confirmed
+const HELD_PARENT_INTERVAL_SECS: u64 = 30;
+
+pub const LISTEN_ADDR: &str = "127.0.0.1:3000";
+
+// Port the onion service exposes, forwarded to the first listen address
+const ONION_PORT: u16 = 80;
+
+// How often to report status to systemd when it hasn't asked for watchdog pings
+const STATUS_INTERVAL_SECS: u64 = 60;
+
+// Parent size wallet capacity is counted for, a few inputs and outputs
+const CAPACITY_PARENT_VSIZE: u64 = 250;
+
+// When to tell submitters turned away at capacity to try again, about when a block
+// has confirmed the change of the children in flight
+const CAPACITY_RETRY_SECS: u64 = 60;
+
+// How often the wallets' UTXOs are counted for capacity, off the request path
+const CAPACITY_REFRESH_SECS: u64 = 15;
+
+// When to tell submitters whose job timed out to try again, long enough for a slow ord
+// or bitcoind to catch up
+const JOB_RETRY_AFTER_SECS: u64 = 30;
+
+// How often to check on bitcoind and confirmations when there are notification sinks
+const NOTIFY_CHECK_SECS: u64 = 60;
+
+// Package submissions bitcoind refuses in a row before the operator is told
+const SUBMIT_FAILURE_ALERT: u64 = 3;
+
+// First Bitcoin Core version whose submitpackage takes zero-fee TRUC parents
+const PACKAGE_RELAY_VERSION: usize = 280000;
+
+// How long read-only responses are reused. /info only changes on restart, /stats is
+// costly to compute, and quotes follow the fee rate so they're kept briefly.
+const INFO_CACHE_SECS: u64 = 60;
+const STATS_CACHE_SECS: u64 = 30;
+const QUOTE_CACHE_SECS: u64 = 5;
+
+// How often standing quotes are checked against the fee rate for spikes
+const FEE_SPIKE_CHECK_SECS: u64 = 30;
+
+// How often submitted packages are checked against the next block template
+const NEXT_BLOCK_CHECK_SECS: u64 = 60;
+// How far over the next block's lowest package rate a bumped package pays, in sat/vB
+const NEXT_BLOCK_BUMP_MARGIN: f64 = 1.0;
+
+// How often submitted parents in the mempool are checked for a different witness
+const PARENT_WITNESS_CHECK_SECS: u64 = 60;
+
+// What to do with a submitted parent when its child can't be built or signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoftFail {
+    #[default]
+    Off,
+    // Broadcast the parent alone when bitcoind would relay it
+    Broadcast,
+    // Hand the parent back with why the child failed and whether it relays alone
+    Return,
+}
+
+// Sats every child sends to a cold address, taking profit out of the hot wallet as it goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revenue {
+    pub address: Address,
+    pub amount_sat: u64,
+}
+
+impl Revenue {
+    fn output(&self) -> TxOut {
+        TxOut { value: Amount::from_sat(self.amount_sat), script_pubkey: self.address.script_pubkey() }
+    }
+}
+
+// Balances under which the searcher stops taking submissions and tells the operator,
+// checked every `interval` seconds
+#[derive(Debug, Clone)]
+pub struct LowFunds {
+    pub min_balance_sat: Option<u64>,
+    pub min_utxos: Option<u64>,
+    pub interval: u64,
+    pub webhook: Option<String>,
+    // Hex pubkey mentioned in a Nostr note sent with nostr_secret_key to nostr_relays
+    pub nostr_pubkey: Option<String>,
+}
+
+// Another network served by the same process under /<network>, parsed from
+// "network=<name>,bitcoind=<url>,ord=<url>,wallet=<name>[,rune-address=<address>]"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub network: Network,
+    pub bitcoind_host: String,
+    pub ord_server: String,
+    pub wallet: String,
+    pub rune_address: Option<String>,
+}
+
+impl FromStr for NetworkConfig {
+    type Err = SearcherError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = HashMap::new();
+        for field in s.split(',') {
+            let (key, value) = field.split_once('=')
+                .ok_or_else(|| SearcherError::Config(format!("expected key=value in {}, got {}", s, field)))?;
+            if !["network", "bitcoind", "ord", "wallet", "rune-address"].contains(&key) {
+                return Err(SearcherError::Config(format!("unknown key {} in {}", key, s)));
+            }
+            fields.insert(key, value.to_string());
+        }
+        let mut required = |key| fields.remove(key)
+            .ok_or_else(|| SearcherError::Config(format!("{} is missing {}", s, key)));
+        let network = required("network")?;
+        Ok(NetworkConfig {
+            network: Network::from_str(&network)
+                .map_err(|_| SearcherError::Config(format!("unknown network {}", network)))?,
+            bitcoind_host: required("bitcoind")?,
+            ord_server: required("ord")?,
+            wallet: required("wallet")?,
+            rune_address: fields.remove("rune-address"),
+        })
+    }
+}
+
+// Another bitcoind packages are sent to once the main one accepts them, so they reach more
+// of the network, parsed from "[<user>:<password>@]<host>[:<port>]". It only broadcasts,
+// never holds a wallet, and uses the main node's credentials unless given its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastNode {
+    pub host: String,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+impl FromStr for BroadcastNode {
+    type Err = SearcherError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Only the host, since the rest may hold a password
+        let (credentials, address) = match s.rsplit_once('@') {
+            Some((credentials, address)) => (Some(credentials), address),
+            None => (None, s),
+        };
+        let invalid = || SearcherError::Config(format!(
+            "invalid broadcast node {}: expected [<user>:<password>@]<host>[:<port>]",
+            address
+        ));
+        let (user, password) = match credentials.map(|credentials| credentials.split_once(':')) {
+            None => (None, None),
+            Some(Some((user, password))) if !user.is_empty() => (Some(user.to_string()), Some(password.to_string())),
+            Some(_) => return Err(invalid()),
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse().map_err(|_| invalid())?)),
+            None => (address, None),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(BroadcastNode { host: host.to_string(), port, user, password })
+    }
+}
+
+impl BroadcastNode {
+    // How jobs and /health name it
+    pub fn address(&self, network: Network) -> String {
+        format!("{}:{}", self.host, self.port.unwrap_or_else(|| rpc::rpc_port(network)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bitcoind_host: String,
+    pub bitcoind_user: Option<String>,
+    pub bitcoind_password: Option<String>,
+    // Nodes accepted packages are also sent to, besides bitcoind_host
+    pub broadcast_nodes: Vec<BroadcastNode>,
+    // P2P peer ("host:port") to announce packages to when bitcoind has no submitpackage
+    pub package_peer: Option<String>,
+    pub network: Network,
+    pub ord_server: String,
+    // Jobs are sharded across these round-robin; the first also handles quotes and liquidation
+    pub wallets: Vec<String>,
+    pub fee_rate: f64,
+    // Highest rate to ever pay, clamping static and estimated rates alike
+    pub max_fee_rate: Option<f64>,
+    // Refuse to pay more than this per job, in sats or as a percentage of the UTXO spent
+    pub max_fee: Option<u64>,
+    pub max_fee_percent: Option<f64>,
+    // Most a parent may pay itself; the child is priced as if it paid nothing
+    pub max_parent_fee: Option<u64>,
+    pub rune_address: Option<String>,
+    // Give each parent paying in runes its own receive address instead of rune_address
+    pub rotate_rune_address: bool,
+    // In the rune's smallest units, with the rune's decimal places for reading and showing amounts
+    pub rune_price: u64,
+    pub rune_divisibility: u8,
+    // Confirmations the transactions funding rune payment inputs need
+    pub min_payment_confirmations: u32,
+    // Other indexes that have to agree with ord about the runes in every payment input
+    pub rune_indexes: Vec<Arc<dyn RuneIndex>>,
+    pub quote_ttl: u64,
+    // Withdraw standing quotes once the fee rate climbs this many percent over what they were priced at
+    pub quote_fee_spike: Option<f64>,
+    // Check packages against bitcoind's block template, to report and watch whether they make the next block
+    pub predict_next_block: bool,
+    // Replace the child of a package that fell out of the next block with one paying its way back in
+    pub bump_to_next_block: bool,
+    pub public_url: Option<String>,
+    // How to reach the operator, published at /info
+    pub contact: Option<String>,
+    pub nostr_relays: Vec<String>,
+    pub nostr_secret_key: Option<String>,
+    pub lightning: Option<LightningConfig>,
+    pub inscription_payment: Option<InscriptionPayment>,
+    pub usd_price: Option<f64>,
+    pub rate_providers: Vec<RateProvider>,
+    pub rate_max_age: u64,
+    // Price from the mempool's fee histogram instead of rune_price, which is the fallback
+    pub histogram_pricing: Option<HistogramPricing>,
+    // Deepest into the mempool a sponsored package may sit, in vbytes from the top
+    pub max_mempool_depth: Option<u64>,
+    // Price multipliers by parent vsize, and the largest package sponsored at all
+    pub price_tiers: Vec<PriceTier>,
+    pub max_package_vsize: Option<u64>,
+    pub ephemeral_policy: PolicyMode,
+    // Whether parents anchor with P2A or to a key /quote hands out
+    pub anchor_policy: AnchorPolicy,
+    // Deposit parents with a keyed anchor have to carry, refundable after a delay
+    pub anchor_deposit: Option<DepositTerms>,
+    pub revenue: Option<Revenue>,
+    pub low_funds: Option<LowFunds>,
+    pub consolidation: Option<ConsolidationConfig>,
+    // Where operators hear about trouble, and the package fee past which a confirmation is news
+    pub notify: Vec<Arc<dyn NotificationSink>>,
+    pub notify_large_job_sat: Option<u64>,
+    // Sign children, payjoin inputs and replacements here instead of in bitcoind
+    pub external_signer: Option<SignerConfig>,
+    // Confirmation target for dynamic fee rates, with fee_rate as the fallback
+    pub fee_target: Option<u16>,
+    pub mempool_api: Option<String>,
+    pub pid_file: Option<PathBuf>,
+    pub http: HttpConfig,
+    // Addresses to serve on, and how to find clients' addresses behind a proxy
+    pub listen: Vec<SocketAddr>,
+    pub trusted_proxies: Vec<IpNet>,
+    pub proxy_protocol: bool,
+    // Tor control port to publish the searcher as an onion service through
+    pub tor_control: Option<String>,
+    pub tor_password: Option<String>,
+    pub tor_key_file: Option<PathBuf>,
+    // Shared secret to sign responses with, so clients behind untrusted proxies can verify them
+    pub response_key: Option<String>,
+    // JSON Lines file recording every job, kept across restarts for /stats
+    pub job_store: Option<PathBuf>,
+    // Hash-chained JSON Lines record of every job accepted, rejected, cancelled or confirmed
+    pub audit_log: Option<PathBuf>,
+    // Sell accumulated runes for BTC through a marketplace
+    pub liquidation: Option<LiquidationConfig>,
+    // Anchor parents paying whitelisted scripts for free, up to a daily budget
+    pub sponsorship: Option<SponsorshipConfig>,
+    // Seconds between scans of the mempool for parents broadcast without the API
+    pub mempool_scan: Option<u64>,
+    pub soft_fail: SoftFail,
+    // Seconds a submission may take before it's abandoned and the client told to retry
+    pub job_timeout: u64,
+    pub abuse: AbuseConfig,
+    // Further networks to serve alongside this one
+    pub networks: Vec<NetworkConfig>,
+    // Operator rules every parent has to pass before it's sponsored or paid for
+    pub rules: Vec<Arc<dyn ParentRule>>,
+    // Regtest only: mine a block after each accepted package, and every this many seconds
+    pub auto_mine: bool,
+    pub auto_mine_interval: Option<u64>,
+}
+
+impl Config {
+    // The configuration for another network: its own nodes, wallet and job store, and none
+    // of the addresses, Lightning node, sponsorship or liquidation set up for this one
+    fn for_network(&self, network: &NetworkConfig) -> Config {
+        Config {
+            bitcoind_host: network.bitcoind_host.clone(),
+            network: network.network,
+            ord_server: network.ord_server.clone(),
+            wallets: vec![network.wallet.clone()],
+            rune_address: network.rune_address.clone(),
+            rotate_rune_address: self.rotate_rune_address || network.rune_address.is_none(),
+            public_url: None,
+            nostr_relays: Vec::new(),
+            nostr_secret_key: None,
+            lightning: None,
+            inscription_payment: None,
+            pid_file: None,
+            tor_control: None,
+            tor_password: None,
+            tor_key_file: None,
+            job_store: self.job_store.as_ref()
+                .map(|path| PathBuf::from(format!("{}.{}", path.display(), network.network))),
+            audit_log: self.audit_log.as_ref()
+                .map(|path| PathBuf::from(format!("{}.{}", path.display(), network.network))),
+            liquidation: None,
+            sponsorship: None,
+            revenue: None,
+            // Without the Nostr key, which is this process's identity on the main network
+            low_funds: self.low_funds.clone().map(|low_funds| LowFunds { nostr_pubkey: None, ..low_funds }),
+            networks: Vec::new(),
+            // Rules can name addresses, which belong to one network
+            rules: Vec::new(),
+            rune_indexes: Vec::new(),
+            broadcast_nodes: Vec::new(),
+            package_peer: None,
+            auto_mine: self.auto_mine && network.network == Network::Regtest,
+            auto_mine_interval: self.auto_mine_interval.filter(|_| network.network == Network::Regtest),
+            ..self.clone()
+        }
+    }
+}
+
+// Cumulative counts come from the job store, so they survive restarts along with it
+#[derive(Debug, Default)]
+struct Metrics {
+    pending_jobs: AtomicU64,
+}
+
+// Counts a submission as pending for as long as its handler is running
+struct PendingJob<'a>(&'a AtomicU64);
+
+impl<'a> PendingJob<'a> {
+    fn start(pending_jobs: &'a AtomicU64) -> Self {
+        pending_jobs.fetch_add(1, Ordering::Relaxed);
+        PendingJob(pending_jobs)
+    }
+}
+
+impl Drop for PendingJob<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct AppState {
+    config: Config,
+    started_at: Instant,
+    metrics: Metrics,
+    lightning: Option<LightningNode>,
+    price_feed: Option<PriceFeed>,
+    fees: Option<FeeEstimator>,
+    histogram: Option<HistogramEstimator>,
+    depth: Option<HistogramEstimator>,
+    next_block: Option<NextBlock>,
+    // Shared by every ord, pricing and Lightning request so connections are pooled
+    http: reqwest::Client,
+    jobs: JobStore,
+    // Round-robin position across config.wallets
+    next_wallet: AtomicUsize,
+    budget: Option<Budget>,
+    // Rotated rune addresses by the parent they were handed out for, until the quote expires
+    rune_addresses: Mutex<HashMap<Txid, (Address, u64)>>,
+    abuse: Arc<AbuseTracker>,
+    signer: Option<ExternalSigner>,
+    // Set while the wallets are under the low funds thresholds
+    low_funds: Mutex<Option<WalletFunds>>,
+    // The wallets' funds as last counted in the background, None until bitcoind answers
+    funds: Mutex<Option<WalletFunds>>,
+    // UTXOs a job has picked and not yet spent, so concurrent jobs each fund from their own
+    claimed_utxos: Mutex<HashSet<OutPoint>>,
+    notifier: Notifier,
+    // What the operator was last told, so each problem is announced once
+    bitcoind_down: AtomicBool,
+    wallet_empty: AtomicBool,
+    submit_failures: AtomicU64,
+    // A lock per job whose child is being replaced, so replacements of a job wait their
+    // turn instead of competing for one anchor
+    replacing: Mutex<HashMap<u64, Arc<tokio::sync::Mutex<()>>>>,
+    quotes: QuoteBook,
+    // Why each broadcast node last refused a package, until it next accepts one
+    node_rejections: Mutex<HashMap<String, String>>,
+    // Whether bitcoind takes packages through submitpackage, checked at startup
+    package_rpc: AtomicBool,
+}
+
+// Spendable sats across the wallets, and how many of their UTXOs could fund a package
+#[derive(Debug, Clone, Copy)]
+struct WalletFunds {
+    balance_sat: u64,
+    free_utxos: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// The rate to sponsor at right now, static unless a confirmation target is configured
+fn fee_rate(state: &AppState, client: Option<&Client>) -> f64 {
+    let rate = match &state.fees {
+        // Estimation makes blocking HTTP calls
+        Some(fees) => tokio::task::block_in_place(|| fees.rate(client)),
+        None => state.config.fee_rate,
+    };
+    match state.config.max_fee_rate {
+        Some(max) if rate > max => {
+            error!("Fee rate of {} sat/vB is over the {} sat/vB cap, clamping", rate, max);
+            max
+        }
+        _ => rate,
+    }
+}
+
+// The rate that gets a package within --max-mempool-depth of the top of the mempool. None
+// without a depth, or when there's no histogram to read it off.
+fn depth_rate(state: &AppState, client: Option<&Client>) -> Option<f64> {
+    let estimator = state.depth.as_ref()?;
+    let rate = tokio::task::block_in_place(|| estimator.rate(client));
+    if rate.is_none() {
+        error!("No mempool fee histogram available, not enforcing the mempool depth");
+    }
+    rate
+}
+
+// What the child pays: the searcher's rate, raised to reach the mempool depth but never
+// past the cap
+fn child_rate(state: &AppState, rate: f64, depth_rate: Option<f64>) -> f64 {
+    match (depth_rate, state.config.max_fee_rate) {
+        (Some(depth), Some(max)) if depth > rate => depth.min(max),
+        (Some(depth), None) => rate.max(depth),
+        _ => rate,
+    }
+}
+
+// The fee rate rune prices pay for: the histogram's under histogram pricing, otherwise
+// the rate the searcher would pay
+fn priced_rate(state: &AppState, client: Option<&Client>, fee_rate: f64) -> f64 {
+    match (&state.config.histogram_pricing, &state.histogram) {
+        (Some(_), Some(estimator)) => tokio::task::block_in_place(|| estimator.rate(client)).unwrap_or(fee_rate),
+        _ => fee_rate,
+    }
+}
+
+// A rune price scaled up from the rate it pays for to the rate the mempool depth takes
+fn price_for_depth(price: RuneAmount, priced_rate: f64, depth_rate: Option<f64>) -> RuneAmount {
+    match depth_rate {
+        Some(depth) if depth > priced_rate && priced_rate > 0.0 => {
+            RuneAmount::from_units_ceil(price.units as f64 * depth / priced_rate, price.divisibility)
+        }
+        _ => price,
+    }
+}
+
+// Last line of defence against a bad rate or estimate draining a UTXO into fees
+fn check_fee(config: &Config, fee: u64, utxo_value: u64) -> Result<(), SearcherError> {
+    let percent_cap = config.max_fee_percent.map(|percent| (utxo_value as f64 * percent / 100.0) as u64);
+    match config.max_fee.into_iter().chain(percent_cap).min() {
+        Some(cap) if fee > cap => Err(SearcherError::FeeCap { fee, cap }),
+        _ => Ok(()),
+    }
+}
+
+// Rune units for a package at the rate `depth_vbytes` into the mempool. None when there's
+// no histogram to read, so the static price applies.
+fn price_package_histogram(state: &AppState, pricing: &HistogramPricing, package_vsize: u64, multiplier: f64) -> Option<RuneAmount> {
+    let estimator = state.histogram.as_ref()?;
+    let client = connect_rpc(state, primary_wallet(&state.config)).ok();
+    // Reading the histogram makes blocking RPC and HTTP calls
+    let rate = tokio::task::block_in_place(|| estimator.rate(client.as_ref()))?;
+    let per_vbyte = pricing.units_per_vbyte(rate, state.config.rune_divisibility);
+    Some(RuneAmount::from_units_ceil(package_vsize as f64 * per_vbyte * multiplier, state.config.rune_divisibility))
+}
+
+fn price_package_usd(package_vsize: u64, usd_price: f64, usd_per_rune: f64, divisibility: u8) -> RuneAmount {
+    RuneAmount::from_units_ceil(package_vsize as f64 * usd_price / usd_per_rune, divisibility)
+}
+
+fn connect_rpc(state: &AppState, wallet: &str) -> Result<Client, bitcoincore_rpc::Error> {
+    let config = &state.config;
+    match &config.bitcoind_user {
+        Some(user) if config.bitcoind_password.is_some() => info!("Using RPC auth with user: {}", user),
+        _ => info!("Using RPC with no auth"),
+    }
+
+    let rpc_url = rpc::rpc_url(&config.bitcoind_host, config.network, Some(wallet));
+    info!("Connecting to Bitcoin Core RPC at: {} (network: {:?})", redact_url(&rpc_url), config.network);
+
+    match rpc::connect(
+        &config.bitcoind_host,
+        config.bitcoind_user.as_deref(),
+        config.bitcoind_password.as_deref(),
+        config.network,
+        Some(wallet),
+    ) {
+        Ok(client) => {
+            info!("Successfully connected to Bitcoin Core");
+            Ok(client)
+        },
+        Err(e) => {
+            error!("Failed to connect to Bitcoin Core at {}: {}", redact_url(&rpc_url), e);
+            Err(e)
+        }
+    }
+}
+
+// One huge consolidation shouldn't be able to eat the whole wallet or sponsorship budget
+fn check_package_size(config: &Config, package_vsize: u64) -> Result<(), ValidationError> {
+    match config.max_package_vsize {
+        Some(max) if package_vsize > max => Err(ValidationError::PackageTooLarge { vsize: package_vsize, max }),
+        _ => Ok(()),
+    }
+}
+
+// What anchoring a parent is expected to cost, before a child exists to measure
+fn estimate_package_cost(state: &AppState, parent_vsize: u64) -> u64 {
+    (fee_rate(state, None) * (parent_vsize + child_vsize_estimate(&state.config)) as f64).ceil() as u64
+}
+
+fn child_vsize_estimate(config: &Config) -> u64 {
+    CHILD_VSIZE_ESTIMATE + config.revenue.as_ref().map_or(0, |_| RUNE_OUTPUT_VSIZE)
+}
+
+// A wallet's confirmed coins that are plain bitcoin. Runes paid to the searcher sit as
+// dust on its rune addresses, and spending one of those as bitcoin takes the runes along.
+fn spendable_utxos(config: &Config, client: &Client) -> Result<Vec<json::ListUnspentResultEntry>, bitcoincore_rpc::Error> {
+    let carries_runes = |utxo: &json::ListUnspentResultEntry| {
+        utxo.label.as_deref() == Some(RUNE_ADDRESS_LABEL)
+            || utxo.address.as_ref().zip(config.rune_address.as_ref())
+                .is_some_and(|(address, rune_address)| address.assume_checked_ref().to_string() == *rune_address)
+    };
+    Ok(client.list_unspent(Some(1), None, None, None, None)?
+        .into_iter()
+        .filter(|utxo| !carries_runes(utxo))
+        .collect())
+}
+
+// The smallest UTXO that pays for the package at `rate` and leaves the child's outputs
+// above dust, so a child isn't built from a coin that can't afford it
+fn covering_utxo(config: &Config, parent: &Transaction, rate: f64, rune_output: bool) -> impl Fn(&[json::ListUnspentResultEntry]) -> Option<json::ListUnspentResultEntry> {
+    let keyed = parent.output.first().is_some_and(|anchor| anchor.script_pubkey.is_p2wpkh());
+    let vsize = parent.vsize() as u64 + child_vsize_estimate(config)
+        + if keyed { KEYED_ANCHOR_EXTRA_VSIZE } else { 0 }
+        + if rune_output { RUNE_OUTPUT_VSIZE } else { 0 };
+    let needed = (rate * vsize as f64).ceil() as u64
+        + config.revenue.as_ref().map_or(0, |revenue| revenue.amount_sat)
+        + if rune_output { OUTPUT_DUST_SAT } else { 0 }
+        + OUTPUT_DUST_SAT;
+    move |unspent| {
+        unspent.iter()
+            .filter(|utxo| utxo.amount.to_sat() >= needed)
+            .min_by_key(|utxo| utxo.amount)
+            .cloned()
+    }
+}
+
+// How many packages the wallets can fund at once: their confirmed UTXOs big enough to pay
+// for a typical package. None when no wallet could be asked.
+fn wallet_funds(state: &AppState) -> Option<WalletFunds> {
+    let cost = estimate_package_cost(state, CAPACITY_PARENT_VSIZE);
+    let cost = state.config.max_fee.map_or(cost, |max| cost.min(max))
+        + state.config.revenue.as_ref().map_or(0, |revenue| revenue.amount_sat);
+    let mut funds = None;
+    for wallet in &state.config.wallets {
+        let unspent = connect_rpc(state, wallet)
+            .and_then(|client| spendable_utxos(&state.config, &client));
+        match unspent {
+            Ok(unspent) => {
+                let funds = funds.get_or_insert(WalletFunds { balance_sat: 0, free_utxos: 0 });
+                funds.balance_sat += unspent.iter().map(|utxo| utxo.amount.to_sat()).sum::<u64>();
+                funds.free_utxos += unspent.iter().filter(|utxo| utxo.amount.to_sat() > cost).count() as u64;
+            }
+            Err(e) => error!("Could not count UTXOs of wallet {}: {}", wallet, e),
+        }
+    }
+    funds
+}
+
+// As of the last background count. UTXOs jobs have claimed are still in it, as are the
+// jobs that claimed them, so the two cancel out.
+fn wallet_capacity(state: &AppState) -> Option<u64> {
+    state.funds.lock().unwrap_or_else(|e| e.into_inner()).map(|funds| funds.free_utxos)
+}
+
+// Counts the wallets' UTXOs, which takes a listunspent per wallet, so submissions can be
+// turned away at capacity without asking bitcoind
+async fn count_funds(state: Arc<AppState>) {
+    loop {
+        let funds = tokio::task::block_in_place(|| wallet_funds(&state));
+        *state.funds.lock().unwrap_or_else(|e| e.into_inner()) = funds;
+        tokio::time::sleep(Duration::from_secs(CAPACITY_REFRESH_SECS)).await;
+    }
+}
+
+fn refuse_submission(e: SearcherError, retry_after: u64) -> Response {
+    error!("Refusing submission: {}", e);
+    let body = Json(serde_json::json!({ "success": false, "message": e.to_string() }));
+    (e.status_code(), [(header::RETRY_AFTER, retry_after.to_string())], body).into_response()
+}
+
+// Turn submissions away while every fundable UTXO already has a job, instead of failing
+// them at UTXO selection after the payment was checked
+async fn backpressure(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let low_funds = *state.low_funds.lock().unwrap_or_else(|e| e.into_inner());
+    if let (Some(funds), Some(config)) = (low_funds, &state.config.low_funds) {
+        let e = SearcherError::LowFunds { balance_sat: funds.balance_sat, free_utxos: funds.free_utxos };
+        return refuse_submission(e, config.interval);
+    }
+
+    let in_flight = state.metrics.pending_jobs.load(Ordering::Relaxed);
+    if let Some(capacity) = wallet_capacity(&state)
+        && in_flight >= capacity
+    {
+        return refuse_submission(SearcherError::AtCapacity { in_flight, capacity }, CAPACITY_RETRY_SECS);
+    }
+    next.run(request).await
+}
+
+// Switch to quotes only while the wallets are low, rather than have submissions fail at
+// UTXO selection, and tell the operator when that starts and ends
+async fn watch_funds(state: Arc<AppState>, low_funds: LowFunds) {
+    loop {
+        check_funds(&state, &low_funds).await;
+        tokio::time::sleep(Duration::from_secs(low_funds.interval)).await;
+    }
+}
+
+async fn consolidate_change(state: Arc<AppState>, consolidation: ConsolidationConfig) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(consolidation.interval)).await;
+        for wallet in &state.config.wallets {
+            // listunspent and signing are blocking RPC calls
+            match tokio::task::block_in_place(|| consolidate_wallet(&state, wallet, &consolidation)) {
+                Ok(Some(txid)) => info!("Consolidated change of wallet {} in {}", wallet, txid),
+                Ok(None) => {}
+                Err(e) => error!("Failed to consolidate wallet {}: {}", wallet, e),
+            }
+        }
+    }
+}
+
+// Merges a wallet's confirmed change while fees are low. The inputs are locked before
+// anything else, so children stop picking them, and handed back if a job was already
+// in flight and might have picked one first.
+fn consolidate_wallet(state: &AppState, wallet: &str, consolidation: &ConsolidationConfig) -> Result<Option<Txid>, SearcherError> {
+    let client = connect_rpc(state, wallet)?;
+    let rate = fee_rate(state, Some(&client));
+    if rate > consolidation.max_fee_rate {
+        return Ok(None);
+    }
+    let claimed = state.claimed_utxos.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    // Runes on the searcher's rune addresses would end up on plain change
+    let unspent: Vec<_> = spendable_utxos(&state.config, &client)?
+        .into_iter()
+        .filter(|utxo| !claimed.contains(&OutPoint { txid: utxo.txid, vout: utxo.vout }))
+        .collect();
+    let Some(plan) = consolidate::plan(&unspent, consolidation, rate) else {
+        return Ok(None);
+    };
+
+    client.lock_unspent(&plan.inputs)?;
+    if state.metrics.pending_jobs.load(Ordering::Relaxed) > 0 {
+        info!("Jobs in flight, putting off consolidating wallet {}", wallet);
+        client.unlock_unspent(&plan.inputs)?;
+        return Ok(None);
+    }
+    let broadcast = (0..plan.outputs.len())
+        .map(|_| change_script(&client, state.config.network))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|scripts| {
+            let tx = consolidate::transaction(&plan, scripts);
+            let signed = client.sign_raw_transaction_with_wallet(&tx, None, None)?;
+            if !signed.complete {
+                return Err(SearcherError::IncompleteSignature);
+            }
+            Ok(client.send_raw_transaction(&signed.hex)?)
+        });
+    match broadcast {
+        Ok(txid) => {
+            info!("Merged {} change UTXOs into {} paying {} sats", plan.inputs.len(), plan.outputs.len(), plan.fee);
+            Ok(Some(txid))
+        }
+        Err(e) => {
+            let _ = client.unlock_unspent(&plan.inputs);
+            Err(e)
+        }
+    }
+}
+
+async fn check_funds(state: &AppState, low_funds: &LowFunds) {
+    // An unreachable bitcoind is for /health to report, not a funds problem
+    // listunspent is a blocking RPC call
+    let Some(funds) = tokio::task::block_in_place(|| wallet_funds(state)) else {
+        return;
+    };
+    let low = low_funds.min_balance_sat.is_some_and(|min| funds.balance_sat < min)
+        || low_funds.min_utxos.is_some_and(|min| funds.free_utxos < min);
+    let was_low = std::mem::replace(&mut *state.low_funds.lock().unwrap_or_else(|e| e.into_inner()), low.then_some(funds))
+        .is_some();
+
+    let (event, message) = match (was_low, low) {
+        (false, true) => {
+            let message = SearcherError::LowFunds { balance_sat: funds.balance_sat, free_utxos: funds.free_utxos }.to_string();
+            error!("{}", message);
+            state.notifier.notify("low_funds", message.clone());
+            ("low_funds", message)
+        }
+        (true, false) => {
+            let message = format!(
+                "Searcher wallets are funded again ({} sats in {} usable UTXOs), taking submissions",
+                funds.balance_sat,
+                funds.free_utxos
+            );
+            info!("{}", message);
+            state.notifier.notify("funds_restored", message.clone());
+            ("funds_restored", message)
+        }
+        _ => return,
+    };
+
+    if let Some(url) = &low_funds.webhook {
+        let alert = FundsAlert {
+            event: event.to_string(),
+            network: state.config.network.to_string(),
+            balance_sat: funds.balance_sat,
+            free_utxos: funds.free_utxos,
+            message: message.clone(),
+        };
+        match state.http.post(url).json(&alert).send().await.and_then(|response| response.error_for_status()) {
+            Ok(_) => info!("Sent {} alert to {}", event, redact_url(url)),
+            Err(e) => error!("Failed to send {} alert to {}: {}", event, redact_url(url), e),
+        }
+    }
+    if let (Some(recipient), Some(secret_key)) = (&low_funds.nostr_pubkey, &state.config.nostr_secret_key) {
+        let event = nostr::parse_secret_key(secret_key)
+            .and_then(|keypair| nostr::mention_event(&keypair, recipient, &message));
+        let relays = state.config.nostr_relays.clone();
+        let published = tokio::task::spawn_blocking(move || {
+            let event = event?;
+            for relay in &relays {
+                if let Err(e) = nostr::publish(relay, &event) {
+                    error!("Failed to send alert to {}: {}", relay, e);
+                }
+            }
+            Ok::<_, NostrError>(())
+        });
+        if let Ok(Err(e)) = published.await {
+            error!("Failed to sign Nostr alert: {}", e);
+        }
+    }
+}
+
+// Reserve budget for a parent paying a whitelisted script, if there's enough left today
+fn sponsor<'a>(state: &'a AppState, tx: &Transaction) -> Option<(String, Reservation<'a>)> {
+    let templates = &state.config.sponsorship.as_ref()?.templates;
+    let template = sponsorship::matching_template(templates, tx)?;
+    let budget = state.budget.as_ref()?;
+
+    let estimate = estimate_package_cost(state, tx.vsize() as u64);
+    match budget.reserve(estimate, now()) {
+        Some(reservation) => {
+            info!("Parent pays sponsored script {}, reserved {} sats of today's budget", template.name, estimate);
+            Some((template.name.clone(), reservation))
+        }
+        None => {
+            info!("Parent pays sponsored script {} but today's budget is spent, requiring payment", template.name);
+            None
+        }
+    }
+}
+
+fn primary_wallet(config: &Config) -> &str {
+    config.wallets.first().map(String::as_str).unwrap_or_default()
+}
+
+// A UTXO one job picked to fund with. Other jobs pass over it until the claim is dropped,
+// by which time the job has spent it, locked it in bitcoind or given up on it.
+struct UtxoClaim<'a> {
+    state: &'a AppState,
+    outpoint: OutPoint,
+}
+
+impl Drop for UtxoClaim<'_> {
+    fn drop(&mut self) {
+        self.state.claimed_utxos.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.outpoint);
+    }
+}
+
+// Find a UTXO to fund a job with, starting from the next wallet in round-robin order.
+// Wallets that are empty or unreachable are skipped, so one wallet whose coins are tied
+// up in stuck children only slows down its own share of jobs. UTXOs other jobs have
+// claimed are skipped too, and the one picked is claimed before anyone else can look.
+fn select_wallet_utxo<'a>(
+    state: &'a AppState,
+    pick: impl Fn(&[json::ListUnspentResultEntry]) -> Option<json::ListUnspentResultEntry>,
+) -> Result<(Client, String, json::ListUnspentResultEntry, UtxoClaim<'a>), SearcherError> {
+    let wallets = &state.config.wallets;
+    let start = state.next_wallet.fetch_add(1, Ordering::Relaxed);
+    let mut last_error = None;
+
+    for i in 0..wallets.len() {
+        let wallet = &wallets[(start + i) % wallets.len()];
+        info!("Fetching UTXOs of wallet {}...", wallet);
+        let unspent = connect_rpc(state, wallet)
+            .and_then(|client| Ok((spendable_utxos(&state.config, &client)?, client)));
+        match unspent {
+            Ok((unspent, client)) => {
+                info!("Found {} unspent UTXOs in wallet {}", unspent.len(), wallet);
+                let mut claimed = state.claimed_utxos.lock().unwrap_or_else(|e| e.into_inner());
+                let unclaimed: Vec<_> = unspent.into_iter()
+                    .filter(|utxo| !claimed.contains(&OutPoint { txid: utxo.txid, vout: utxo.vout }))
+                    .collect();
+                if let Some(utxo) = pick(&unclaimed) {
+                    let outpoint = OutPoint { txid: utxo.txid, vout: utxo.vout };
+                    claimed.insert(outpoint);
+                    state.wallet_empty.store(false, Ordering::Relaxed);
+                    return Ok((client, wallet.clone(), utxo, UtxoClaim { state, outpoint }));
+                }
+            }
+            Err(e) => {
+                error!("Skipping wallet {}, make sure Bitcoin Core is running and the wallet is loaded: {}", wallet, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    // Only blame bitcoind when no wallet could even be asked
+    match last_error {
+        Some(e) if wallets.len() == 1 => Err(e.into()),
+        _ => {
+            if !state.wallet_empty.swap(true, Ordering::Relaxed) {
+                state.notifier.notify("wallet_empty", "No wallet has a confirmed UTXO left to fund a child".to_string());
+            }
+            Err(SearcherError::NoWalletUtxos)
+        }
+    }
+}
+
+// One span per submission, with a child span for each step so exported traces show where time goes
+#[tracing::instrument(name = "submit_psbt", skip_all, fields(txid = tracing::field::Empty, otel.status_code = tracing::field::Empty))]
+async fn handle_submit_psbt(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    ValidJson(payload): ValidJson<SubmitPsbtRequest>,
+) -> Response {
+    info!("Received PSBT submission");
+    let _pending = PendingJob::start(&state.metrics.pending_jobs);
+    let job = state.jobs.start(JobKind::Package, now());
+    state.jobs.update(job, |job| job.client = connect_info.map(|ConnectInfo(peer)| peer.ip().to_string()));
+
+    match within_job_timeout(&state, move |state| async move { submit_psbt(&state, &payload, job).await }).await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(e) => submission_failed(&state, job, e),
+    }
+}
+
+// Give up on a job that overruns --job-timeout. The caller fails the job and releases
+// anything it held.
+async fn within_job_timeout<T, F>(state: &Arc<AppState>, job: impl FnOnce(Arc<AppState>) -> F) -> Result<T, SearcherError>
+where
+    T: Send + 'static,
+    F: Future<Output = Result<T, SearcherError>> + Send + 'static,
+{
+    run_with_timeout(state.config.job_timeout, job(state.clone())).await
+}
+
+// Runs a job on a thread of its own and gives up on it after `secs`. Jobs make blocking RPC
+// calls, which would hold up a runtime worker and with it the timer the job is timed by, so
+// the job runs on the blocking pool and is timed from outside. The abandoned job is dropped
+// at its next await point, which releases its UTXO claim and sponsorship reservation. Until
+// then it keeps running, but the job has been failed by then, so it stops before submitting
+// anything. Broadcasting has no await points, so a package is never abandoned half sent.
+pub async fn run_with_timeout<T, F>(secs: u64, job: F) -> Result<T, SearcherError>
+where
+    T: Send + 'static,
+    F: Future<Output = Result<T, SearcherError>> + Send + 'static,
+{
+    let (abandon, abandoned) = tokio::sync::oneshot::channel::<()>();
+    let runtime = tokio::runtime::Handle::current();
+    let job = job.in_current_span();
+    let task = tokio::task::spawn_blocking(move || {
+        runtime.block_on(async move {
+            tokio::select! {
+                result = job => result,
+                _ = abandoned => Err(SearcherError::Cancelled),
+            }
+        })
+    });
+    match tokio::time::timeout(Duration::from_secs(secs), task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => {
+            drop(abandon);
+            Err(SearcherError::Timeout { secs, retry_after: JOB_RETRY_AFTER_SECS })
+        }
+    }
+}
+
+// The Retry-After header for errors worth retrying, matching the body's retry_after
+fn retry_after_header(e: &SearcherError) -> Option<[(HeaderName, String); 1]> {
+    e.retry_after().map(|secs| [(header::RETRY_AFTER, secs.to_string())])
+}
+
+fn submission_failed(state: &AppState, job: u64, e: SearcherError) -> Response {
+    Span::current().record("otel.status_code", "ERROR");
+    error!("Submission failed: {}", e);
+    state.jobs.fail(job, &e);
+    let (diagnosis, parent_hex) = match &e {
+        SearcherError::ChildFailed { diagnosis, parent_hex, .. } => (Some(diagnosis.clone()), Some(parent_hex.clone())),
+        _ => (None, None),
+    };
+    (e.status_code(), retry_after_header(&e), Json(SubmitPsbtResponse {
+        success: false,
+        protocol_version: Some(PROTOCOL_VERSION),
+        message: e.to_string(),
+        package_txids: None,
+        diagnosis,
+        parent_hex,
+        findings: e.findings(),
+        retry_after: e.retry_after(),
+        next_block: None,
+    })).into_response()
+}
+
+#[tracing::instrument(name = "rescue", skip_all, fields(txid = %payload.txid, otel.status_code = tracing::field::Empty))]
+async fn handle_rescue(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    ValidJson(payload): ValidJson<RescueRequest>,
+) -> Response {
+    info!("Received rescue request for {}", payload.txid);
+    let _pending = PendingJob::start(&state.metrics.pending_jobs);
+    let job = state.jobs.start(JobKind::Package, now());
+    state.jobs.update(job, |job| job.client = connect_info.map(|ConnectInfo(peer)| peer.ip().to_string()));
+
+    match within_job_timeout(&state, move |state| async move { rescue(&state, &payload, job).await }).await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(e) => submission_failed(&state, job, e),
+    }
+}
+
+// Take over a parent someone already broadcast, like one whose searcher failed to make a
+// child, and anchor it as if it had been submitted, for the usual payment
+async fn rescue(state: &AppState, payload: &RescueRequest, job: u64) -> Result<SubmitPsbtResponse, SearcherError> {
+    let txid = Txid::from_str(&payload.txid).map_err(|e| SearcherError::NotRescuable(e.to_string()))?;
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    let entry = client.get_mempool_entry(&txid)
+        .map_err(|_| SearcherError::NotRescuable(format!("{} is not in the mempool", txid)))?;
+    // A TRUC package is one parent and one child, so the parent can have neither
+    // unconfirmed ancestors nor a child already
+    if !entry.depends.is_empty() {
+        return Err(SearcherError::NotRescuable("the parent spends unconfirmed outputs".to_string()));
+    }
+    if entry.descendant_count > 1 {
+        return Err(SearcherError::NotRescuable("the parent already has a child".to_string()));
+    }
+    let tx = client.get_raw_transaction(&txid, None)
+        .map_err(|_| SearcherError::NotRescuable(format!("{} left the mempool", txid)))?;
+    if tx.version != Version(3) {
+        return Err(SearcherError::NotRescuable("the parent is not a v3 transaction".to_string()));
+    }
+    check_parent_fee(&state.config, Ok(entry.fees.base))?;
+
+    let payment_inputs = payload.payment_inputs.clone().unwrap_or_else(|| runes::last_input(&tx));
+    if let Some(index) = payment_inputs.iter().find(|index| **index >= tx.input.len()) {
+        return Err(ValidationError::PaymentInput(format!("input {} does not exist", index)).into());
+    }
+    state.jobs.update(job, |job| {
+        job.cancel_hash = payload.cancel_token.as_deref().map(cancel_hash);
+        job.quote_client = state.abuse.quoted_by(txid).map(|client| client.to_string());
+    });
+    state.jobs.log(job, "rescue", format!(
+        "Took over parent {} of {} vbytes from the mempool, paying {} sats, payment inputs {:?}",
+        txid,
+        tx.vsize(),
+        entry.fees.base.to_sat(),
+        payment_inputs
+    ));
+
+    anchor_parent(state, &tx, entry.fees.base.to_sat(), payload.payment_hash.as_deref(), &payment_inputs, job).await
+}
+
+// The fee is paid either over Lightning, with a rune input, or with an inscription
+async fn validate_payment(
+    state: &AppState,
+    tx: &Transaction,
+    payment_hash: Option<&str>,
+    payment_inputs: &[usize],
+    accept_inscriptions: bool,
+) -> Result<Payment, SearcherError> {
+    match payment_hash {
+        Some(payment_hash) => {
+            info!("Checking Lightning payment {}...", payment_hash);
+            let amount_sat = await_lightning_payment(state, payment_hash, tx.compute_txid())
+                .instrument(info_span!("validate-lightning"))
+                .await?;
+            info!("Lightning payment settled");
+            Ok(Payment::Lightning { amount_sat })
+        }
+        None => {
+            info!("Validating rune input...");
+            let paid_in_runes = validate_rune_input(
+                &state.http,
+                tx,
+                payment_inputs,
+                state.config.min_payment_confirmations,
+                state.config.network,
+                &state.config.ord_server,
+                &state.config.rune_indexes,
+            )
+                .instrument(info_span!("validate-rune"))
+                .await;
+            match (paid_in_runes, &state.config.inscription_payment) {
+                (Err(ValidationError::MissingRune(_)), Some(payment)) if accept_inscriptions => {
+                    info!("No rune payment, validating inscription payment...");
+                    validate_inscription_payment(&state.http, tx, &state.config.ord_server, payment)
+                        .instrument(info_span!("validate-inscription"))
+                        .await?;
+                    info!("Inscription payment validation passed");
+                    Ok(Payment::Inscription)
+                }
+                (result, _) => {
+                    let amount = result?;
+                    // Prices would be off by powers of ten
+                    if amount.divisibility != state.config.rune_divisibility {
+                        return Err(SearcherError::Config(format!(
+                            "ord reports {} with divisibility {}, but the searcher is configured with {}",
+                            RUNE_NAME,
+                            amount.divisibility,
+                            state.config.rune_divisibility
+                        )));
+                    }
+                    info!("Rune input validation passed: {} {}", amount, RUNE_NAME);
+                    Ok(Payment::Rune { amount: amount.units })
+                }
+            }
+        }
+    }
+}
+
+// A parent paying part of the fee would have the child overpay, since the child is
+// priced for the whole package. Inputs without values make the fee unknowable.
+fn check_parent_fee(config: &Config, fee: Result<Amount, psbt::Error>) -> Result<(), ValidationError> {
+    let Some(max) = config.max_parent_fee else {
+        return Ok(());
+    };
+    let fee = fee.map_err(|e| ValidationError::UnknownFee(e.to_string()))?.to_sat();
+    if fee > max {
+        return Err(ValidationError::ParentFee { fee, max });
+    }
+    Ok(())
+}
+
+// Extended keys are the only part of a PSBT that says which network it was made for
+fn check_psbt_network(psbt: &Psbt, network: Network) -> Result<(), ValidationError> {
+    match psbt.xpub.keys().find(|xpub| xpub.network != NetworkKind::from(network)) {
+        Some(xpub) => Err(ValidationError::WrongNetwork(format!("extended key {} is not for {}", xpub, network))),
+        None => Ok(()),
+    }
+}
+
+// Addresses from bitcoind are checked rather than assumed, in case it's on another chain
+fn require_network(address: Address<NetworkUnchecked>, network: Network) -> Result<Address, SearcherError> {
+    address.require_network(network)
+        .map_err(|e| SearcherError::Config(format!("bitcoind returned an address for the wrong network: {}", e)))
+}
+
+// Check the parent's anchor, returning the wallet holding the key when it's keyed
+fn check_anchor(state: &AppState, tx: &Transaction) -> Result<Option<String>, SearcherError> {
+    let Anchor::Keyed(script) = find_anchor(tx, state.config.anchor_policy)? else {
+        return Ok(None);
+    };
+    let address = Address::from_script(&script, state.config.network)
+        .map_err(|_| ValidationError::UnknownAnchorKey(script.to_hex_string()))?;
+
+    for wallet in &state.config.wallets {
+        if connect_rpc(state, wallet)?.get_address_info(&address)?.is_mine == Some(true) {
+            info!("Anchor key {} belongs to wallet {}", address, wallet);
+            return Ok(Some(wallet.clone()));
+        }
+    }
+    Err(ValidationError::UnknownAnchorKey(address.to_string()).into())
+}
+
+// Keyed parents have to carry the deposit the searcher asks for. Returns where it is and
+// its witness script, which the searcher needs to claim it.
+fn check_deposit(state: &AppState, psbt: &Psbt) -> Result<Option<Deposit>, SearcherError> {
+    let Some(terms) = &state.config.anchor_deposit else {
+        return Ok(None);
+    };
+    if !psbt.unsigned_tx.output.first().is_some_and(|anchor| anchor.script_pubkey.is_p2wpkh()) {
+        return Ok(None);
+    }
+    let vout = find_deposit(psbt, terms)?;
+    Ok(Some(Deposit {
+        outpoint: OutPoint { txid: psbt.unsigned_tx.compute_txid(), vout: vout as u32 }.to_string(),
+        amount_sat: psbt.unsigned_tx.output[vout].value.to_sat(),
+        witness_script: psbt.outputs[vout].witness_script.as_ref().map(|script| script.to_hex_string()).unwrap_or_default(),
+    }))
+}
+
+// A keyed anchor's key can sit in another wallet than the one funding the child
+fn anchor_signer(state: &AppState, anchor_wallet: Option<&str>, wallet: &str) -> Result<Option<Client>, SearcherError> {
+    match anchor_wallet {
+        Some(anchor_wallet) if anchor_wallet != wallet => Ok(Some(connect_rpc(state, anchor_wallet)?)),
+        _ => Ok(None),
+    }
+}
+
+// Pay back to fresh change rather than to the spent UTXO, which would reuse its address
+// and isn't possible at all for UTXOs bitcoind can't give an address for
+fn change_script(client: &Client, network: Network) -> Result<bitcoin::ScriptBuf, SearcherError> {
+    Ok(require_network(client.get_raw_change_address(None)?, network)?.script_pubkey())
+}
+
+// The rune address handed out for this parent, or a fresh one held for it, so the runes
+// each customer pays can't be linked to the others on-chain
+fn rune_address_for(state: &AppState, parent: Txid) -> Result<Address, SearcherError> {
+    {
+        let mut addresses = state.rune_addresses.lock().unwrap_or_else(|e| e.into_inner());
+        addresses.retain(|_, (_, expires_at)| *expires_at > now());
+        if let Some((address, _)) = addresses.get(&parent) {
+            return Ok(address.clone());
+        }
+    }
+
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    let address = client.get_new_address(Some(RUNE_ADDRESS_LABEL), Some(json::AddressType::Bech32m))?;
+    let address = require_network(address, state.config.network)?;
+    state.rune_addresses.lock().unwrap_or_else(|e| e.into_inner())
+        .insert(parent, (address.clone(), now() + state.config.quote_ttl));
+    Ok(address)
+}
+
+// A fresh key of the primary wallet for a parent to anchor to
+fn new_anchor_script(state: &AppState) -> Result<bitcoin::ScriptBuf, SearcherError> {
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    let address = client.get_new_address(Some("slugline-anchor"), Some(json::AddressType::Bech32))?;
+    Ok(require_network(address, state.config.network)?.script_pubkey())
+}
+
+fn sign_child(
+    client: &Client,
+    parent: &Transaction,
+    cpfp_tx: &Transaction,
+    anchor_signer: Option<&Client>,
+) -> Result<json::SignRawTransactionResult, SearcherError> {
+    // We need to provide the anchor output details since it's not on-chain yet
+    let anchor_script = parent.output[0].script_pubkey.clone();
+    
+    info!("Anchor script for signing: {}", anchor_script.to_hex_string());
+    info!("Parent txid: {}", parent.compute_txid());
+    
+    let prev_tx_input = json::SignRawTransactionInput {
+        txid: parent.compute_txid(),
+        vout: 0, // The anchor is always first output
+        script_pub_key: anchor_script,
+        redeem_script: None,
+        amount: Some(bitcoin::Amount::from_sat(0)), // Anchors have 0 value
+    };
+    
+    let prevtxs = vec![prev_tx_input];
+
+    // Sign the anchor first where its key is, leaving the funding input for the wallet below
+    let cpfp_tx = match anchor_signer {
+        Some(signer) => signer.sign_raw_transaction_with_wallet(cpfp_tx, Some(&prevtxs), None)?
+            .transaction()
+            .map_err(|e| SearcherError::Cpfp(e.to_string()))?,
+        None => cpfp_tx.clone(),
+    };
+    
+    // The sign_raw_transaction_with_wallet method expects the transaction itself, not hex
+    let sign_result = client.sign_raw_transaction_with_wallet(&cpfp_tx, Some(&prevtxs), None)?;
+    
+    if !sign_result.complete {
+        if let Some(errors) = &sign_result.errors {
+            for error in errors {
+                error!("Signing error: {:?}", error);
+            }
+        }
+        return Err(SearcherError::IncompleteSignature);
+    }
+    Ok(sign_result)
+}
+
+// Sign the child with the external signer when there is one, otherwise in bitcoind
+async fn sign_child_with(
+    state: &AppState,
+    client: &Client,
+    parent: &Transaction,
+    cpfp_tx: &Transaction,
+    anchor_signer: Option<&Client>,
+) -> Result<json::SignRawTransactionResult, SearcherError> {
+    let Some(signer) = &state.signer else {
+        return sign_child(client, parent, cpfp_tx, anchor_signer);
+    };
+    let clients: Vec<&Client> = anchor_signer.into_iter().chain([client]).collect();
+    let signed = sign_externally(signer, &clients, cpfp_tx, &[(0, parent.output[0].clone())]).await?;
+    Ok(json::SignRawTransactionResult {
+        hex: bitcoin::consensus::encode::serialize(&signed),
+        complete: true,
+        errors: None,
+    })
+}
+
+// Have the wallets fill in what they know about the inputs, watch-only as they may be,
+// then have the signer sign and finalize the result. `prevouts` are outputs bitcoind
+// doesn't know yet, like the parent's anchor.
+async fn sign_externally(
+    signer: &ExternalSigner,
+    clients: &[&Client],
+    tx: &Transaction,
+    prevouts: &[(usize, TxOut)],
+) -> Result<Transaction, SearcherError> {
+    let mut psbt = process_for_signer(clients, tx, prevouts)?;
+    psbt = signer.sign(&psbt).await?;
+    Ok(signer::finalize(psbt)?)
+}
+
+fn process_for_signer(clients: &[&Client], tx: &Transaction, prevouts: &[(usize, TxOut)]) -> Result<Psbt, SearcherError> {
+    let mut psbt = Psbt::from_unsigned_tx(tx.clone()).map_err(|e| SearcherError::Cpfp(e.to_string()))?;
+    for (index, output) in prevouts {
+        psbt.inputs[*index].witness_utxo = Some(output.clone());
+    }
+    for client in clients {
+        let processed = client.wallet_process_psbt(&psbt.to_string(), Some(false), None, Some(true))?;
+        psbt = Psbt::from_str(&processed.psbt).map_err(ValidationError::from)?;
+    }
+    Ok(psbt)
+}
+
+async fn submit_psbt(state: &AppState, payload: &SubmitPsbtRequest, job: u64) -> Result<SubmitPsbtResponse, SearcherError> {
+    // Span guards can't be held across an await, so each step drops its own before the next
+    let parse = info_span!("parse").entered();
+
+    // Parse PSBT
+    let psbt = Psbt::from_str(&payload.psbt).map_err(ValidationError::from)?;
+    check_psbt_network(&psbt, state.config.network)?;
+    info!("Successfully parsed PSBT");
+
+    // Needs the input values, which extracting the transaction drops
+    policy::check_standard(&psbt)?;
+    state.config.ephemeral_policy.apply(policy::check_parent(&psbt))?;
+    check_parent_fee(&state.config, psbt.fee())?;
+    let parent_fee = psbt.fee().map(Amount::to_sat).unwrap_or(0);
+    // Markers on the inputs are dropped with the rest of the PSBT too, as are witness scripts
+    let payment_inputs = runes::payment_inputs(&psbt, payload.payment_inputs.as_deref())?;
+    let deposit = check_deposit(state, &psbt)?;
+
+    let tx = psbt.extract_tx().map_err(ValidationError::from)?;
+    drop(parse);
+    Span::current().record("txid", tracing::field::display(tx.compute_txid()));
+    state.jobs.update(job, |job| {
+        job.cancel_hash = payload.cancel_token.as_deref().map(cancel_hash);
+        job.quote_client = state.abuse.quoted_by(tx.compute_txid()).map(|client| client.to_string());
+        job.deposit = deposit;
+    });
+    state.jobs.log(job, "parse", format!(
+        "Parsed parent {} of {} vbytes paying {} sats, payment inputs {:?}",
+        tx.compute_txid(),
+        tx.vsize(),
+        parent_fee,
+        payment_inputs
+    ));
+
+    if let Some(commit_psbt) = &payload.commit_psbt {
+        return hold_for_commit(state, commit_psbt, payload, &tx, &payment_inputs, job).await;
+    }
+    anchor_or_soft_fail(state, &tx, parent_fee, payload.payment_hash.as_deref(), &payment_inputs, job).await
+}
+
+async fn anchor_or_soft_fail(
+    state: &AppState,
+    tx: &Transaction,
+    parent_fee: u64,
+    payment_hash: Option<&str>,
+    payment_inputs: &[usize],
+    job: u64,
+) -> Result<SubmitPsbtResponse, SearcherError> {
+    match anchor_parent(state, tx, parent_fee, payment_hash, payment_inputs, job).await {
+        Err(e) if state.config.soft_fail != SoftFail::Off && is_child_failure(&e) => soft_fail(state, tx, parent_fee, job, e),
+        result => result,
+    }
+}
+
+// The commit has to confirm without help, since a TRUC child can't also carry a parent's
+// unconfirmed parent. Returns the commit, or an error if the parent doesn't spend it or
+// it pays less than the rate the searcher would pay for its own packages.
+fn check_commit(state: &AppState, client: &Client, commit_psbt: &str, tx: &Transaction) -> Result<Transaction, SearcherError> {
+    let commit = Psbt::from_str(commit_psbt).map_err(ValidationError::from)?;
+    check_psbt_network(&commit, state.config.network)?;
+    let commit_fee = commit.fee().map_err(|e| ValidationError::UnknownFee(e.to_string()))?.to_sat();
+    let commit = commit.extract_tx().map_err(ValidationError::from)?;
+    let commit_txid = commit.compute_txid();
+    if !tx.input.iter().any(|input| input.previous_output.txid == commit_txid) {
+        return Err(ValidationError::UnrelatedCommit(commit_txid.to_string()).into());
+    }
+
+    let rate = commit_fee as f64 / commit.vsize() as f64;
+    let min = fee_rate(state, Some(client));
+    if rate < min {
+        return Err(ValidationError::CommitFeeRate { rate, min }.into());
+    }
+    Ok(commit)
+}
+
+// Check the parent and its payment now, broadcast the commit, and leave the parent for
+// anchor_held_parents to anchor once the commit confirms
+async fn hold_for_commit(
+    state: &AppState,
+    commit_psbt: &str,
+    payload: &SubmitPsbtRequest,
+    tx: &Transaction,
+    payment_inputs: &[usize],
+    job: u64,
+) -> Result<SubmitPsbtResponse, SearcherError> {
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    let commit = check_commit(state, &client, commit_psbt, tx)?;
+    let commit_txid = commit.compute_txid();
+    state.jobs.update(job, |job| {
+        job.parent_txid = Some(tx.compute_txid().to_string());
+        job.parent_inputs = tx.input.iter().map(|input| input.previous_output.to_string()).collect();
+        job.commit_txid = Some(commit_txid.to_string());
+    });
+    for (stale, reason) in check_anchor_reuse(state, tx, Some(job))? {
+        retire_job(state, &stale, &reason);
+    }
+    check_package_size(&state.config, tx.vsize() as u64 + child_vsize_estimate(&state.config))?;
+    check_anchor(state, tx)?;
+    check_rules(&state.config.rules, tx)?;
+
+    // Checked again when the parent is anchored, but a bad payment shouldn't get as far
+    // as broadcasting the commit
+    if sponsor(state, tx).is_none() {
+        validate_payment(state, tx, payload.payment_hash.as_deref(), payment_inputs, true).await?;
+    }
+
+    // Held before broadcasting, so a restart in between leaves a job that still gets anchored
+    state.jobs.update(job, |job| {
+        job.held_psbt = Some(payload.psbt.clone());
+        job.payment_hash = payload.payment_hash.clone();
+        job.payment_inputs = payload.payment_inputs.clone();
+    });
+    // Submitters may have broadcast the commit themselves
+    let vout = tx.input.iter()
+        .find(|input| input.previous_output.txid == commit_txid)
+        .map_or(0, |input| input.previous_output.vout);
+    if client.get_tx_out(&commit_txid, vout, Some(true))?.is_none() {
+        client.send_raw_transaction(&commit)
+            .map_err(|e| SearcherError::CommitRejected(e.to_string()))?;
+        info!("Broadcast commit {}", commit_txid);
+    }
+
+    Ok(SubmitPsbtResponse {
+        success: true,
+        protocol_version: Some(PROTOCOL_VERSION),
+        message: format!("Commit {} broadcast, the parent will be anchored once it confirms", commit_txid),
+        package_txids: Some(vec![commit_txid.to_string()]),
+        diagnosis: None,
+        parent_hex: None,
+        findings: None,
+        retry_after: None,
+        next_block: None,
+    })
+}
+
+// Anchor held parents as their commits confirm. Each attempt gets the job timeout, and a
+// job whose attempts keep failing on bitcoind for longer than that fails too.
+async fn anchor_held_parents(state: Arc<AppState>) {
+    // When each job's attempts started failing on bitcoind
+    let mut failing_since: HashMap<u64, u64> = HashMap::new();
+    loop {
+        let held = state.jobs.jobs()
+            .into_iter()
+            .filter(|job| job.status == JobStatus::Pending && job.held_psbt.is_some());
+        for job in held {
+            let id = job.id;
+            let span = info_span!("held_parent", job = id);
+            let attempt = within_job_timeout(&state, move |state| async move { anchor_held_parent(&state, &job).await });
+            match attempt.instrument(span).await {
+                Ok(()) => {
+                    failing_since.remove(&id);
+                }
+                // bitcoind being down isn't the parent's fault, so try again later, for a while
+                Err(SearcherError::Rpc(e)) if now() - *failing_since.entry(id).or_insert(now()) < state.config.job_timeout => {
+                    error!("Failed to check the commit of job {}: {}", id, e);
+                }
+                Err(e) => {
+                    error!("Failed to anchor the held parent of job {}: {}", id, e);
+                    failing_since.remove(&id);
+                    state.jobs.fail(id, &e);
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(HELD_PARENT_INTERVAL_SECS)).await;
+    }
+}
+
+// Does nothing while the commit is still in the mempool
+async fn anchor_held_parent(state: &AppState, job: &jobs::Job) -> Result<(), SearcherError> {
+    let psbt = Psbt::from_str(job.held_psbt.as_deref().unwrap_or_default()).map_err(ValidationError::from)?;
+    let parent_fee = psbt.fee().map(Amount::to_sat).unwrap_or(0);
+    let payment_inputs = runes::payment_inputs(&psbt, job.payment_inputs.as_deref())?;
+    let tx = psbt.extract_tx().map_err(ValidationError::from)?;
+    let commit = job.commit_txid.clone().unwrap_or_default();
+    let Some(outpoint) = tx.input.iter()
+        .map(|input| input.previous_output)
+        .find(|outpoint| outpoint.txid.to_string() == commit)
+    else {
+        return Err(ValidationError::UnrelatedCommit(commit).into());
+    };
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    if client.get_tx_out(&outpoint.txid, outpoint.vout, Some(false))?.is_none() {
+        if client.get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?.is_some() {
+            return Ok(());
+        }
+        // Spent, by the parent when someone else broadcast it, in which case anchoring
+        // settles it if it's confirmed and sponsors it if it's in the mempool
+        let broadcast = client.get_mempool_entry(&tx.compute_txid()).is_ok() || parent_confirmed_at(&client, &tx)?.is_some();
+        if !broadcast {
+            return Err(SearcherError::CommitDropped(outpoint.txid.to_string()));
+        }
+        info!("Commit {} already spent by parent {}", outpoint.txid, tx.compute_txid());
+    }
+
+    info!("Commit {} confirmed, anchoring parent {}", outpoint.txid, tx.compute_txid());
+    let _pending = PendingJob::start(&state.metrics.pending_jobs);
+    anchor_or_soft_fail(state, &tx, parent_fee, job.payment_hash.as_deref(), &payment_inputs, job.id).await?;
+    info!("Anchored held parent {}", tx.compute_txid());
+    Ok(())
+}
+
+// Failures of the searcher's own wallet rather than of the submitted parent
+fn is_child_failure(e: &SearcherError) -> bool {
+    matches!(
+        e,
+        SearcherError::Rpc(_)
+            | SearcherError::NoWalletUtxos
+            | SearcherError::Cpfp(_)
+            | SearcherError::IncompleteSignature
+            | SearcherError::Signer(_)
+            | SearcherError::FeeCap { .. }
+    )
+}
+
+// The child couldn't be made, but the parent may still relay without it, e.g. when it
+// pays its own way on a node with relaxed dust rules
+fn soft_fail(
+    state: &AppState,
+    tx: &Transaction,
+    parent_fee: u64,
+    job: u64,
+    e: SearcherError,
+) -> Result<SubmitPsbtResponse, SearcherError> {
+    let Ok(client) = connect_rpc(state, primary_wallet(&state.config)) else {
+        return Err(e);
+    };
+    let parent_hex = bitcoin::consensus::encode::serialize_hex(tx);
+    let rejection = match client.call::<Vec<serde_json::Value>>("testmempoolaccept", &[serde_json::json!([parent_hex])]) {
+        Ok(results) if results.first().is_some_and(|result| result["allowed"] == true) => None,
+        Ok(results) => Some(
+            results.first()
+                .and_then(|result| result["reject-reason"].as_str())
+                .unwrap_or("rejected")
+                .to_string(),
+        ),
+        // bitcoind itself is down, so there's nothing better to offer
+        Err(_) => return Err(e),
+    };
+
+    if state.config.soft_fail == SoftFail::Broadcast && rejection.is_none() {
+        let broadcast = state.jobs.transition(job, JobStatus::Pending, |job| {
+            job.status = JobStatus::Submitted;
+            job.vsize = Some(tx.vsize() as u64);
+            job.fee_sat = Some(parent_fee);
+            job.submitted_at = Some(now());
+            job.failure_message = Some(format!("Broadcast without a child: {}", e));
+        });
+        if broadcast {
+            client.send_raw_transaction(parent_hex.as_str())?;
+            info!("Child failed ({}), broadcast parent {} alone", e, tx.compute_txid());
+            return Ok(SubmitPsbtResponse {
+                success: true,
+                protocol_version: Some(PROTOCOL_VERSION),
+                message: "Parent broadcast without a child".to_string(),
+                package_txids: Some(vec![tx.compute_txid().to_string()]),
+                diagnosis: Some(e.to_string()),
+                parent_hex: None,
+                findings: None,
+                retry_after: None,
+                next_block: None,
+            });
+        }
+    }
+
+    let diagnosis = match rejection {
+        Some(reason) => format!("{}; the parent would not relay alone: {}", e, reason),
+        None => format!("{}; the parent would relay alone", e),
+    };
+    Err(SearcherError::ChildFailed { source: Box::new(e), parent_hex, diagnosis })
+}
+
+fn submit_package(client: &Client, package: Vec<String>) -> Result<(), SearcherError> {
+    // bitcoind refuses packages through an RPC error, which is a rejection rather than an
+    // outage, unless it doesn't know the method at all
+    let result = client.call::<serde_json::Value>("submitpackage", &[serde_json::json!(package)])
+        .map_err(|e| match method_missing(&e) {
+            true => SearcherError::Rpc(e),
+            false => SearcherError::PackageRejected(e.to_string()),
+        })?;
+    info!("Package submission response: {:?}", result);
+    
+    // Check if the response indicates an error
+    if let Some(package_msg) = result.get("package_msg")
+        && package_msg == "transaction failed"
+    {
+        // Extract error details
+        let mut error_details = Vec::new();
+
+        if let Some(tx_results) = result.get("tx-results").and_then(|v| v.as_object()) {
+            for (txid, tx_result) in tx_results {
+                if let Some(error) = tx_result.get("error").and_then(|v| v.as_str()) {
+                    error_details.push(format!("{}: {}", txid, error));
+                }
+            }
+        }
+
+        let error_msg = if error_details.is_empty() {
+            "unknown error".to_string()
+        } else {
+            error_details.join(", ")
+        };
+
+        return Err(SearcherError::PackageRejected(error_msg));
+    }
+    Ok(())
+}
+
+fn method_missing(e: &bitcoincore_rpc::Error) -> bool {
+    matches!(e, bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)) if e.code == -32601)
+}
+
+// Nodes before 28.0 refuse zero-fee parents in packages, or lack submitpackage entirely
+fn detect_package_rpc(state: &AppState) {
+    let fallback = match &state.config.package_peer {
+        Some(peer) => format!("announcing packages to peer {}", peer),
+        None => "sending parent and child one at a time".to_string(),
+    };
+    match connect_rpc(state, primary_wallet(&state.config)).and_then(|client| rpc::node_version(&client)) {
+        Ok((version, subversion)) if version < PACKAGE_RELAY_VERSION => {
+            info!("bitcoind {} predates package relay, {}", subversion, fallback);
+            state.package_rpc.store(false, Ordering::Relaxed);
+        }
+        Ok((_, subversion)) => info!("bitcoind {} takes packages through submitpackage", subversion),
+        Err(e) => error!("Cannot check bitcoind's version, assuming it has submitpackage: {}", e),
+    }
+}
+
+// Submit a package through submitpackage, or on a node without it announce it to
+// package_peer, or failing that send it one transaction at a time, which only relays a
+// parent paying the minimum relay fee itself. Returns how it went out, for the job log.
+fn relay_package(state: &AppState, client: &Client, package: Vec<String>) -> Result<String, SearcherError> {
+    if state.package_rpc.load(Ordering::Relaxed) {
+        match submit_package(client, package.clone()) {
+            Err(SearcherError::Rpc(e)) if method_missing(&e) => {
+                error!("bitcoind has no submitpackage, falling back: {}", e);
+                state.package_rpc.store(false, Ordering::Relaxed);
+            }
+            result => return result.map(|()| "bitcoind accepted the package".to_string()),
+        }
+    }
+    if let Some(peer) = &state.config.package_peer {
+        let transactions = package.iter()
+            .map(|tx_hex| deserialize_hex::<Transaction>(tx_hex))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| SearcherError::PackageRejected(e.to_string()))?;
+        filters::announce_package(peer, state.config.network, &transactions)
+            .map_err(|e| SearcherError::PackageRejected(format!("peer {}: {}", peer, e)))?;
+        return Ok(format!("Announced the package to peer {}", peer));
+    }
+    for tx_hex in &package {
+        client.send_raw_transaction(tx_hex.as_str()).map_err(|e| SearcherError::PackageRejected(e.to_string()))?;
+    }
+    Ok("bitcoind accepted the parent, then the child".to_string())
+}
+
+fn connect_node(config: &Config, node: &BroadcastNode) -> Result<Client, bitcoincore_rpc::Error> {
+    let user = node.user.as_deref().or(config.bitcoind_user.as_deref());
+    let password = node.password.as_deref().or(config.bitcoind_password.as_deref());
+    Client::new(&format!("http://{}", node.address(config.network)), rpc::auth(user, password))
+}
+
+// Send a package the main node accepted on to the broadcast nodes. They only help it
+// propagate, so a refusal is logged on the job and kept for /health rather than failing it.
+fn broadcast_package(state: &AppState, job: u64, package: &[String]) {
+    for node in &state.config.broadcast_nodes {
+        let address = node.address(state.config.network);
+        // submitpackage is a blocking RPC call
+        let submitted = tokio::task::block_in_place(|| {
+            connect_node(&state.config, node)
+                .map_err(SearcherError::from)
+                .and_then(|client| submit_package(&client, package.to_vec()))
+        });
+        let mut rejections = state.node_rejections.lock().unwrap_or_else(|e| e.into_inner());
+        match submitted {
+            Ok(()) => {
+                rejections.remove(&address);
+                state.jobs.log(job, "broadcast", format!("{} accepted the package", address));
+                state.jobs.update(job, |job| job.broadcast_to.push(address));
+            }
+            Err(e) => {
+                error!("Broadcast node {} refused job {}: {}", address, job, e);
+                state.jobs.log(job, "broadcast", format!("{} refused the package: {}", address, e));
+                rejections.insert(address, e.to_string());
+            }
+        }
+    }
+}
+
+// Compare a parent with the ones earlier jobs anchored. The same parent again, or one
+// spending the same inputs as a parent still around, would only get a child that can't
+// relay. Earlier jobs whose parent and child are gone for good are returned with why,
+// so a submission can take their place.
+fn check_anchor_reuse(
+    state: &AppState,
+    tx: &Transaction,
+    current: Option<u64>,
+) -> Result<Vec<(jobs::Job, SearcherError)>, SearcherError> {
+    let txid = tx.compute_txid();
+    let inputs: HashSet<String> = tx.input.iter().map(|input| input.previous_output.to_string()).collect();
+    let mut stale = Vec::new();
+
+    for job in state.jobs.jobs() {
+        if Some(job.id) == current
+            || job.kind != JobKind::Package
+            || !matches!(job.status, JobStatus::Pending | JobStatus::Submitted | JobStatus::Confirmed)
+        {
+            continue;
+        }
+        let Some(parent) = job.parent_txid.as_deref().and_then(|txid| Txid::from_str(txid).ok()) else {
+            continue;
+        };
+        let same_parent = parent == txid;
+        if !same_parent && !job.parent_inputs.iter().any(|input| inputs.contains(input)) {
+            continue;
+        }
+        let conflict = || match same_parent {
+            true => SearcherError::AlreadyAnchored { parent: parent.to_string(), job: job.id },
+            false => SearcherError::ConflictingParent { parent: parent.to_string(), job: job.id },
+        };
+        if job.status != JobStatus::Submitted {
+            return Err(conflict());
+        }
+
+        // A child still in the mempool or confirmed means the earlier package stands
+        let client = connect_rpc(state, job.wallet.as_deref().unwrap_or(primary_wallet(&state.config)))?;
+        let child = job.child_txid.as_deref().and_then(|txid| Txid::from_str(txid).ok());
+        let child_live = child.is_some_and(|child| {
+            client.get_mempool_entry(&child).is_ok()
+                || client.get_transaction(&child, None).is_ok_and(|tx| tx.info.confirmations > 0)
+        });
+        // Resubmitting our own parent is fine once its child is gone, unless it never had one
+        let parent_live = client.get_mempool_entry(&parent).is_ok();
+        if child_live || (parent_live && (!same_parent || child.is_none())) {
+            return Err(conflict());
+        }
+        let reason = match (same_parent, child) {
+            (true, Some(child)) => SearcherError::Dropped(child.to_string()),
+            _ => SearcherError::Replaced(txid.to_string()),
+        };
+        stale.push((job, reason));
+    }
+    Ok(stale)
+}
+
+// Give up on a job whose package left the mempool, freeing its child's input for reuse
+fn retire_job(state: &AppState, job: &jobs::Job, reason: &SearcherError) {
+    if let Some(child) = &job.child_txid {
+        let abandoned = connect_rpc(state, job.wallet.as_deref().unwrap_or(primary_wallet(&state.config)))
+            .and_then(|client| client.call::<serde_json::Value>("abandontransaction", &[child.as_str().into()]));
+        if let Err(e) = abandoned {
+            error!("Failed to abandon child {} of job {}: {}", child, job.id, e);
+        }
+    }
+    info!("Retiring job {}: {}", job.id, reason);
+    state.jobs.fail(job.id, reason);
+}
+
+// A rune payment has to cover the package at the price a quote would give now, tier
+// included, and at the rate that reaches the mempool depth when there is one
+async fn check_rune_payment(state: &AppState, client: &Client, tx: &Transaction, paid: u64, depth_rate: Option<f64>) -> Result<(), SearcherError> {
+    let parent_vsize = tx.vsize() as u64;
+    let package_vsize = quoted_package_vsize(state, parent_vsize, false);
+    let multiplier = tier_multiplier(&state.config.price_tiers, parent_vsize);
+    let (price, _) = rune_price(state, package_vsize, multiplier).await?;
+    if paid < price.units {
+        return Err(ValidationError::Underpaid { paid: RuneAmount::new(paid, price.divisibility), price }.into());
+    }
+    let Some(depth_rate) = depth_rate else {
+        return Ok(());
+    };
+    let priced_rate = priced_rate(state, Some(client), fee_rate(state, Some(client)));
+    let required = price_for_depth(price, priced_rate, Some(depth_rate)).units;
+    if paid < required {
+        return Err(ValidationError::BelowMempoolDepth {
+            paid,
+            required,
+            rate: depth_rate,
+            depth: state.config.max_mempool_depth.unwrap_or_default(),
+        }.into());
+    }
+    Ok(())
+}
+
+// Check, collect payment for and CPFP a parent, whether it was submitted or found in the mempool
+async fn anchor_parent(
+    state: &AppState,
+    tx: &Transaction,
+    parent_fee: u64,
+    payment_hash: Option<&str>,
+    payment_inputs: &[usize],
+    job: u64,
+) -> Result<SubmitPsbtResponse, SearcherError> {
+    state.jobs.update(job, |job| {
+        job.parent_txid = Some(tx.compute_txid().to_string());
+        job.parent_wtxid = Some(tx.compute_wtxid().to_string());
+        job.parent_inputs = tx.input.iter().map(|input| input.previous_output.to_string()).collect();
+    });
+    info!("Transaction has {} inputs and {} outputs", tx.input.len(), tx.output.len());
+
+    // Someone else anchored it or it paid its own way, so there's nothing left to sponsor
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    if let Some(blocktime) = parent_confirmed_at(&client, tx)? {
+        info!("Parent {} already confirmed, skipping the child", tx.compute_txid());
+        state.jobs.log(job, "confirmed", "Parent confirmed before a child was made".to_string());
+        let confirmed = state.jobs.transition(job, JobStatus::Pending, |job| {
+            job.status = JobStatus::Confirmed;
+            job.confirmed_at = Some(blocktime);
+        });
+        if !confirmed {
+            return Err(SearcherError::Cancelled);
+        }
+        return Ok(SubmitPsbtResponse {
+            success: true,
+            protocol_version: Some(PROTOCOL_VERSION),
+            message: "Parent already confirmed, no child needed".to_string(),
+            package_txids: Some(vec![tx.compute_txid().to_string()]),
+            diagnosis: None,
+            parent_hex: None,
+            findings: None,
+            retry_after: None,
+            next_block: None,
+        });
+    }
+
+    for (stale, reason) in check_anchor_reuse(state, tx, Some(job))? {
+        retire_job(state, &stale, &reason);
+    }
+    check_package_size(&state.config, tx.vsize() as u64 + child_vsize_estimate(&state.config))?;
+    
+    // Validate anchor output
+    let anchor = info_span!("validate-anchor").entered();
+    info!("Validating anchor output...");
+    let anchor_wallet = check_anchor(state, tx)?;
+    info!("Anchor output validation passed");
+    drop(anchor);
+    check_rules(&state.config.rules, tx)?;
+    state.jobs.log(job, "validate", "Anchor, package size and parent rules passed".to_string());
+    
+    let sponsored = sponsor(state, tx);
+    let depth_rate = depth_rate(state, Some(&client));
+    let mut rune_address = None;
+    if sponsored.is_none() {
+        let payment = validate_payment(state, tx, payment_hash, payment_inputs, true).await?;
+        if let Payment::Rune { amount } = payment {
+            check_rune_payment(state, &client, tx, amount, depth_rate).await?;
+        }
+        if state.config.rotate_rune_address && matches!(payment, Payment::Rune { .. }) {
+            rune_address = Some(rune_address_for(state, tx.compute_txid())?);
+        }
+        state.jobs.log(job, "payment", format!("Accepted payment {:?}", payment));
+        state.jobs.update(job, |job| {
+            job.payment = Some(payment);
+            job.rune_address = rune_address.as_ref().map(Address::to_string);
+        });
+    }
+    
+    let select = info_span!("select-utxo").entered();
+
+    let rate = child_rate(state, fee_rate(state, Some(&client)), depth_rate);
+    let pick = covering_utxo(&state.config, tx, rate, rune_address.is_some());
+    let (client, wallet, searcher_utxo, _claim) = select_wallet_utxo(state, pick)?;
+    let anchor_signer = anchor_signer(state, anchor_wallet.as_deref(), &wallet)?;
+    state.jobs.log(job, "select-utxo", format!(
+        "Funding the child from {}:{} ({} sats) in wallet {}",
+        searcher_utxo.txid,
+        searcher_utxo.vout,
+        searcher_utxo.amount.to_sat(),
+        wallet
+    ));
+    state.jobs.update(job, |job| job.wallet = Some(wallet));
+    
+    // Create CPFP transaction
+    let cpfp_tx = create_cpfp_transaction(
+        tx,
+        &searcher_utxo,
+        change_script(&client, state.config.network)?,
+        rune_address.map(|address| address.script_pubkey()),
+        state.config.revenue.as_ref().map(Revenue::output),
+        rate,
+    )?;
+    state.config.ephemeral_policy.apply(policy::check_package(tx, &cpfp_tx))?;
+    let child_fee = searcher_utxo.amount.to_sat()
+        .saturating_sub(cpfp_tx.output.iter().map(|output| output.value.to_sat()).sum());
+    state.jobs.log(job, "fee", format!(
+        "Child {} pays {} sats, {:.2} sat/vB across parent and child",
+        cpfp_tx.compute_txid(),
+        child_fee,
+        rate
+    ));
+    check_fee(&state.config, child_fee, searcher_utxo.amount.to_sat())?;
+    drop(select);
+    
+    // Log CPFP transaction details
+    info!("CPFP transaction has {} inputs:", cpfp_tx.input.len());
+    for (i, input) in cpfp_tx.input.iter().enumerate() {
+        info!("  Input {}: {}:{}", i, input.previous_output.txid, input.previous_output.vout);
+    }
+    
+    // Convert parent transaction to hex
+    let parent_hex = bitcoin::consensus::encode::serialize_hex(tx);
+    info!("Parent transaction hex: {}", parent_hex);
+    
+    // Sign the CPFP transaction
+    let sign = info_span!("sign");
+    let sign_result = async {
+        info!("Signing CPFP transaction with {}...", state.signer.as_ref().map_or("wallet".to_string(), |signer| redact_url(signer.url())));
+        sign_child_with(state, &client, tx, &cpfp_tx, anchor_signer.as_ref()).await
+    }.instrument(sign.clone()).await?;
+    let sign = sign.entered();
+    
+    // Convert the signed transaction result to hex string
+    let child_hex = hex::encode(&sign_result.hex);
+    let child_vsize = sign_result.transaction()
+        .map(|child| child.vsize() as u64)
+        .unwrap_or(child_vsize_estimate(&state.config));
+    info!("Signed child transaction hex: {}", child_hex);
+    drop(sign);
+    
+    // Past this point the package is out, so a cancel has to replace the child instead
+    let submitted = state.jobs.transition(job, JobStatus::Pending, |job| {
+        job.status = JobStatus::Submitted;
+        job.child_txid = Some(cpfp_tx.compute_txid().to_string());
+        job.vsize = Some(tx.vsize() as u64 + child_vsize);
+        job.fee_sat = Some(parent_fee + child_fee);
+        job.submitted_at = Some(now());
+    });
+    if !submitted {
+        return Err(SearcherError::Cancelled);
+    }
+
+    // Submit package
+    let _submit = info_span!("submit").entered();
+    let relayed = match relay_package(state, &client, vec![parent_hex.clone(), child_hex.clone()]) {
+        Ok(relayed) => relayed,
+        Err(e) => {
+            let failures = state.submit_failures.fetch_add(1, Ordering::Relaxed) + 1;
+            if failures == SUBMIT_FAILURE_ALERT {
+                state.notifier.notify("submit_failures", format!("{} package submissions failed in a row, the last with: {}", failures, e));
+            }
+            return Err(e);
+        }
+    };
+    state.submit_failures.store(0, Ordering::Relaxed);
+    state.jobs.log(job, "submit", relayed);
+    broadcast_package(state, job, &[parent_hex, child_hex]);
+    let next_block = predict_next_block(state, &client, (parent_fee + child_fee) as f64 / (tx.vsize() as u64 + child_vsize) as f64);
+    state.jobs.update(job, |job| {
+        job.fees_spent_sat = Some(child_fee);
+        job.revenue_sat = state.config.revenue.as_ref().map(|revenue| revenue.amount_sat);
+        job.next_block = next_block;
+    });
+    
+    // Success case
+    if state.config.auto_mine {
+        match mine_block(state) {
+            Ok(block) => info!("Mined block {} with the package", block),
+            Err(e) => error!("Failed to mine a block with the package: {}", e),
+        }
+    }
+    if let Some((template, reservation)) = sponsored {
+        reservation.commit(child_fee);
+        state.jobs.update(job, |job| job.payment = Some(Payment::Sponsored { template, amount_sat: child_fee }));
+    }
+    let txids = vec![
+        tx.compute_txid().to_string(),
+        cpfp_tx.compute_txid().to_string(),
+    ];
+    
+    Ok(SubmitPsbtResponse {
+        success: true,
+        protocol_version: Some(PROTOCOL_VERSION),
+        message: "Package submitted successfully".to_string(),
+        package_txids: Some(txids),
+        diagnosis: None,
+        parent_hex: None,
+        findings: None,
+        retry_after: None,
+        next_block,
+    })
+}
+
+// Whether a package paying `rate` makes bitcoind's next block template, checked before the
+// package is in it. None unless predictions are on and bitcoind can make a template.
+fn predict_next_block(state: &AppState, client: &Client, rate: f64) -> Option<bool> {
+    let next_block = state.next_block.as_ref()?;
+    // getblocktemplate is a blocking RPC call
+    let floor = tokio::task::block_in_place(|| next_block.floor(client))?;
+    Some(floor.includes(rate))
+}
+
+// Submitted packages that slip out of the next block as fees rise need a bump. Each one
+// is logged and the operator told when it falls behind, and again if it catches up. With
+// bumping on, its child is replaced by one paying just over the next block's floor.
+async fn watch_next_block(state: Arc<AppState>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(NEXT_BLOCK_CHECK_SECS)).await;
+        let client = match connect_rpc(&state, primary_wallet(&state.config)) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Cannot check packages against the next block: {}", e);
+                continue;
+            }
+        };
+        // getblocktemplate is a blocking RPC call
+        let Some(floor) = state.next_block.as_ref().and_then(|next_block| tokio::task::block_in_place(|| next_block.floor(&client))) else {
+            continue;
+        };
+        let submitted = state.jobs.jobs().into_iter()
+            .filter(|job| job.kind == JobKind::Package && job.status == JobStatus::Submitted && job.child_txid.is_some());
+        for job in submitted {
+            let Some(rate) = job.fee_rate() else {
+                continue;
+            };
+            let next_block = floor.includes(rate);
+            if next_block {
+                if job.next_block != Some(true) {
+                    state.jobs.update(job.id, |job| job.next_block = Some(true));
+                    state.jobs.log(job.id, "next_block", format!("Package at {:.2} sat/vB is back in the next block", rate));
+                    state.notifier.notify("package_caught_up", format!(
+                        "Job {} for parent {} is back in the next block",
+                        job.id, job.parent_txid.as_deref().unwrap_or("?"),
+                    ));
+                }
+                continue;
+            }
+            if state.config.bump_to_next_block {
+                bump_into_next_block(&state, &job, floor.rate + NEXT_BLOCK_BUMP_MARGIN).await;
+                continue;
+            }
+            if job.next_block != Some(false) {
+                state.jobs.update(job.id, |job| job.next_block = Some(false));
+                state.jobs.log(job.id, "needs_bump", format!("Package at {:.2} sat/vB fell out of the next block", rate));
+                state.notifier.notify("package_needs_bump", format!(
+                    "Job {} for parent {} at {:.2} sat/vB fell out of the next block",
+                    job.id, job.parent_txid.as_deref().unwrap_or("?"), rate,
+                ));
+            }
+        }
+    }
+}
+
+// A package that fell out of the next block bumped back in, or the operator told it couldn't be
+async fn bump_into_next_block(state: &AppState, job: &jobs::Job, rate: f64) {
+    let parent = job.parent_txid.as_deref().unwrap_or("?");
+    match bump_child(state, job.id, rate).await {
+        Ok(Some(replacement)) => {
+            info!("Job {} bumped to {:.2} sat/vB, child replaced by {}", job.id, rate, replacement);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("Failed to bump job {} into the next block: {}", job.id, e);
+            if job.next_block != Some(false) {
+                state.jobs.update(job.id, |job| job.next_block = Some(false));
+                state.jobs.log(job.id, "needs_bump", format!("Package fell out of the next block and couldn't be bumped: {}", e));
+                state.notifier.notify("package_needs_bump", format!(
+                    "Job {} for parent {} fell out of the next block and couldn't be bumped: {}",
+                    job.id, parent, e,
+                ));
+            }
+        }
+    }
+}
+
+// Anyone who can sign for a parent's inputs can swap its witness without changing its
+// txid, and a node may take the other version. The child spends the parent by txid, so
+// it still fits, but the job should know which parent is out there, and a child the swap
+// pushed out of the mempool has to go back in.
+async fn watch_parent_witnesses(state: Arc<AppState>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(PARENT_WITNESS_CHECK_SECS)).await;
+        let submitted = state.jobs.jobs().into_iter()
+            .filter(|job| job.kind == JobKind::Package && job.status == JobStatus::Submitted && job.parent_wtxid.is_some());
+        for job in submitted {
+            if let Err(e) = check_parent_witness(&state, &job) {
+                error!("Failed to check the parent witness of job {}: {}", job.id, e);
+            }
+        }
+    }
+}
+
+fn check_parent_witness(state: &AppState, job: &jobs::Job) -> Result<(), SearcherError> {
+    let (Some(parent), Some(expected), Some(child)) = (&job.parent_txid, &job.parent_wtxid, &job.child_txid) else {
+        return Ok(());
+    };
+    let parent = Txid::from_str(parent).map_err(|e| SearcherError::Cpfp(e.to_string()))?;
+    let child = Txid::from_str(child).map_err(|e| SearcherError::Cpfp(e.to_string()))?;
+    let client = connect_rpc(state, job.wallet.as_deref().unwrap_or(primary_wallet(&state.config)))?;
+    // Confirmed or evicted parents are left to confirmation tracking
+    let Ok(entry) = client.get_mempool_entry(&parent) else {
+        return Ok(());
+    };
+    let observed = entry.wtxid.to_string();
+    if observed == *expected {
+        return Ok(());
+    }
+
+    info!("Parent {} of job {} was malleated, wtxid {} is in the mempool instead of {}", parent, job.id, observed, expected);
+    state.jobs.log(job.id, "malleated", format!("Parent is in the mempool with wtxid {} instead of {}", observed, expected));
+    state.jobs.update(job.id, |job| job.parent_wtxid = Some(observed));
+    if entry.spent_by.contains(&child) {
+        return Ok(());
+    }
+    let resent = client.get_transaction(&child, None)
+        .map_err(SearcherError::from)
+        .and_then(|child_tx| Ok(client.send_raw_transaction(&child_tx.hex)?));
+    match resent {
+        Ok(_) => state.jobs.log(job.id, "resent", format!("Child {} resent against the malleated parent", child)),
+        Err(e) => state.jobs.log(job.id, "resent", format!("Child {} could not be resent against the malleated parent: {}", child, e)),
+    }
+    Ok(())
+}
+
+// Time of the block a parent confirmed in, if it has
+fn parent_confirmed_at(client: &Client, tx: &Transaction) -> Result<Option<u64>, SearcherError> {
+    let txid = tx.compute_txid();
+    if let Ok(info) = client.get_raw_transaction_info(&txid, None) {
+        return Ok(info.blocktime.map(|time| time as u64));
+    }
+
+    // Without txindex bitcoind only finds a confirmed transaction through its unspent outputs
+    for vout in 0..tx.output.len() as u32 {
+        if let Some(out) = client.get_tx_out(&txid, vout, Some(false))?
+            && out.confirmations > 0
+        {
+            let height = client.get_block_count()? + 1 - u64::from(out.confirmations);
+            let header = client.get_block_header_info(&client.get_block_hash(height)?)?;
+            return Ok(Some(header.time as u64));
+        }
+    }
+    Ok(None)
+}
+
+// Mine a block to the primary wallet, standing in for miners on regtest
+fn mine_block(state: &AppState) -> Result<BlockHash, SearcherError> {
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    let address = require_network(client.get_new_address(Some("slugline-mining"), None)?, state.config.network)?;
+    client.generate_to_address(1, &address)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| SearcherError::Config("generatetoaddress mined no block".to_string()))
+}
+
+async fn mine_blocks(state: Arc<AppState>, interval: u64) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        match mine_block(&state) {
+            Ok(block) => info!("Mined block {}", block),
+            Err(e) => error!("Failed to mine a block: {}", e),
+        }
+    }
+}
+
+// Anchor paying parents that reached our mempool without going through the API, e.g.
+// broadcast by a submitter who can meet the relay fee or sent over another relay
+async fn scan_mempool(state: Arc<AppState>, interval: u64) {
+    let mut seen = HashSet::new();
+    loop {
+        if let Err(e) = scan_mempool_once(&state, &mut seen).await {
+            error!("Mempool scan failed: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+async fn scan_mempool_once(state: &AppState, seen: &mut HashSet<Txid>) -> Result<(), SearcherError> {
+    let client = connect_rpc(state, primary_wallet(&state.config))?;
+    let mempool = client.get_raw_mempool_verbose()?;
+
+    // Forget anything that left the mempool since the last scan
+    seen.retain(|txid| mempool.contains_key(txid));
+
+    for (txid, entry) in &mempool {
+        if !seen.insert(*txid) {
+            continue;
+        }
+        // TRUC packages are one parent and one child, so the parent can have neither
+        // unconfirmed ancestors nor a child already
+        if !entry.depends.is_empty() || entry.descendant_count > 1 {
+            continue;
+        }
+        // The tx may have been evicted or mined between the two calls
+        let Ok(tx) = client.get_raw_transaction(txid, None) else {
+            continue;
+        };
+        if tx.version != bitcoin::transaction::Version(3)
+            || find_anchor(&tx, state.config.anchor_policy).is_err()
+            || check_parent_fee(&state.config, Ok(entry.fees.base)).is_err()
+            || check_rules(&state.config.rules, &tx).is_err()
+        {
+            continue;
+        }
+
+        // Other protocols use anchors too, so only record parents that pay us
+        let sponsored = state.config.sponsorship.as_ref()
+            .is_some_and(|sponsorship| sponsorship::matching_template(&sponsorship.templates, &tx).is_some());
+        if !sponsored && validate_payment(state, &tx, None, &runes::last_input(&tx), true).await.is_err() {
+            continue;
+        }
+
+        info!("Found paying parent {} in the mempool", txid);
+        let _pending = PendingJob::start(&state.metrics.pending_jobs);
+        let job = state.jobs.start(JobKind::Package, now());
+        match anchor_parent(state, &tx, entry.fees.base.to_sat(), None, &runes::last_input(&tx), job)
+            .instrument(info_span!("mempool_parent", %txid))
+            .await
+        {
+            Ok(_) => info!("Anchored mempool parent {}", txid),
+            Err(e) => {
+                error!("Failed to anchor mempool parent {}: {}", txid, e);
+                state.jobs.fail(job, &e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_validate_psbt(
+    State(state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<SubmitPsbtRequest>,
+) -> (StatusCode, Json<ValidatePsbtResponse>) {
+    info!("Received PSBT to validate");
+
+    let mut report = ValidatePsbtResponse { protocol_version: Some(PROTOCOL_VERSION), ..Default::default() };
+    match validate_psbt(&state, &payload, &mut report).await {
+        Ok(()) => (StatusCode::OK, Json(report)),
+        Err(e) => {
+            info!("Dry run failed: {}", e);
+            report.success = false;
+            report.message = e.to_string();
+            report.findings = e.findings();
+            (e.status_code(), Json(report))
+        }
+    }
+}
+
+// A Lightning payment check that, unlike a submission, doesn't wait or bind the invoice
+async fn check_lightning_payment(state: &AppState, payment_hash: &str, parent: Txid) -> Result<u64, PaymentError> {
+    let node = state.lightning.as_ref().ok_or(PaymentError::NotConfigured)?;
+    let quote = state.jobs.invoice(payment_hash)
+        .ok_or_else(|| PaymentError::UnknownInvoice(payment_hash.to_string()))?;
+
+    if quote.parent.as_ref().is_some_and(|bound| *bound != parent.to_string()) {
+        return Err(PaymentError::WrongParent(payment_hash.to_string()));
+    }
+    if !node.is_paid(payment_hash).await? {
+        return Err(if now() >= quote.expires_at {
+            PaymentError::Expired(payment_hash.to_string())
+        } else {
+            PaymentError::Unpaid(payment_hash.to_string())
+        });
+    }
+    Ok(quote.amount_sat)
+}
+
+// Run a submission up to the point of spending anything: the child is built and signed,
+// then tested against the mempool with testmempoolaccept instead of submitted
+async fn validate_psbt(state: &AppState, payload: &SubmitPsbtRequest, report: &mut ValidatePsbtResponse) -> Result<(), SearcherError> {
+    let psbt = Psbt::from_str(&payload.psbt).map_err(ValidationError::from)?;
+    check_psbt_network(&psbt, state.config.network)?;
+    policy::check_standard(&psbt)?;
+    state.config.ephemeral_policy.apply(policy::check_parent(&psbt))?;
+    check_parent_fee(&state.config, psbt.fee())?;
+    let parent_fee = psbt.fee().map(Amount::to_sat).unwrap_or(0);
+    let payment_inputs = runes::payment_inputs(&psbt, payload.payment_inputs.as_deref())?;
+    check_deposit(state, &psbt)?;
+    let tx = psbt.extract_tx().map_err(ValidationError::from)?;
+    let parent_txid = tx.compute_txid();
+    report.parent_txid = Some(parent_txid.to_string());
+    report.parent_vsize = Some(tx.vsize() as u64);
+    let commit = match &payload.commit_psbt {
+        Some(commit_psbt) => Some(check_commit(state, &connect_rpc(state, primary_wallet(&state.config))?, commit_psbt, &tx)?),
+        None => None,
+    };
+    let commit_txid = commit.as_ref().map(Transaction::compute_txid);
+    check_anchor_reuse(state, &tx, None)?;
+    check_package_size(&state.config, tx.vsize() as u64 + child_vsize_estimate(&state.config))?;
+
+    let anchor_wallet = check_anchor(state, &tx)?;
+    check_rules(&state.config.rules, &tx)?;
+
+    let sponsored = state.config.sponsorship.as_ref()
+        .and_then(|sponsorship| sponsorship::matching_template(&sponsorship.templates, &tx))
+        .filter(|_| {
+            let estimate = estimate_package_cost(state, tx.vsize() as u64);
+            state.budget.as_ref().is_some_and(|budget| budget.remaining(now()) >= estimate)
+        });
+    report.payment = Some(match (sponsored, &payload.payment_hash) {
+        (Some(template), _) => Payment::Sponsored {
+            template: template.name.clone(),
+            amount_sat: estimate_package_cost(state, tx.vsize() as u64),
+        },
+        (None, Some(payment_hash)) => Payment::Lightning {
+            amount_sat: check_lightning_payment(state, payment_hash, parent_txid).await?,
+        },
+        (None, None) => validate_payment(state, &tx, None, &payment_inputs, true).await?,
+    });
+
+    let rune_script = match report.payment {
+        Some(Payment::Rune { .. }) if state.config.rotate_rune_address => Some(rune_address_for(state, parent_txid)?.script_pubkey()),
+        _ => None,
+    };
+    let rate = fee_rate(state, connect_rpc(state, primary_wallet(&state.config)).ok().as_ref());
+    let (client, wallet, searcher_utxo, _claim) = select_wallet_utxo(state, covering_utxo(&state.config, &tx, rate, rune_script.is_some()))?;
+    let anchor_signer = anchor_signer(state, anchor_wallet.as_deref(), &wallet)?;
+    report.wallet = Some(wallet);
+
+    // Spent or made-up inputs would otherwise only show up as a mempool rejection
+    let outpoints: Vec<OutPoint> = tx.input.iter()
+        .map(|input| input.previous_output)
+        .filter(|outpoint| Some(outpoint.txid) != commit_txid)
+        .collect();
+    if let Some(outpoint) = rpc::missing_outputs(&client, &outpoints)?.first() {
+        return Err(ValidationError::MissingInput(outpoint.to_string()).into());
+    }
+
+    let cpfp_tx = create_cpfp_transaction(
+        &tx,
+        &searcher_utxo,
+        change_script(&client, state.config.network)?,
+        rune_script,
+        state.config.revenue.as_ref().map(Revenue::output),
+        rate,
+    )?;
+    state.config.ephemeral_policy.apply(policy::check_package(&tx, &cpfp_tx))?;
+    let child_fee = searcher_utxo.amount.to_sat()
+        .saturating_sub(cpfp_tx.output.iter().map(|output| output.value.to_sat()).sum());
+    check_fee(&state.config, child_fee, searcher_utxo.amount.to_sat())?;
+    let sign_result = sign_child_with(state, &client, &tx, &cpfp_tx, anchor_signer.as_ref()).await?;
+    let child = sign_result.transaction().map_err(|e| SearcherError::Cpfp(e.to_string()))?;
+    let package_vsize = tx.vsize() as u64 + child.vsize() as u64;
+    report.child_txid = Some(child.compute_txid().to_string());
+    report.child_vsize = Some(child.vsize() as u64);
+    report.child_fee = Some(child_fee);
+    report.package_fee_rate = Some((parent_fee + child_fee) as f64 / package_vsize as f64);
+
+    // The parent can't reach the mempool before its commit confirms, so only the commit is tested
+    let package = match &commit {
+        Some(commit) => vec![commit],
+        None => vec![&tx, &child],
+    };
+    let rejections: Vec<String> = package.iter()
+        .zip(rpc::test_package(&client, &package)?)
+        .filter_map(|(tx, rejection)| Some(format!("{}: {}", tx.compute_txid(), rejection?)))
+        .collect();
+
+    report.mempool_accepted = Some(rejections.is_empty());
+    if !rejections.is_empty() {
+        report.reject_reason = Some(rejections.join(", "));
+        return Err(SearcherError::PackageRejected(rejections.join(", ")));
+    }
+
+    report.success = true;
+    report.message = match commit_txid {
+        Some(commit_txid) => format!("Commit {} would be accepted, and the parent anchored once it confirms", commit_txid),
+        None => "Package would be accepted".to_string(),
+    };
+    Ok(())
+}
+
+async fn handle_payjoin(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    ValidJson(payload): ValidJson<SubmitPsbtRequest>,
+) -> Response {
+    info!("Received payjoin request");
+    let _pending = PendingJob::start(&state.metrics.pending_jobs);
+    let job = state.jobs.start(JobKind::Payjoin, now());
+    state.jobs.update(job, |job| job.client = connect_info.map(|ConnectInfo(peer)| peer.ip().to_string()));
+
+    // A payjoin that times out after locking its UTXO is released like any other failure
+    match within_job_timeout(&state, move |state| async move { payjoin(&state, &payload, job).await }).await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(e) => {
+            error!("Payjoin failed: {}", e);
+            state.jobs.fail(job, &e);
+            if let Some(job) = state.jobs.get(job) {
+                release_utxo(&state, &job);
+            }
+            (e.status_code(), retry_after_header(&e), Json(PayjoinResponse {
+                success: false,
+                protocol_version: Some(PROTOCOL_VERSION),
+                message: e.to_string(),
+                psbt: None,
+                fee: None,
+                findings: e.findings(),
+                retry_after: e.retry_after(),
+            })).into_response()
+        }
+    }
+}
+
+// Fund the parent directly instead of with a child: the anchor is replaced by the
+// searcher's change and the searcher's UTXO becomes the last input
+async fn payjoin(state: &AppState, payload: &SubmitPsbtRequest, job: u64) -> Result<PayjoinResponse, SearcherError> {
+    let mut psbt = Psbt::from_str(&payload.psbt).map_err(ValidationError::from)?;
+    check_psbt_network(&psbt, state.config.network)?;
+    let tx = psbt.unsigned_tx.clone();
+    state.jobs.update(job, |job| {
+        job.cancel_hash = payload.cancel_token.as_deref().map(cancel_hash);
+        job.quote_client = state.abuse.quoted_by(tx.compute_txid()).map(|client| client.to_string());
+    });
+    if payload.commit_psbt.is_some() {
+        return Err(SearcherError::Payjoin("a payjoin is broadcast by the submitter, who broadcasts its commit too".to_string()));
+    }
+    info!("Transaction has {} inputs and {} outputs", tx.input.len(), tx.output.len());
+
+    // The anchor is replaced, so a keyed one doesn't need to be ours
+    find_anchor(&tx, state.config.anchor_policy)?;
+    check_rules(&state.config.rules, &tx)?;
+    // Our input and change take about as much room as a child would
+    check_package_size(&state.config, estimate_signed_vsize(&psbt) + CHILD_VSIZE_ESTIMATE)?;
+    // The searcher's change takes sats ahead of the submitter's outputs, which would
+    // move any inscription paid to us, so only runes and Lightning can pay here
+    let payment_inputs = runes::payment_inputs(&psbt, payload.payment_inputs.as_deref())?;
+    let payment = validate_payment(state, &tx, payload.payment_hash.as_deref(), &payment_inputs, false).await?;
+    state.jobs.log(job, "payment", format!("Accepted payment {:?}", payment));
+    state.jobs.update(job, |job| job.payment = Some(payment));
+
+    let (client, wallet, searcher_utxo, _claim) = select_wallet_utxo(state, |unspent| {
+        unspent.iter().max_by_key(|utxo| utxo.amount).cloned()
+    })?;
+    state.jobs.update(job, |job| job.wallet = Some(wallet));
+    let change_script = change_script(&client, state.config.network)?;
+
+    psbt.unsigned_tx.input.push(TxIn {
+        previous_output: OutPoint { txid: searcher_utxo.txid, vout: searcher_utxo.vout },
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        ..Default::default()
+    });
+    psbt.inputs.push(psbt::Input {
+        witness_utxo: Some(TxOut { value: searcher_utxo.amount, script_pubkey: searcher_utxo.script_pub_key.clone() }),
+        ..Default::default()
+    });
+
+    // Replacing output 0 keeps the submitter's runestone edicts pointing at the same outputs
+    psbt.unsigned_tx.output[0] = TxOut { value: searcher_utxo.amount, script_pubkey: change_script };
+    psbt.outputs[0] = psbt::Output::default();
+
+    let vsize = estimate_signed_vsize(&psbt);
+    let fee = (vsize as f64 * fee_rate(state, Some(&client))).ceil() as u64;
+    check_fee(&state.config, fee, searcher_utxo.amount.to_sat())?;
+    let change = searcher_utxo.amount.to_sat()
+        .checked_sub(fee)
+        .ok_or(SearcherError::NoWalletUtxos)?;
+    psbt.unsigned_tx.output[0].value = Amount::from_sat(change);
+    info!("Payjoin vsize: {} vbytes, fee: {} sats, change: {} sats", vsize, fee, change);
+    state.jobs.log(job, "fee", format!(
+        "Adding {}:{} ({} sats): {} vbytes pay {} sats, {} sats change",
+        searcher_utxo.txid,
+        searcher_utxo.vout,
+        searcher_utxo.amount.to_sat(),
+        vsize,
+        fee,
+        change
+    ));
+
+    // Keep the UTXO out of other jobs until the submitter broadcasts or it's unlocked
+    let outpoint = OutPoint { txid: searcher_utxo.txid, vout: searcher_utxo.vout };
+    client.lock_unspent(&[outpoint])?;
+    state.jobs.update(job, |job| job.locked_utxo = Some(outpoint.to_string()));
+
+    info!("Signing searcher input with wallet...");
+    let signed = match &state.signer {
+        Some(signer) => {
+            let processed = client.wallet_process_psbt(&psbt.to_string(), Some(false), None, Some(true))?;
+            signer.sign(&Psbt::from_str(&processed.psbt).map_err(ValidationError::from)?).await?
+        }
+        None => {
+            let processed = client.wallet_process_psbt(&psbt.to_string(), Some(true), None, None)?;
+            Psbt::from_str(&processed.psbt).map_err(ValidationError::from)?
+        }
+    };
+    let searcher_input = signed.inputs.last().ok_or(SearcherError::IncompleteSignature)?;
+    if searcher_input.final_script_witness.is_none() && searcher_input.partial_sigs.is_empty() {
+        return Err(SearcherError::IncompleteSignature);
+    }
+
+    info!("Payjoin {} ready for the submitter to sign", signed.unsigned_tx.compute_txid());
+    state.jobs.log(job, "sign", "Searcher input signed".to_string());
+    let submitted = state.jobs.transition(job, JobStatus::Pending, |job| {
+        job.status = JobStatus::Submitted;
+        job.parent_txid = Some(signed.unsigned_tx.compute_txid().to_string());
+        job.vsize = Some(vsize);
+        job.fee_sat = Some(fee);
+        job.fees_spent_sat = Some(fee);
+        job.submitted_at = Some(now());
+    });
+    // Failed meanwhile, like by timing out, after the failure released what it knew of
+    if !submitted {
+        if let Some(job) = state.jobs.get(job) {
+            release_utxo(state, &job);
+        }
+        return Err(SearcherError::Cancelled);
+    }
+
+    Ok(PayjoinResponse {
+        success: true,
+        protocol_version: Some(PROTOCOL_VERSION),
+        message: "Searcher input signed, sign the remaining inputs and broadcast".to_string(),
+        psbt: Some(signed.to_string()),
+        fee: Some(fee),
+        findings: None,
+        retry_after: None,
+    })
+}
+
+// Wait for a quoted invoice to settle, making sure it pays for this parent
+async fn await_lightning_payment(state: &AppState, payment_hash: &str, parent: Txid) -> Result<u64, PaymentError> {
+    let node = state.lightning.as_ref().ok_or(PaymentError::NotConfigured)?;
+
+    // Invoices quoted by vsize are bound to the first parent submitted with them
+    let quote = state.jobs.update_invoice(payment_hash, |quote| {
+        quote.parent.get_or_insert_with(|| parent.to_string());
+    });
+    let quote = quote.ok_or_else(|| PaymentError::UnknownInvoice(payment_hash.to_string()))?;
+    if quote.parent != Some(parent.to_string()) {
+        return Err(PaymentError::WrongParent(payment_hash.to_string()));
+    }
+
+    let deadline = now() + PAYMENT_WAIT_SECS;
+    loop {
+        if node.is_paid(payment_hash).await? {
+            return Ok(quote.amount_sat);
+        }
+        if now() >= quote.expires_at {
+            return Err(PaymentError::Expired(payment_hash.to_string()));
+        }
+        if now() >= deadline {
+            return Err(PaymentError::Unpaid(payment_hash.to_string()));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+// Every so often, compare the fee rate with what standing quotes were priced at. Past
+// the spike, the quotes no longer stand: unpaid invoices are cancelled at the node so
+// they can't be paid, and subscribers are told which quotes to drop.
+async fn watch_fee_spikes(state: Arc<AppState>, spike: f64) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(FEE_SPIKE_CHECK_SECS)).await;
+        let client = connect_rpc(&state, primary_wallet(&state.config)).ok();
+        let rate = fee_rate(&state, client.as_ref());
+        let stale_below = rate / (1.0 + spike / 100.0);
+        if !state.quotes.withdraw_below(stale_below, now()) {
+            continue;
+        }
+
+        let mut cancelled_invoices = Vec::new();
+        if let Some(node) = &state.lightning {
+            let stale = state.jobs.invoices().into_iter()
+                .filter(|quote| quote.fee_rate < stale_below && quote.expires_at > now())
+                .map(|quote| quote.payment_hash)
+                .collect::<Vec<_>>();
+            for payment_hash in stale {
+                // Paid ones fail to cancel and are honored
+                match node.cancel_invoice(&payment_hash).await {
+                    Ok(()) => {
+                        state.jobs.update_invoice(&payment_hash, |quote| quote.expires_at = now());
+                        cancelled_invoices.push(payment_hash);
+                    }
+                    Err(e) => info!("Keeping invoice {}: {}", payment_hash, e),
+                }
+            }
+        }
+
+        let subscribers = state.quotes.publish(QuoteEvent {
+            event: "quotes_expired".to_string(),
+            at: now(),
+            fee_rate: rate,
+            stale_below,
+            cancelled_invoices,
+        });
+        info!(
+            "Fee rate rose to {} sat/vB, withdrew quotes under {:.2} sat/vB and told {} subscribers",
+            rate, stale_below, subscribers,
+        );
+    }
+}
+
+// Upgrades to a WebSocket that gets a QuoteEvent each time standing quotes are withdrawn
+async fn handle_quote_events(State(state): State<Arc<AppState>>, mut request: Request) -> Response {
+    let websocket = request.headers().get(header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let Some(key) = request.headers().get(header::SEC_WEBSOCKET_KEY).filter(|_| websocket) else {
+        let body = Json(serde_json::json!({ "success": false, "message": "Expected a WebSocket upgrade" }));
+        return (StatusCode::BAD_REQUEST, body).into_response();
+    };
+    let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+
+    let events = state.quotes.subscribe();
+    let upgrade = hyper::upgrade::on(&mut request);
+    tokio::spawn(async move {
+        match upgrade.await {
+            Ok(upgraded) => quotes::stream(upgraded, events).await,
+            Err(e) => info!("Quote event subscriber left before upgrading: {}", e),
+        }
+    });
+    let headers = [
+        (header::UPGRADE, "websocket".to_string()),
+        (header::CONNECTION, "Upgrade".to_string()),
+        (header::SEC_WEBSOCKET_ACCEPT, accept),
+    ];
+    (StatusCode::SWITCHING_PROTOCOLS, headers).into_response()
+}
+
+async fn handle_quote(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    ValidJson(payload): ValidJson<QuoteRequest>,
+) -> Response {
+    info!("Received quote request");
+
+    match quote(&state, &payload, connect_info.map(|ConnectInfo(peer)| peer.ip())).await {
+        // Invoices and keyed anchor scripts are made fresh for each quote
+        Ok(response) if response.invoice.is_some() || response.anchor_script.is_some() => {
+            (StatusCode::OK, [(header::CACHE_CONTROL, "no-store")], Json(response)).into_response()
+        }
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(e) => {
+            error!("Quote failed: {}", e);
+            (e.status_code(), Json(QuoteResponse {
+                success: false,
+                protocol_version: Some(PROTOCOL_VERSION),
+                message: e.to_string(),
+                ..Default::default()
+            })).into_response()
+        }
+    }
+}
+
+// What a package costs in runes, with the exchange rate when the price is in USD
+async fn rune_price(state: &AppState, package_vsize: u64, multiplier: f64) -> Result<(RuneAmount, Option<ExchangeRate>), SearcherError> {
+    // USD prices are converted to rune units at the current exchange rate
+    Ok(match (&state.price_feed, state.config.usd_price) {
+        (Some(feed), Some(usd_price)) => {
+            let rate = feed.rate().await?;
+            let amount = price_package_usd(package_vsize, usd_price * multiplier, rate.usd_per_rune, state.config.rune_divisibility);
+            (amount, Some(rate))
+        }
+        _ => {
+            let from_histogram = state.config.histogram_pricing
+                .and_then(|pricing| price_package_histogram(state, &pricing, package_vsize, multiplier));
+            if state.config.histogram_pricing.is_some() && from_histogram.is_none() {
+                error!("No mempool fee histogram available, quoting the static rune price");
+            }
+            let amount = from_histogram.unwrap_or_else(|| {
+                RuneAmount::new(price_package(package_vsize, state.config.rune_price, multiplier), state.config.rune_divisibility)
+            });
+            (amount, None)
+        }
+    })
+}
+
+// The package a quote prices. A keyed anchor takes a signature to spend, so the child
+// comes out bigger, and runes paid to a rotated address take an extra child output.
+fn quoted_package_vsize(state: &AppState, parent_vsize: u64, lightning: bool) -> u64 {
+    parent_vsize + child_vsize_estimate(&state.config)
+        + if state.config.anchor_policy.accepts_keyed() { KEYED_ANCHOR_EXTRA_VSIZE } else { 0 }
+        + if state.config.rotate_rune_address && !lightning { RUNE_OUTPUT_VSIZE } else { 0 }
+}
+
+async fn quote(state: &AppState, payload: &QuoteRequest, client: Option<IpAddr>) -> Result<QuoteResponse, SearcherError> {
+    // Size the parent either from the PSBT itself or from the caller's estimate
+    let (parent_vsize, parent) = match (&payload.psbt, payload.vsize) {
+        (Some(psbt), _) => {
+            let psbt = Psbt::from_str(psbt).map_err(ValidationError::from)?;
+            check_psbt_network(&psbt, state.config.network)?;
+            check_parent_fee(&state.config, psbt.fee())?;
+            (estimate_signed_vsize(&psbt), Some(psbt.unsigned_tx.compute_txid()))
+        }
+        (None, Some(vsize)) => (vsize, None),
+        (None, None) => return Err(SearcherError::MissingQuoteInput),
+    };
+
+    // Each quote hands out a fresh key for a keyed anchor
+    let anchor_script = match state.config.anchor_policy.accepts_keyed() {
+        true => Some(new_anchor_script(state)?),
+        false => None,
+    };
+    let package_vsize = quoted_package_vsize(state, parent_vsize, payload.lightning);
+    let anchor_script = anchor_script.map(|script| script.to_hex_string());
+    let (deposit_amount, deposit_delay) = state.config.anchor_deposit
+        .filter(|_| anchor_script.is_some())
+        .map(|deposit| (deposit.amount_sat, deposit.delay))
+        .unzip();
+    check_package_size(&state.config, package_vsize)?;
+    let multiplier = tier_multiplier(&state.config.price_tiers, parent_vsize);
+    let price_multiplier = (!state.config.price_tiers.is_empty()).then_some(multiplier);
+    let expires_at = now() + state.config.quote_ttl;
+    let rpc = connect_rpc(state, primary_wallet(&state.config)).ok();
+    let fee_rate = fee_rate(state, rpc.as_ref());
+    let depth_rate = depth_rate(state, rpc.as_ref());
+    if let (Some(parent), Some(client)) = (parent, client) {
+        state.abuse.record_quoted_parent(parent, client, expires_at);
+    }
+
+    // Sponsored parents are free while the budget lasts, which a later submission rechecks
+    if let (Some(psbt), Some(sponsorship), Some(budget)) = (&payload.psbt, &state.config.sponsorship, &state.budget) {
+        let tx = Psbt::from_str(psbt).map_err(ValidationError::from)?.unsigned_tx;
+        if let Some(template) = sponsorship::matching_template(&sponsorship.templates, &tx)
+            && budget.remaining(now()) >= estimate_package_cost(state, parent_vsize)
+        {
+            info!("Quoted sponsored {} vbyte package for free", package_vsize);
+            return Ok(QuoteResponse {
+                success: true,
+                protocol_version: Some(PROTOCOL_VERSION),
+                message: format!("Sponsored for paying {}, no payment needed", template.name),
+                rune: Some(RUNE_NAME.to_string()),
+                rune_amount: Some(0),
+                fee_rate: Some(fee_rate),
+                package_vsize: Some(package_vsize),
+                expires_at: Some(expires_at),
+                anchor_script,
+                deposit_amount,
+                deposit_delay,
+                max_package_vsize: state.config.max_package_vsize,
+                sponsored: true,
+                ..Default::default()
+            });
+        }
+    }
+
+    if payload.lightning {
+        let node = state.lightning.as_ref().ok_or(PaymentError::NotConfigured)?;
+        let amount_sat = price_package(package_vsize, node.price(fee_rate), multiplier);
+        let memo = format!("slugline fee sponsorship for {} vbytes", package_vsize);
+        let invoice = node.create_invoice(amount_sat, &memo, state.config.quote_ttl).await?;
+
+        // Segwit parents keep their txid once signed, so bind the invoice to it now
+        state.jobs.quote_invoice(jobs::Invoice {
+            payment_hash: invoice.payment_hash.clone(),
+            parent: parent.map(|parent| parent.to_string()),
+            expires_at: invoice.expires_at,
+            amount_sat,
+            fee_rate,
+        });
+        state.quotes.issue(fee_rate, invoice.expires_at);
+
+        info!("Quoted {} sats over Lightning for {} vbyte package", amount_sat, package_vsize);
+
+        return Ok(QuoteResponse {
+            success: true,
+            protocol_version: Some(PROTOCOL_VERSION),
+            message: "Quote created successfully".to_string(),
+            fee_rate: Some(fee_rate),
+            package_vsize: Some(package_vsize),
+            expires_at: Some(invoice.expires_at),
+            invoice: Some(invoice.payment_request),
+            payment_hash: Some(invoice.payment_hash),
+            amount_sat: Some(amount_sat),
+            price_multiplier,
+            max_package_vsize: state.config.max_package_vsize,
+            anchor_script,
+            deposit_amount,
+            deposit_delay,
+            ..Default::default()
+        });
+    }
+
+    let (rune_amount, rate) = rune_price(state, package_vsize, multiplier).await?;
+    let rune_amount = price_for_depth(rune_amount, priced_rate(state, rpc.as_ref(), fee_rate), depth_rate);
+    let fee_rate = child_rate(state, fee_rate, depth_rate);
+    state.quotes.issue(fee_rate, expires_at);
+
+    info!("Quoted {} {} for {} vbyte package", rune_amount, RUNE_NAME, package_vsize);
+
+    // Rotated addresses are tied to a parent, so quotes by vsize don't name one
+    let rune_address = match (state.config.rotate_rune_address, parent) {
+        (true, Some(parent)) => Some(rune_address_for(state, parent)?.to_string()),
+        (true, None) => None,
+        (false, _) => state.config.rune_address.clone(),
+    };
+
+    Ok(QuoteResponse {
+        success: true,
+        protocol_version: Some(PROTOCOL_VERSION),
+        message: "Quote created successfully".to_string(),
+        rune: Some(RUNE_NAME.to_string()),
+        rune_amount: Some(rune_amount.units),
+        rune_divisibility: Some(rune_amount.divisibility),
+        rune_address,
+        fee_rate: Some(fee_rate),
+        package_vsize: Some(package_vsize),
+        expires_at: Some(expires_at),
+        // Inscriptions are accepted in place of the runes
+        inscription_collection: state.config.inscription_payment.as_ref().and_then(|p| p.collection.clone()),
+        ordinal_address: state.config.inscription_payment.as_ref().map(|p| p.address.clone()),
+        exchange_rate: rate.as_ref().map(|r| r.usd_per_rune),
+        rate_timestamp: rate.as_ref().map(|r| r.timestamp),
+        price_multiplier,
+        max_package_vsize: state.config.max_package_vsize,
+        anchor_script,
+        deposit_amount,
+        deposit_delay,
+        ..Default::default()
+    })
+}
+
+async fn handle_info(State(state): State<Arc<AppState>>) -> Json<InfoResponse> {
+    let config = &state.config;
+    let payment_methods = [
+        Some("rune"),
+        config.lightning.as_ref().map(|_| "lightning"),
+        config.inscription_payment.as_ref().map(|_| "inscription"),
+        config.sponsorship.as_ref().map(|_| "sponsorship"),
+    ];
+
+    Json(InfoResponse {
+        protocol_version: PROTOCOL_VERSION,
+        protocol_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+        network: config.network.to_string(),
+        runes: vec![RuneTerms {
+            name: RUNE_NAME.to_string(),
+            // Neither is fixed when prices follow the mempool
+            price_per_vbyte: (config.usd_price.is_none() && config.histogram_pricing.is_none()).then_some(config.rune_price),
+            divisibility: Some(config.rune_divisibility),
+            usd_per_vbyte: config.usd_price,
+            min_confirmations: Some(config.min_payment_confirmations),
+        }],
+        payment_methods: payment_methods.into_iter().flatten().map(str::to_string).collect(),
+        max_package_vsize: config.max_package_vsize,
+        rune_address: config.rune_address.clone().filter(|_| !config.rotate_rune_address),
+        contact: config.contact.clone(),
+    })
+}
+
+async fn handle_health(State(state): State<Arc<AppState>>) -> (StatusCode, Json<HealthResponse>) {
+    let mut health = HealthResponse {
+        status: "ok".to_string(),
+        message: "Searcher is healthy".to_string(),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        pending_jobs: state.metrics.pending_jobs.load(Ordering::Relaxed),
+        wallet_utxos: None,
+        last_block_height: None,
+        last_block_hash: None,
+        nodes: Vec::new(),
+    };
+
+    // Report degraded rather than failing outright so callers still get the counters
+    let checked = state.config.wallets.iter().try_fold(0, |utxos, wallet| {
+        connect_rpc(&state, wallet)
+            .and_then(|client| spendable_utxos(&state.config, &client))
+            .map(|unspent| utxos + unspent.len())
+            .map_err(|e| format!("wallet {}: {}", wallet, e))
+    }).and_then(|utxos| {
+        let client = connect_rpc(&state, primary_wallet(&state.config)).map_err(|e| e.to_string())?;
+        let height = client.get_block_count().map_err(|e| e.to_string())?;
+        let hash = client.get_block_hash(height).map_err(|e| e.to_string())?;
+        Ok((utxos, height, hash))
+    });
+
+    health.nodes.push(NodeHealth {
+        node: redact_url(&state.config.bitcoind_host),
+        role: "primary".to_string(),
+        status: if checked.is_ok() { "ok" } else { "unreachable" }.to_string(),
+        last_block_height: checked.as_ref().ok().map(|(_, height, _)| *height),
+        last_rejection: None,
+    });
+    // Broadcast nodes being down only slows propagation, so they don't degrade the searcher
+    let rejections = state.node_rejections.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    for node in &state.config.broadcast_nodes {
+        let address = node.address(state.config.network);
+        let height = connect_node(&state.config, node).and_then(|client| client.get_block_count());
+        if let Err(e) = &height {
+            error!("Broadcast node {} unreachable: {}", address, e);
+        }
+        health.nodes.push(NodeHealth {
+            role: "broadcast".to_string(),
+            status: if height.is_ok() { "ok" } else { "unreachable" }.to_string(),
+            last_block_height: height.ok(),
+            last_rejection: rejections.get(&address).cloned(),
+            node: address,
+        });
+    }
+
+    match checked {
+        Ok((wallet_utxos, height, hash)) => {
+            health.wallet_utxos = Some(wallet_utxos);
+            health.last_block_height = Some(height);
+            health.last_block_hash = Some(hash.to_string());
+            // Still up and quoting, but relays and monitoring should know submissions are refused
+            let low_funds = *state.low_funds.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(funds) = low_funds {
+                health.status = "low_funds".to_string();
+                health.message = SearcherError::LowFunds { balance_sat: funds.balance_sat, free_utxos: funds.free_utxos }.to_string();
+            }
+            (StatusCode::OK, Json(health))
+        }
+        Err(e) => {
+            error!("Health check failed: {}", e);
+            health.status = "degraded".to_string();
+            health.message = format!("Bitcoin Core unavailable: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, Json(health))
+        }
+    }
+}
+
+async fn handle_metrics(State(state): State<Arc<AppState>>) -> String {
+    let low_funds = *state.low_funds.lock().unwrap_or_else(|e| e.into_inner());
+    let earnings = state.jobs.earnings(0);
+    let metrics = [
+        ("slugline_uptime_seconds", "gauge", "Seconds since the searcher started", state.started_at.elapsed().as_secs()),
+        ("slugline_pending_jobs", "gauge", "Submissions currently being processed", state.metrics.pending_jobs.load(Ordering::Relaxed)),
+        ("slugline_submissions_total", "counter", "PSBT submissions received", earnings.jobs),
+        ("slugline_packages_submitted_total", "counter", "Packages accepted by bitcoind", earnings.packages_submitted),
+        ("slugline_payjoins_signed_total", "counter", "Payjoins signed and handed back", earnings.payjoins_signed),
+        ("slugline_fees_spent_sat_total", "counter", "Sats the wallets paid in child, payjoin and replacement fees", earnings.fees_spent_sat),
+        ("slugline_runes_earned_total", "counter", "Runes received for sponsored jobs, in the rune's smallest units", earnings.runes_earned),
+        ("slugline_lightning_earned_sat_total", "counter", "Sats received over Lightning for sponsored jobs", earnings.lightning_earned_sat),
+        ("slugline_clients_flagged_total", "counter", "Clients flagged for abusing quotes or submissions", state.abuse.flagged.load(Ordering::Relaxed)),
+        ("slugline_revenue_sat_total", "counter", "Sats children sent to the revenue address", earnings.revenue_sat),
+        ("slugline_low_funds", "gauge", "1 while the wallets are too low to take submissions", u64::from(low_funds.is_some())),
+    ];
+
+    // Prometheus text exposition format
+    let mut body = String::new();
+    for (name, kind, help, value) in metrics {
+        body.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+    }
+    body
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsQuery {
+    // Only count jobs received in the last this many days
+    days: Option<u64>,
+}
+
+// Record confirmations of sponsored jobs, from the wallet since every job spends one of its coins
+// Returns the jobs that confirmed since the last refresh
+fn refresh_confirmations(state: &AppState) -> Result<Vec<u64>, SearcherError> {
+    let unconfirmed: Vec<(u64, Txid, String)> = state.jobs.jobs()
+        .into_iter()
+        .filter(|job| job.status == JobStatus::Submitted)
+        .filter_map(|job| {
+            let txid = Txid::from_str(job.tracked_txid()?).ok()?;
+            let wallet = job.wallet.clone().unwrap_or_else(|| primary_wallet(&state.config).to_string());
+            Some((job.id, txid, wallet))
+        })
+        .collect();
+
+    let mut clients: HashMap<String, Client> = HashMap::new();
+    let mut confirmed = Vec::new();
+    for (id, txid, wallet) in unconfirmed {
+        if !clients.contains_key(&wallet) {
+            clients.insert(wallet.clone(), connect_rpc(state, &wallet)?);
+        }
+        match clients[&wallet].get_transaction(&txid, None) {
+            Ok(tx) if tx.info.confirmations > 0 => {
+                state.jobs.update(id, |job| {
+                    job.status = JobStatus::Confirmed;
+                    job.confirmed_at = tx.info.blocktime.or(Some(now()));
+                });
+                confirmed.push(id);
+            }
+            Ok(_) => {}
+            // Payjoins the submitter never broadcast stay unknown to the wallet
+            Err(e) => info!("No confirmation for job {} ({}): {}", id, txid, e),
+        }
+    }
+    Ok(confirmed)
+}
+
+// Tell the operator when bitcoind goes away or comes back, and about big jobs confirming
+async fn watch_operations(state: Arc<AppState>) {
+    loop {
+        let reachable = connect_rpc(&state, primary_wallet(&state.config)).and_then(|client| client.get_block_count());
+        match reachable {
+            Err(e) => {
+                if !state.bitcoind_down.swap(true, Ordering::Relaxed) {
+                    error!("bitcoind unreachable: {}", e);
+                    state.notifier.notify("bitcoind_unreachable", format!("bitcoind is unreachable: {}", e));
+                }
+            }
+            Ok(_) => {
+                if state.bitcoind_down.swap(false, Ordering::Relaxed) {
+                    info!("bitcoind reachable again");
+                    state.notifier.notify("bitcoind_reachable", "bitcoind is reachable again".to_string());
+                }
+                if let Some(min_fee) = state.config.notify_large_job_sat {
+                    notify_large_jobs(&state, min_fee);
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(NOTIFY_CHECK_SECS)).await;
+    }
+}
+
+fn notify_large_jobs(state: &AppState, min_fee: u64) {
+    let confirmed = match refresh_confirmations(state) {
+        Ok(confirmed) => confirmed,
+        Err(e) => {
+            error!("Failed to refresh confirmations: {}", e);
+            return;
+        }
+    };
+    for job in confirmed.into_iter().filter_map(|id| state.jobs.get(id)) {
+        if let Some(fee) = job.fee_sat.filter(|fee| *fee >= min_fee) {
+            state.notifier.notify("large_job_confirmed", format!(
+                "Job {} confirmed, paying {} sats for parent {}",
+                job.id,
+                fee,
+                job.parent_txid.as_deref().unwrap_or("?")
+            ));
+        }
+    }
+}
+
+// Unlock the UTXO a payjoin job held, so other jobs can spend it
+fn release_utxo(state: &AppState, job: &jobs::Job) {
+    let (Some(outpoint), Some(wallet)) = (&job.locked_utxo, &job.wallet) else {
+        return;
+    };
+    let released = OutPoint::from_str(outpoint)
+        .map_err(|e| e.to_string())
+        .and_then(|outpoint| {
+            let client = connect_rpc(state, wallet).map_err(|e| e.to_string())?;
+            client.unlock_unspent(&[outpoint]).map_err(|e| e.to_string())
+        });
+    match released {
+        Ok(_) => info!("Unlocked {} held by job {}", outpoint, job.id),
+        // Already spent, or unlocked by a bitcoind restart
+        Err(e) => info!("Could not unlock {} held by job {}: {}", outpoint, job.id, e),
+    }
+    state.jobs.update(job.id, |job| job.locked_utxo = None);
+}
+
+// The searcher may have died between submitting a package and recording it
+fn recover_pending_package(state: &AppState, client: &Client, job: &jobs::Job) {
+    let child = job.parent_txid.as_deref()
+        .and_then(|txid| Txid::from_str(txid).ok())
+        .and_then(|parent| client.get_mempool_entry(&parent).ok())
+        .and_then(|entry| entry.spent_by.into_iter().find(|child| client.get_transaction(child, None).is_ok()));
+
+    match child {
+        Some(child) => {
+            info!("Job {} was submitted as {} before the restart", job.id, child);
+            state.jobs.update(job.id, |job| {
+                job.status = JobStatus::Submitted;
+                job.child_txid = Some(child.to_string());
+                job.submitted_at = Some(now());
+            });
+        }
+        None if job.held_psbt.is_some() => info!("Job {} is still waiting for its commit to confirm", job.id),
+        None => state.jobs.fail(job.id, &SearcherError::Interrupted),
+    }
+}
+
+// Children that dropped out of the mempool while we were down are rebroadcast, or
+// abandoned so the wallet can spend their input again
+fn recover_package(state: &AppState, client: &Client, job: &jobs::Job) {
+    let Some(child) = job.child_txid.as_deref().and_then(|txid| Txid::from_str(txid).ok()) else {
+        return;
+    };
+    let wallet_tx = match client.get_transaction(&child, None) {
+        Ok(wallet_tx) => wallet_tx,
+        Err(e) => {
+            error!("Cannot check child {} of job {}: {}", child, job.id, e);
+            return;
+        }
+    };
+
+    if wallet_tx.info.confirmations > 0 {
+        info!("Job {} confirmed while the searcher was down", job.id);
+        state.jobs.update(job.id, |job| {
+            job.status = JobStatus::Confirmed;
+            job.confirmed_at = wallet_tx.info.blocktime.or(Some(now()));
+        });
+        return;
+    }
+    if wallet_tx.info.confirmations == 0 && client.get_mempool_entry(&child).is_ok() {
+        return;
+    }
+
+    // A conflicted child was replaced or double spent, so there's nothing to rebroadcast
+    let child_hex = hex::encode(&wallet_tx.hex);
+    let parent_hex = job.parent_txid.as_deref()
+        .and_then(|txid| Txid::from_str(txid).ok())
+        .and_then(|parent| client.get_raw_transaction_hex(&parent, None).ok());
+    let rebroadcast = match (wallet_tx.info.confirmations, parent_hex) {
+        (confirmations, _) if confirmations < 0 => Err(SearcherError::Dropped(child.to_string())),
+        (_, Some(parent_hex)) => relay_package(state, client, vec![parent_hex, child_hex]).map(|_| ()),
+        // Without txindex bitcoind only has the parent if it's still unconfirmed
+        (_, None) => client.send_raw_transaction(child_hex.as_str()).map(|_| ()).map_err(SearcherError::from),
+    };
+
+    match rebroadcast {
+        Ok(()) => info!("Rebroadcast package of job {}", job.id),
+        Err(e) => {
+            error!("Job {} left the mempool and could not be rebroadcast: {}", job.id, e);
+            if let Err(e) = client.call::<serde_json::Value>("abandontransaction", &[child.to_string().into()]) {
+                error!("Failed to abandon child {}: {}", child, e);
+            }
+            state.jobs.fail(job.id, &SearcherError::Dropped(child.to_string()));
+        }
+    }
+}
+
+// A payjoin only shows up in the wallet once the submitter broadcasts it
+fn recover_payjoin(state: &AppState, client: &Client, job: &jobs::Job) {
+    let Some(txid) = job.parent_txid.as_deref().and_then(|txid| Txid::from_str(txid).ok()) else {
+        return;
+    };
+    match client.get_transaction(&txid, None) {
+        Ok(wallet_tx) if wallet_tx.info.confirmations > 0 => state.jobs.update(job.id, |job| {
+            job.status = JobStatus::Confirmed;
+            job.confirmed_at = wallet_tx.info.blocktime.or(Some(now()));
+            job.locked_utxo = None;
+        }),
+        Ok(_) => {}
+        Err(_) if job.submitted_at.is_none_or(|at| now().saturating_sub(at) > PAYJOIN_LOCK_SECS) => {
+            info!("Payjoin of job {} was never broadcast, releasing its UTXO", job.id);
+            release_utxo(state, job);
+            state.jobs.fail(job.id, &SearcherError::PayjoinExpired);
+        }
+        // Locks don't survive a bitcoind restart, so take it again while the submitter may still broadcast
+        Err(_) => {
+            if let Some(outpoint) = job.locked_utxo.as_deref().and_then(|outpoint| OutPoint::from_str(outpoint).ok()) {
+                let _ = client.lock_unspent(&[outpoint]);
+            }
+        }
+    }
+}
+
+// Reconcile jobs a crash or restart left unfinished with what bitcoind knows now
+fn recover_jobs(state: &AppState) -> Result<(), SearcherError> {
+    let unfinished: Vec<jobs::Job> = state.jobs.jobs()
+        .into_iter()
+        .filter(|job| match job.status {
+            JobStatus::Pending | JobStatus::Submitted => true,
+            JobStatus::Failed | JobStatus::Cancelled => job.locked_utxo.is_some(),
+            JobStatus::Confirmed => false,
+        })
+        .collect();
+    if unfinished.is_empty() {
+        return Ok(());
+    }
+    info!("Reconciling {} unfinished jobs with bitcoind", unfinished.len());
+
+    let mut clients: HashMap<String, Client> = HashMap::new();
+    for job in unfinished {
+        let wallet = job.wallet.clone().unwrap_or_else(|| primary_wallet(&state.config).to_string());
+        if !clients.contains_key(&wallet) {
+            clients.insert(wallet.clone(), connect_rpc(state, &wallet)?);
+        }
+        let client = &clients[&wallet];
+
+        match (job.kind, job.status) {
+            (JobKind::Package, JobStatus::Pending) => recover_pending_package(state, client, &job),
+            (JobKind::Package, JobStatus::Submitted) => recover_package(state, client, &job),
+            (JobKind::Payjoin, JobStatus::Submitted) => recover_payjoin(state, client, &job),
+            (JobKind::Payjoin, JobStatus::Pending) => {
+                release_utxo(state, &job);
+                state.jobs.fail(job.id, &SearcherError::Interrupted);
+            }
+            (_, JobStatus::Failed | JobStatus::Cancelled) => release_utxo(state, &job),
+            (_, JobStatus::Confirmed) => {}
+        }
+    }
+    Ok(())
+}
+
+// Only a hash of the token is kept, so the job log can't be used to cancel jobs
+fn cancel_hash(token: &str) -> String {
+    sha256::Hash::hash(token.as_bytes()).to_string()
+}
+
+async fn handle_cancel(
+    State(state): State<Arc<AppState>>,
+    Path(txid): Path<String>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<CancelResponse>) {
+    match cancel(&state, &txid, bearer_token(&headers)).await {
+        Ok(response) => (StatusCode::OK, Json(response)),
+        Err(e) => {
+            error!("Cancelling {} failed: {}", txid, e);
+            (e.status_code(), Json(CancelResponse {
+                success: false,
+                message: e.to_string(),
+                replacement_txid: None,
+            }))
+        }
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// The latest job for a parent, and whether the token is the one it was submitted with
+fn find_job(state: &AppState, txid: &str, token: Option<&str>) -> Result<(jobs::Job, bool), SearcherError> {
+    let job = state.jobs.jobs()
+        .into_iter()
+        .rev()
+        .find(|job| job.parent_txid.as_deref() == Some(txid))
+        .ok_or_else(|| SearcherError::UnknownJob(txid.to_string()))?;
+    let authorized = job.cancel_hash.is_some() && job.cancel_hash == token.map(cancel_hash);
+    Ok((job, authorized))
+}
+
+async fn handle_job_log(
+    State(state): State<Arc<AppState>>,
+    Path(txid): Path<String>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<JobLogResponse>) {
+    let job = find_job(&state, &txid, bearer_token(&headers))
+        .and_then(|(job, authorized)| if authorized { Ok(job) } else { Err(SearcherError::LogDenied) });
+    match job {
+        Ok(job) => (StatusCode::OK, Json(JobLogResponse {
+            success: true,
+            message: format!("Job {} is {:?}", job.id, job.status).to_lowercase(),
+            status: Some(job.status),
+            failure_reason: job.failure_reason,
+            events: job.log,
+        })),
+        Err(e) => {
+            info!("Refusing log of {}: {}", txid, e);
+            (e.status_code(), Json(JobLogResponse {
+                success: false,
+                message: e.to_string(),
+                status: None,
+                failure_reason: None,
+                events: Vec::new(),
+            }))
+        }
+    }
+}
+
+async fn cancel(state: &AppState, txid: &str, token: Option<&str>) -> Result<CancelResponse, SearcherError> {
+    let (job, authorized) = find_job(state, txid, token)?;
+    if !authorized {
+        return Err(SearcherError::CancelDenied);
+    }
+
+    let mark_cancelled = |job: &mut jobs::Job| {
+        job.status = JobStatus::Cancelled;
+        job.failure_reason = Some(SearcherError::Cancelled.reason().to_string());
+        job.failure_message = Some(SearcherError::Cancelled.to_string());
+    };
+
+    // Still being checked or waiting on payment: the submission sees it and stops
+    if state.jobs.transition(job.id, JobStatus::Pending, mark_cancelled) {
+        info!("Job {} cancelled before broadcast", job.id);
+        return Ok(CancelResponse {
+            success: true,
+            message: "Job cancelled before broadcast".to_string(),
+            replacement_txid: None,
+        });
+    }
+
+    let claim = match job.kind {
+        JobKind::Package => Some(ReplacementClaim::take(state, job.id).await),
+        _ => None,
+    };
+    // Read after the claim, so a replacement that finished meanwhile is seen
+    let job = state.jobs.get(job.id).ok_or_else(|| SearcherError::UnknownJob(txid.to_string()))?;
+    match (job.kind, job.status) {
+        (JobKind::Package, JobStatus::Submitted) => {
+            let replaced = replace_child(state, &job, None).await?;
+            let generation = job.replacements + 1;
+            state.jobs.transition(job.id, JobStatus::Submitted, |job| {
+                mark_cancelled(job);
+                job.failure_message = Some(format!("Child replaced by {}", replaced.txid));
+                job.replacements = generation;
+                // The replacement pays more than the child it evicts and nothing to the revenue address
+                job.fees_spent_sat = Some(replaced.fee);
+                job.revenue_sat = None;
+            });
+            drop(claim);
+            state.jobs.log(job.id, "replaced", format!("Child replaced by {}, replacement {}", replaced.txid, generation));
+            info!("Job {} cancelled, child replaced by {}", job.id, replaced.txid);
+            Ok(CancelResponse {
+                success: true,
+                message: "Child replaced, the parent is no longer sponsored".to_string(),
+                replacement_txid: Some(replaced.txid.to_string()),
+            })
+        }
+        (JobKind::Payjoin, JobStatus::Submitted) => {
+            let client = connect_rpc(state, job.wallet.as_deref().unwrap_or(primary_wallet(&state.config)))?;
+            let parent = Txid::from_str(txid).map_err(|e| SearcherError::UnknownJob(e.to_string()))?;
+            if client.get_mempool_entry(&parent).is_ok() {
+                return Err(SearcherError::NotCancellable("the payjoin was already broadcast".to_string()));
+            }
+            // The payjoin spends a wallet input, so the wallet has it once it's confirmed
+            if client.get_transaction(&parent, None).is_ok_and(|wallet_tx| wallet_tx.info.confirmations > 0) {
+                return Err(SearcherError::NotCancellable("the payjoin already confirmed".to_string()));
+            }
+            // Spent by a payjoin the wallet hasn't caught up with, or by something else
+            if let Some(outpoint) = job.locked_utxo.as_deref().and_then(|outpoint| OutPoint::from_str(outpoint).ok())
+                && client.get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?.is_none()
+            {
+                return Err(SearcherError::NotCancellable("the searcher's input was already spent".to_string()));
+            }
+            release_utxo(state, &job);
+            state.jobs.transition(job.id, JobStatus::Submitted, mark_cancelled);
+            info!("Job {} cancelled, payjoin input released", job.id);
+            Ok(CancelResponse {
+                success: true,
+                message: "Payjoin cancelled, the searcher's input will be spent elsewhere".to_string(),
+                replacement_txid: None,
+            })
+        }
+        (_, status) => Err(SearcherError::NotCancellable(format!("the job is {:?}", status).to_lowercase())),
+    }
+}
+
+// A job's turn to replace its child. Replacements of one job are made one at a time: one
+// arriving while another is being built and broadcast waits for it to finish, then works
+// from the child it left, instead of racing it to double-spend the same input.
+struct ReplacementClaim<'a> {
+    state: &'a AppState,
+    job: u64,
+    _turn: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl<'a> ReplacementClaim<'a> {
+    async fn take(state: &'a AppState, job: u64) -> Self {
+        let turn = state.replacing.lock().unwrap_or_else(|e| e.into_inner())
+            .entry(job)
+            .or_default()
+            .clone();
+        ReplacementClaim { state, job, _turn: turn.lock_owned().await }
+    }
+}
+
+impl Drop for ReplacementClaim<'_> {
+    fn drop(&mut self) {
+        // Held by the map and this claim alone, so nothing else is waiting its turn
+        let mut replacing = self.state.replacing.lock().unwrap_or_else(|e| e.into_inner());
+        if replacing.get(&self.job).is_some_and(|turn| Arc::strong_count(turn) <= 2) {
+            replacing.remove(&self.job);
+        }
+    }
+}
+
+// A child's replacement as broadcast, its fee and the fee of the child it replaced
+struct Replaced {
+    txid: Txid,
+    fee: u64,
+    replaced_fee: u64,
+}
+
+// Bump a submitted package to `rate` by replacing its child. The job stays submitted with
+// the replacement as its child, a generation on. None when the job settled while it
+// waited its turn.
+async fn bump_child(state: &AppState, job: u64, rate: f64) -> Result<Option<Txid>, SearcherError> {
+    let _claim = ReplacementClaim::take(state, job).await;
+    let Some(job) = state.jobs.get(job).filter(|job| job.status == JobStatus::Submitted) else {
+        return Ok(None);
+    };
+    let replaced = replace_child(state, &job, Some(rate)).await?;
+    let generation = job.replacements + 1;
+    state.jobs.transition(job.id, JobStatus::Submitted, |job| {
+        job.child_txid = Some(replaced.txid.to_string());
+        job.fee_sat = job.fee_sat.map(|fee| fee.saturating_sub(replaced.replaced_fee) + replaced.fee);
+        job.fees_spent_sat = Some(replaced.fee);
+        job.replacements = generation;
+        job.next_block = Some(true);
+    });
+    state.jobs.log(job.id, "bumped", format!(
+        "Child replaced by {} paying {} sats for {:.2} sat/vB, replacement {}",
+        replaced.txid, replaced.fee, rate, generation,
+    ));
+    Ok(Some(replaced.txid))
+}
+
+// bitcoind's incremental relay fee in sat/vB, which every replacement has to pay on its
+// own size on top of the fees of what it replaces
+fn incremental_relay_rate(client: &Client) -> Result<f64, SearcherError> {
+    let info = client.call::<serde_json::Value>("getnetworkinfo", &[])?;
+    // In BTC/kvB
+    Ok(info.get("incrementalfee").and_then(|fee| fee.as_f64()).map_or(1.0, |fee| fee * 100_000.0))
+}
+
+// Replace a broadcast child. With a target rate the replacement still spends the anchor
+// and pays enough for the package to reach that rate. Without one it spends the child's
+// wallet input back to ourselves at the current fee rate, and the parent is left without
+// a sponsor. Either way it pays the child's fee plus the incremental relay fee on its
+// own size, as BIP 125's fourth rule asks.
+async fn replace_child(state: &AppState, job: &jobs::Job, bump_to: Option<f64>) -> Result<Replaced, SearcherError> {
+    let wallet = job.wallet.as_deref().unwrap_or(primary_wallet(&state.config));
+    let client = connect_rpc(state, wallet)?;
+    let (Some(parent), Some(child)) = (&job.parent_txid, &job.child_txid) else {
+        return Err(SearcherError::NotCancellable("the job has no child".to_string()));
+    };
+    let parent = Txid::from_str(parent).map_err(|e| SearcherError::Cpfp(e.to_string()))?;
+    let child = Txid::from_str(child).map_err(|e| SearcherError::Cpfp(e.to_string()))?;
+
+    let entry = client.get_mempool_entry(&child)
+        .map_err(|_| SearcherError::NotCancellable("the child is no longer in the mempool".to_string()))?;
+    let child_tx = client.get_raw_transaction(&child, None)?;
+    let input = child_tx.input.iter()
+        .find(|input| input.previous_output.txid != parent)
+        .ok_or_else(|| SearcherError::Cpfp("child has no wallet input".to_string()))?;
+    let utxo = client.get_tx_out(&input.previous_output.txid, input.previous_output.vout, Some(false))?
+        .ok_or_else(|| SearcherError::NotCancellable("the child's wallet input is already spent".to_string()))?;
+
+    // Neither replacement is bigger than the child, so its size stands in for theirs
+    let replaced_fee = entry.fees.base.to_sat();
+    let vsize = entry.vsize;
+    let rule_four = replaced_fee + (incremental_relay_rate(&client)? * vsize as f64).ceil() as u64;
+    let target = match bump_to {
+        Some(rate) => {
+            let parent_entry = client.get_mempool_entry(&parent)
+                .map_err(|_| SearcherError::NotCancellable("the parent is no longer in the mempool".to_string()))?;
+            let package_fee = (rate * (parent_entry.vsize + vsize) as f64).ceil() as u64;
+            package_fee.saturating_sub(parent_entry.fees.base.to_sat())
+        }
+        None => (fee_rate(state, Some(&client)) * vsize as f64).ceil() as u64,
+    };
+    let fee = rule_four.max(target);
+    check_fee(&state.config, fee, utxo.value.to_sat())?;
+
+    let replacement = match bump_to {
+        // The same child paying more out of its change, which comes last
+        Some(_) => {
+            let mut bumped = child_tx.clone();
+            for input in &mut bumped.input {
+                input.witness = Witness::default();
+            }
+            let change = bumped.output.last_mut().ok_or_else(|| SearcherError::Cpfp("child has no change".to_string()))?;
+            change.value = change.value.to_sat()
+                .checked_sub(fee - replaced_fee)
+                .filter(|value| *value >= change.script_pubkey.minimal_non_dust().to_sat())
+                .map(Amount::from_sat)
+                .ok_or(SearcherError::NoWalletUtxos)?;
+            bumped
+        }
+        None => Transaction {
+            version: Version(3),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: input.previous_output,
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(utxo.value.to_sat().checked_sub(fee).ok_or(SearcherError::NoWalletUtxos)?),
+                script_pubkey: change_script(&client, state.config.network)?,
+            }],
+        },
+    };
+
+    let signed = match bump_to {
+        Some(_) => {
+            let parent_tx = client.get_raw_transaction(&parent, None)?;
+            let anchor_signer = anchor_signer(state, check_anchor(state, &parent_tx)?.as_deref(), wallet)?;
+            let signed = sign_child_with(state, &client, &parent_tx, &replacement, anchor_signer.as_ref()).await?;
+            signed.transaction().map_err(|e| SearcherError::Cpfp(e.to_string()))?
+        }
+        None => match &state.signer {
+            Some(signer) => sign_externally(signer, &[&client], &replacement, &[]).await?,
+            None => {
+                let signed = client.sign_raw_transaction_with_wallet(&replacement, None, None)?;
+                if !signed.complete {
+                    return Err(SearcherError::IncompleteSignature);
+                }
+                signed.transaction().map_err(|e| SearcherError::Cpfp(e.to_string()))?
+            }
+        },
+    };
+    Ok(Replaced { txid: client.send_raw_transaction(&signed)?, fee, replaced_fee })
+}
+
+async fn handle_stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatsQuery>,
+) -> (StatusCode, Json<StatsResponse>) {
+    if let Err(e) = refresh_confirmations(&state) {
+        error!("Failed to refresh confirmations: {}", e);
+    }
+
+    let since = query.days.map_or(0, |days| now().saturating_sub(days * 86_400));
+    (StatusCode::OK, Json(jobs::stats(&state.jobs.jobs(), since)))
+}
+
+pub fn app(config: Config) -> Result<Router, SearcherError> {
+    if config.wallets.is_empty() {
+        return Err(SearcherError::Config("at least one wallet is required".to_string()));
+    }
+    // A fixed fee rate never spikes
+    if config.quote_fee_spike.is_some() && config.fee_target.is_none() {
+        return Err(SearcherError::Config("withdrawing quotes on fee spikes needs --fee-target".to_string()));
+    }
+    if config.max_fee_percent.is_some_and(|percent| !(percent > 0.0 && percent <= 100.0)) {
+        return Err(SearcherError::Config("--max-fee-percent must be above 0 and at most 100".to_string()));
+    }
+    if config.bump_to_next_block && !config.predict_next_block {
+        return Err(SearcherError::Config("bumping packages into the next block needs --predict-next-block".to_string()));
+    }
+    if (config.auto_mine || config.auto_mine_interval.is_some()) && config.network != Network::Regtest {
+        return Err(SearcherError::Config("auto-mining is only available on regtest".to_string()));
+    }
+    if let Some(revenue) = &config.revenue
+        && revenue.amount_sat < revenue.address.script_pubkey().minimal_non_dust().to_sat()
+    {
+        return Err(SearcherError::Config(format!("revenue of {} sats would be a dust output", revenue.amount_sat)));
+    }
+    if config.anchor_deposit.is_some() && !config.anchor_policy.accepts_keyed() {
+        return Err(SearcherError::Config("deposits need keyed anchors, set --anchor-policy keyed or either".to_string()));
+    }
+    if let Some(low_funds) = &config.low_funds {
+        if low_funds.min_balance_sat.is_none() && low_funds.min_utxos.is_none() {
+            return Err(SearcherError::Config("low funds alerts need a minimum balance or UTXO count".to_string()));
+        }
+        if let Some(pubkey) = &low_funds.nostr_pubkey {
+            if config.nostr_secret_key.is_none() || config.nostr_relays.is_empty() {
+                return Err(SearcherError::Config("Nostr alerts need --nostr-secret-key and --nostr-relay".to_string()));
+            }
+            bitcoin::secp256k1::XOnlyPublicKey::from_str(pubkey).map_err(NostrError::from)?;
+        }
+    }
+    let jobs = match &config.job_store {
+        Some(path) => JobStore::open(path.clone())?,
+        None => JobStore::in_memory(),
+    };
+    let jobs = match &config.audit_log {
+        Some(path) => {
+            let audit = AuditLog::open(path.clone())
+                .map_err(|e| SearcherError::Config(format!("{} doesn't verify, check it with verify-audit-log: {}", path.display(), e)))?;
+            let head = audit.head();
+            info!("Audit log {} has {} entries, ending at {}", path.display(), head.entries, head.head);
+            jobs.with_audit_log(audit)
+        }
+        None => jobs,
+    };
+    // Carry on from what was already spent today before a restart
+    let budget = config.sponsorship.as_ref()
+        .map(|sponsorship| Budget::new(sponsorship.daily_budget_sat, now(), jobs::sponsored_today(&jobs.jobs(), now())));
+    let http = config.http.client();
+    let lightning = config.lightning.clone().map(|lightning| LightningNode::new(lightning, http.clone()));
+    let price_feed = config.usd_price
+        .map(|_| PriceFeed::new(config.rate_providers.clone(), config.rate_max_age, http.clone()));
+    let fees = config.fee_target
+        .map(|target| FeeEstimator::new(target, config.mempool_api.clone(), config.fee_rate));
+    let histogram = config.histogram_pricing
+        .map(|pricing| HistogramEstimator::new(pricing.depth_vbytes, config.mempool_api.clone()));
+    let next_block = config.predict_next_block.then(NextBlock::default);
+    let depth = config.max_mempool_depth
+        .map(|depth| HistogramEstimator::new(depth, config.mempool_api.clone()));
+    let abuse = Arc::new(AbuseTracker::new(config.abuse.clone()));
+    let signer = config.external_signer.clone().map(|signer| ExternalSigner::new(signer, http.clone()));
+    let notifier = Notifier::new(config.notify.clone(), http.clone(), config.network.to_string());
+    let state = Arc::new(AppState {
+        config,
+        started_at: Instant::now(),
+        metrics: Metrics::default(),
+        lightning,
+        price_feed,
+        fees,
+        histogram,
+        depth,
+        next_block,
+        http,
+        jobs,
+        next_wallet: AtomicUsize::new(0),
+        budget,
+        rune_addresses: Mutex::new(HashMap::new()),
+        abuse,
+        signer,
+        low_funds: Mutex::new(None),
+        funds: Mutex::new(None),
+        claimed_utxos: Mutex::new(HashSet::new()),
+        notifier,
+        bitcoind_down: AtomicBool::new(false),
+        wallet_empty: AtomicBool::new(false),
+        submit_failures: AtomicU64::new(0),
+        replacing: Mutex::new(HashMap::new()),
+        quotes: QuoteBook::default(),
+        node_rejections: Mutex::new(HashMap::new()),
+        package_rpc: AtomicBool::new(true),
+    });
+    detect_package_rpc(&state);
+
+    // Catch up on whatever a crash or restart left half done before taking new jobs
+    if let Err(e) = recover_jobs(&state) {
+        error!("Failed to reconcile jobs with bitcoind: {}", e);
+    }
+
+    if let Some(interval) = state.config.mempool_scan {
+        tokio::spawn(scan_mempool(state.clone(), interval));
+    }
+    tokio::spawn(anchor_held_parents(state.clone()));
+    tokio::spawn(watch_parent_witnesses(state.clone()));
+    tokio::spawn(count_funds(state.clone()));
+    if let Some(low_funds) = state.config.low_funds.clone() {
+        tokio::spawn(watch_funds(state.clone(), low_funds));
+    }
+    if !state.config.notify.is_empty() {
+        tokio::spawn(watch_operations(state.clone()));
+    }
+    if let Some(consolidation) = state.config.consolidation.clone() {
+        tokio::spawn(consolidate_change(state.clone(), consolidation));
+    }
+    if let Some(interval) = state.config.auto_mine_interval {
+        tokio::spawn(mine_blocks(state.clone(), interval));
+    }
+    if let Some(spike) = state.config.quote_fee_spike {
+        tokio::spawn(watch_fee_spikes(state.clone(), spike));
+    }
+    if state.config.predict_next_block {
+        tokio::spawn(watch_next_block(state.clone()));
+    }
+
+    let router = Router::new()
+        .route("/submit-psbt", post(handle_submit_psbt))
+        .route("/payjoin", post(handle_payjoin))
+        .route("/rescue", post(handle_rescue))
+        .route_layer(middleware::from_fn_with_state(state.clone(), backpressure))
+        .route("/validate-psbt", post(handle_validate_psbt))
+        .route("/quote", post(handle_quote).layer(middleware::from_fn_with_state(
+            ResponseCache::new(Duration::from_secs(QUOTE_CACHE_SECS), false),
+            cache::cache,
+        )))
+        .route_layer(middleware::from_fn_with_state(state.abuse.clone(), abuse::guard))
+        .route("/info", get(handle_info).layer(middleware::from_fn_with_state(
+            ResponseCache::new(Duration::from_secs(INFO_CACHE_SECS), true),
+            cache::cache,
+        )))
+        .route("/health", get(handle_health))
+        .route("/metrics", get(handle_metrics))
+        .route("/stats", get(handle_stats).layer(middleware::from_fn_with_state(
+            ResponseCache::new(Duration::from_secs(STATS_CACHE_SECS), true),
+            cache::cache,
+        )))
+        .route("/jobs/:txid", delete(handle_cancel))
+        .route("/jobs/:txid/log", get(handle_job_log));
+    let router = match state.config.quote_fee_spike {
+        Some(_) => router.route("/quotes/events", get(handle_quote_events)),
+        None => router,
+    };
+    let router = match state.config.response_key.clone() {
+        Some(key) => router.layer(middleware::from_fn_with_state(Arc::new(key), signing::sign_responses)),
+        None => router,
+    };
+
+    Ok(router
+        .layer(tower_http::trace::TraceLayer::new_for_http())
+        .with_state(state))
+}
+
+// Periodically publish this searcher's announcement to the configured Nostr relays
+fn start_announcing(config: &Config) -> Result<(), SearcherError> {
+    let url = config.public_url.clone()
+        .ok_or_else(|| SearcherError::Nostr(NostrError::InvalidEvent("--public-url is required to announce".to_string())))?;
+    let secret_key = config.nostr_secret_key.as_deref()
+        .ok_or_else(|| SearcherError::Nostr(NostrError::InvalidEvent("--nostr-secret-key is required to announce".to_string())))?;
+    let keypair = nostr::parse_secret_key(secret_key)?;
+
+    let announcement = Announcement {
+        url,
+        network: config.network.to_string(),
+        runes: vec![RUNE_NAME.to_string()],
+        rune_price: config.rune_price,
+        usd_price: config.usd_price,
+        fee_rate: config.fee_rate,
+        rune_address: config.rune_address.clone(),
+    };
+    let relays = config.nostr_relays.clone();
+    info!("Announcing on Nostr as {}", keypair.x_only_public_key().0);
+
+    std::thread::spawn(move || loop {
+        match nostr::announcement_event(&keypair, &announcement) {
+            Ok(event) => {
+                for relay in &relays {
+                    if let Err(e) = nostr::publish(relay, &event) {
+                        error!("Failed to announce on {}: {}", relay, e);
+                    }
+                }
+            }
+            Err(e) => error!("Failed to build announcement: {}", e),
+        }
+        std::thread::sleep(Duration::from_secs(ANNOUNCE_INTERVAL_SECS));
+    });
+
+    Ok(())
+}
+
+// Periodically offer runes above the threshold for sale, keeping the wallet's BTC topped up
+fn start_liquidating(config: &Config, liquidation: LiquidationConfig) -> Result<(), SearcherError> {
+    let rune_address = config.rune_address.clone()
+        .ok_or_else(|| LiquidationError::Offer("--rune-address is required to liquidate".to_string()))?;
+
+    // Offers are signed by the wallet, so it has to hold the key for the rune address
+    let client = rpc::connect(
+        &config.bitcoind_host,
+        config.bitcoind_user.as_deref(),
+        config.bitcoind_password.as_deref(),
+        config.network,
+        Some(primary_wallet(config)),
+    )?;
+    let address = Address::from_str(&rune_address)
+        .and_then(|address| address.require_network(config.network))
+        .map_err(|e| LiquidationError::Offer(format!("Invalid rune address {}: {}", rune_address, e)))?;
+    if client.get_address_info(&address)?.is_mine != Some(true) {
+        return Err(LiquidationError::Offer(format!("{} is not in wallet {}", rune_address, primary_wallet(config))).into());
+    }
+
+    let ord_server = config.ord_server.clone();
+    let http = config.http.clone();
+    std::thread::spawn(move || {
+        let http = http.blocking_client();
+        let mut offered = HashSet::new();
+        loop {
+            match liquidation::liquidate(&client, &http, &ord_server, &rune_address, RUNE_NAME, &liquidation, &mut offered) {
+                Ok(offers) if !offers.is_empty() => info!("Posted {} rune sell offers", offers.len()),
+                Ok(_) => {}
+                Err(e) => error!("Liquidation failed: {}", e),
+            }
+            std::thread::sleep(Duration::from_secs(LIQUIDATION_INTERVAL_SECS));
+        }
+    });
+
+    Ok(())
+}
+
+// Log the preflight report, refusing to start when anything failed
+fn check_preflight(config: &Config) -> Result<(), SearcherError> {
+    let report = preflight::preflight(config);
+    info!("Preflight for {}:", config.network);
+    for check in &report.checks {
+        match check.outcome {
+            Outcome::Pass => info!("  {}", check),
+            Outcome::Warn | Outcome::Fail => error!("  {}", check),
+        }
+    }
+    let failures: Vec<String> = report.failures().into_iter().map(|check| format!("{}: {}", check.name, check.detail)).collect();
+    if !failures.is_empty() {
+        return Err(SearcherError::Config(format!("preflight failed: {}", failures.join("; "))));
+    }
+    Ok(())
+}
+
+// Whether bitcoind and ord are reachable, as a status line for systemd
+fn dependency_status(config: &Config, http: &reqwest::blocking::Client) -> Result<(), String> {
+    rpc::connect(
+        &config.bitcoind_host,
+        config.bitcoind_user.as_deref(),
+        config.bitcoind_password.as_deref(),
+        config.network,
+        Some(primary_wallet(config)),
+    )
+    .and_then(|client| client.get_block_count())
+    .map_err(|e| format!("bitcoind unreachable: {}", e))?;
+
+    http.get(format!("{}/blockcount", config.ord_server))
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("ord unreachable: {}", e))?;
+
+    Ok(())
+}
+
+// Ping systemd's watchdog and keep its status line current. The watchdog is fed even
+// while degraded: restarting won't bring bitcoind or ord back, the status says what's wrong.
+fn start_watchdog(config: &Config) {
+    let interval = daemon::watchdog_interval().unwrap_or(Duration::from_secs(STATUS_INTERVAL_SECS));
+    let config = config.clone();
+
+    std::thread::spawn(move || {
+        let http = config.http.blocking_client();
+        let mut degraded = false;
+        loop {
+            let status = match dependency_status(&config, &http) {
+                Ok(()) => {
+                    if degraded {
+                        info!("bitcoind and ord are reachable again");
+                    }
+                    degraded = false;
+                    "Sponsoring packages".to_string()
+                }
+                Err(problem) => {
+                    if !degraded {
+                        error!("Searcher degraded: {}", problem);
+                    }
+                    degraded = true;
+                    format!("Degraded: {}", problem)
+                }
+            };
+            daemon::notify(&format!("WATCHDOG=1\nSTATUS={}", status));
+            std::thread::sleep(interval);
+        }
+    });
+}
+
+pub fn run(mut config: Config) -> Result<(), SearcherError> {
+    info!("Starting slugline searcher...");
+    info!("Configuration:");
+    info!("  Bitcoin host: {}", redact_url(&config.bitcoind_host));
+    info!("  Bitcoin user: {}", config.bitcoind_user.as_deref().unwrap_or("<none>"));
+    for node in &config.broadcast_nodes {
+        info!("  Broadcast node: {}", node.address(config.network));
+    }
+    if let Some(peer) = &config.package_peer {
+        info!("  Package peer: {}, if bitcoind has no submitpackage", peer);
+    }
+    info!("  Network: {:?}", config.network);
+    info!("  Wallets: {}", config.wallets.join(", "));
+    info!("  Rune: {}", RUNE_NAME);
+    for index in &config.rune_indexes {
+        info!("  Rune index: {}, cross-checked with ord", index.name());
+    }
+    match config.fee_target {
+        Some(target) => info!("  Fee rate: estimated for {} blocks, {} sat/vB fallback", target, config.fee_rate),
+        None => info!("  Fee rate: {} sat/vB", config.fee_rate),
+    }
+    if let Some(max) = config.max_fee_rate {
+        info!("  Fee rate cap: {} sat/vB", max);
+    }
+    if let Some(max) = config.max_parent_fee {
+        info!("  Largest parent fee: {} sats", max);
+    }
+    match (config.max_fee, config.max_fee_percent) {
+        (Some(max), Some(percent)) => info!("  Fee cap: {} sats or {}% of the UTXO spent", max, percent),
+        (Some(max), None) => info!("  Fee cap: {} sats", max),
+        (None, Some(percent)) => info!("  Fee cap: {}% of the UTXO spent", percent),
+        (None, None) => {}
+    }
+    // Likely an extra zero, or a sat/kvB figure passed as sat/vB
+    if config.fee_rate > FAT_FINGER_FEE_RATE {
+        error!("Fee rate of {} sat/vB is unusually high, check --fee-rate isn't a typo", config.fee_rate);
+    }
+    if let Some(max) = config.max_fee_rate
+        && config.fee_rate > max
+    {
+        error!("--fee-rate of {} sat/vB is over --max-fee-rate and will be clamped to {} sat/vB", config.fee_rate, max);
+    }
+    if let Some(url) = &config.mempool_api {
+        info!("  Mempool API: {}", redact_url(url));
+    }
+    match (config.usd_price, config.histogram_pricing) {
+        (Some(usd_price), _) => info!("  Rune price: ${} per vbyte", usd_price),
+        (None, Some(pricing)) => info!(
+            "  Rune price: fee rate {} vbytes deep +{}% at {} sats per rune, else {} per vbyte",
+            pricing.depth_vbytes, pricing.margin_percent, pricing.sats_per_rune,
+            RuneAmount::new(config.rune_price, config.rune_divisibility),
+        ),
+        (None, None) => info!("  Rune price: {} per vbyte", RuneAmount::new(config.rune_price, config.rune_divisibility)),
+    }
+    if let Some(depth) = config.max_mempool_depth {
+        info!("  Mempool depth: rune payments must reach {} vbytes from the top", depth);
+    }
+    for tier in &config.price_tiers {
+        info!("  Price tier: x{} up to {} vbytes", tier.multiplier, tier.max_vsize);
+    }
+    if let Some(max) = config.max_package_vsize {
+        info!("  Largest package sponsored: {} vbytes", max);
+    }
+    if config.predict_next_block {
+        info!("  Next block: packages checked against bitcoind's block template");
+    }
+    if config.bump_to_next_block {
+        info!("  Bumps: packages that fall out of the next block get a child paying their way back in");
+    }
+    if let Some(spike) = config.quote_fee_spike {
+        info!("  Quotes withdrawn when fees rise {}% over their price, announced at /quotes/events", spike);
+    }
+    match config.rotate_rune_address {
+        true => info!("  Rune address: a fresh one per parent"),
+        false => info!("  Rune address: {}", config.rune_address.as_deref().unwrap_or("<none>")),
+    }
+    if let Some(lightning) = &config.lightning {
+        info!("  Lightning: {:?} at {}", lightning.backend, redact_url(&lightning.url));
+    }
+    if let Some(payment) = &config.inscription_payment {
+        info!("  Inscriptions: {} to {}", payment.collection.as_deref().unwrap_or("any collection"), payment.address);
+    }
+    info!("  Ephemeral dust policy: {:?}", config.ephemeral_policy);
+    info!("  Anchor policy: {:?}", config.anchor_policy);
+    if let Some(signer) = &config.external_signer {
+        info!("  External signer: {}", redact_url(&signer.url));
+    }
+    if let Some(revenue) = &config.revenue {
+        info!("  Revenue: {} sats per child to {}", revenue.amount_sat, revenue.address);
+    }
+    for sink in &config.notify {
+        info!("  Notify: {}", sink.name());
+    }
+    if let Some(min_fee) = config.notify_large_job_sat {
+        info!("  Notify about jobs paying over {} sats", min_fee);
+    }
+    if let Some(low_funds) = &config.low_funds {
+        info!(
+            "  Low funds: quotes only under {} sats or {} usable UTXOs, checked every {} seconds",
+            low_funds.min_balance_sat.map_or("any".to_string(), |min| min.to_string()),
+            low_funds.min_utxos.map_or("any".to_string(), |min| min.to_string()),
+            low_funds.interval
+        );
+    }
+    if let Some(consolidation) = &config.consolidation {
+        info!(
+            "  Consolidation: {} or more change UTXOs into {} sat UTXOs at up to {} sat/vB, every {} seconds",
+            consolidation.min_inputs, consolidation.target_sat, consolidation.max_fee_rate, consolidation.interval
+        );
+    }
+    if let Some(deposit) = &config.anchor_deposit {
+        info!("  Anchor deposit: {} sats, refundable after {} blocks", deposit.amount_sat, deposit.delay);
+    }
+    if let Some(interval) = config.mempool_scan {
+        info!("  Mempool scan: every {} seconds", interval);
+    }
+    if config.soft_fail != SoftFail::Off {
+        info!("  Soft fail: {:?}", config.soft_fail);
+    }
+    info!("  Job timeout: {} seconds", config.job_timeout);
+    for rule in &config.rules {
+        info!("  Parent rule: {}", rule.name());
+    }
+    if config.auto_mine {
+        info!("  Auto-mine: a block after each package");
+    }
+    if let Some(interval) = config.auto_mine_interval {
+        info!("  Auto-mine: a block every {} seconds", interval);
+    }
+
+    check_preflight(&config)?;
+
+    let mut networks: Vec<Config> = Vec::new();
+    for network in &config.networks {
+        if network.network == config.network || networks.iter().any(|other| other.network == network.network) {
+            return Err(SearcherError::Config(format!("{} is served more than once", network.network)));
+        }
+        info!("  Also serving {} at /{}: bitcoind {}, ord {}, wallet {}", network.network, network.network, redact_url(&network.bitcoind_host), redact_url(&network.ord_server), network.wallet);
+        let network = config.for_network(network);
+        check_preflight(&network)?;
+        networks.push(network);
+    }
+
+    // Held until run returns, which removes the file
+    let _pid_file = match &config.pid_file {
+        Some(path) => {
+            info!("  PID file: {}", path.display());
+            Some(PidFile::create(path)?)
+        }
+        None => None,
+    };
+
+    if config.listen.is_empty() {
+        return Err(SearcherError::Config("nothing to listen on".to_string()));
+    }
+    for proxy in &config.trusted_proxies {
+        info!("  Trusted proxy: {}", proxy);
+    }
+    if config.proxy_protocol {
+        info!("  PROXY protocol: required on every connection");
+    }
+
+    // Held until run returns: Tor drops the service when the control connection closes
+    let _onion = match (&config.tor_control, listen::local_target(&config.listen)) {
+        (Some(control), Some(target)) => {
+            let service = tor::publish(
+                control,
+                config.tor_password.as_deref(),
+                config.tor_key_file.as_deref(),
+                ONION_PORT,
+                &target.to_string(),
+            )?;
+            info!("  Onion service: http://{}", service.onion_address);
+            if config.public_url.is_none() {
+                config.public_url = Some(format!("http://{}", service.onion_address));
+            }
+            Some(service)
+        }
+        _ => None,
+    };
+
+    if !config.nostr_relays.is_empty() {
+        start_announcing(&config)?;
+    }
+    
+    if let Some(liquidation) = config.liquidation.clone() {
+        info!("  Liquidation: above {} {} at {} sats each via {}", liquidation.threshold, RUNE_NAME, liquidation.price, redact_url(&liquidation.venue_url));
+        start_liquidating(&config, liquidation)?;
+    }
+
+    if daemon::notify_enabled() {
+        start_watchdog(&config);
+    }
+
+    // Build the runtime
+    let runtime = tokio::runtime::Runtime::new()?;
+    
+    runtime.block_on(async {
+        // Every network is served under its name, and the main one at the root too
+        let main = format!("/{}", config.network);
+        let (addrs, trusted_proxies, proxy_protocol) = (config.listen.clone(), config.trusted_proxies.clone(), config.proxy_protocol);
+        let main_app = app(config)?;
+        let mut app = Router::new().nest(&main, main_app.clone()).merge(main_app);
+        for network in networks {
+            app = app.nest(&format!("/{}", network.network), self::app(network)?);
+        }
+        if !trusted_proxies.is_empty() {
+            let proxies = Arc::new(TrustedProxies(trusted_proxies));
+            app = app.layer(middleware::from_fn_with_state(proxies, listen::client_ip));
+        }
+
+        let listeners = listen::bind(&addrs)?;
+        for addr in &addrs {
+            info!("Searcher listening on {}", addr);
+        }
+        daemon::notify("READY=1\nSTATUS=Listening");
+        listen::serve(listeners, app, proxy_protocol).await?;
+        Ok(())
+    })
+}
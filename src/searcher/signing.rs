@@ -0,0 +1,111 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+use crate::error::SignatureError;
+
+pub const TIMESTAMP_HEADER: &str = "x-slugline-timestamp";
+pub const NONCE_HEADER: &str = "x-slugline-nonce";
+pub const SIGNATURE_HEADER: &str = "x-slugline-signature";
+
+// How far a response's timestamp may drift from the client's clock
+pub const MAX_SKEW_SECS: u64 = 300;
+const MAX_NONCE_LEN: usize = 64;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Nonces only need to be unique, not secret
+pub fn new_nonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&nanos.to_le_bytes());
+    engine.input(&COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    engine.input(&std::process::id().to_le_bytes());
+    hex::encode(&sha256::Hash::from_engine(engine)[..16])
+}
+
+// HMAC-SHA256 over the timestamp, nonce, request path and body, so a signed response
+// can't be moved to a different request or endpoint
+pub fn sign(key: &str, timestamp: u64, nonce: &str, path: &str, body: &[u8]) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(key.as_bytes());
+    engine.input(format!("{}\n{}\n{}\n", timestamp, nonce, path).as_bytes());
+    engine.input(body);
+    hex::encode(hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array())
+}
+
+// Check a response was signed with our key for the nonce we sent, recently. Headers are
+// looked up through `header` since reqwest and axum use different versions of `http`.
+pub fn verify(
+    key: &str,
+    header: impl Fn(&str) -> Option<String>,
+    nonce: &str,
+    path: &str,
+    body: &[u8],
+) -> Result<(), SignatureError> {
+    let header = |name: &str| header(name).ok_or_else(|| SignatureError::MissingHeader(name.to_string()));
+
+    let timestamp: u64 = header(TIMESTAMP_HEADER)?
+        .parse()
+        .map_err(|_| SignatureError::MissingHeader(TIMESTAMP_HEADER.to_string()))?;
+    let skew = now().abs_diff(timestamp);
+    if skew > MAX_SKEW_SECS {
+        return Err(SignatureError::Stale(skew));
+    }
+
+    // A replayed response carries the nonce of the request it originally answered
+    if header(NONCE_HEADER)? != nonce {
+        return Err(SignatureError::NonceMismatch);
+    }
+
+    let expected = sign(key, timestamp, nonce, path, body);
+    let signature = header(SIGNATURE_HEADER)?;
+    // Compare without short-circuiting so timing doesn't leak how much matched
+    let matches = signature.len() == expected.len()
+        && signature.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0;
+    if !matches {
+        return Err(SignatureError::BadSignature);
+    }
+    Ok(())
+}
+
+// Middleware signing every response. Clients send their own nonce so they can tell a
+// fresh answer from a replayed one; requests without one get a nonce generated here.
+pub async fn sign_responses(State(key): State<Arc<String>>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let nonce = request.headers()
+        .get(NONCE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|nonce| !nonce.is_empty() && nonce.len() <= MAX_NONCE_LEN && nonce.bytes().all(|b| b.is_ascii_alphanumeric()))
+        .map(str::to_string)
+        .unwrap_or_else(new_nonce);
+
+    let (mut parts, body) = next.run(request).await.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to buffer response for signing: {}", e);
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let timestamp = now();
+    let signature = sign(&key, timestamp, &nonce, &path, &body);
+    for (name, value) in [(TIMESTAMP_HEADER, timestamp.to_string()), (NONCE_HEADER, nonce), (SIGNATURE_HEADER, signature)] {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            parts.headers.insert(name, value);
+        }
+    }
+    Response::from_parts(parts, Body::from(body))
+}
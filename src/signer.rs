@@ -0,0 +1,103 @@
+use bitcoin::{psbt::Psbt, transaction::Transaction, Witness};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing::info;
+
+use crate::anchor::is_p2a;
+use crate::error::SignerError;
+
+// A remote HSM or signing daemon holding the searcher's keys, so bitcoind only needs
+// watch-only wallets. It is sent a base64 PSBT and answers with its signatures added.
+#[derive(Debug, Clone)]
+pub struct SignerConfig {
+    pub url: String,
+    // Sent as a bearer token when set
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignRequest {
+    pub psbt: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignResponse {
+    pub psbt: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalSigner {
+    config: SignerConfig,
+    http: reqwest::Client,
+}
+
+impl ExternalSigner {
+    pub fn new(config: SignerConfig, http: reqwest::Client) -> Self {
+        ExternalSigner { config, http }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.config.url
+    }
+
+    // Have the signer add its signatures. It may not change the transaction itself.
+    pub async fn sign(&self, psbt: &Psbt) -> Result<Psbt, SignerError> {
+        let mut request = self.http
+            .post(&self.config.url)
+            .json(&SignRequest { psbt: psbt.to_string() });
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(SignerError::Refused {
+                url: self.config.url.clone(),
+                message: format!("{}: {}", status, response.text().await.unwrap_or_default()),
+            });
+        }
+        let signed: SignResponse = response.json().await?;
+        let signed = Psbt::from_str(&signed.psbt).map_err(|e| SignerError::Malformed(e.to_string()))?;
+        if signed.unsigned_tx != psbt.unsigned_tx {
+            return Err(SignerError::Changed(signed.unsigned_tx.compute_txid().to_string()));
+        }
+
+        info!("External signer signed {}", signed.unsigned_tx.compute_txid());
+        Ok(signed)
+    }
+}
+
+// Finalize the inputs a searcher spends: P2A anchors with an empty witness, P2WPKH from
+// its signature and key, and taproot key spends from their signature
+pub fn finalize(mut psbt: Psbt) -> Result<Transaction, SignerError> {
+    for (index, (txin, input)) in psbt.unsigned_tx.input.iter().zip(psbt.inputs.iter_mut()).enumerate() {
+        if input.final_script_witness.is_some() || input.final_script_sig.is_some() {
+            continue;
+        }
+        let spent = match (&input.witness_utxo, &input.non_witness_utxo) {
+            (Some(output), _) => Some(output),
+            (None, Some(prev)) => prev.output.get(txin.previous_output.vout as usize),
+            (None, None) => None,
+        };
+        let script_pubkey = spent.map(|output| output.script_pubkey.clone()).ok_or(SignerError::Unsigned(index))?;
+
+        let witness = if is_p2a(&script_pubkey) {
+            Witness::new()
+        } else if script_pubkey.is_p2wpkh()
+            && let Some((key, signature)) = input.partial_sigs.iter().next()
+        {
+            Witness::p2wpkh(signature, &key.inner)
+        } else if script_pubkey.is_p2tr()
+            && let Some(signature) = input.tap_key_sig
+        {
+            Witness::p2tr_key_spend(&signature)
+        } else {
+            return Err(SignerError::Unsigned(index));
+        };
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+        input.tap_key_sig = None;
+    }
+    Ok(psbt.extract_tx_unchecked_fee_rate())
+}
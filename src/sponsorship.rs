@@ -0,0 +1,117 @@
+use bitcoin::{Address, Network, ScriptBuf, Transaction};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+#[derive(Debug, Clone)]
+pub struct SponsorshipConfig {
+    pub templates: Vec<ScriptTemplate>,
+    pub daily_budget_sat: u64,
+}
+
+// An output script the searcher sponsors for free: an address, an exact script
+// (`script:<hex>`), or a script prefix (`prefix:<hex>`) to match a family of covenants
+#[derive(Debug, Clone)]
+pub struct ScriptTemplate {
+    pub name: String,
+    script: ScriptBuf,
+    prefix: bool,
+}
+
+impl ScriptTemplate {
+    pub fn parse(s: &str, network: Network) -> Result<Self, String> {
+        let (script, prefix) = if let Some(hex) = s.strip_prefix("script:") {
+            (ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid script {}: {}", hex, e))?, false)
+        } else if let Some(hex) = s.strip_prefix("prefix:") {
+            (ScriptBuf::from_hex(hex).map_err(|e| format!("Invalid script prefix {}: {}", hex, e))?, true)
+        } else {
+            let address = Address::from_str(s)
+                .and_then(|address| address.require_network(network))
+                .map_err(|e| format!("Invalid template {} (expected an address, script:<hex> or prefix:<hex>): {}", s, e))?;
+            (address.script_pubkey(), false)
+        };
+        Ok(ScriptTemplate { name: s.to_string(), script, prefix })
+    }
+
+    pub fn matches(&self, script: &bitcoin::Script) -> bool {
+        if self.prefix {
+            script.as_bytes().starts_with(self.script.as_bytes())
+        } else {
+            script == self.script.as_script()
+        }
+    }
+}
+
+// Sats the searcher will spend sponsoring whitelisted transactions each UTC day
+#[derive(Debug)]
+pub struct Budget {
+    daily_sat: u64,
+    // UTC day number and sats committed or reserved on it
+    spent: Mutex<(u64, u64)>,
+}
+
+impl Budget {
+    pub fn new(daily_sat: u64, now: u64, spent_today: u64) -> Self {
+        Budget { daily_sat, spent: Mutex::new((now / SECS_PER_DAY, spent_today)) }
+    }
+
+    fn today(&self, now: u64) -> std::sync::MutexGuard<'_, (u64, u64)> {
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        if spent.0 != now / SECS_PER_DAY {
+            *spent = (now / SECS_PER_DAY, 0);
+        }
+        spent
+    }
+
+    pub fn remaining(&self, now: u64) -> u64 {
+        self.daily_sat.saturating_sub(self.today(now).1)
+    }
+
+    // Set aside an estimated cost, failing if it doesn't fit in what's left today
+    pub fn reserve(&self, amount: u64, now: u64) -> Option<Reservation<'_>> {
+        let mut spent = self.today(now);
+        if spent.1 + amount > self.daily_sat {
+            return None;
+        }
+        spent.1 += amount;
+        Some(Reservation { budget: self, day: spent.0, amount, committed: false })
+    }
+
+    fn adjust(&self, day: u64, release: u64, charge: u64) {
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        // Yesterday's reservations don't count against today
+        if spent.0 == day {
+            spent.1 = spent.1.saturating_sub(release) + charge;
+        }
+    }
+}
+
+// Budget held for a job in flight, released unless the job commits its actual cost
+#[derive(Debug)]
+pub struct Reservation<'a> {
+    budget: &'a Budget,
+    day: u64,
+    amount: u64,
+    committed: bool,
+}
+
+impl Reservation<'_> {
+    pub fn commit(mut self, actual: u64) {
+        self.budget.adjust(self.day, self.amount, actual);
+        self.committed = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.budget.adjust(self.day, self.amount, 0);
+        }
+    }
+}
+
+// The first template any output of the transaction matches
+pub fn matching_template<'a>(templates: &'a [ScriptTemplate], tx: &Transaction) -> Option<&'a ScriptTemplate> {
+    templates.iter().find(|template| tx.output.iter().any(|output| template.matches(&output.script_pubkey)))
+}
@@ -0,0 +1,37 @@
+use opentelemetry::trace::{TraceError, TracerProvider};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracerProvider, Tracer};
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+// Kept so spans still batched in memory can be flushed on the way out
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+// A tracing layer exporting spans to an OTLP/HTTP collector, like http://localhost:4318/v1/traces
+pub fn otlp_layer<S>(endpoint: &str) -> Result<OpenTelemetryLayer<S, Tracer>, TraceError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("slugline").build())
+        .build();
+
+    let tracer = provider.tracer("slugline");
+    let _ = PROVIDER.set(provider);
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+// Export whatever spans are still buffered
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        let _ = provider.shutdown();
+    }
+}
@@ -0,0 +1,336 @@
+use bitcoin::{Address, Amount, Network};
+use bitcoincore_rpc::{Client, RpcApi, json};
+use serde::Deserialize;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::error::TestkitError;
+use crate::http::HttpConfig;
+use crate::ord::fetch_utxos;
+use crate::rpc;
+
+// Regtest coinbase outputs need 100 confirmations before they can be spent
+const COINBASE_MATURITY: u64 = 100;
+
+// How long to wait for signet coins or an etching to confirm
+const SIGNET_TIMEOUT_SECS: u64 = 3600;
+const REGTEST_TIMEOUT_SECS: u64 = 60;
+
+// Sats sent to ord's wallet to pay for the etching and rune transfers
+const ORD_FUNDING: u64 = 100_000;
+
+// Supply of runes etched by setup, so later fixtures can reuse the etching
+const RUNE_SUPPLY: u64 = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub struct TestkitConfig {
+    pub bitcoind_host: String,
+    pub bitcoind_user: Option<String>,
+    pub bitcoind_password: Option<String>,
+    pub network: Network,
+    pub ord_server: String,
+    pub ord_binary: String,
+    // Bitcoin Core wallet that owns the fixture addresses, created if missing
+    pub wallet_name: String,
+    pub ord_wallet: String,
+    // Faucet speaking Bitcoin Core's contrib/signet/getcoins.py protocol, required on signet
+    pub faucet_url: Option<String>,
+    pub http: HttpConfig,
+}
+
+// The two addresses build-tx needs: plain bitcoin to spend and runes to pay with
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub btc_address: Address,
+    pub runes_address: Address,
+    pub rune: String,
+    pub rune_amount: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReceiveResponse {
+    addresses: Vec<String>,
+}
+
+// Bootstraps a signet or regtest environment for trying slugline end to end
+pub struct Testkit {
+    config: TestkitConfig,
+    node: Client,
+    wallet: Client,
+    http: reqwest::blocking::Client,
+}
+
+// A random rune name long enough to be unlocked on signet and regtest from the first block
+pub fn throwaway_rune_name() -> String {
+    let mut seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+        | 1;
+    (0..15)
+        .map(|_| {
+            // xorshift64, plenty for picking letters
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (b'A' + (seed % 26) as u8) as char
+        })
+        .collect()
+}
+
+impl Testkit {
+    pub fn new(config: TestkitConfig) -> Result<Self, TestkitError> {
+        match config.network {
+            Network::Regtest => {}
+            Network::Signet if config.faucet_url.is_some() => {}
+            Network::Signet => return Err(TestkitError::Unsupported("a faucet URL is required on signet".to_string())),
+            network => return Err(TestkitError::Unsupported(format!("the testkit only runs on signet or regtest, not {}", network))),
+        }
+
+        let connect = |wallet| rpc::connect(
+            &config.bitcoind_host,
+            config.bitcoind_user.as_deref(),
+            config.bitcoind_password.as_deref(),
+            config.network,
+            wallet,
+        );
+        let node = connect(None)?;
+        if !node.list_wallets()?.contains(&config.wallet_name) && node.load_wallet(&config.wallet_name).is_err() {
+            node.create_wallet(&config.wallet_name, None, None, None, None)?;
+            info!("Created wallet {}", config.wallet_name);
+        }
+        let wallet = connect(Some(&config.wallet_name))?;
+
+        let http = config.http.blocking_client();
+        Ok(Testkit { config, node, wallet, http })
+    }
+
+    pub fn new_address(&self, label: &str) -> Result<Address, TestkitError> {
+        Ok(self.wallet
+            .get_new_address(Some(label), Some(json::AddressType::Bech32))?
+            .require_network(self.config.network)?)
+    }
+
+    fn timeout(&self) -> Duration {
+        match self.config.network {
+            Network::Regtest => Duration::from_secs(REGTEST_TIMEOUT_SECS),
+            _ => Duration::from_secs(SIGNET_TIMEOUT_SECS),
+        }
+    }
+
+    // Mine blocks on regtest; on signet blocks come on their own
+    pub fn mine(&self, blocks: u64) -> Result<(), TestkitError> {
+        if self.config.network != Network::Regtest {
+            return Ok(());
+        }
+        let address = self.new_address("slugline-testkit-mining")?;
+        self.node.generate_to_address(blocks, &address)?;
+        self.wait_for_ord()
+    }
+
+    // ord indexes asynchronously, so wait until it has caught up with bitcoind
+    pub fn wait_for_ord(&self) -> Result<(), TestkitError> {
+        let target = self.node.get_block_count()?;
+        let url = format!("{}/blockcount", self.config.ord_server);
+        let started = Instant::now();
+
+        loop {
+            let height: u64 = self.http.get(&url).send()?.error_for_status()?.text()?.trim().parse().unwrap_or_default();
+            if height >= target {
+                return Ok(());
+            }
+            if started.elapsed() > self.timeout() {
+                return Err(TestkitError::Timeout(format!("ord to reach height {}", target)));
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    // Wait until ord sees a confirmed output at the address, optionally holding a rune
+    pub fn wait_for_output(&self, address: &Address, rune: Option<&str>) -> Result<(), TestkitError> {
+        let started = Instant::now();
+        loop {
+            let found = fetch_utxos(&self.http, &self.config.ord_server, &address.to_string())?
+                .iter()
+                .any(|u| u.confirmations > 0 && rune.is_none_or(|rune| u.runes.contains_key(rune)));
+            if found {
+                return Ok(());
+            }
+            if started.elapsed() > self.timeout() {
+                return Err(TestkitError::Timeout(format!("a confirmed output at {}", address)));
+            }
+            thread::sleep(Duration::from_secs(if self.config.network == Network::Regtest { 1 } else { 30 }));
+        }
+    }
+
+    // Send coins to an address: mined ourselves on regtest, requested from the faucet on signet
+    pub fn fund(&self, address: &Address, amount: Amount) -> Result<(), TestkitError> {
+        match &self.config.faucet_url {
+            Some(faucet) if self.config.network == Network::Signet => {
+                info!("Requesting {} for {} from {}", amount, address, faucet);
+                let response = self.http
+                    .post(faucet)
+                    .form(&[
+                        ("address", address.to_string()),
+                        ("password", String::new()),
+                        ("amount", amount.to_btc().to_string()),
+                    ])
+                    .send()?;
+                let status = response.status();
+                let body = response.text()?;
+                if !status.is_success() {
+                    return Err(TestkitError::Faucet(format!("{}: {}", status, body.trim())));
+                }
+                info!("Faucet: {}", body.trim());
+            }
+            _ => {
+                let balance = self.wallet.get_balances()?.mine.trusted;
+                if balance < amount {
+                    let address = self.new_address("slugline-testkit-mining")?;
+                    self.node.generate_to_address(COINBASE_MATURITY + 1, &address)?;
+                }
+                let txid = self.wallet.send_to_address(address, amount, None, None, None, None, None, None)?;
+                info!("Sent {} to {} ({})", amount, address, txid);
+                self.mine(1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ord(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(&self.config.ord_binary);
+        command
+            .arg("--chain")
+            .arg(self.config.network.to_string())
+            .arg("--bitcoin-rpc-url")
+            .arg(rpc::rpc_url(&self.config.bitcoind_host, self.config.network, None));
+        if let Some(user) = &self.config.bitcoind_user {
+            command.arg("--bitcoin-rpc-username").arg(user);
+        }
+        if let Some(pass) = &self.config.bitcoind_password {
+            command.arg("--bitcoin-rpc-password").arg(pass);
+        }
+        command
+            .arg("wallet")
+            .arg("--name")
+            .arg(&self.config.ord_wallet)
+            .arg("--server-url")
+            .arg(&self.config.ord_server)
+            .args(args);
+        command
+    }
+
+    fn ord_output(&self, args: &[&str]) -> Result<String, TestkitError> {
+        let output = self.ord(args).output()?;
+        if !output.status.success() {
+            return Err(TestkitError::OrdWallet(format!(
+                "{}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // Create ord's wallet if needed and make sure it can pay for an etching
+    pub fn fund_ord_wallet(&self) -> Result<(), TestkitError> {
+        // ord refuses to create a wallet that already exists, which is fine
+        let _ = self.ord(&["create"]).stdout(Stdio::null()).stderr(Stdio::null()).status();
+
+        let receive: ReceiveResponse = serde_json::from_str(&self.ord_output(&["receive"])?)?;
+        let address = receive.addresses.first()
+            .ok_or_else(|| TestkitError::OrdWallet("receive returned no address".to_string()))?;
+        let address = Address::from_str(address)?.require_network(self.config.network)?;
+
+        self.fund(&address, Amount::from_sat(ORD_FUNDING))?;
+        self.wait_for_output(&address, None)
+    }
+
+    pub fn rune_exists(&self, rune: &str) -> Result<bool, TestkitError> {
+        let response = self.http
+            .get(format!("{}/rune/{}", self.config.ord_server, rune))
+            .header("Accept", "application/json")
+            .send()?;
+        Ok(response.status().is_success())
+    }
+
+    // Etch a rune with its whole supply premined to ord's wallet
+    pub fn etch(&self, rune: &str, supply: u64) -> Result<(), TestkitError> {
+        if self.rune_exists(rune)? {
+            info!("{} is already etched", rune);
+            return Ok(());
+        }
+
+        // ord requires an inscription alongside the etching
+        let dir = std::env::temp_dir().join(format!("slugline-testkit-{}", rune));
+        fs::create_dir_all(&dir)?;
+        let inscription = dir.join("etching.txt");
+        fs::write(&inscription, format!("slugline testkit rune {}", rune))?;
+        let batch = dir.join("batch.yaml");
+        fs::write(&batch, format!(
+            "mode: separate-outputs\n\
+             etching:\n  rune: {}\n  divisibility: 0\n  premine: '{}'\n  supply: '{}'\n  symbol: '$'\n\
+             inscriptions:\n  - file: {}\n",
+            rune,
+            supply,
+            supply,
+            inscription.display()
+        ))?;
+
+        // The reveal only goes out once the commit matures: mined here on regtest,
+        // about an hour of waiting on signet
+        info!("Etching {}...", rune);
+        let batch = batch.display().to_string();
+        let mut etching = self.ord(&["batch", "--fee-rate", "1", "--batch", &batch])
+            .stdout(Stdio::null())
+            .spawn()?;
+        let status = loop {
+            if let Some(status) = etching.try_wait()? {
+                break status;
+            }
+            self.mine(1)?;
+            thread::sleep(Duration::from_secs(1));
+        };
+        if !status.success() {
+            return Err(TestkitError::OrdWallet(format!("failed to etch {}", rune)));
+        }
+
+        self.mine(1)?;
+        info!("Etched {}", rune);
+        Ok(())
+    }
+
+    pub fn send_runes(&self, address: &Address, rune: &str, amount: u64) -> Result<(), TestkitError> {
+        let outgoing = format!("{}:{}", amount, rune);
+        self.ord_output(&["send", "--fee-rate", "1", &address.to_string(), &outgoing])?;
+        info!("Sent {} to {}", outgoing, address);
+        self.mine(1)
+    }
+
+    // Everything build-tx needs: an etched rune, a runes address holding some of it,
+    // and a funded bitcoin address
+    pub fn setup(&self, rune: &str, rune_amount: u64, btc_amount: Amount) -> Result<Fixture, TestkitError> {
+        if !self.rune_exists(rune)? {
+            self.fund_ord_wallet()?;
+            self.etch(rune, RUNE_SUPPLY.max(rune_amount))?;
+        }
+
+        let fixture = Fixture {
+            btc_address: self.new_address("slugline-testkit-btc")?,
+            runes_address: self.new_address("slugline-testkit-runes")?,
+            rune: rune.to_string(),
+            rune_amount,
+        };
+        self.send_runes(&fixture.runes_address, rune, rune_amount)?;
+        self.fund(&fixture.btc_address, btc_amount)?;
+
+        self.wait_for_output(&fixture.runes_address, Some(rune))?;
+        self.wait_for_output(&fixture.btc_address, None)?;
+        info!("Fixture ready: bitcoin at {}, {} {} at {}", fixture.btc_address, rune_amount, rune, fixture.runes_address);
+        Ok(fixture)
+    }
+}
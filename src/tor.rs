@@ -0,0 +1,140 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use tracing::info;
+
+use crate::error::TorError;
+
+// A connection to Tor's control port (ControlPort in torrc)
+struct TorControl {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl TorControl {
+    fn connect(address: &str) -> Result<TorControl, TorError> {
+        let writer = TcpStream::connect(address)?;
+        Ok(TorControl {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        })
+    }
+
+    // Send a command and collect the reply lines without their status prefix
+    fn command(&mut self, command: &str) -> Result<Vec<String>, TorError> {
+        self.writer.write_all(format!("{}\r\n", command).as_bytes())?;
+
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(TorError::Command("control connection closed".to_string()));
+            }
+            let line = line.trim_end();
+            if line.len() < 4 {
+                return Err(TorError::Command(format!("malformed reply: {}", line)));
+            }
+
+            let (status, rest) = line.split_at(3);
+            if status != "250" {
+                return Err(TorError::Command(rest[1..].to_string()));
+            }
+            // "250-" continues the reply, "250 " ends it
+            lines.push(rest[1..].to_string());
+            if rest.starts_with(' ') {
+                return Ok(lines);
+            }
+        }
+    }
+
+    // Password if given, otherwise whatever PROTOCOLINFO says the port accepts
+    fn authenticate(&mut self, password: Option<&str>) -> Result<(), TorError> {
+        if let Some(password) = password {
+            let escaped = password.replace('\\', "\\\\").replace('"', "\\\"");
+            return self.command(&format!("AUTHENTICATE \"{}\"", escaped))
+                .map(|_| ())
+                .map_err(|e| TorError::Auth(e.to_string()));
+        }
+
+        let info = self.command("PROTOCOLINFO 1")?;
+        let auth = info.iter()
+            .find(|line| line.starts_with("AUTH "))
+            .ok_or_else(|| TorError::Auth("PROTOCOLINFO lists no auth methods".to_string()))?;
+        let methods = auth
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("METHODS="))
+            .unwrap_or_default();
+
+        let result = if methods.split(',').any(|m| m == "NULL") {
+            self.command("AUTHENTICATE")
+        } else if methods.split(',').any(|m| m == "COOKIE") {
+            let cookie_file = auth
+                .split("COOKIEFILE=")
+                .nth(1)
+                .map(|path| path.trim_matches('"'))
+                .ok_or_else(|| TorError::Auth("no cookie file advertised".to_string()))?;
+            let cookie = fs::read(cookie_file)?;
+            self.command(&format!("AUTHENTICATE {}", hex::encode(cookie)))
+        } else {
+            return Err(TorError::Auth(format!("unsupported auth methods {}, pass a control password", methods)));
+        };
+        result.map(|_| ()).map_err(|e| TorError::Auth(e.to_string()))
+    }
+}
+
+// An onion service forwarding to a local port. Tor removes it when the control
+// connection closes, so keep this alive for as long as the service should be up.
+pub struct HiddenService {
+    pub onion_address: String,
+    _control: TorControl,
+}
+
+// Publish `target` (like 127.0.0.1:3000) as port `virtual_port` of an onion service. With
+// a key file the service keeps its address across restarts: the key is read from it, or
+// generated and written to it on first use.
+pub fn publish(
+    control_address: &str,
+    password: Option<&str>,
+    key_file: Option<&Path>,
+    virtual_port: u16,
+    target: &str,
+) -> Result<HiddenService, TorError> {
+    let mut control = TorControl::connect(control_address)?;
+    control.authenticate(password)?;
+
+    let existing_key = match key_file {
+        Some(path) if path.exists() => Some(fs::read_to_string(path)?.trim().to_string()),
+        _ => None,
+    };
+    let key = existing_key.as_deref().unwrap_or("NEW:ED25519-V3");
+    let flags = if key_file.is_none() { " Flags=DiscardPK" } else { "" };
+
+    let reply = control.command(&format!("ADD_ONION {}{} Port={},{}", key, flags, virtual_port, target))?;
+    let field = |name: &str| reply.iter().find_map(|line| line.strip_prefix(name)).map(str::to_string);
+
+    let service_id = field("ServiceID=")
+        .ok_or_else(|| TorError::Command("ADD_ONION returned no service ID".to_string()))?;
+    if let (Some(path), None, Some(private_key)) = (key_file, &existing_key, field("PrivateKey=")) {
+        write_key(path, &private_key)?;
+        info!("Saved onion service key to {}", path.display());
+    }
+
+    Ok(HiddenService {
+        onion_address: format!("{}.onion", service_id),
+        _control: control,
+    })
+}
+
+// The key is the service's identity, so keep it private to this user
+fn write_key(path: &Path, key: &str) -> Result<(), TorError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+        file.write_all(key.as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    fs::write(path, key)?;
+    Ok(())
+}
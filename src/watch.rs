@@ -0,0 +1,122 @@
+use bitcoin::psbt::Psbt;
+use bitcoin::{Address, Network};
+use bitcoincore_rpc::{Client, RpcApi};
+use serde_json::json;
+use tracing::info;
+
+use crate::error::BuildError;
+use crate::ord::{fetch_output, Utxo};
+
+// A descriptor imported watch-only into a bitcoind wallet, so coins can be found
+// without knowing which of its addresses holds them
+#[derive(Debug)]
+pub struct WatchedDescriptor {
+    // Canonical form with checksum, as returned by getdescriptorinfo
+    pub descriptor: String,
+    pub ranged: bool,
+}
+
+// Load the watch-only wallet, creating it without private keys if it doesn't exist yet
+pub fn ensure_watch_wallet(node: &Client, wallet_name: &str) -> Result<(), BuildError> {
+    if node.list_wallets()?.iter().any(|w| w == wallet_name) {
+        return Ok(());
+    }
+    if node.load_wallet(wallet_name).is_ok() {
+        info!("Loaded watch-only wallet {}", wallet_name);
+        return Ok(());
+    }
+
+    node.create_wallet(wallet_name, Some(true), Some(true), None, None)?;
+    info!("Created watch-only wallet {}", wallet_name);
+    Ok(())
+}
+
+// Import the descriptor unless the wallet already tracks it. Importing rescans the
+// chain from `rescan_since` (a unix timestamp), which can take a while on mainnet.
+pub fn import_descriptor(
+    wallet: &Client,
+    descriptor: &str,
+    range: u32,
+    rescan_since: u64,
+) -> Result<WatchedDescriptor, BuildError> {
+    let info = wallet.get_descriptor_info(descriptor)?;
+    if info.has_private_keys {
+        // getdescriptorinfo strips them, but they shouldn't be on a command line at all
+        return Err(BuildError::Descriptor("use the public form of the descriptor".to_string()));
+    }
+    let watched = WatchedDescriptor {
+        descriptor: info.descriptor,
+        ranged: info.is_range,
+    };
+
+    let imported: serde_json::Value = wallet.call("listdescriptors", &[])?;
+    let already_imported = imported["descriptors"]
+        .as_array()
+        .is_some_and(|descs| descs.iter().any(|d| d["desc"] == watched.descriptor.as_str()));
+    if already_imported {
+        info!("Descriptor already imported");
+        return Ok(watched);
+    }
+
+    let mut request = json!({
+        "desc": watched.descriptor,
+        "timestamp": rescan_since,
+    });
+    if watched.ranged {
+        request["range"] = json!([0, range]);
+    }
+
+    info!("Importing descriptor and rescanning, this can take a while...");
+    let results: serde_json::Value = wallet.call("importdescriptors", &[json!([request])])?;
+    match results.get(0) {
+        Some(result) if result["success"] == true => Ok(watched),
+        Some(result) => Err(BuildError::Descriptor(
+            result["error"]["message"].as_str().unwrap_or("import failed").to_string(),
+        )),
+        None => Err(BuildError::Descriptor("importdescriptors returned no result".to_string())),
+    }
+}
+
+// Address at an index of the descriptor, used for change
+pub fn derive_address(
+    wallet: &Client,
+    watched: &WatchedDescriptor,
+    index: u32,
+    network: Network,
+) -> Result<Address, BuildError> {
+    let range = watched.ranged.then_some([index, index]);
+    let address = wallet.derive_addresses(&watched.descriptor, range)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| BuildError::Descriptor("descriptor derives no addresses".to_string()))?;
+    Ok(address.require_network(network)?)
+}
+
+// Have the watch-only wallet add what signers need for the descriptor's inputs: the spent
+// outputs, witness scripts and key origins. Inputs from elsewhere are left as they are.
+pub fn fill_psbt(wallet: &Client, psbt: &Psbt) -> Result<Psbt, BuildError> {
+    let processed = wallet.wallet_process_psbt(&psbt.to_string(), Some(false), None, Some(true))?;
+    processed.psbt.parse()
+        .map_err(|e| BuildError::Descriptor(format!("wallet returned an invalid PSBT: {}", e)))
+}
+
+// Spendable coins of the watched descriptor, skipping any that carry runes or
+// inscriptions so they aren't spent as plain bitcoin
+pub fn fetch_descriptor_utxos(
+    wallet: &Client,
+    http: &reqwest::blocking::Client,
+    ord_server: &str,
+) -> Result<Vec<Utxo>, BuildError> {
+    let unspent = wallet.list_unspent(Some(1), None, None, None, None)?;
+    info!("Watch-only wallet has {} confirmed UTXOs", unspent.len());
+
+    let mut utxos = Vec::new();
+    for entry in unspent {
+        let outpoint = bitcoin::OutPoint { txid: entry.txid, vout: entry.vout };
+        let utxo = fetch_output(http, ord_server, &outpoint)?;
+        if utxo.runes.is_empty() && utxo.inscriptions.is_empty() {
+            utxos.push(utxo);
+        }
+    }
+    Ok(utxos)
+}
@@ -0,0 +1,183 @@
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::Hash;
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Transaction, TxOut, Txid, WPubkeyHash, WScriptHash};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use slugline::anchor::{
+    create_cpfp_transaction, create_p2a_script, find_anchor, is_p2a, validate_anchor, Anchor, AnchorPolicy, P2A_SCRIPT,
+};
+use slugline::build_tx::build_transaction;
+use slugline::error::{SearcherError, ValidationError};
+use slugline::ord::{RuneInfo, Utxo};
+use slugline::runes::RUNE_NAME;
+use std::collections::HashMap;
+
+// Seeded, so a failing case can be replayed
+const CASES: u64 = 256;
+
+fn random_address(rng: &mut StdRng) -> Address {
+    let script = match rng.gen_bool(0.5) {
+        true => ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(rng.r#gen())),
+        false => ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array(rng.r#gen())),
+    };
+    Address::from_script(&script, Network::Regtest).unwrap()
+}
+
+fn random_utxo(rng: &mut StdRng, rune: bool) -> Utxo {
+    let address = random_address(rng);
+    let runes = match rune {
+        true => HashMap::from([(
+            RUNE_NAME.to_string(),
+            RuneInfo { amount: rng.gen_range(1..1_000_000), divisibility: 0, symbol: "$".to_string() },
+        )]),
+        false => HashMap::new(),
+    };
+    Utxo {
+        address: address.to_string(),
+        confirmations: rng.gen_range(0..1000),
+        indexed: true,
+        inscriptions: Vec::new(),
+        outpoint: OutPoint::new(Txid::from_byte_array(rng.r#gen()), rng.gen_range(0..10)).to_string(),
+        runes,
+        sat_ranges: None,
+        script_pubkey: address.script_pubkey().to_hex_string(),
+        spent: false,
+        transaction: String::new(),
+        value: rng.gen_range(546..100_000_000),
+    }
+}
+
+// A parent as build-tx makes it, from random coins, amounts and addresses
+fn random_parent(rng: &mut StdRng, anchor_script: ScriptBuf) -> Transaction {
+    let utxos: Vec<Utxo> = (0..rng.gen_range(1..6)).map(|_| random_utxo(rng, false)).collect();
+    let selected: Vec<&Utxo> = utxos.iter().collect();
+    let rune_utxo = rng.gen_bool(0.8).then(|| random_utxo(rng, true));
+    let total: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+    build_transaction(
+        &selected,
+        rune_utxo.as_ref(),
+        &random_address(rng).to_string(),
+        &random_address(rng).to_string(),
+        rng.gen_range(1..=total),
+        anchor_script,
+        Network::Regtest,
+    )
+    .unwrap()
+}
+
+fn searcher_utxo(rng: &mut StdRng) -> ListUnspentResultEntry {
+    ListUnspentResultEntry {
+        txid: Txid::from_byte_array(rng.r#gen()),
+        vout: rng.gen_range(0..10),
+        address: None,
+        label: None,
+        redeem_script: None,
+        witness_script: None,
+        script_pub_key: random_address(rng).script_pubkey(),
+        amount: Amount::from_sat(rng.gen_range(10_000..100_000_000)),
+        confirmations: 1,
+        spendable: true,
+        solvable: true,
+        descriptor: None,
+        safe: true,
+    }
+}
+
+#[test]
+fn p2a_script_is_op_1_push_4e73() {
+    let script = create_p2a_script();
+    assert_eq!(script.as_bytes(), P2A_SCRIPT);
+    assert_eq!(script.to_hex_string(), "51024e73");
+    assert!(script.is_witness_program());
+    assert!(is_p2a(&script));
+    assert!(is_p2a(&ScriptBuf::from_hex("51024e73").unwrap()));
+    assert!(!is_p2a(&ScriptBuf::from_hex("51024e74").unwrap()));
+    assert!(!is_p2a(&ScriptBuf::from_hex("0014").unwrap()));
+}
+
+#[test]
+fn built_p2a_anchors_pass_validation() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let parent = random_parent(&mut rng, create_p2a_script());
+
+        assert!(validate_anchor(&parent).is_ok(), "seed {}", seed);
+        assert_eq!(find_anchor(&parent, AnchorPolicy::P2a).unwrap(), Anchor::P2a, "seed {}", seed);
+        assert_eq!(find_anchor(&parent, AnchorPolicy::Either).unwrap(), Anchor::P2a, "seed {}", seed);
+        assert!(
+            matches!(find_anchor(&parent, AnchorPolicy::Keyed), Err(ValidationError::MissingKeyedAnchor)),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn built_keyed_anchors_pass_validation() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let anchor_script = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(rng.r#gen()));
+        let parent = random_parent(&mut rng, anchor_script.clone());
+
+        let keyed = Anchor::Keyed(anchor_script);
+        assert_eq!(find_anchor(&parent, AnchorPolicy::Keyed).unwrap(), keyed, "seed {}", seed);
+        assert_eq!(find_anchor(&parent, AnchorPolicy::Either).unwrap(), keyed, "seed {}", seed);
+        assert!(
+            matches!(find_anchor(&parent, AnchorPolicy::P2a), Err(ValidationError::MissingAnchor)),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn anchors_survive_serialization() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let parent = random_parent(&mut rng, create_p2a_script());
+        let parent: Transaction = deserialize(&serialize(&parent)).unwrap();
+
+        assert!(validate_anchor(&parent).is_ok(), "seed {}", seed);
+        let script = ScriptBuf::from_hex(&parent.output[0].script_pubkey.to_hex_string()).unwrap();
+        assert!(is_p2a(&script), "seed {}", seed);
+    }
+}
+
+#[test]
+fn children_spend_the_validated_anchor() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let parent = random_parent(&mut rng, create_p2a_script());
+        let utxo = searcher_utxo(&mut rng);
+        let change = random_address(&mut rng).script_pubkey();
+        let child = create_cpfp_transaction(&parent, &utxo, change, None, None, rng.gen_range(0.1..50.0)).unwrap();
+
+        let anchor = child.input[0].previous_output;
+        assert_eq!(anchor, OutPoint::new(parent.compute_txid(), 0), "seed {}", seed);
+        assert!(is_p2a(&parent.output[anchor.vout as usize].script_pubkey), "seed {}", seed);
+        assert_eq!(child.input[1].previous_output, OutPoint::new(utxo.txid, utxo.vout), "seed {}", seed);
+    }
+}
+
+#[test]
+fn children_never_pay_more_than_their_utxo() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let parent = random_parent(&mut rng, create_p2a_script());
+    let change = random_address(&mut rng).script_pubkey();
+    let revenue = TxOut { value: Amount::from_sat(5_000), script_pubkey: random_address(&mut rng).script_pubkey() };
+    let child_with = |sats| {
+        let utxo = ListUnspentResultEntry { amount: Amount::from_sat(sats), ..searcher_utxo(&mut StdRng::seed_from_u64(sats)) };
+        create_cpfp_transaction(&parent, &utxo, change.clone(), None, Some(revenue.clone()), 10.0)
+    };
+
+    let child = child_with(100_000).unwrap();
+    let paid_out: u64 = child.output.iter().map(|output| output.value.to_sat()).sum();
+    let fee = 100_000 - paid_out;
+    assert!(fee as f64 >= 10.0 * (parent.vsize() + child.vsize()) as f64);
+
+    // Short of the fee and revenue, or leaving only dust change
+    assert!(matches!(child_with(fee + 4_000), Err(SearcherError::Cpfp(_))));
+    assert!(matches!(child_with(fee + 5_000 + 100), Err(SearcherError::Cpfp(_))));
+    assert!(child_with(fee + 5_000 + 1_000).is_ok());
+}
@@ -0,0 +1,112 @@
+use slugline::error::SearcherError;
+use slugline::searcher::jobs::{self, Invoice, JobKind, JobStatus, JobStore};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn store_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("slugline-{}-{}.jsonl", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn reloads_logs_appended_a_line_at_a_time() {
+    let path = store_path("reload");
+    let store = JobStore::open(path.clone()).unwrap();
+    let id = store.start(JobKind::Package, 1);
+    store.log(id, "validate", "Anchor passed".to_string());
+    store.update(id, |job| job.parent_txid = Some("parent".to_string()));
+    store.log(id, "payment", "Accepted payment".to_string());
+    store.fail(id, &SearcherError::Cancelled);
+
+    let jobs = jobs::read(&path).unwrap();
+    let job = &jobs[&id];
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.parent_txid.as_deref(), Some("parent"));
+    let steps: Vec<&str> = job.log.iter().map(|event| event.step.as_str()).collect();
+    assert_eq!(steps, ["validate", "payment", "failed"]);
+
+    // Each change adds a line, and none of them repeats the log
+    let lines = fs::read_to_string(&path).unwrap();
+    assert_eq!(lines.lines().count(), 5);
+    assert_eq!(lines.matches("Anchor passed").count(), 1);
+
+    // Reopening compacts to a line per job that keeps the whole log
+    drop(store);
+    let store = JobStore::open(path.clone()).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    assert_eq!(store.get(id).unwrap().log.len(), 3);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn reads_lines_carrying_the_whole_log() {
+    let path = store_path("legacy");
+    let store = JobStore::open(path.clone()).unwrap();
+    let id = store.start(JobKind::Package, 1);
+    store.log(id, "validate", "Anchor passed".to_string());
+    store.log(id, "payment", "Accepted payment".to_string());
+    let job = store.get(id).unwrap();
+    drop(store);
+
+    // Written before lines only carried new events, each repeating the log so far
+    let mut legacy = serde_json::to_value(&job).unwrap();
+    legacy.as_object_mut().unwrap().remove("log");
+    let mut lines = Vec::new();
+    for logged in 0..=job.log.len() {
+        legacy["log"] = serde_json::to_value(&job.log[..logged]).unwrap();
+        lines.push(legacy.to_string());
+    }
+    fs::write(&path, lines.join("\n")).unwrap();
+
+    let jobs = jobs::read(&path).unwrap();
+    assert_eq!(jobs[&id].log.len(), 2);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn compacts_while_running() {
+    let path = store_path("compact");
+    let store = JobStore::open(path.clone()).unwrap();
+    let id = store.start(JobKind::Package, 1);
+    for i in 0..3000 {
+        store.log(id, "step", format!("Event {}", i));
+    }
+
+    let lines = fs::read_to_string(&path).unwrap().lines().count();
+    assert!(lines < 2000, "{} lines", lines);
+    assert_eq!(jobs::read(&path).unwrap()[&id].log.len(), 3000);
+    assert_eq!(store.get(id).unwrap().log.len(), 3000);
+    fs::remove_file(&path).unwrap();
+}
+
+fn invoice(payment_hash: &str, expires_at: u64) -> Invoice {
+    Invoice { payment_hash: payment_hash.to_string(), parent: None, expires_at, amount_sat: 1000, fee_rate: 2.0 }
+}
+
+#[test]
+fn keeps_quoted_invoices_over_a_restart() {
+    let path = store_path("invoices");
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let store = JobStore::open(path.clone()).unwrap();
+    let id = store.start(JobKind::Package, 1);
+    store.quote_invoice(invoice("paid", now + 600));
+    store.update_invoice("paid", |quote| quote.parent = Some("parent".to_string()));
+    // Still kept in case it was paid just before it expired, until a day has passed
+    store.quote_invoice(invoice("late", now - 600));
+    store.quote_invoice(invoice("stale", now - 2 * 86_400));
+    assert_eq!(store.update_invoice("unknown", |_| {}), None);
+    drop(store);
+
+    // Invoice lines don't get in the way of reading jobs
+    assert_eq!(jobs::read(&path).unwrap().len(), 1);
+
+    let store = JobStore::open(path.clone()).unwrap();
+    assert_eq!(store.get(id).unwrap().id, id);
+    assert_eq!(store.invoice("paid").unwrap().parent.as_deref(), Some("parent"));
+    assert!(store.invoice("late").is_some());
+    assert_eq!(store.invoice("stale"), None);
+    assert_eq!(store.invoices().len(), 2);
+    fs::remove_file(&path).unwrap();
+}
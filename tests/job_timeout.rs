@@ -0,0 +1,47 @@
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use slugline::error::SearcherError;
+use slugline::searcher::server::run_with_timeout;
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Well under the RPC client's own 15 second read timeout
+const JOB_TIMEOUT_SECS: u64 = 1;
+
+// Long past the job timeout, but short enough that the abandoned job doesn't hold up the test
+const STALL_SECS: u64 = 3;
+
+// A bitcoind that takes connections and hangs up on them without answering
+fn stalled_bitcoind() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            thread::spawn(move || {
+                thread::sleep(Duration::from_secs(STALL_SECS));
+                drop(stream);
+            });
+        }
+    });
+    address
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn times_out_during_stalled_rpc() {
+    let url = format!("http://{}", stalled_bitcoind());
+    let started = Instant::now();
+    let result = run_with_timeout(JOB_TIMEOUT_SECS, async move {
+        let client = Client::new(&url, Auth::None)?;
+        Ok(client.get_block_count()?)
+    })
+    .await;
+
+    assert!(matches!(result, Err(SearcherError::Timeout { secs: JOB_TIMEOUT_SECS, .. })), "{:?}", result);
+    assert!(started.elapsed() < Duration::from_secs(STALL_SECS), "took {:?}", started.elapsed());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn returns_jobs_that_finish_in_time() {
+    let result = run_with_timeout(JOB_TIMEOUT_SECS, async { Ok::<_, SearcherError>(42) }).await;
+    assert_eq!(result.unwrap(), 42);
+}
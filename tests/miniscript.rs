@@ -0,0 +1,116 @@
+use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CLTV, OP_CSV, OP_DROP, OP_ELSE, OP_ENDIF, OP_IF, OP_IFDUP, OP_NOTIF};
+use bitcoin::script::{Builder, ScriptBuf};
+use bitcoin::{absolute, Sequence};
+use slugline::miniscript::{analyze, SpendPath};
+
+const KEY_A: [u8; 33] = [2; 33];
+const KEY_B: [u8; 33] = [3; 33];
+const SIGNATURE: u64 = 1 + 72;
+const SELECTOR: u64 = 1 + 1;
+
+fn witness_weight(script: &ScriptBuf, elements: u64) -> u64 {
+    1 + elements + 1 + script.len() as u64
+}
+
+// or_d(pk(A),and_v(v:pk(B),older(4320))), a vault whose recovery key waits a month
+fn vault() -> ScriptBuf {
+    Builder::new()
+        .push_slice(KEY_A)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_IFDUP)
+        .push_opcode(OP_NOTIF)
+        .push_slice(KEY_B)
+        .push_opcode(OP_CHECKSIGVERIFY)
+        .push_int(4320)
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_ENDIF)
+        .into_script()
+}
+
+// One key after a relative lock, the other after an absolute one
+fn either_timelock() -> ScriptBuf {
+    Builder::new()
+        .push_opcode(OP_IF)
+        .push_slice(KEY_A)
+        .push_opcode(OP_CHECKSIGVERIFY)
+        .push_int(144)
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_ELSE)
+        .push_slice(KEY_B)
+        .push_opcode(OP_CHECKSIGVERIFY)
+        .push_int(900_000)
+        .push_opcode(OP_CLTV)
+        .push_opcode(OP_ENDIF)
+        .push_opcode(OP_DROP)
+        .push_int(1)
+        .into_script()
+}
+
+#[test]
+fn plans_each_branch_of_a_vault() {
+    let script = vault();
+    let spend = analyze(&script).unwrap();
+    assert_eq!(spend.paths, [
+        SpendPath {
+            witness_weight: witness_weight(&script, 2 * SIGNATURE + SELECTOR),
+            older: Some(Sequence::from_consensus(4320)),
+            after: None,
+        },
+        SpendPath { witness_weight: witness_weight(&script, SIGNATURE + SELECTOR), older: None, after: None },
+    ]);
+    assert_eq!(spend.max_witness_weight(), witness_weight(&script, 2 * SIGNATURE + SELECTOR));
+    assert_eq!(spend.timelocked(), spend.paths.first());
+}
+
+#[test]
+fn sizes_the_branch_the_input_can_take() {
+    let spend = analyze(&vault()).unwrap();
+    let [recovery, hot] = [spend.paths[0].witness_weight, spend.paths[1].witness_weight];
+
+    // Without a relative lock only the hot key can spend
+    assert_eq!(spend.witness_weight(Sequence::ENABLE_RBF_NO_LOCKTIME, absolute::LockTime::ZERO), hot);
+    assert_eq!(spend.witness_weight(Sequence::from_height(4319), absolute::LockTime::ZERO), hot);
+    assert_eq!(spend.witness_weight(Sequence::from_height(4320), absolute::LockTime::ZERO), recovery);
+    // Time-based locks don't satisfy a height-based older()
+    assert_eq!(spend.witness_weight(Sequence::from_512_second_intervals(4320), absolute::LockTime::ZERO), hot);
+}
+
+#[test]
+fn keeps_timelocks_to_their_branch() {
+    let spend = analyze(&either_timelock()).unwrap();
+    let older = SpendPath { older: Some(Sequence::from_consensus(144)), after: None, ..spend.paths[0] };
+    let after = SpendPath { older: None, after: Some(absolute::LockTime::from_consensus(900_000)), ..spend.paths[1] };
+    assert_eq!(spend.paths, [older, after]);
+    assert_eq!(spend.timelocked(), Some(&older));
+
+    let height = absolute::LockTime::from_consensus(900_000);
+    assert!(spend.paths[1].is_satisfied_by(Sequence::ENABLE_LOCKTIME_NO_RBF, height));
+    // A final sequence turns the locktime off
+    assert!(!spend.paths[1].is_satisfied_by(Sequence::MAX, height));
+    assert!(!spend.paths[1].is_satisfied_by(Sequence::ENABLE_LOCKTIME_NO_RBF, absolute::LockTime::from_consensus(899_999)));
+}
+
+#[test]
+fn multiplies_paths_of_consecutive_ifs() {
+    let branch = |builder: Builder, key: [u8; 33]| {
+        builder
+            .push_opcode(OP_IF)
+            .push_slice(key)
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_opcode(OP_ENDIF)
+    };
+    let script = branch(branch(Builder::new(), KEY_A), KEY_B).push_int(1).into_script();
+    let spend = analyze(&script).unwrap();
+    let weights: Vec<u64> = spend.paths.iter().map(|path| path.witness_weight).collect();
+    let elements = [2 * SIGNATURE, SIGNATURE, SIGNATURE, 0].map(|signatures| witness_weight(&script, signatures + 2 * SELECTOR));
+    assert_eq!(weights, elements);
+    assert_eq!(spend.timelocked(), None);
+}
+
+#[test]
+fn rejects_unbalanced_ifs() {
+    let script = Builder::new().push_opcode(OP_IF).push_slice(KEY_A).push_opcode(OP_CHECKSIG).into_script();
+    assert_eq!(analyze(&script), None);
+    let script = Builder::new().push_opcode(OP_ENDIF).into_script();
+    assert_eq!(analyze(&script), None);
+}
@@ -0,0 +1,403 @@
+use bitcoin::key::TapTweak;
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey};
+use slugline::error::MusigError;
+use slugline::musig::{
+    aggregate_partials, generate_nonce, nonce_gen, partial_sign, sort_keys, verify_partial, AggregateNonce, KeyAgg,
+    PartialSignature, PublicNonce, SecretNonce,
+};
+use std::str::FromStr;
+
+// The BIP 327 test vectors, as libsecp256k1 carries them
+// BIP 327 key aggregation: keys 3 to 5 are invalid encodings and tweak 0 is the curve order
+const KEY_AGG_KEYS: [&str; 7] = [
+    "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    "03dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+    "023590a94e768f8e1815c2f24b4d80a8e3149316c3518ce7b7ad338368d038ca66",
+    "020000000000000000000000000000000000000000000000000000000000000005",
+    "02fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc30",
+    "04f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    "03935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+];
+const KEY_AGG_TWEAKS: [&str; 2] = [
+    "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+    "252e4bd67410a76cdf933d30eaa1608214037f1b105a013eccd3c5c184a6110b",
+];
+
+const KEY_AGG_VALID: [(&[usize], &str); 4] = [
+    (&[0, 1, 2], "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"),
+    (&[2, 1, 0], "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"),
+    (&[0, 0, 0], "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"),
+    (&[0, 0, 1, 1], "69bc22bfa5d106306e48a20679de1d7389386124d07571d0d872686028c26a3e"),
+];
+
+// Keys, tweaks, and whether each tweak is x-only
+const KEY_AGG_ERRORS: [(&[usize], &[usize], &[bool]); 5] = [
+    (&[0, 3], &[], &[]),
+    (&[0, 4], &[], &[]),
+    (&[5, 0], &[], &[]),
+    (&[0, 1], &[0], &[true]),
+    (&[6], &[1], &[false]),
+];
+
+struct NonceGenCase {
+    random: &'static str,
+    secret_key: Option<&'static str>,
+    key: &'static str,
+    aggregate_key: Option<&'static str>,
+    message: Option<&'static str>,
+    extra: &'static str,
+    secret: &'static str,
+    public: &'static str,
+}
+
+const NONCE_GEN: [NonceGenCase; 2] = [
+    NonceGenCase {
+        random: "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f",
+        secret_key: Some("0202020202020202020202020202020202020202020202020202020202020202"),
+        key: "024d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d0766",
+        aggregate_key: Some("0707070707070707070707070707070707070707070707070707070707070707"),
+        message: Some("0101010101010101010101010101010101010101010101010101010101010101"),
+        extra: "0808080808080808080808080808080808080808080808080808080808080808",
+        secret: "b114e502beaa4e301dd08a50264172c84e41650e6cb726b410c0694d59effb6495b5caf28d045b973d63e3c99a44b807bde375fd6cb39e46dc4a511708d0e9d2024d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d0766",
+        public: "02f7be7089e8376eb355272368766b17e88e7db72047d05e56aa881ea52b3b35df02c29c8046fdd0ded4c7e55869137200fbdbfe2eb654267b6d7013602caed3115a",
+    },
+    NonceGenCase {
+        random: "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f",
+        secret_key: None,
+        key: "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        aggregate_key: None,
+        message: None,
+        extra: "",
+        secret: "89bdd787d0284e5e4d5fc572e49e316bab7e21e3b1830de37dfe80156fa41a6d0b17ae8d024c53679699a6fd7944d9c4a366b514baf43088e0708b1023dd289702f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        public: "02c96e7cb1e8aa5dac64d872947914198f607d90ecde5200de52978ad5ded63c000299ec5117c2d29edee8a2092587c3909be694d5cff0667d6c02ea4059f7cd9786",
+    },
+];
+
+// Nonces 4 to 6 are invalid encodings
+const NONCE_AGG_NONCES: [&str; 7] = [
+    "020151c80f435648df67a22b749cd798ce54e0321d034b92b709b567d60a42e66603ba47fbc1834437b3212e89a84d8425e7bf12e0245d98262268ebdcb385d50641",
+    "03ff406ffd8adb9cd29877e4985014f66a59f6cd01c0e88caa8e5f3166b1f676a60248c264cdd57d3c24d79990b0f865674eb62a0f9018277a95011b41bfc193b833",
+    "020151c80f435648df67a22b749cd798ce54e0321d034b92b709b567d60a42e6660279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    "03ff406ffd8adb9cd29877e4985014f66a59f6cd01c0e88caa8e5f3166b1f676a60379be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    "04ff406ffd8adb9cd29877e4985014f66a59f6cd01c0e88caa8e5f3166b1f676a60248c264cdd57d3c24d79990b0f865674eb62a0f9018277a95011b41bfc193b833",
+    "03ff406ffd8adb9cd29877e4985014f66a59f6cd01c0e88caa8e5f3166b1f676a60248c264cdd57d3c24d79990b0f865674eb62a0f9018277a95011b41bfc193b831",
+    "03ff406ffd8adb9cd29877e4985014f66a59f6cd01c0e88caa8e5f3166b1f676a602fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc30",
+];
+
+const NONCE_AGG_VALID: [([usize; 2], &str); 2] = [
+    ([0, 1], "035fe1873b4f2967f52fea4a06ad5a8eccbe9d0fd73068012c894e2e87ccb5804b024725377345bde0e9c33af3c43c0a29a9249f2f2956fa8cfeb55c8573d0262dc8"),
+    ([2, 3], "035fe1873b4f2967f52fea4a06ad5a8eccbe9d0fd73068012c894e2e87ccb5804b000000000000000000000000000000000000000000000000000000000000000000"),
+];
+
+const NONCE_AGG_ERRORS: [[usize; 2]; 3] = [[0, 4], [5, 1], [6, 1]];
+
+// Signing and verifying: key 3, aggregate nonces 2 to 4 and public nonce 4 are invalid, and
+// secret nonce 1 is zero
+const SIGN_SECRET_KEY: &str = "7fb9e0e687ada1eebf7ecfe2f21e73ebdb51a7d450948dfe8d76d7f2d1007671";
+const SIGN_KEYS: [&str; 4] = [
+    "03935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+    "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    "02dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba661",
+    "020000000000000000000000000000000000000000000000000000000000000007",
+];
+const SIGN_SECRET_NONCES: [&str; 2] = [
+    "508b81a611f100a6b2b6b29656590898af488bcf2e1f55cf22e5cfb84421fe61fa27fd49b1d50085b481285e1ca205d55c82cc1b31ff5cd54a489829355901f703935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+    "0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000003935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+];
+const SIGN_PUBLIC_NONCES: [&str; 5] = [
+    "0337c87821afd50a8644d820a8f3e02e499c931865c2360fb43d0a0d20dafe07ea0287bf891d2a6deaebadc909352aa9405d1428c15f4b75f04dae642a95c2548480",
+    "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817980279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    "032de2662628c90b03f5e720284eb52ff7d71f4284f627b68a853d78c78e1ffe9303e4c5524e83ffe1493b9077cf1ca6beb2090c93d930321071ad40b2f44e599046",
+    "0237c87821afd50a8644d820a8f3e02e499c931865c2360fb43d0a0d20dafe07ea0387bf891d2a6deaebadc909352aa9405d1428c15f4b75f04dae642a95c2548480",
+    "0200000000000000000000000000000000000000000000000000000000000000090287bf891d2a6deaebadc909352aa9405d1428c15f4b75f04dae642a95c2548480",
+];
+const SIGN_AGGREGATE_NONCES: [&str; 5] = [
+    "028465fcf0bbdbcf443aabcce533d42b4b5a10966ac09a49655e8c42daab8fcd61037496a3cc86926d452cafcfd55d25972ca1675d549310de296bff42f72eeea8c9",
+    "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    "048465fcf0bbdbcf443aabcce533d42b4b5a10966ac09a49655e8c42daab8fcd61037496a3cc86926d452cafcfd55d25972ca1675d549310de296bff42f72eeea8c9",
+    "028465fcf0bbdbcf443aabcce533d42b4b5a10966ac09a49655e8c42daab8fcd61020000000000000000000000000000000000000000000000000000000000000009",
+    "028465fcf0bbdbcf443aabcce533d42b4b5a10966ac09a49655e8c42daab8fcd6102fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc30",
+];
+const SIGN_MESSAGE: &str = "f95466d086770e689964664219266fe5ed215c92ae20bab5c9d79addddf3c0cf";
+
+// Keys, aggregate nonce and the signer's position among the keys
+const SIGN_VALID: [(&[usize], usize, usize, &str); 4] = [
+    (&[0, 1, 2], 0, 0, "012abbcb52b3016ac03ad82395a1a415c48b93def78718e62a7a90052fe224fb"),
+    (&[1, 0, 2], 0, 1, "9ff2f7aaa856150cc8819254218d3adeeb0535269051897724f9db3789513a52"),
+    (&[1, 2, 0], 0, 2, "fa23c359f6fac4e7796bb93bc9f0532a95468c539ba20ff86d7c76ed92227900"),
+    (&[0, 1], 1, 0, "ae386064b26105404798f75de2eb9af5eda5387b064b83d049cb7c5e08879531"),
+];
+
+// Keys, aggregate nonce and secret nonce
+const SIGN_ERRORS: [(&[usize], usize, usize); 6] = [
+    (&[1, 2], 0, 0),
+    (&[1, 0, 3], 0, 0),
+    (&[1, 2, 0], 2, 0),
+    (&[1, 2, 0], 3, 0),
+    (&[1, 2, 0], 4, 0),
+    (&[0, 1, 2], 0, 1),
+];
+
+// Partial signature, keys, public nonces and the signer's position
+const VERIFY_FAILS: [(&str, &[usize], &[usize], usize); 5] = [
+    ("fed54434ad4cfe953fc527dc6a5e5be8f6234907b7c187559557ce87a0541c46", &[0, 1, 2], &[0, 1, 2], 0),
+    ("012abbcb52b3016ac03ad82395a1a415c48b93def78718e62a7a90052fe224fb", &[0, 1, 2], &[0, 1, 2], 1),
+    ("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", &[0, 1, 2], &[0, 1, 2], 0),
+    ("012abbcb52b3016ac03ad82395a1a415c48b93def78718e62a7a90052fe224fb", &[0, 1, 2], &[4, 1, 2], 0),
+    ("012abbcb52b3016ac03ad82395a1a415c48b93def78718e62a7a90052fe224fb", &[3, 1, 2], &[0, 1, 2], 0),
+];
+
+// Signing under tweaks: tweak 4 is the curve order
+const TWEAK_SECRET_KEY: &str = "7fb9e0e687ada1eebf7ecfe2f21e73ebdb51a7d450948dfe8d76d7f2d1007671";
+const TWEAK_SECRET_NONCE: &str = "508b81a611f100a6b2b6b29656590898af488bcf2e1f55cf22e5cfb84421fe61fa27fd49b1d50085b481285e1ca205d55c82cc1b31ff5cd54a489829355901f703935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9";
+const TWEAK_AGGREGATE_NONCE: &str = "028465fcf0bbdbcf443aabcce533d42b4b5a10966ac09a49655e8c42daab8fcd61037496a3cc86926d452cafcfd55d25972ca1675d549310de296bff42f72eeea8c9";
+const TWEAK_MESSAGE: &str = "f95466d086770e689964664219266fe5ed215c92ae20bab5c9d79addddf3c0cf";
+const TWEAK_KEYS: [&str; 3] = [
+    "03935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+    "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+    "02dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+];
+const TWEAK_TWEAKS: [&str; 5] = [
+    "e8f791ff9225a2af0102afff4a9a723d9612a682a25ebe79802b263cdfcd83bb",
+    "ae2ea797cc0fe72ac5b97b97f3c6957d7e4199a167a58eb08bcaffda70ac0455",
+    "f52ecbc565b3d8bea2dfd5b75a4f457e54369809322e4120831626f290fa87e0",
+    "1969ad73cc177fa0b4fced6df1f7bf9907e665fde9ba196a74fed0a3cf5aef9d",
+    "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+];
+
+// Tweaks, whether each is x-only, then the partial signature
+const TWEAK_VALID: [(&[usize], &[bool], &str); 5] = [
+    (&[0], &[true], "e28a5c66e61e178c2ba19db77b6cf9f7e2f0f56c17918cd13135e60cc848fe91"),
+    (&[0], &[false], "38b0767798252f21bf5702c48028b095428320f73a4b14db1e25de58543d2d2d"),
+    (&[0, 1], &[false, true], "408a0a21c4a0f5dacaf9646ad6eb6fecd7f7a11f03ed1f48dfff2185bc2c2408"),
+    (&[0, 1, 2, 3], &[false, false, true, true], "45abd206e61e3df2ec9e264a6fec8292141a633c28586388235541f9ade75435"),
+    (&[0, 1, 2, 3], &[true, false, true, false], "b255fdcac27b40c7ce7848e2d3b7bf5ea0ed756da81565ac804ccca3e1d5d239"),
+];
+
+// Aggregating partial signatures: partial signature 8 is the curve order
+const AGG_KEYS: [&str; 4] = [
+    "03935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+    "02d2dc6f5df7c56acf38c7fa0ae7a759ae30e19b37359dfde015872324c7ef6e05",
+    "03c7fb101d97ff930acd0c6760852ef64e69083de0b06ac6335724754bb4b0522c",
+    "02352433b21e7e05d3b452b81cae566e06d2e003ece16d1074aaba4289e0e3d581",
+];
+const AGG_TWEAKS: [&str; 3] = [
+    "b511da492182a91b0ffb9a98020d55f260ae86d7ecbd0399c7383d59a5f2af7c",
+    "a815fe049ee3c5aab66310477fbc8bcccac2f3395f59f921c364acd78a2f48dc",
+    "75448a87274b056468b977be06eb1e9f657577b7320b0a3376ea51fd420d18a8",
+];
+const AGG_PARTIALS: [&str; 9] = [
+    "b15d2cd3c3d22b04dae438ce653f6b4ecf042f42cfded7c41b64aaf9b4af53fb",
+    "6193d6ac61b354e9105bbdc8937a3454a6d705b6d57322a5a472a02ce99fcb64",
+    "9a87d3b79ec67228cb97878b76049b15dbd05b8158d17b5b9114d3c226887505",
+    "66f82ea90923689b855d36c6b7e032fb9970301481b99e01cdb4d6ac7c347a15",
+    "4f5aee41510848a6447dcd1bbc78457ef69024944c87f40250d3ef2c25d33efe",
+    "ddef427bbb847cc027beff4edb01038148917832253ebc355fc33f4a8e2fcce4",
+    "97b890a26c981da8102d3bc294159d171d72810fdf7c6a691def02f0f7af3fdc",
+    "53fa9e08ba5243cbcb0d797c5ee83bc6728e539eb76c2d0bf0f971ee4e909971",
+    "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+];
+const AGG_MESSAGE: &str = "599c67ea410d005b9da90817cf03ed3b1c868e4da4edf00a5880b0082c237869";
+
+struct SigAggCase {
+    keys: &'static [usize],
+    tweaks: &'static [usize],
+    x_only: &'static [bool],
+    aggregate_nonce: &'static str,
+    partials: &'static [usize],
+    expected: &'static str,
+}
+
+const AGG_VALID: [SigAggCase; 4] = [
+    SigAggCase {
+        keys: &[0, 1],
+        tweaks: &[],
+        x_only: &[],
+        aggregate_nonce: "0341432722c5cd0268d829c702cf0d1cbce57033eed201fd335191385227c3210c03d377f2d258b64aadc0e16f26462323d701d286046a2ea93365656afd9875982b",
+        partials: &[0, 1],
+        expected: "041da22223ce65c92c9a0d6c2cac828aaf1eee56304fec371ddf91ebb2b9ef0912f1038025857fedeb3ff696f8b99fa4bb2c5812f6095a2e0004ec99ce18de1e",
+    },
+    SigAggCase {
+        keys: &[0, 2],
+        tweaks: &[],
+        x_only: &[],
+        aggregate_nonce: "0224afd36c902084058b51b5d36676bba4dc97c775873768e58822f87fe437d792028cb15929099eee2f5dae404cd39357591ba32e9af4e162b8d3e7cb5efe31cb20",
+        partials: &[2, 3],
+        expected: "1069b67ec3d2f3c7c08291accb17a9c9b8f2819a52eb5df8726e17e7d6b52e9f01800260a7e9dac450f4be522de4ce12ba91aeaf2b4279219ef74be1d286add9",
+    },
+    SigAggCase {
+        keys: &[0, 2],
+        tweaks: &[0],
+        x_only: &[false],
+        aggregate_nonce: "0208c5c438c710f4f96a61e9ff3c37758814b8c3ae12bfea0ed2c87ff6954ff186020b1816ea104b4fca2d304d733e0e19cead51303ff6420bfd222335caa402916d",
+        partials: &[4, 5],
+        expected: "5c558e1dcade86da0b2f02626a512e30a22cf5255caea7ee32c38e9a71a0e9148ba6c0e6ec7683b64220f0298696f1b878cd47b107b81f7188812d593971e0cc",
+    },
+    SigAggCase {
+        keys: &[0, 3],
+        tweaks: &[0, 1, 2],
+        x_only: &[true, false, true],
+        aggregate_nonce: "02b5ad07afcd99b6d92cb433fbd2a28fdeb98eae2eb09b6014ef0f8197cd58403302e8616910f9293cf692c49f351db86b25e352901f0e237bafda11f1c1cef29ffd",
+        partials: &[6, 7],
+        expected: "839b08820b681dba8daf4cc7b104e8f2638f9388f8d7a555dc17b6e6971d7426ce07bf6ab01f1db50e4e33719295f4094572b79868e440fb3defd3fac1db589e",
+    },
+];
+
+fn key(hex: &str) -> Result<PublicKey, MusigError> {
+    Ok(PublicKey::from_slice(&hex::decode(hex).unwrap())?)
+}
+
+fn keys(all: &[&str], indices: &[usize]) -> Result<Vec<PublicKey>, MusigError> {
+    indices.iter().map(|i| key(all[*i])).collect()
+}
+
+fn bytes32(hex: &str) -> [u8; 32] {
+    hex::decode(hex).unwrap().try_into().unwrap()
+}
+
+fn tweaked(agg: KeyAgg, all: &[&str], indices: &[usize], x_only: &[bool]) -> Result<KeyAgg, MusigError> {
+    indices.iter().zip(x_only).try_fold(agg, |agg, (i, x_only)| agg.tweak(bytes32(all[*i]), *x_only))
+}
+
+#[test]
+fn key_agg_vectors() {
+    for (indices, expected) in KEY_AGG_VALID {
+        let agg = KeyAgg::new(&keys(&KEY_AGG_KEYS, indices).unwrap()).unwrap();
+        assert_eq!(agg.output_key().to_string(), expected);
+        assert_eq!(agg.internal_key().to_string(), expected);
+    }
+    for (indices, tweaks, x_only) in KEY_AGG_ERRORS {
+        let result = keys(&KEY_AGG_KEYS, indices)
+            .and_then(|keys| KeyAgg::new(&keys))
+            .and_then(|agg| tweaked(agg, &KEY_AGG_TWEAKS, tweaks, x_only));
+        assert!(result.is_err(), "keys {:?} with tweaks {:?} should fail", indices, tweaks);
+    }
+}
+
+#[test]
+fn nonce_gen_vectors() {
+    for NonceGenCase { random, secret_key, key: public_key, aggregate_key, message, extra, secret, public } in NONCE_GEN {
+        let secret_key = secret_key.map(|hex| SecretKey::from_slice(&hex::decode(hex).unwrap()).unwrap());
+        let aggregate_key = aggregate_key.map(|hex| XOnlyPublicKey::from_str(hex).unwrap());
+        let message = message.map(|hex| hex::decode(hex).unwrap());
+        let (secret_nonce, public_nonce) = nonce_gen(
+            bytes32(random),
+            secret_key.as_ref(),
+            &key(public_key).unwrap(),
+            aggregate_key.as_ref(),
+            message.as_deref(),
+            &hex::decode(extra).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(hex::encode(secret_nonce.to_bytes()), secret);
+        assert_eq!(public_nonce.to_string(), public);
+    }
+}
+
+#[test]
+fn nonce_agg_vectors() {
+    for ([i, j], expected) in NONCE_AGG_VALID {
+        let nonces = [NONCE_AGG_NONCES[i], NONCE_AGG_NONCES[j]].map(|hex| PublicNonce::from_str(hex).unwrap());
+        let aggregate = AggregateNonce::new(&nonces).unwrap();
+        assert_eq!(aggregate.to_string(), expected);
+        assert_eq!(AggregateNonce::from_str(expected).unwrap(), aggregate);
+    }
+    for [i, j] in NONCE_AGG_ERRORS {
+        let nonces: Result<Vec<_>, _> = [i, j].iter().map(|i| PublicNonce::from_str(NONCE_AGG_NONCES[*i])).collect();
+        assert!(nonces.is_err(), "nonces {} and {} should not parse", i, j);
+    }
+}
+
+#[test]
+fn sign_verify_vectors() {
+    let secret_key = SecretKey::from_slice(&hex::decode(SIGN_SECRET_KEY).unwrap()).unwrap();
+    let message = bytes32(SIGN_MESSAGE);
+    for (indices, aggregate_nonce, signer, expected) in SIGN_VALID {
+        let agg = KeyAgg::new(&keys(&SIGN_KEYS, indices).unwrap()).unwrap();
+        let aggregate_nonce = AggregateNonce::from_str(SIGN_AGGREGATE_NONCES[aggregate_nonce]).unwrap();
+        let secret_nonce = SecretNonce::from_bytes(&hex::decode(SIGN_SECRET_NONCES[0]).unwrap()).unwrap();
+        let partial = partial_sign(secret_nonce, &secret_key, &agg, &aggregate_nonce, &message).unwrap();
+        assert_eq!(partial.to_string(), expected, "signer {} of {:?}", signer, indices);
+    }
+    for (indices, aggregate_nonce, secret_nonce) in SIGN_ERRORS {
+        let result = keys(&SIGN_KEYS, indices).and_then(|keys| {
+            let agg = KeyAgg::new(&keys)?;
+            let aggregate_nonce = AggregateNonce::from_str(SIGN_AGGREGATE_NONCES[aggregate_nonce])?;
+            let secret_nonce = SecretNonce::from_bytes(&hex::decode(SIGN_SECRET_NONCES[secret_nonce]).unwrap())?;
+            partial_sign(secret_nonce, &secret_key, &agg, &aggregate_nonce, &message)
+        });
+        assert!(result.is_err(), "keys {:?} with aggregate nonce {} should fail", indices, aggregate_nonce);
+    }
+
+    // Aggregate nonce 0 is the sum of the public nonces of keys 0 to 2
+    for (indices, _, signer, expected) in SIGN_VALID.iter().filter(|(_, aggregate_nonce, _, _)| *aggregate_nonce == 0) {
+        let keys = keys(&SIGN_KEYS, indices).unwrap();
+        let nonces: Vec<_> = indices.iter().map(|i| PublicNonce::from_str(SIGN_PUBLIC_NONCES[*i]).unwrap()).collect();
+        let agg = KeyAgg::new(&keys).unwrap();
+        let aggregate_nonce = AggregateNonce::new(&nonces).unwrap();
+        let partial = PartialSignature::from_str(expected).unwrap();
+        verify_partial(&partial, &keys[*signer], &nonces[*signer], &agg, &aggregate_nonce, &message).unwrap();
+    }
+    for (partial, indices, nonces, signer) in VERIFY_FAILS {
+        let result = PartialSignature::from_str(partial).and_then(|partial| {
+            let keys = keys(&SIGN_KEYS, indices)?;
+            let nonces = nonces.iter().map(|i| PublicNonce::from_str(SIGN_PUBLIC_NONCES[*i])).collect::<Result<Vec<_>, _>>()?;
+            let agg = KeyAgg::new(&keys)?;
+            let aggregate_nonce = AggregateNonce::new(&nonces)?;
+            verify_partial(&partial, &keys[signer], &nonces[signer], &agg, &aggregate_nonce, &message)
+        });
+        assert!(result.is_err(), "partial signature {} from signer {} should not verify", partial, signer);
+    }
+}
+
+#[test]
+fn tweak_vectors() {
+    let secret_key = SecretKey::from_slice(&hex::decode(TWEAK_SECRET_KEY).unwrap()).unwrap();
+    let aggregate_nonce = AggregateNonce::from_str(TWEAK_AGGREGATE_NONCE).unwrap();
+    let message = bytes32(TWEAK_MESSAGE);
+    // The signer is always the last of keys 1, 2 and 0
+    let agg = || KeyAgg::new(&keys(&TWEAK_KEYS, &[1, 2, 0]).unwrap()).unwrap();
+    for (tweaks, x_only, expected) in TWEAK_VALID {
+        let agg = tweaked(agg(), &TWEAK_TWEAKS, tweaks, x_only).unwrap();
+        let secret_nonce = SecretNonce::from_bytes(&hex::decode(TWEAK_SECRET_NONCE).unwrap()).unwrap();
+        let partial = partial_sign(secret_nonce, &secret_key, &agg, &aggregate_nonce, &message).unwrap();
+        assert_eq!(partial.to_string(), expected, "tweaks {:?}", tweaks);
+    }
+    assert!(matches!(tweaked(agg(), &TWEAK_TWEAKS, &[4], &[false]), Err(MusigError::OutOfRange(_))));
+}
+
+#[test]
+fn sig_agg_vectors() {
+    let message = bytes32(AGG_MESSAGE);
+    for SigAggCase { keys: indices, tweaks, x_only, aggregate_nonce, partials, expected } in AGG_VALID {
+        let agg = tweaked(KeyAgg::new(&keys(&AGG_KEYS, indices).unwrap()).unwrap(), &AGG_TWEAKS, tweaks, x_only).unwrap();
+        let aggregate_nonce = AggregateNonce::from_str(aggregate_nonce).unwrap();
+        let partials: Vec<_> = partials.iter().map(|i| PartialSignature::from_str(AGG_PARTIALS[*i]).unwrap()).collect();
+        let signature = aggregate_partials(&partials, &agg, &aggregate_nonce, &message).unwrap();
+        assert_eq!(hex::encode(signature.serialize()), expected);
+    }
+    assert!(matches!(PartialSignature::from_str(AGG_PARTIALS[8]), Err(MusigError::OutOfRange(_))));
+}
+
+// A 2-of-2 spend end to end, the way the musig command drives it
+#[test]
+fn taproot_round_trip() {
+    let secp = Secp256k1::new();
+    let secret_keys = [[1u8; 32], [2u8; 32]].map(|bytes| SecretKey::from_slice(&bytes).unwrap());
+    let mut keys: Vec<_> = secret_keys.iter().map(|secret_key| secret_key.public_key(&secp)).collect();
+    sort_keys(&mut keys);
+    let agg = KeyAgg::new(&keys).unwrap().tweak_for_taproot(None).unwrap();
+    let (output_key, _) = agg.internal_key().tap_tweak(&secp, None);
+    assert_eq!(output_key.to_x_only_public_key(), agg.output_key());
+
+    let message = [7u8; 32];
+    let nonces: Vec<_> = secret_keys.iter().map(|secret_key| generate_nonce(secret_key, &agg, &message).unwrap()).collect();
+    let aggregate_nonce = AggregateNonce::new(&nonces.iter().map(|(_, public)| *public).collect::<Vec<_>>()).unwrap();
+    let partials: Vec<_> = secret_keys
+        .iter()
+        .zip(nonces)
+        .map(|(secret_key, (secret, _))| partial_sign(secret, secret_key, &agg, &aggregate_nonce, &message).unwrap())
+        .collect();
+    let signature = aggregate_partials(&partials, &agg, &aggregate_nonce, &message).unwrap();
+    secp.verify_schnorr(&signature, &Message::from_digest(message), &output_key.to_x_only_public_key()).unwrap();
+}
@@ -0,0 +1,30 @@
+use slugline::error::ValidationError;
+use slugline::pricing::{price_package, tier_multiplier, PriceTier};
+use slugline::runes::RuneAmount;
+
+fn tiers() -> Vec<PriceTier> {
+    ["400:1", "1000:1.5", "10000:3"].iter().map(|tier| tier.parse().unwrap()).collect()
+}
+
+#[test]
+fn prices_parents_by_their_tier() {
+    let tiers = tiers();
+    assert_eq!(tier_multiplier(&tiers, 200), 1.0);
+    assert_eq!(tier_multiplier(&tiers, 401), 1.5);
+    assert_eq!(tier_multiplier(&tiers, 10_000), 3.0);
+    // Bigger than every tier pays the largest
+    assert_eq!(tier_multiplier(&tiers, 50_000), 3.0);
+    assert_eq!(tier_multiplier(&[], 50_000), 1.0);
+}
+
+#[test]
+fn big_parents_cost_more_than_one_unit() {
+    let price = price_package(10_000 + 150, 1, tier_multiplier(&tiers(), 10_000));
+    assert_eq!(price, 30_450);
+    // Rounded up, so a quote never asks for less than the price
+    assert_eq!(price_package(3, 1, 1.5), 5);
+
+    let underpaid = ValidationError::Underpaid { paid: RuneAmount::new(1, 0), price: RuneAmount::new(price, 0) };
+    assert_eq!(underpaid.reason(), "underpaid");
+    assert_eq!(underpaid.to_string(), "Payment of 1 TESTSLUGLINERUNE is less than the 30450 the package costs");
+}
@@ -0,0 +1,40 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::Transaction;
+use slugline::rules::{ParentRule, RuleCommand};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+fn rule(name: &str, script: &str) -> (RuleCommand, PathBuf) {
+    let path = std::env::temp_dir().join(format!("slugline-rule-{}-{}", name, std::process::id()));
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    (RuleCommand { program: path.display().to_string() }, path)
+}
+
+fn parent() -> Transaction {
+    Transaction { version: Version(3), lock_time: LockTime::ZERO, input: Vec::new(), output: Vec::new() }
+}
+
+#[test]
+fn accepts_on_exit_zero_and_names_the_reason_otherwise() {
+    let (accept, path) = rule("accept", "cat > /dev/null");
+    assert_eq!(accept.check(&parent()), Ok(()));
+    fs::remove_file(path).unwrap();
+
+    let (refuse, path) = rule("refuse", "echo\necho 'too many outputs' >&2\nexit 1");
+    assert_eq!(refuse.check(&parent()), Err("too many outputs".to_string()));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn answers_promptly_however_much_the_program_prints() {
+    // Far more than a pipe buffer holds, which used to block until the timeout
+    let (chatty, path) = rule("chatty", "echo refused\nhead -c 1000000 /dev/zero | tr '\\0' x\nexit 1");
+    let started = Instant::now();
+    assert_eq!(chatty.check(&parent()), Err("refused".to_string()));
+    assert!(started.elapsed() < Duration::from_secs(5));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,66 @@
+use slugline::error::AmountError;
+use slugline::runes::RuneAmount;
+
+#[test]
+fn parses_decimals_into_units() {
+    assert_eq!(RuneAmount::parse("1.5", 2).unwrap().units, 150);
+    assert_eq!(RuneAmount::parse("1.05", 2).unwrap().units, 105);
+    assert_eq!(RuneAmount::parse(".5", 1).unwrap().units, 5);
+    assert_eq!(RuneAmount::parse("2.", 3).unwrap().units, 2000);
+    assert_eq!(RuneAmount::parse("42", 0).unwrap().units, 42);
+}
+
+#[test]
+fn trailing_zeros_past_the_divisibility_are_not_rounding() {
+    assert_eq!(RuneAmount::parse("1.500", 1).unwrap().units, 15);
+    assert_eq!(RuneAmount::parse("3.0", 0).unwrap().units, 3);
+}
+
+#[test]
+fn refuses_to_round_extra_places() {
+    assert!(matches!(
+        RuneAmount::parse("1.55", 1),
+        Err(AmountError::TooPrecise { divisibility: 1, .. })
+    ));
+    assert!(matches!(RuneAmount::parse("0.1", 0), Err(AmountError::TooPrecise { .. })));
+}
+
+#[test]
+fn refuses_malformed_amounts() {
+    for amount in ["", ".", "-1", "+1", "1.2.3", "1e3", " 1", "abc"] {
+        assert!(matches!(RuneAmount::parse(amount, 2), Err(AmountError::Invalid(_))), "{:?}", amount);
+    }
+}
+
+#[test]
+fn refuses_amounts_past_u64() {
+    assert!(matches!(RuneAmount::parse("18446744073709551616", 0), Err(AmountError::Overflow(_))));
+    assert!(matches!(RuneAmount::parse("18446744073709551615", 1), Err(AmountError::Overflow(_))));
+    assert_eq!(RuneAmount::parse("18446744073709551615", 0).unwrap().units, u64::MAX);
+}
+
+#[test]
+fn displays_whole_runes_without_trailing_zeros() {
+    assert_eq!(RuneAmount::new(150, 2).to_string(), "1.5");
+    assert_eq!(RuneAmount::new(105, 2).to_string(), "1.05");
+    assert_eq!(RuneAmount::new(5, 3).to_string(), "0.005");
+    assert_eq!(RuneAmount::new(200, 2).to_string(), "2");
+    assert_eq!(RuneAmount::new(0, 8).to_string(), "0");
+    assert_eq!(RuneAmount::new(u64::MAX, 38).to_string(), "0.00000000000000000018446744073709551615");
+}
+
+#[test]
+fn display_round_trips() {
+    for (units, divisibility) in [(1, 0), (150, 2), (123_456_789, 8), (u64::MAX, 19)] {
+        let amount = RuneAmount::new(units, divisibility);
+        assert_eq!(RuneAmount::parse(&amount.to_string(), divisibility).unwrap(), amount);
+    }
+}
+
+#[test]
+fn computed_prices_round_up() {
+    assert_eq!(RuneAmount::from_units_ceil(149.01, 2).units, 150);
+    assert_eq!(RuneAmount::from_units_ceil(150.0, 2).units, 150);
+    assert_eq!(RuneAmount::from_units_ceil(0.0001, 0).units, 1);
+    assert_eq!(RuneAmount::from_units_ceil(0.0, 0).units, 0);
+}
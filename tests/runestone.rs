@@ -0,0 +1,161 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::opcodes::all::{OP_PUSHNUM_13, OP_RETURN, OP_VERIFY};
+use bitcoin::script::{Builder, PushBytesBuf};
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, ScriptBuf, Transaction, TxOut};
+use slugline::runestone::{cenotaph, has_runestone, runestone, Flaw};
+
+// Tags and flags as the runes protocol numbers them
+const BODY: u128 = 0;
+const FLAGS: u128 = 2;
+const RUNE: u128 = 4;
+const MINT: u128 = 20;
+const POINTER: u128 = 22;
+// Even and unassigned, like ord's Tag::Cenotaph
+const UNKNOWN_EVEN: u128 = 126;
+const UNKNOWN_ODD: u128 = 127;
+const ETCHING: u128 = 1;
+const RUNE_ID: (u64, u32) = (840_000, 1);
+
+fn varint(mut n: u128) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    while n >> 7 > 0 {
+        bytes.push((n as u8 & 0b0111_1111) | 0b1000_0000);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+    bytes
+}
+
+fn runestone_script(payload: &[u8]) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_opcode(OP_PUSHNUM_13)
+        .push_slice(PushBytesBuf::try_from(payload.to_vec()).unwrap())
+        .into_script()
+}
+
+// A runestone in the first output, followed by `outputs` others
+fn with_payload(payload: &[u8], outputs: usize) -> Transaction {
+    let output = std::iter::once(runestone_script(payload))
+        .chain(std::iter::repeat_with(ScriptBuf::new).take(outputs))
+        .map(|script_pubkey| TxOut { value: Amount::ZERO, script_pubkey })
+        .collect();
+    Transaction { version: Version::TWO, lock_time: LockTime::ZERO, input: Vec::new(), output }
+}
+
+fn with_integers(integers: &[u128], outputs: usize) -> Transaction {
+    with_payload(&integers.iter().flat_map(|n| varint(*n)).collect::<Vec<_>>(), outputs)
+}
+
+#[test]
+fn accepts_valid_runestones() {
+    assert_eq!(cenotaph(&with_integers(&[], 1)), None);
+    assert_eq!(cenotaph(&with_integers(&[FLAGS, ETCHING, RUNE, 4], 1)), None);
+    assert_eq!(cenotaph(&with_integers(&[MINT, 1, MINT, 0, POINTER, 0], 1)), None);
+    // Unknown odd tags are ignored
+    assert_eq!(cenotaph(&with_integers(&[UNKNOWN_ODD, 1], 1)), None);
+    // The transaction's runestone output counts, so an output of 2 splits across both
+    assert_eq!(cenotaph(&with_integers(&[BODY, 1, 1, 5, 0, 0, 1, 5, 2], 1)), None);
+    // Block 0's only valid ID is 0:0
+    assert_eq!(cenotaph(&with_integers(&[BODY, 0, 0, 5, 0], 1)), None);
+}
+
+#[test]
+fn ignores_transactions_without_runestones() {
+    let tx = Transaction {
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script() }],
+        ..with_integers(&[], 0)
+    };
+    assert!(!has_runestone(&tx));
+    assert_eq!(cenotaph(&tx), None);
+}
+
+#[test]
+fn rejects_varint_overflow() {
+    // u128::MAX takes 19 bytes, and a 19th byte can only carry its two low bits
+    assert_eq!(varint(u128::MAX).len(), 19);
+    let field = |value: &[u8]| [varint(UNKNOWN_ODD).as_slice(), value].concat();
+    assert_eq!(cenotaph(&with_payload(&field(&varint(u128::MAX)), 1)), None);
+    let mut overflow = varint(u128::MAX);
+    overflow[18] = 0b0000_0100;
+    assert_eq!(cenotaph(&with_payload(&field(&overflow), 1)), Some(Flaw::Varint));
+    // Overlong: a 20th byte
+    let mut overlong = vec![0b1000_0000; 19];
+    overlong.push(0);
+    assert_eq!(cenotaph(&with_payload(&field(&overlong), 1)), Some(Flaw::Varint));
+    // Unterminated
+    assert_eq!(cenotaph(&with_payload(&[0b1000_0000], 1)), Some(Flaw::Varint));
+}
+
+#[test]
+fn rejects_unrecognized_even_tags() {
+    assert_eq!(cenotaph(&with_integers(&[UNKNOWN_EVEN, 0], 1)), Some(Flaw::UnrecognizedEvenTag));
+    // A flag nothing defines
+    assert_eq!(cenotaph(&with_integers(&[FLAGS, 1 << 3], 1)), Some(Flaw::UnrecognizedFlag));
+    // A pointer past the outputs is left unrecognized
+    assert_eq!(cenotaph(&with_integers(&[POINTER, 2], 1)), Some(Flaw::UnrecognizedEvenTag));
+}
+
+#[test]
+fn rejects_trailing_integers() {
+    for trailing in 1..4 {
+        let mut integers = vec![BODY, 1, 1, 5, 0];
+        integers.extend(std::iter::repeat_n(0, trailing));
+        assert_eq!(cenotaph(&with_integers(&integers, 1)), Some(Flaw::TrailingIntegers));
+    }
+}
+
+#[test]
+fn rejects_truncated_fields() {
+    assert_eq!(cenotaph(&with_integers(&[RUNE], 1)), Some(Flaw::TruncatedField));
+}
+
+#[test]
+fn rejects_bad_edict_outputs() {
+    assert_eq!(cenotaph(&with_integers(&[BODY, 1, 1, 5, 3], 1)), Some(Flaw::EdictOutput));
+    assert_eq!(cenotaph(&with_integers(&[BODY, 1, 1, 5, u128::from(u32::MAX) + 1], 1)), Some(Flaw::EdictOutput));
+}
+
+#[test]
+fn rejects_rune_ids_in_block_zero() {
+    // ord's RuneId::new refuses a transaction index in block 0
+    assert_eq!(cenotaph(&with_integers(&[BODY, 0, 1, 5, 0], 1)), Some(Flaw::EdictRuneId));
+    // Deltas that land there are just as bad
+    assert_eq!(cenotaph(&with_integers(&[BODY, 0, 0, 5, 0, 0, 1, 5, 0], 1)), Some(Flaw::EdictRuneId));
+    // A mint of 0:1 is left behind, so its even tag goes unrecognized
+    assert_eq!(cenotaph(&with_integers(&[MINT, 0, MINT, 1], 1)), Some(Flaw::UnrecognizedEvenTag));
+    assert_eq!(cenotaph(&with_integers(&[MINT, 0, MINT, 0], 1)), None);
+}
+
+#[test]
+fn rejects_opcodes_in_the_payload() {
+    let script = Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_opcode(OP_PUSHNUM_13)
+        .push_opcode(OP_VERIFY)
+        .into_script();
+    let tx = Transaction { output: vec![TxOut { value: Amount::ZERO, script_pubkey: script }], ..with_integers(&[], 0) };
+    assert_eq!(cenotaph(&tx), Some(Flaw::Opcode));
+}
+
+#[test]
+fn follows_edicts_and_pointers_to_the_outputs() {
+    // The runestone is output 0, so the first output runes can land on is 1
+    let allocated = |integers: &[u128], output| {
+        let tx = with_integers(integers, 2);
+        runestone(&tx).unwrap().allocated(&tx, Some(RUNE_ID), 101, output)
+    };
+    assert_eq!(allocated(&[], 1), 101);
+    assert_eq!(allocated(&[POINTER, 2], 1), 0);
+    assert_eq!(allocated(&[POINTER, 2], 2), 101);
+    // 60 to output 2, the rest to the first output
+    assert_eq!(allocated(&[BODY, 840_000, 1, 60, 2], 1), 41);
+    // Edicts for other runes leave this one alone
+    assert_eq!(allocated(&[BODY, 840_000, 2, 0, 2], 1), 101);
+    // An output of 3 splits across both outputs, the remainder going first
+    assert_eq!(allocated(&[BODY, 840_000, 1, 0, 3], 1), 51);
+    assert_eq!(allocated(&[BODY, 840_000, 1, 0, 3], 2), 50);
+    assert_eq!(allocated(&[POINTER, 2, BODY, 840_000, 1, 30, 3], 1), 30);
+    assert_eq!(runestone(&with_integers(&[UNKNOWN_EVEN, 0], 1)), None);
+}
@@ -0,0 +1,92 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut};
+use bitcoincore_rpc::json::{AddressType, SignRawTransactionInput};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use slugline::rpc::test_package;
+
+// Run with a regtest bitcoind:
+// SLUGLINE_REGTEST_RPC=http://127.0.0.1:18443 SLUGLINE_REGTEST_USER=.. SLUGLINE_REGTEST_PASSWORD=.. \
+//     cargo test --test test_package -- --ignored
+const WALLET: &str = "slugline-test-package";
+
+// OP_1 <0x4e73>, with the smallest value that isn't dust before ephemeral dust
+const P2A: [u8; 4] = [0x51, 0x02, 0x4e, 0x73];
+const ANCHOR_SAT: u64 = 240;
+const CHILD_FEE_SAT: u64 = 2000;
+
+fn regtest_wallet() -> Client {
+    let url = std::env::var("SLUGLINE_REGTEST_RPC").expect("SLUGLINE_REGTEST_RPC is not set");
+    let auth = match (std::env::var("SLUGLINE_REGTEST_USER"), std::env::var("SLUGLINE_REGTEST_PASSWORD")) {
+        (Ok(user), Ok(password)) => Auth::UserPass(user, password),
+        _ => Auth::None,
+    };
+    let node = Client::new(&url, auth.clone()).unwrap();
+    if !node.list_wallets().unwrap().iter().any(|wallet| wallet == WALLET) && node.load_wallet(WALLET).is_err() {
+        node.create_wallet(WALLET, None, None, None, None).unwrap();
+    }
+    let wallet = Client::new(&format!("{}/wallet/{}", url, WALLET), auth).unwrap();
+    let address = wallet.get_new_address(None, Some(AddressType::Bech32)).unwrap().assume_checked();
+    wallet.generate_to_address(101, &address).unwrap();
+    wallet
+}
+
+fn wallet_script(wallet: &Client) -> ScriptBuf {
+    wallet.get_new_address(None, Some(AddressType::Bech32)).unwrap().assume_checked().script_pubkey()
+}
+
+// A version 3 transaction spending `inputs`, signed by the wallet
+fn spend(wallet: &Client, inputs: &[(OutPoint, TxOut)], output: Vec<TxOut>) -> Transaction {
+    let tx = Transaction {
+        version: Version(3),
+        lock_time: LockTime::ZERO,
+        input: inputs.iter()
+            .map(|(outpoint, _)| TxIn { previous_output: *outpoint, sequence: Sequence::ENABLE_RBF_NO_LOCKTIME, ..Default::default() })
+            .collect(),
+        output,
+    };
+    let prevtxs: Vec<SignRawTransactionInput> = inputs.iter()
+        .map(|(outpoint, spent)| SignRawTransactionInput {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            script_pub_key: spent.script_pubkey.clone(),
+            redeem_script: None,
+            amount: Some(spent.value),
+        })
+        .collect();
+    let signed = wallet.sign_raw_transaction_with_wallet(&tx, Some(&prevtxs), None).unwrap();
+    assert!(signed.complete);
+    signed.transaction().unwrap()
+}
+
+// A zero-fee parent paying its input back to the wallet behind an anchor, and a child
+// spending the parent's change and paying `child_fee` for both
+fn package(wallet: &Client, child_fee: u64) -> (Transaction, Transaction) {
+    let utxo = wallet.list_unspent(Some(1), None, None, None, None).unwrap().remove(0);
+    let spent = TxOut { value: utxo.amount, script_pubkey: utxo.script_pub_key };
+    let parent = spend(wallet, &[(OutPoint { txid: utxo.txid, vout: utxo.vout }, spent.clone())], vec![
+        TxOut { value: Amount::from_sat(ANCHOR_SAT), script_pubkey: ScriptBuf::from_bytes(P2A.to_vec()) },
+        TxOut { value: utxo.amount - Amount::from_sat(ANCHOR_SAT), script_pubkey: wallet_script(wallet) },
+    ]);
+    let change = parent.output[1].clone();
+    let child = spend(wallet, &[(OutPoint { txid: parent.compute_txid(), vout: 1 }, change.clone())], vec![
+        TxOut { value: change.value - Amount::from_sat(child_fee), script_pubkey: wallet_script(wallet) },
+    ]);
+    (parent, child)
+}
+
+#[test]
+#[ignore = "needs a regtest bitcoind, see SLUGLINE_REGTEST_RPC"]
+fn accepts_a_zero_fee_parent_its_child_pays_for() {
+    let wallet = regtest_wallet();
+    let (parent, child) = package(&wallet, CHILD_FEE_SAT);
+    assert_eq!(test_package(&wallet, &[&parent, &child]).unwrap(), [None, None]);
+
+    // Without the child bitcoind's rejection stands
+    let rejections = test_package(&wallet, &[&parent]).unwrap();
+    assert!(rejections[0].as_deref().is_some_and(|reason| reason.starts_with("min relay fee not met")), "{:?}", rejections);
+
+    // As it does when the child doesn't pay for the package either
+    let (parent, child) = package(&wallet, 0);
+    assert_ne!(test_package(&wallet, &[&parent, &child]).unwrap(), [None, None]);
+}